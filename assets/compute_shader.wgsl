@@ -8,36 +8,177 @@ struct Config {
     damping_factor: f32,            // 4 bytes
     fixed_delta_time: f32,          // 4 bytes
     frame_count: u32,               // 4 bytes
-    gravity: f32,                   // 4 bytes
+    velocity_history_frames: f32,   // 4 bytes     length of the velocity variance window
+
+    gravity: vec2<f32>,             // 8 bytes     pixels/s^2, (0, -g) pulls straight down
+    _gravity_padding: vec2<f32>,    // 8 bytes
 
     density_kernel_norm: f32,       // 4 bytes
     near_density_kernel_norm: f32,  // 4 bytes
     viscocity_kernel_norm: f32,     // 4 bytes
-    _padding: f32,                  // 4 bytes
+    paused: u32,                    // 4 bytes
 
     target_density: f32,            // 4 bytes
     pressure_multiplier: f32,       // 4 bytes
     viscocity_strength: f32,        // 4 bytes
     near_density_multiplier: f32,   // 4 bytes
 
+    spatial_grid_width: u32,        // 4 bytes     neighbor search cells, one smoothing radius across
+    spatial_grid_height: u32,       // 4 bytes
+    _spatial_grid_padding: vec2<f32>,// 8 bytes
+
+    scalar_grid_width: u32,         // 4 bytes
+    scalar_grid_height: u32,        // 4 bytes
+    scalar_grid_cell_size: f32,     // 4 bytes
+    smoke_enabled: u32,             // 4 bytes
+
+    smoke_injection: f32,           // 4 bytes
+    smoke_dissipation: f32,         // 4 bytes
+    smoke_opacity: f32,             // 4 bytes
+    divergence_view: u32,           // 4 bytes
+
+    flip_enabled: u32,              // 4 bytes
+    flip_ratio: f32,                // 4 bytes
+    pressure_iterations: u32,       // 4 bytes
+    divergence_range: f32,          // 4 bytes
+
+    color_field: u32,               // 4 bytes
+    colormap: u32,                  // 4 bytes
+    color_min: f32,                 // 4 bytes
+    color_max: f32,                 // 4 bytes
+
+    interaction_position: vec2<f32>,// 8 bytes
+    interaction_strength: f32,      // 4 bytes     > 0 attracts, < 0 repels, 0 when idle
+    interaction_radius: f32,        // 4 bytes
+
+    impulse_position: vec2<f32>,    // 8 bytes
+    impulse_strength: f32,          // 4 bytes     > 0 pushes out, < 0 pulls in, 0 outside the frame it fires
+    impulse_radius: f32,            // 4 bytes
+
+    fan_origin: vec2<f32>,          // 8 bytes
+    fan_direction: vec2<f32>,       // 8 bytes     unit vector
+    fan_strength: f32,              // 4 bytes     0 while the fan is off
+    fan_reach: f32,                 // 4 bytes
+    fan_cos_half_angle: f32,        // 4 bytes
+    _fan_padding: f32,              // 4 bytes
+
+    render_mode: u32,               // 4 bytes     0 draws particles, 1 a smoothed liquid surface, 2 a density heatmap
+    surface_threshold: f32,         // 4 bytes     blurred thickness at the liquid's edge
+    surface_blur_radius: f32,       // 4 bytes     in pixels
+    heatmap_range: f32,             // 4 bytes     density at the top of the heatmap, in target densities
+
+    surrogate_enabled: u32,         // 4 bytes     a learned velocity correction is in surrogate_correction
+    surrogate_strength: f32,        // 4 bytes
+    compensated_summation: u32,     // 4 bytes     picks the COMPENSATED_SUMMATION pipelines, not read here
+    deterministic_order: u32,       // 4 bytes     picks the order_cell_contents pass, not read here
+
+    temperature_enabled: u32,       // 4 bytes
+    ambient_temperature: f32,       // 4 bytes
+    heat_diffusion: f32,            // 4 bytes     per second, towards the neighbors' temperature
+    cooling_rate: f32,              // 4 bytes     per second, towards the ambient temperature
+
+    thermal_expansion: f32,         // 4 bytes     buoyancy against gravity per degree above ambient
+    heater_temperature: f32,        // 4 bytes
+    heater_height: f32,             // 4 bytes     how far above the floor the heaters reach
+    heater_count: u32,              // 4 bytes
+
+    solver: u32,                    // 4 bytes     picks the pbf_* passes, not read here
+    pbf_iterations: u32,            // 4 bytes     constraint projections per step
+    pbf_relaxation: f32,            // 4 bytes     softens the density constraint where gradients vanish
+    _solver_padding: f32,           // 4 bytes
+
+    substeps: u32,                  // 4 bytes     particle steps this frame, the grid passes still run once
+    substep_delta_time: f32,        // 4 bytes     fixed_delta_time / substeps, the particle passes' time step
+    cfl_number: f32,                // 4 bytes     not read here
+    max_substeps: u32,              // 4 bytes     not read here
+
+    trail_length: u32,              // 4 bytes     recorded positions drawn per trail, 0 or 1 for none
+    trail_opacity: f32,             // 4 bytes     alpha of the newest segment, fading to 0 at the oldest
+    trail_width: f32,               // 4 bytes
+    _trail_padding: f32,            // 4 bytes
+
+    tonemapping: u32,               // 4 bytes     not read here
+    exposure: f32,                  // 4 bytes     not read here
+    _tonemapping_padding: vec2<f32>,// 8 bytes
+
+    density_alpha_enabled: u32,     // 4 bytes     not read here
+    density_alpha_min: f32,         // 4 bytes     not read here
+    density_alpha_range: f32,       // 4 bytes     not read here
+    density_alpha_gamma: f32,       // 4 bytes     not read here
+
+    velocity_field_mode: u32,       // 4 bytes     not read here
+    velocity_field_spacing: f32,    // 4 bytes     distance between samples of the velocity field
+    velocity_field_full_speed: f32, // 4 bytes     not read here
+    _velocity_field_padding: f32,   // 4 bytes
+
+    outline_enabled: u32,           // 4 bytes     not read here
+    outline_width: f32,             // 4 bytes     not read here
+    _outline_padding: vec2<f32>,    // 8 bytes
+    outline_color: vec4<f32>,       // 16 bytes    not read here
+
+    retro_enabled: u32,             // 4 bytes     not read here
+    palette_size: u32,              // 4 bytes     not read here
+    palette_dither: f32,            // 4 bytes     not read here
+    retro_pixel_size: f32,          // 4 bytes     not read here
+    palette: array<vec4<f32>, 16>,  // 256 bytes   not read here
+
+    heater_spans: array<vec4<f32>, 2>,  // 32 bytes     start, end pairs along the floor as fractions of its width
+
+    boundary_modes: vec4<u32>,      // 16 bytes     BOUNDARY_* of the left, right, bottom and top edges
+
     screen_bounds: vec4<f32>,       // 16 bytes     [x_min, x_max, y_min, y_max]
     view_proj: mat4x4<f32>,         // 64 bytes
 }
 
-struct SortingParams
-{
-    n: u32,
-    group_width: u32,
-    group_height: u32,
-    step_index: u32
-}
-
 struct Particle {
     position: vec2<f32>,
     velocity: vec2<f32>,
     color: vec4<f32>,
 }
 
+struct Obstacle {
+    center: vec2<f32>,
+    half_extents: vec2<f32>,    // radius in both for circles
+    rotation: vec2<f32>,        // cos, sin
+    velocity: vec2<f32>,        // zero unless the obstacle is a rigid body
+    shape: u32,                 // OBSTACLE_SHAPE_CIRCLE or OBSTACLE_SHAPE_BOX
+    body: u32,                  // slot in body_impulses, NO_BODY for static obstacles
+    angular_velocity: f32,      // radians per second, counter clockwise
+    _padding: u32,
+}
+
+struct ForceField {
+    position: vec2<f32>,
+    direction: vec2<f32>,       // unit vector, wind and waves
+    kind: u32,                  // FORCE_FIELD_WIND, _WAVE, _VORTEX or _ATTRACTOR
+    strength: f32,              // peak acceleration
+    radius: f32,                // vortices and attractors fade out to nothing here
+    wavenumber: f32,            // radians per world unit along the direction, waves
+    phase: f32,                 // radians the wave has travelled
+    _padding: u32,
+}
+
+struct Stats {
+    histogram: array<atomic<u32>, STATS_HISTOGRAM_BINS>,   // occupied cell keys holding 1, 2, ... particles, the last bin also everything above
+    partials: array<vec4<f32>>,     // per reduction workgroup: density sum, kinetic energy, max speed, particle count, then RMS velocity fluctuation sum
+}
+
+struct PressureProbes {
+    count: u32,
+    positions: array<vec4<f32>, MAX_PRESSURE_PROBES>,       // xy, written by the CPU every frame
+    samples: array<vec4<f32>, PRESSURE_PROBE_SAMPLES>,      // density, near density, pressure, frame (bitcast) per probe, a ring of PRESSURE_PROBE_HISTORY frames
+}
+
+struct ObstacleList {
+    count: u32,
+    obstacles: array<Obstacle, MAX_OBSTACLES>,
+}
+
+struct ForceFieldList {
+    count: u32,
+    fields: array<ForceField, MAX_FORCE_FIELDS>,
+}
+
 /* ----------------------------------- BINDINGS -----------------------------------*/
 @group(0) @binding(0)
 var<storage, read_write> particles: array<Particle>;
@@ -46,7 +187,7 @@ var<storage, read_write> particles: array<Particle>;
 var<uniform> config: Config;
 
 @group(0) @binding(2)
-var<uniform> sorting_params: SortingParams;
+var<storage, read_write> sort_scratch: array<vec2<u32>>;  // cell key, rank among particles sharing the key
 
 @group(0) @binding(3) 
 var<storage, read_write> spatial_lookup: array<vec2<u32>>;  // cell key, particle index
@@ -60,12 +201,144 @@ var<storage, read_write> particle_densities: array<vec2<f32>>;  // density, near
 @group(0) @binding(6) 
 var<storage, read_write> predicted_positions: array<vec2<f32>>;
 
+@group(0) @binding(7) 
+var<storage, read_write> scalar_grid_accumulation: array<atomic<i32>>;  // velocity x, velocity y, weight, dye per cell (fixed point)
+
+@group(0) @binding(8) 
+var<storage, read_write> scalar_grid_velocities: array<vec2<f32>>;  // resolved velocities, then projected velocities
+
+@group(0) @binding(9) 
+var<storage, read_write> scalar_field: array<f32>;  // two halves, ping-ponged by frame parity
+
+@group(0) @binding(10) 
+var<storage, read_write> grid_pressure: array<f32>;  // divergence, then two ping-ponged pressure fields
+
+@group(0) @binding(11) 
+var<storage, read> obstacles: ObstacleList;
+
+@group(0) @binding(12) 
+var<storage, read_write> key_counts: array<atomic<u32>>;  // particles per cell key
+
+@group(0) @binding(13) 
+var<storage, read_write> scan_block_totals: array<u32>;  // key counts per scan block, then their exclusive prefix sum
+
+@group(0) @binding(14) 
+var<storage, read> surrogate_correction: array<vec2<f32>>;  // learned acceleration per scalar grid cell
+
+@group(0) @binding(15) 
+var<storage, read_write> body_impulses: array<atomic<i32>>;  // impulse x, impulse y, angular impulse per rigid body slot (fixed point)
+
+@group(0) @binding(16) 
+var<storage, read_write> stats: Stats;
+
+@group(0) @binding(17) 
+var<storage, read_write> velocity_history: array<vec4<f32>>;  // mean velocity x, y, mean squared speed, variance per particle
+
+@group(0) @binding(18) 
+var<storage, read_write> pressure_probes: PressureProbes;
+
+@group(0) @binding(19) 
+var<storage, read_write> obstacle_forces: array<atomic<i32>>;  // impulse x, impulse y, angular impulse per obstacle (fixed point), cleared on readback
+
+@group(0) @binding(20) 
+var<storage, read_write> particle_temperatures: array<vec2<f32>>;  // current, next; next is written from the neighbors' current
+
+@group(0) @binding(21) 
+var<storage, read_write> max_speed: atomic<u32>;  // float bits of the frame's largest speed, cleared before reduce_max_speed
+
+@group(0) @binding(22) 
+var<storage, read_write> trail_positions: array<vec2<f32>>;  // TRAIL_HISTORY per particle, a ring indexed by frame count
+
+@group(0) @binding(23) 
+var<storage, read_write> velocity_field: array<vec2<f32>>;  // averaged velocity per velocity field sample, row major
+
+@group(0) @binding(24) 
+var<storage, read> force_fields: ForceFieldList;
+
 /* --------------------------------- CONSTANTS ---------------------------------*/
 const PI: f32 = 3.14159;
 const WORKGROUP_SIZE: u32 = 64u;
+const TRAIL_HISTORY: u32 = 16u;             // must match particle_render.rs
+const VELOCITY_FIELD_MAX_SIDE: u32 = 64u;   // must match particle_render.rs
 const SHADER_DELAY: u32 = 5u;
+const GRID_FIXED_POINT_SCALE: f32 = 256.0;  // atomics are integer only, so splatted values are fixed point
+const GRID_EMPTY_WEIGHT: f32 = 0.0001;      // cells with less splatted weight than this hold no fluid
+const SCAN_BLOCK_SIZE: u32 = 256u;         // keys prefix summed per workgroup, must match particle_compute.rs
+const MAX_OBSTACLES: u32 = 64u;             // must match MAX_OBSTACLES in obstacle.rs
+const MAX_FORCE_FIELDS: u32 = 16u;          // must match MAX_FORCE_FIELDS in force_field.rs
+const STATS_HISTOGRAM_BINS: u32 = 16u;      // must match stats.rs
+const STATS_WORKGROUP_SIZE: u32 = 256u;     // particles reduced per workgroup, must match stats.rs
+const OBSTACLE_SHAPE_CIRCLE: u32 = 0u;
+const OBSTACLE_SHAPE_BOX: u32 = 1u;
+const FORCE_FIELD_WIND: u32 = 0u;           // must match ForceFieldKind in force_field.rs
+const FORCE_FIELD_WAVE: u32 = 1u;
+const FORCE_FIELD_VORTEX: u32 = 2u;
+const FORCE_FIELD_ATTRACTOR: u32 = 3u;
+const NO_BODY: u32 = 0xffffffffu;
+const BODY_FIXED_POINT_SCALE: f32 = 16.0;           // must match rigid_body.rs
+const BODY_ANGULAR_FIXED_POINT_SCALE: f32 = 1.0;    // must match rigid_body.rs, angular impulses are larger by the lever arm
+const MAX_PRESSURE_PROBES: u32 = 16u;       // must match pressure_probe.rs
+const PRESSURE_PROBE_HISTORY: u32 = 64u;    // frames kept in the sample ring, must match pressure_probe.rs
+const PRESSURE_PROBE_SAMPLES: u32 = 1024u;  // MAX_PRESSURE_PROBES * PRESSURE_PROBE_HISTORY
+const BOUNDARY_BOUNCE: u32 = 0u;    // must match BoundaryMode in parameter_gui.rs
+const BOUNDARY_WRAP: u32 = 1u;
+const BOUNDARY_DRAIN: u32 = 2u;
+const BOUNDARY_OPEN: u32 = 3u;
+const DRAINED_OFFSET: f32 = 100000.0;   // how far below and left of the bounds drained particles are parked
 
 /* --------------------------------- MISC FUNCTIONS ---------------------------------*/
+// drained particles are marked with a zero color alpha (live ones are uploaded with 1) and
+// parked far outside the bounds, where they can't be anyone's neighbor
+fn is_drained(i: u32) -> bool
+{
+    return particles[i].color.a == 0.0;
+}
+
+fn drain_particle(i: u32)
+{
+    let parked = vec2(config.screen_bounds[0], config.screen_bounds[2]) - vec2(DRAINED_OFFSET);
+    particles[i].position = parked;
+    particles[i].velocity = vec2(0.0, 0.0);
+    particles[i].color.a = 0.0;
+    predicted_positions[i] = parked;
+}
+
+fn crosses_drain(position: f32, low: f32, high: f32, modes: vec2<u32>) -> bool
+{
+    return (position <= low && modes.x == BOUNDARY_DRAIN) || (position >= high && modes.y == BOUNDARY_DRAIN);
+}
+
+// position and velocity along one axis after its low and high edges, modes in the same order
+fn resolve_axis(position: f32, velocity: f32, low: f32, high: f32, modes: vec2<u32>) -> vec2<f32>
+{
+    if (position <= low) {
+        switch modes.x {
+            case BOUNDARY_WRAP: {
+                return vec2(position + (high - low), velocity);
+            }
+            case BOUNDARY_OPEN: {
+                return vec2(position, velocity);
+            }
+            default: {
+                return vec2(low, abs(velocity) * config.damping_factor);  // force positive
+            }
+        }
+    } else if (position >= high) {
+        switch modes.y {
+            case BOUNDARY_WRAP: {
+                return vec2(position - (high - low), velocity);
+            }
+            case BOUNDARY_OPEN: {
+                return vec2(position, velocity);
+            }
+            default: {
+                return vec2(high, -abs(velocity) * config.damping_factor);  // force negative
+            }
+        }
+    }
+    return vec2(position, velocity);
+}
+
 fn check_screen_bounds(i: u32) 
 {
     let x_min = config.screen_bounds[0];
@@ -73,72 +346,193 @@ fn check_screen_bounds(i: u32)
     let y_min = config.screen_bounds[2];
     let y_max = config.screen_bounds[3];
 
-    var pos = particles[i].position;
-    var vel = particles[i].velocity;
+    let pos = particles[i].position;
+    let vel = particles[i].velocity;
 
-    // X bounds
-    if (pos.x <= x_min) {
-        pos.x = x_min;
-        vel.x = abs(vel.x) * config.damping_factor;  // force positive
-    } else if (pos.x >= x_max) {
-        pos.x = x_max;
-        vel.x = -abs(vel.x) * config.damping_factor;  // force negative
+    if (crosses_drain(pos.x, x_min, x_max, config.boundary_modes.xy) ||
+        crosses_drain(pos.y, y_min, y_max, config.boundary_modes.zw)) {
+        drain_particle(i);
+        return;
     }
 
-    // Y bounds
-    if (pos.y <= y_min) {
-        pos.y = y_min;
-        vel.y = abs(vel.y) * config.damping_factor;  // force positive
-    } else if (pos.y >= y_max) {
-        pos.y = y_max;
-        vel.y = -abs(vel.y) * config.damping_factor;  // force negative
+    let x = resolve_axis(pos.x, vel.x, x_min, x_max, config.boundary_modes.xy);
+    let y = resolve_axis(pos.y, vel.y, y_min, y_max, config.boundary_modes.zw);
+
+    particles[i].position = vec2(x[0], y[0]);
+    particles[i].velocity = vec2(x[1], y[1]);
+}
+
+// push particles that ended up inside an obstacle back to its surface, damping the
+// velocity into the surface the same way the screen edges do. Every obstacle sums the
+// impulse of each bounce in obstacle_forces, rigid bodies also collect it in body_impulses.
+fn resolve_obstacle_collisions(i: u32)
+{
+    let obstacle_count = min(obstacles.count, MAX_OBSTACLES);
+    for (var o = 0u; o < obstacle_count; o++) {
+        let obstacle = obstacles.obstacles[o];
+        let offset = particles[i].position - obstacle.center;
+
+        var normal = vec2<f32>(0.0, 1.0);
+        var penetration = 0.0;
+        if (obstacle.shape == OBSTACLE_SHAPE_CIRCLE) {
+            let radius = obstacle.half_extents.x;
+            let distance = length(offset);
+            if (distance >= radius) { continue; }
+            if (distance > 0.0001f) {
+                normal = offset / distance;
+            }
+            penetration = radius - distance;
+        } else {
+            // into the box frame, test, then push out along the nearest face
+            let c = obstacle.rotation.x;
+            let s = obstacle.rotation.y;
+            let local = vec2<f32>(c * offset.x + s * offset.y, -s * offset.x + c * offset.y);
+            let q = abs(local) - obstacle.half_extents;
+            if (q.x >= 0.0 || q.y >= 0.0) { continue; }
+
+            var local_normal: vec2<f32>;
+            if (q.x > q.y) {
+                local_normal = vec2<f32>(select(-1.0, 1.0, local.x >= 0.0), 0.0);
+                penetration = -q.x;
+            } else {
+                local_normal = vec2<f32>(0.0, select(-1.0, 1.0, local.y >= 0.0));
+                penetration = -q.y;
+            }
+            normal = vec2<f32>(c * local_normal.x - s * local_normal.y, s * local_normal.x + c * local_normal.y);
+        }
+
+        particles[i].position += normal * penetration;
+
+        // bounce off the surface as it moves, which is still for static obstacles
+        let arm = particles[i].position - obstacle.center;
+        let surface_velocity = obstacle.velocity + obstacle.angular_velocity * vec2<f32>(-arm.y, arm.x);
+        let normal_speed = dot(particles[i].velocity - surface_velocity, normal);
+        if (normal_speed < 0.0) {
+            let delta_velocity = -(1.0 + config.damping_factor) * normal_speed * normal;
+            particles[i].velocity += delta_velocity;
+
+            // particles have unit mass, so the obstacle takes the opposite of the velocity change
+            let impulse = -delta_velocity;
+            let angular_impulse = arm.x * impulse.y - arm.y * impulse.x;
+            atomicAdd(&obstacle_forces[o * 3u + 0u], i32(impulse.x * BODY_FIXED_POINT_SCALE));
+            atomicAdd(&obstacle_forces[o * 3u + 1u], i32(impulse.y * BODY_FIXED_POINT_SCALE));
+            atomicAdd(&obstacle_forces[o * 3u + 2u], i32(angular_impulse * BODY_ANGULAR_FIXED_POINT_SCALE));
+
+            if (obstacle.body != NO_BODY) {
+                atomicAdd(&body_impulses[obstacle.body * 3u + 0u], i32(impulse.x * BODY_FIXED_POINT_SCALE));
+                atomicAdd(&body_impulses[obstacle.body * 3u + 1u], i32(impulse.y * BODY_FIXED_POINT_SCALE));
+                atomicAdd(&body_impulses[obstacle.body * 3u + 2u], i32(angular_impulse * BODY_ANGULAR_FIXED_POINT_SCALE));
+            }
+        }
     }
+}
 
-    particles[i].position = pos;
-    particles[i].velocity = vel;
+/* --------------------------------- SPATIAL LOOKUP FUNCTIONS ---------------------------------*/
+// The neighbor search grid covers the screen bounds with cells one smoothing radius across and
+// every cell has its own key, so a cell's particles are never interleaved with another's.
+fn spatial_grid_cell_count() -> u32
+{
+    return config.spatial_grid_width * config.spatial_grid_height;
 }
 
-fn set_color(i: u32) 
+// positions past the bounds are clamped into the edge cells, which still leaves every
+// neighbor within a smoothing radius in the surrounding 3x3 cells
+fn position_to_cell_coord(position: vec2<f32>) -> vec2<i32>
 {
-    let speed_sq = dot(particles[i].velocity, particles[i].velocity);
-    let energy = 0.5 * 1.0 * speed_sq;
+    let grid_origin = vec2(config.screen_bounds[0], config.screen_bounds[2]);
+    let cell = vec2<i32>(floor((position - grid_origin) / config.smoothing_radius));
+    let last_cell = vec2(i32(config.spatial_grid_width), i32(config.spatial_grid_height)) - 1;
+    return clamp(cell, vec2(0), last_cell);
+}
 
-    let normalized = clamp(energy / config.max_energy, 0.0, 1.0);
+fn in_spatial_grid(cell: vec2<i32>) -> bool
+{
+    return all(cell >= vec2(0)) && cell.x < i32(config.spatial_grid_width) && cell.y < i32(config.spatial_grid_height);
+}
 
-    var rgb: vec3<f32>;
-    if (normalized < 0.5) {
-        let t = normalized * 2.0;
-        rgb = mix(vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0), t); // Blue → Green
-    } else {
-        let t = (normalized - 0.5) * 2.0;
-        rgb = mix(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), t); // Green → Red
-    }
+fn get_cell_key(cell: vec2<i32>) -> u32
+{
+    return u32(cell.y) * config.spatial_grid_width + u32(cell.x);
+}
 
-    particles[i].color = vec4(rgb, 1.0);
+/* --------------------------------- SCALAR GRID FUNCTIONS ---------------------------------*/
+fn scalar_grid_cell_count() -> u32
+{
+    return config.scalar_grid_width * config.scalar_grid_height;
 }
 
-/* --------------------------------- SPATIAL LOOKUP FUNCTIONS ---------------------------------*/
-fn particle_position_to_cell_coord(i: u32) -> vec2<i32>
+// continuous grid coordinates with cell centers at integer values
+fn world_to_scalar_grid(position: vec2<f32>) -> vec2<f32>
 {
-    let x_max = config.screen_bounds[1];
-    let y_max = config.screen_bounds[3];
+    let origin = vec2(config.screen_bounds[0], config.screen_bounds[2]);
+    return (position - origin) / config.scalar_grid_cell_size - vec2(0.5);
+}
+
+fn scalar_grid_index(cell: vec2<i32>) -> u32
+{
+    return u32(cell.y) * config.scalar_grid_width + u32(cell.x);
+}
+
+fn sample_scalar_field(position: vec2<f32>, offset: u32) -> f32
+{
+    let max_cell = vec2(i32(config.scalar_grid_width) - 1, i32(config.scalar_grid_height) - 1);
+    let p = clamp(position, vec2(0.0), vec2<f32>(max_cell));
+    let base = vec2<i32>(floor(p));
+    let next = min(base + vec2(1, 1), max_cell);
+    let t = p - floor(p);
+
+    let s00 = scalar_field[offset + scalar_grid_index(base)];
+    let s10 = scalar_field[offset + scalar_grid_index(vec2(next.x, base.y))];
+    let s01 = scalar_field[offset + scalar_grid_index(vec2(base.x, next.y))];
+    let s11 = scalar_field[offset + scalar_grid_index(next)];
+    return mix(mix(s00, s10, t.x), mix(s01, s11, t.x), t.y);
+}
+
+fn sample_grid_velocity(position: vec2<f32>, offset: u32) -> vec2<f32>
+{
+    let max_cell = vec2(i32(config.scalar_grid_width) - 1, i32(config.scalar_grid_height) - 1);
+    let p = clamp(position, vec2(0.0), vec2<f32>(max_cell));
+    let base = vec2<i32>(floor(p));
+    let next = min(base + vec2(1, 1), max_cell);
+    let t = p - floor(p);
+
+    let v00 = scalar_grid_velocities[offset + scalar_grid_index(base)];
+    let v10 = scalar_grid_velocities[offset + scalar_grid_index(vec2(next.x, base.y))];
+    let v01 = scalar_grid_velocities[offset + scalar_grid_index(vec2(base.x, next.y))];
+    let v11 = scalar_grid_velocities[offset + scalar_grid_index(next)];
+    return mix(mix(v00, v10, t.x), mix(v01, v11, t.x), t.y);
+}
 
-    let cell_x = i32((particles[i].position.x + x_max) / f32(config.smoothing_radius));
-    let cell_y = i32((particles[i].position.y + y_max) / f32(config.smoothing_radius));
+fn sample_surrogate_correction(position: vec2<f32>) -> vec2<f32>
+{
+    let max_cell = vec2(i32(config.scalar_grid_width) - 1, i32(config.scalar_grid_height) - 1);
+    let p = clamp(position, vec2(0.0), vec2<f32>(max_cell));
+    let base = vec2<i32>(floor(p));
+    let next = min(base + vec2(1, 1), max_cell);
+    let t = p - floor(p);
+
+    let c00 = surrogate_correction[scalar_grid_index(base)];
+    let c10 = surrogate_correction[scalar_grid_index(vec2(next.x, base.y))];
+    let c01 = surrogate_correction[scalar_grid_index(vec2(base.x, next.y))];
+    let c11 = surrogate_correction[scalar_grid_index(next)];
+    return mix(mix(c00, c10, t.x), mix(c01, c11, t.x), t.y);
+}
 
-    return vec2(cell_x, cell_y);
+fn scalar_grid_cell_weight(i: u32) -> f32
+{
+    return f32(atomicLoad(&scalar_grid_accumulation[i * 4u + 2u])) / GRID_FIXED_POINT_SCALE;
 }
 
-fn hash_cell(cell_x: i32, cell_y: i32) -> u32
+// neighbor cell index, clamped so cells on the domain edge see themselves (zero gradient)
+fn scalar_grid_neighbor(cell: vec2<i32>, offset: vec2<i32>) -> u32
 {
-    let a = u32(cell_x) * 15823u; 
-    let b = u32(cell_y) * 9737333u; 
-    return a + b;
+    let max_cell = vec2(i32(config.scalar_grid_width) - 1, i32(config.scalar_grid_height) - 1);
+    return scalar_grid_index(clamp(cell + offset, vec2(0, 0), max_cell));
 }
 
-fn get_key_from_hash(hash_value: u32) -> u32
+fn scalar_grid_cell(i: u32) -> vec2<i32>
 {
-    return hash_value % config.particle_count;
+    return vec2(i32(i % config.scalar_grid_width), i32(i / config.scalar_grid_width));
 }
 
 /* --------------------------------- KERNEL FUNCTIONS ---------------------------------*/
@@ -204,29 +598,41 @@ var<private> GRID_OFFSETS: array<vec2<i32>, 9> = array<vec2<i32>, 9>(
     vec2<i32>( 1,-1), vec2<i32>( 1,0), vec2<i32>( 1,1)
 );
 
-fn calculate_density(curr_particle_index: u32) -> vec2<f32>
-{
-    var density = 0f;
-    var near_density = 0f;
+// Neighbor sums. With COMPENSATED_SUMMATION (a shader def the compute node selects) every add
+// also tracks the low order bits it rounded off and feeds them into the next one (Kahan), which
+// keeps the error from growing with the neighbor count at about four times the cost per add.
+struct Accumulator {
+    sum: vec2<f32>,
+    compensation: vec2<f32>,    // always zero without COMPENSATED_SUMMATION
+}
 
-    let x_max = config.screen_bounds[1];
-    let y_max = config.screen_bounds[3];
+fn accumulate(accumulator: ptr<function, Accumulator>, value: vec2<f32>)
+{
+#ifdef COMPENSATED_SUMMATION
+    let corrected = value - (*accumulator).compensation;
+    let sum = (*accumulator).sum + corrected;
+    (*accumulator).compensation = (sum - (*accumulator).sum) - corrected;
+    (*accumulator).sum = sum;
+#else
+    (*accumulator).sum += value;
+#endif
+}
 
-    let curr_particle = particles[curr_particle_index];
-    let curr_particle_position = predicted_positions[curr_particle_index];
+// density and near density at any point, from the particles' predicted positions
+fn density_at(position: vec2<f32>) -> vec2<f32>
+{
+    var density = Accumulator();
 
-    let cell_x = i32((curr_particle_position.x + x_max) / f32(config.smoothing_radius));
-    let cell_y = i32((curr_particle_position.y + y_max) / f32(config.smoothing_radius));
+    let cell = position_to_cell_coord(position);
 
     let sqr_radius = config.smoothing_radius * config.smoothing_radius;
 
     for (var i: u32; i < 9u; i++)
     {
-        let offset = GRID_OFFSETS[i];
-        let neighbor_cell_x = cell_x + offset.x;
-        let neighbor_cell_y = cell_y + offset.y;
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
 
-        let curr_cell_key = get_key_from_hash(hash_cell(neighbor_cell_x, neighbor_cell_y));
+        let curr_cell_key = get_cell_key(neighbor_cell);
         let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
 
         // loop through neighboring particles
@@ -239,23 +645,27 @@ fn calculate_density(curr_particle_index: u32) -> vec2<f32>
             let other_particle_index = spatial_lookup[i][1];
             let other_particle_position = predicted_positions[other_particle_index];
 
-            let delta = curr_particle_position - other_particle_position;
+            let delta = position - other_particle_position;
             let sqr_distance = dot(delta, delta);
 
             // skip if particle not within squared radius
             if (sqr_distance > sqr_radius) { continue; }
 
             let distance = sqrt(sqr_distance);
-            density += density_kernel(distance);
-            near_density += near_density_kernel(distance);
+            accumulate(&density, vec2(density_kernel(distance), near_density_kernel(distance)));
         }
     }
-    return vec2(density, near_density);
+    return density.sum;
+}
+
+fn calculate_density(curr_particle_index: u32) -> vec2<f32>
+{
+    return density_at(predicted_positions[curr_particle_index]);
 }
 
 fn calculate_pressure_force(curr_particle_index: u32) -> vec2<f32>
 {
-    var pressure_force = vec2(0f, 0f);
+    var pressure_force = Accumulator();
 
     let density = particle_densities[curr_particle_index][0];
     let near_density = particle_densities[curr_particle_index][1];
@@ -263,24 +673,19 @@ fn calculate_pressure_force(curr_particle_index: u32) -> vec2<f32>
     let pressure = density_to_pressure(density);
     let near_pressure = density_to_near_pressure(near_density);
 
-    let x_max = config.screen_bounds[1];
-    let y_max = config.screen_bounds[3];
-
     let curr_particle = particles[curr_particle_index];
     let curr_particle_position = predicted_positions[curr_particle_index];
 
-    let cell_x = i32((curr_particle_position.x + x_max) / f32(config.smoothing_radius));
-    let cell_y = i32((curr_particle_position.y + y_max) / f32(config.smoothing_radius));
+    let cell = position_to_cell_coord(curr_particle_position);
 
     let sqr_radius = config.smoothing_radius * config.smoothing_radius;
 
     for (var i: u32; i < 9u; i++)
     {
-        let offset = GRID_OFFSETS[i];
-        let neighbor_cell_x = cell_x + offset.x;
-        let neighbor_cell_y = cell_y + offset.y;
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
 
-        let curr_cell_key = get_key_from_hash(hash_cell(neighbor_cell_x, neighbor_cell_y));
+        let curr_cell_key = get_cell_key(neighbor_cell);
         let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
 
         // loop through neighboring particles
@@ -326,35 +731,30 @@ fn calculate_pressure_force(curr_particle_index: u32) -> vec2<f32>
             let near_pressure_term = (near_pressure / (density * density)) + 
                                     (neighbor_near_pressure / (neighbor_density * neighbor_near_density));
             
-            pressure_force += direction * pressure_term * density_kernel_derivative(distance);
-            pressure_force += direction * near_pressure_term * near_density_kernel_derivative(distance);
+            accumulate(&pressure_force, direction * pressure_term * density_kernel_derivative(distance));
+            accumulate(&pressure_force, direction * near_pressure_term * near_density_kernel_derivative(distance));
         }
     }
-    return pressure_force;
+    return pressure_force.sum;
 }
 
 fn calculate_viscocity(curr_particle_index: u32) -> vec2<f32>
 {
-    var viscocity = vec2(0f, 0f);
-
-    let x_max = config.screen_bounds[1];
-    let y_max = config.screen_bounds[3];
+    var viscocity = Accumulator();
 
     let curr_particle = particles[curr_particle_index];
     let curr_particle_position = predicted_positions[curr_particle_index];
 
-    let cell_x = i32((curr_particle_position.x + x_max) / f32(config.smoothing_radius));
-    let cell_y = i32((curr_particle_position.y + y_max) / f32(config.smoothing_radius));
+    let cell = position_to_cell_coord(curr_particle_position);
 
     let sqr_radius = config.smoothing_radius * config.smoothing_radius;
 
     for (var i: u32; i < 9u; i++)
     {
-        let offset = GRID_OFFSETS[i];
-        let neighbor_cell_x = cell_x + offset.x;
-        let neighbor_cell_y = cell_y + offset.y;
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
 
-        let curr_cell_key = get_key_from_hash(hash_cell(neighbor_cell_x, neighbor_cell_y));
+        let curr_cell_key = get_cell_key(neighbor_cell);
         let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
 
         // loop through neighboring particles
@@ -377,10 +777,10 @@ fn calculate_viscocity(curr_particle_index: u32) -> vec2<f32>
             if (sqr_distance > sqr_radius) { continue; }
 
             let distance = sqrt(sqr_distance);
-            viscocity += (other_particle.velocity - curr_particle.velocity) * viscosity_kernel(distance);
+            accumulate(&viscocity, (other_particle.velocity - curr_particle.velocity) * viscosity_kernel(distance));
         }
     }
-    return viscocity;
+    return viscocity.sum;
 }
 
 fn update_particle_density(i: u32)
@@ -391,138 +791,1127 @@ fn update_particle_density(i: u32)
 
 fn update_particle_positions(i: u32)
 {
-    particles[i].position += particles[i].velocity * config.fixed_delta_time;
+    particles[i].position += particles[i].velocity * config.substep_delta_time;
 }
 
 fn apply_gravity(i: u32)
 {
-    particles[i].velocity += vec2(0.0, -config.gravity) * config.fixed_delta_time;
+    particles[i].velocity += config.gravity * config.substep_delta_time;
 }
 
 fn update_predicted_positions(i: u32)
 {
-    predicted_positions[i] = particles[i].position + particles[i].velocity * config.fixed_delta_time;
+    predicted_positions[i] = particles[i].position + particles[i].velocity * config.substep_delta_time;
 }
 
 fn apply_pressure_force(i: u32)
 {
     let pressure_force = calculate_pressure_force(i);
-    particles[i].velocity += pressure_force * config.fixed_delta_time;
+    particles[i].velocity += pressure_force * config.substep_delta_time;
 }
 
-fn apply_viscocity_force(i: u32)
+// pull particles toward (or push away from) the cursor, fading out at the edge of the radius
+fn apply_interaction_force(i: u32)
 {
-    let viscocity_force = calculate_viscocity(i);
-    particles[i].velocity += viscocity_force * config.viscocity_strength * config.fixed_delta_time;
+    if (config.interaction_strength == 0.0) { return; }
+
+    let offset = config.interaction_position - particles[i].position;
+    let sqr_distance = dot(offset, offset);
+    let radius = config.interaction_radius;
+    if (sqr_distance >= radius * radius) { return; }
+
+    let distance = sqrt(sqr_distance);
+    if (distance < 0.0001f) { return; }
+
+    let falloff = 1.0 - distance / radius;
+    particles[i].velocity += (offset / distance) * config.interaction_strength * falloff * config.substep_delta_time;
 }
 
-/* ----------------------------------- ENTRY POINT FUNCTIONS -----------------------------------*/
-@compute @workgroup_size(64, 1, 1)
-fn pre_simulation_step(@builtin(global_invocation_id) id: vec3<u32>) {
-    let i = id.x;
-    if (i >= arrayLength(&particles)) {
-        return;
-    }
-    if (config.frame_count < SHADER_DELAY) { return; }
+// cone of force along the fan direction, fading out towards the reach and the cone's edge
+fn apply_fan_force(i: u32)
+{
+    if (config.fan_strength == 0.0) { return; }
 
-    apply_gravity(i);
-    
-    update_predicted_positions(i);
+    let offset = particles[i].position - config.fan_origin;
+    let distance = length(offset);
+    if (distance >= config.fan_reach || distance < 0.0001f) { return; }
 
-    update_particle_density(i);
+    let cos_angle = dot(offset / distance, config.fan_direction);
+    if (cos_angle <= config.fan_cos_half_angle) { return; }
+
+    let edge_falloff = (cos_angle - config.fan_cos_half_angle) / (1.0 - config.fan_cos_half_angle);
+    let falloff = (1.0 - distance / config.fan_reach) * edge_falloff;
+    particles[i].velocity += config.fan_direction * config.fan_strength * falloff * config.substep_delta_time;
 }
 
-@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
-fn simulation_step(@builtin(global_invocation_id) id: vec3<u32>) {
-    let i = id.x;
-    if (i >= arrayLength(&particles)) {
-        return;
+// sum of the analytic force fields placed in the scene: wind pushes along its direction everywhere,
+// a wave does too with a strength varying as a sine travelling along it, vortices swirl and
+// attractors pull, both fading out linearly to their radius
+fn apply_force_fields(i: u32)
+{
+    let field_count = min(force_fields.count, MAX_FORCE_FIELDS);
+    var acceleration = vec2<f32>(0.0, 0.0);
+    for (var f = 0u; f < field_count; f++) {
+        let field = force_fields.fields[f];
+        let offset = particles[i].position - field.position;
+
+        switch field.kind {
+            case FORCE_FIELD_WIND: {
+                acceleration += field.direction * field.strength;
+            }
+            case FORCE_FIELD_WAVE: {
+                let travelled = dot(offset, field.direction) * field.wavenumber - field.phase;
+                acceleration += field.direction * field.strength * sin(travelled);
+            }
+            default: {
+                let distance = length(offset);
+                if (distance < field.radius && distance >= 0.0001f) {
+                    let falloff = 1.0 - distance / field.radius;
+                    let outward = offset / distance;
+                    if (field.kind == FORCE_FIELD_VORTEX) {
+                        acceleration += vec2<f32>(-outward.y, outward.x) * field.strength * falloff;
+                    } else {
+                        acceleration -= outward * field.strength * falloff;
+                    }
+                }
+            }
+        }
     }
+    particles[i].velocity += acceleration * config.substep_delta_time;
+}
 
-    if (config.frame_count < SHADER_DELAY) { return; }
+// one-frame radial velocity kick, so unlike the interaction force it isn't scaled by the time step;
+// split across the frame's substeps so the total kick doesn't depend on the count
+fn apply_impulse(i: u32)
+{
+    if (config.impulse_strength == 0.0) { return; }
 
-    apply_pressure_force(i);
+    let offset = particles[i].position - config.impulse_position;
+    let sqr_distance = dot(offset, offset);
+    let radius = config.impulse_radius;
+    if (sqr_distance >= radius * radius) { return; }
 
-    apply_viscocity_force(i);
+    let distance = sqrt(sqr_distance);
+    if (distance < 0.0001f) { return; }
 
-    update_particle_positions(i);
+    let falloff = 1.0 - distance / radius;
+    particles[i].velocity += (offset / distance) * config.impulse_strength * falloff / f32(max(config.substeps, 1u));
+}
 
-    check_screen_bounds(i);
-    
-    set_color(i);
+// experimental: acceleration predicted by a learned model from the (slightly stale) density and
+// velocity grids, blended on top of the SPH forces
+fn apply_surrogate_correction(i: u32)
+{
+    if (config.surrogate_enabled == 0u) { return; }
+
+    let correction = sample_surrogate_correction(world_to_scalar_grid(particles[i].position));
+    particles[i].velocity += correction * config.surrogate_strength * config.substep_delta_time;
 }
 
-@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
-fn bin_particles_in_grid(@builtin(global_invocation_id) id: vec3<u32>)
+// exponential moving averages of the velocity and squared speed, roughly a sliding window of
+// velocity_history_frames steps. Their difference is the velocity variance, a cheap measure of
+// turbulence intensity. A fresh particle starts out at its current velocity.
+fn update_velocity_history(i: u32)
 {
-    let i = id.x;
-    if (i >= arrayLength(&particles)) {
-        return;
+    let velocity = particles[i].velocity;
+    let speed_sq = dot(velocity, velocity);
+    var history = velocity_history[i];
+    if (history.z == 0.0) {
+        history = vec4(velocity, speed_sq, 0.0);
     }
 
-    let cell = particle_position_to_cell_coord(i);
-    let cell_key = get_key_from_hash(hash_cell(cell.x, cell.y));
-    
-    spatial_lookup[i] = vec2(cell_key, i);
-    spatial_lookup_offsets[i] = 0xFFFFFFFFu; // placeholder 
+    let alpha = 1.0 / max(config.velocity_history_frames, 1.0);
+    let mean = mix(history.xy, velocity, alpha);
+    let mean_sq = mix(history.z, speed_sq, alpha);
+    velocity_history[i] = vec4(mean, mean_sq, max(mean_sq - dot(mean, mean), 0.0));
 }
 
-@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
-fn sort_particles(@builtin(global_invocation_id) id: vec3<u32>) 
+fn apply_viscocity_force(i: u32)
 {
-    let i = id.x;
-    if (i >= sorting_params.n / 2u) { return; }
+    let viscocity_force = calculate_viscocity(i);
+    particles[i].velocity += viscocity_force * config.viscocity_strength * config.substep_delta_time;
+}
 
-    let group_width = sorting_params.group_width;
-    let group_height = sorting_params.group_height;
-    let step_index = sorting_params.step_index;
+/* ----------------------------------- TEMPERATURE -----------------------------------*/
+// density weighted average of the neighbors' temperature difference, which is roughly one
+// when every neighbor is a degree warmer
+fn calculate_heat_exchange(curr_particle_index: u32) -> f32
+{
+    var exchange = Accumulator();
 
-    let h_index = i & (group_width - 1u);
-    let index_left = h_index + (group_height + 1u) * (i / group_width);
-    var right_step_size: u32;  
-    if (step_index == 0u)
-    {
-        right_step_size = group_height - 2u * h_index;
-    }
-    else 
+    let curr_temperature = particle_temperatures[curr_particle_index].x;
+    let curr_particle_position = predicted_positions[curr_particle_index];
+
+    let cell = position_to_cell_coord(curr_particle_position);
+
+    let sqr_radius = config.smoothing_radius * config.smoothing_radius;
+
+    for (var i: u32; i < 9u; i++)
     {
-        right_step_size = (group_height + 1u) / 2u;
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
+
+        let curr_cell_key = get_cell_key(neighbor_cell);
+        let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
+
+        // loop through neighboring particles
+        for (var i: u32 = start_idx; i < config.particle_count; i++)
+        {
+            // break when we reach a new cell key
+            let other_particle_cell_key = spatial_lookup[i][0];
+            if (other_particle_cell_key != curr_cell_key) { break; }
+
+            // skip if comparing particle against itself
+            let other_particle_index = spatial_lookup[i][1];
+            if (other_particle_index == curr_particle_index) { continue; }
+
+            let delta = curr_particle_position - predicted_positions[other_particle_index];
+            let sqr_distance = dot(delta, delta);
+
+            // skip if particle not within sqr radius
+            if (sqr_distance > sqr_radius) { continue; }
+
+            let difference = particle_temperatures[other_particle_index].x - curr_temperature;
+            let weight = density_kernel(sqrt(sqr_distance)) / max(particle_densities[other_particle_index][0], 0.0001);
+            accumulate(&exchange, vec2(difference * weight, 0.0));
+        }
     }
-    let index_right = index_left + right_step_size;
-    
-    // Exit if out of bounds (for non-power of 2 input sizes)
-	if (index_right >= sorting_params.n) { return; }
+    return exchange.sum.x;
+}
 
-    let value_left = spatial_lookup[index_left][0];
-    let value_right = spatial_lookup[index_right][0];
+fn on_heater(position: vec2<f32>) -> bool
+{
+    let x_min = config.screen_bounds[0];
+    let x_max = config.screen_bounds[1];
+    let y_min = config.screen_bounds[2];
+    if (position.y > y_min + config.heater_height) { return false; }
 
-    if (value_left > value_right)
+    let along_floor = (position.x - x_min) / (x_max - x_min);
+    for (var h = 0u; h < config.heater_count; h++)
     {
-        let temp = spatial_lookup[index_left];
-        spatial_lookup[index_left] = spatial_lookup[index_right];
-        spatial_lookup[index_right] = temp;
+        let span = config.heater_spans[h / 2u];
+        let bounds = select(span.xy, span.zw, h % 2u == 1u);
+        if (along_floor >= bounds.x && along_floor <= bounds.y) { return true; }
     }
+    return false;
 }
 
-@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
-fn calculate_spatial_lookup_offsets(@builtin(global_invocation_id) id: vec3<u32>)
+// Diffuse heat between neighbors and lose it to the surroundings; particles over a heater are
+// held at its temperature. Neighbors read the current temperature while this writes the next,
+// which pre_simulation_step makes current at the start of the following step.
+fn update_temperature(i: u32)
 {
-    let i = id.x;
-    if (i >= config.particle_count) { return; }
+    if (config.temperature_enabled == 0u) { return; }
 
-    let key = spatial_lookup[i][0];
-    var key_prev = 0xFFFFFFFFu;
-    
-    if (i > 0u)
+    let temperature = particle_temperatures[i].x;
+    var next = temperature
+        + calculate_heat_exchange(i) * config.heat_diffusion * config.substep_delta_time
+        + (config.ambient_temperature - temperature) * config.cooling_rate * config.substep_delta_time;
+    if (on_heater(particles[i].position))
     {
-        key_prev = spatial_lookup[i - 1u][0];
+        next = config.heater_temperature;
     }
+    particle_temperatures[i].y = next;
+}
+
+// Boussinesq buoyancy: warmer than ambient fluid is pushed against gravity, colder fluid with it
+fn apply_buoyancy(i: u32)
+{
+    if (config.temperature_enabled == 0u) { return; }
+
+    let excess = particle_temperatures[i].x - config.ambient_temperature;
+    particles[i].velocity -= config.gravity * config.thermal_expansion * excess * config.substep_delta_time;
+}
+
+/* ----------------------------------- POSITION BASED FLUIDS -----------------------------------*/
+// Instead of pressure forces, each step projects the predicted positions onto the density
+// constraint C = density / target_density - 1 a few times (Macklin and Mueller 2013). Only
+// compression is corrected, so the free surface doesn't clump. While the solver runs the near
+// density slot holds each particle's lambda, and the velocity holds its pending position
+// correction between the correction and apply passes.
+
+// density and the constraint's lambda from the predicted positions
+fn calculate_pbf_lambda(curr_particle_index: u32) -> vec2<f32>
+{
+    var density = 0.0;
+    var gradient_sum = vec2(0.0);       // this particle's gradient of its own constraint, times the target density
+    var gradient_sqr_sum = 0.0;         // squared gradients with respect to the neighbors, times the same squared
 
-    if (key != key_prev)
+    let curr_particle_position = predicted_positions[curr_particle_index];
+
+    let cell = position_to_cell_coord(curr_particle_position);
+
+    let sqr_radius = config.smoothing_radius * config.smoothing_radius;
+
+    for (var i: u32; i < 9u; i++)
     {
-        spatial_lookup_offsets[key] = i;
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
+
+        let curr_cell_key = get_cell_key(neighbor_cell);
+        let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
+
+        // loop through neighboring particles
+        for (var i: u32 = start_idx; i < config.particle_count; i++)
+        {
+            // break when we reach a new cell key
+            let other_particle_cell_key = spatial_lookup[i][0];
+            if (other_particle_cell_key != curr_cell_key) { break; }
+
+            let other_particle_index = spatial_lookup[i][1];
+            let offset = curr_particle_position - predicted_positions[other_particle_index];
+            let sqr_distance = dot(offset, offset);
+
+            // skip if particle not within sqr radius
+            if (sqr_distance > sqr_radius) { continue; }
+            let distance = sqrt(sqr_distance);
+
+            density += density_kernel(distance);
+
+            // the particle itself and exact overlaps have no direction to push along
+            if (other_particle_index == curr_particle_index || distance < 0.0001f) { continue; }
+            let gradient = offset / distance * density_kernel_derivative(distance);
+            gradient_sum += gradient;
+            gradient_sqr_sum += dot(gradient, gradient);
+        }
     }
+
+    let target_density = config.target_density;
+    let constraint = max(density / target_density - 1.0, 0.0);
+    let gradient_term = (dot(gradient_sum, gradient_sum) + gradient_sqr_sum) / (target_density * target_density);
+    return vec2(density, -constraint / (gradient_term + config.pbf_relaxation));
 }
 
+// position correction from this particle's and its neighbors' lambdas
+fn calculate_pbf_correction(curr_particle_index: u32) -> vec2<f32>
+{
+    var correction = vec2(0.0);
+
+    let lambda = particle_densities[curr_particle_index][1];
+    let curr_particle_position = predicted_positions[curr_particle_index];
+
+    let cell = position_to_cell_coord(curr_particle_position);
+
+    let sqr_radius = config.smoothing_radius * config.smoothing_radius;
+
+    for (var i: u32; i < 9u; i++)
+    {
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
 
+        let curr_cell_key = get_cell_key(neighbor_cell);
+        let start_idx = spatial_lookup_offsets[curr_cell_key];   // calculate start idx of this cell key within spatial lookup
 
+        // loop through neighboring particles
+        for (var i: u32 = start_idx; i < config.particle_count; i++)
+        {
+            // break when we reach a new cell key
+            let other_particle_cell_key = spatial_lookup[i][0];
+            if (other_particle_cell_key != curr_cell_key) { break; }
+
+            // skip if comparing particle against itself
+            let other_particle_index = spatial_lookup[i][1];
+            if (other_particle_index == curr_particle_index) { continue; }
+
+            let offset = curr_particle_position - predicted_positions[other_particle_index];
+            let sqr_distance = dot(offset, offset);
+
+            // skip if particle not within sqr radius
+            if (sqr_distance > sqr_radius) { continue; }
+            let distance = sqrt(sqr_distance);
+            if (distance < 0.0001f) { continue; }
+
+            let neighbor_lambda = particle_densities[other_particle_index][1];
+            correction += (lambda + neighbor_lambda) * density_kernel_derivative(distance) * offset / distance;
+        }
+    }
+    return correction / config.target_density;
+}
+
+/* ----------------------------------- ENTRY POINT FUNCTIONS -----------------------------------*/
+@compute @workgroup_size(64, 1, 1)
+fn pre_simulation_step(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= arrayLength(&particles)) {
+        return;
+    }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    if (config.temperature_enabled != 0u)
+    {
+        particle_temperatures[i].x = particle_temperatures[i].y;
+    }
+
+    apply_gravity(i);
+    
+    update_predicted_positions(i);
+
+    update_particle_density(i);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn simulation_step(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= arrayLength(&particles)) {
+        return;
+    }
+
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    apply_pressure_force(i);
+
+    apply_viscocity_force(i);
+
+    update_temperature(i);
+
+    apply_buoyancy(i);
+
+    apply_interaction_force(i);
+
+    apply_fan_force(i);
+
+    apply_force_fields(i);
+
+    apply_impulse(i);
+
+    apply_surrogate_correction(i);
+
+    update_particle_positions(i);
+
+    resolve_obstacle_collisions(i);
+
+    check_screen_bounds(i);
+
+    update_velocity_history(i);
+}
+
+// PBF: every force acts on the velocity before the positions are predicted
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn pbf_predict(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= arrayLength(&particles)) { return; }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    if (config.temperature_enabled != 0u)
+    {
+        particle_temperatures[i].x = particle_temperatures[i].y;
+    }
+
+    apply_gravity(i);
+    apply_buoyancy(i);
+    apply_interaction_force(i);
+    apply_fan_force(i);
+    apply_force_fields(i);
+    apply_impulse(i);
+    apply_surrogate_correction(i);
+
+    update_predicted_positions(i);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn pbf_compute_lambdas(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= arrayLength(&particles)) { return; }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    particle_densities[i] = calculate_pbf_lambda(i);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn pbf_compute_corrections(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= arrayLength(&particles)) { return; }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    particles[i].velocity = calculate_pbf_correction(i);
+}
+
+// a separate pass so every correction in an iteration reads the same predicted positions; the
+// velocity is then the one that moves the particle to its corrected position
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn pbf_apply_corrections(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= arrayLength(&particles)) { return; }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    predicted_positions[i] += particles[i].velocity;
+    particles[i].velocity = (predicted_positions[i] - particles[i].position) / config.substep_delta_time;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn pbf_finalize(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= arrayLength(&particles)) { return; }
+    if (config.frame_count < SHADER_DELAY) { return; }
+    if (is_drained(i)) { return; }
+
+    apply_viscocity_force(i);
+
+    update_temperature(i);
+
+    particles[i].position = predicted_positions[i];
+
+    resolve_obstacle_collisions(i);
+
+    check_screen_bounds(i);
+
+    update_velocity_history(i);
+}
+
+/* ------------------------------ COUNTING SORT ------------------------------*/
+// Keys are bounded by the spatial grid's cell count, so the spatial lookup is sorted with a counting sort:
+// count particles per key, exclusive prefix sum the counts (which gives each key's start index,
+// i.e. the spatial lookup offsets), then scatter every particle to its key's start plus its rank.
+
+var<workgroup> scan_block: array<u32, SCAN_BLOCK_SIZE>;
+
+// inclusive Hillis-Steele scan of scan_block, must be called from uniform control flow
+fn scan_workgroup(t: u32)
+{
+    for (var stride = 1u; stride < SCAN_BLOCK_SIZE; stride <<= 1u)
+    {
+        var value = scan_block[t];
+        if (t >= stride)
+        {
+            value += scan_block[t - stride];
+        }
+        workgroupBarrier();
+        scan_block[t] = value;
+        workgroupBarrier();
+    }
+}
+
+// With SUBGROUP_OPERATIONS (a shader def the subgroup sort backend selects) each subgroup scans
+// its lanes in registers and one thread scans the few subgroup totals, two barriers in all instead
+// of two per stride. That's only in invocation order if every subgroup is the run of invocations
+// starting at a multiple of its size with its lanes in order, which drivers aren't required to
+// do, so the layout is checked first and the workgroup falls back to scan_workgroup otherwise.
+#ifdef SUBGROUP_OPERATIONS
+var<workgroup> scan_subgroup_totals: array<u32, SCAN_BLOCK_SIZE>;
+var<workgroup> scan_layout_mismatch: u32;
+
+fn scan_workgroup_subgroups(t: u32, lane: u32, subgroup_size: u32)
+{
+    // workgroup memory isn't zero initialized, so the flag is cleared before any subgroup sets it
+    if (t == 0u)
+    {
+        scan_layout_mismatch = 0u;
+    }
+    workgroupBarrier();
+
+    let subgroup = t / subgroup_size;
+    let same_subgroup = subgroupAll(subgroup == subgroupBroadcastFirst(subgroup));
+    if (lane != t % subgroup_size || !same_subgroup)
+    {
+        scan_layout_mismatch = 1u;
+    }
+    if (workgroupUniformLoad(&scan_layout_mismatch) != 0u)
+    {
+        scan_workgroup(t);
+        return;
+    }
+
+    let inclusive = subgroupInclusiveAdd(scan_block[t]);
+    if (lane == subgroup_size - 1u)
+    {
+        scan_subgroup_totals[subgroup] = inclusive;
+    }
+    workgroupBarrier();
+
+    if (t == 0u)
+    {
+        var running_total = 0u;
+        for (var s = 0u; s < SCAN_BLOCK_SIZE / subgroup_size; s++)
+        {
+            let subgroup_total = scan_subgroup_totals[s];
+            scan_subgroup_totals[s] = running_total;
+            running_total += subgroup_total;
+        }
+    }
+    workgroupBarrier();
+
+    scan_block[t] = inclusive + scan_subgroup_totals[subgroup];
+    workgroupBarrier();
+}
+#endif
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn clear_key_counts(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= spatial_grid_cell_count()) { return; }
+
+    atomicStore(&key_counts[i], 0u);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn bin_particles_in_grid(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= config.particle_count) {
+        return;
+    }
+
+    let cell_key = get_cell_key(position_to_cell_coord(particles[i].position));
+
+    let rank = atomicAdd(&key_counts[cell_key], 1u);
+    sort_scratch[i] = vec2(cell_key, rank);
+}
+
+// exclusive prefix sum of the key counts within each block, plus each block's total
+@compute @workgroup_size(SCAN_BLOCK_SIZE, 1, 1)
+fn scan_key_counts(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+#ifdef SUBGROUP_OPERATIONS
+    @builtin(subgroup_invocation_id) lane: u32,
+    @builtin(subgroup_size) subgroup_size: u32,
+#endif
+)
+{
+    let i = id.x;
+    let t = local_id.x;
+
+    var count = 0u;
+    if (i < spatial_grid_cell_count())
+    {
+        count = atomicLoad(&key_counts[i]);
+    }
+    scan_block[t] = count;
+    workgroupBarrier();
+
+#ifdef SUBGROUP_OPERATIONS
+    scan_workgroup_subgroups(t, lane, subgroup_size);
+#else
+    scan_workgroup(t);
+#endif
+
+    if (i < spatial_grid_cell_count())
+    {
+        spatial_lookup_offsets[i] = scan_block[t] - count;
+    }
+    if (t == SCAN_BLOCK_SIZE - 1u)
+    {
+        scan_block_totals[group_id.x] = scan_block[t];
+    }
+}
+
+// exclusive prefix sum of the block totals in a single workgroup, each thread
+// summing a contiguous run of blocks so any cell count fits
+@compute @workgroup_size(SCAN_BLOCK_SIZE, 1, 1)
+fn scan_block_sums(
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+#ifdef SUBGROUP_OPERATIONS
+    @builtin(subgroup_invocation_id) lane: u32,
+    @builtin(subgroup_size) subgroup_size: u32,
+#endif
+)
+{
+    let t = local_id.x;
+    let block_count = (spatial_grid_cell_count() + SCAN_BLOCK_SIZE - 1u) / SCAN_BLOCK_SIZE;
+    let blocks_per_thread = (block_count + SCAN_BLOCK_SIZE - 1u) / SCAN_BLOCK_SIZE;
+    let first_block = t * blocks_per_thread;
+
+    // loop bounds stay uniform so the barriers below are reached by every thread
+    var thread_total = 0u;
+    for (var k = 0u; k < blocks_per_thread; k++)
+    {
+        let b = first_block + k;
+        if (b < block_count)
+        {
+            thread_total += scan_block_totals[b];
+        }
+    }
+    scan_block[t] = thread_total;
+    workgroupBarrier();
+
+#ifdef SUBGROUP_OPERATIONS
+    scan_workgroup_subgroups(t, lane, subgroup_size);
+#else
+    scan_workgroup(t);
+#endif
+
+    var running_total = scan_block[t] - thread_total;
+    for (var k = 0u; k < blocks_per_thread; k++)
+    {
+        let b = first_block + k;
+        if (b < block_count)
+        {
+            let block_total = scan_block_totals[b];
+            scan_block_totals[b] = running_total;
+            running_total += block_total;
+        }
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn add_block_offsets(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= spatial_grid_cell_count()) { return; }
+
+    spatial_lookup_offsets[i] += scan_block_totals[i / SCAN_BLOCK_SIZE];
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn sort_particles(@builtin(global_invocation_id) id: vec3<u32>) 
+{
+    let i = id.x;
+    if (i >= config.particle_count) { return; }
+
+    let cell_key = sort_scratch[i][0];
+    let rank = sort_scratch[i][1];
+    spatial_lookup[spatial_lookup_offsets[cell_key] + rank] = vec2(cell_key, i);
+}
+
+// The ranks handed out by the atomics in bin_particles_in_grid vary from run to run, and with
+// them the order the neighbor loops sum in. Seeded runs insertion sort each cell's run of the
+// lookup by particle index so the float sums come out the same every time.
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn order_cell_contents(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let cell_key = id.x;
+    if (cell_key >= spatial_grid_cell_count()) { return; }
+
+    let start = spatial_lookup_offsets[cell_key];
+    let end = start + atomicLoad(&key_counts[cell_key]);
+    for (var i = start + 1u; i < end; i++)
+    {
+        let entry = spatial_lookup[i];
+        var j = i;
+        while (j > start && spatial_lookup[j - 1u][1] > entry[1])
+        {
+            spatial_lookup[j] = spatial_lookup[j - 1u];
+            j--;
+        }
+        spatial_lookup[j] = entry;
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn clear_scalar_grid(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= scalar_grid_cell_count()) { return; }
+
+    for (var k: u32 = 0u; k < 4u; k++)
+    {
+        atomicStore(&scalar_grid_accumulation[i * 4u + k], 0);
+    }
+}
+
+// bilinearly splat each particle's velocity and dye into the 4 surrounding cells
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn splat_particles_to_scalar_grid(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= config.particle_count) { return; }
+
+    let velocity = particles[i].velocity;
+
+    // fast particles deposit more dye, leaving smoke trails behind moving fluid
+    let energy = 0.5 * dot(velocity, velocity);
+    let dye = clamp(energy / config.max_energy, 0.0, 1.0);
+
+    let grid_position = world_to_scalar_grid(particles[i].position);
+    let base = vec2<i32>(floor(grid_position));
+    let t = grid_position - floor(grid_position);
+
+    for (var dy: i32 = 0; dy < 2; dy++)
+    {
+        for (var dx: i32 = 0; dx < 2; dx++)
+        {
+            let cell = base + vec2(dx, dy);
+            if (cell.x < 0 || cell.y < 0 ||
+                cell.x >= i32(config.scalar_grid_width) || cell.y >= i32(config.scalar_grid_height)) { continue; }
+
+            let weight_x = select(1.0 - t.x, t.x, dx == 1);
+            let weight_y = select(1.0 - t.y, t.y, dy == 1);
+            let weight = weight_x * weight_y;
+
+            let index = scalar_grid_index(cell) * 4u;
+            atomicAdd(&scalar_grid_accumulation[index + 0u], i32(velocity.x * weight * GRID_FIXED_POINT_SCALE));
+            atomicAdd(&scalar_grid_accumulation[index + 1u], i32(velocity.y * weight * GRID_FIXED_POINT_SCALE));
+            atomicAdd(&scalar_grid_accumulation[index + 2u], i32(weight * GRID_FIXED_POINT_SCALE));
+            atomicAdd(&scalar_grid_accumulation[index + 3u], i32(dye * weight * GRID_FIXED_POINT_SCALE));
+        }
+    }
+}
+
+// average the splatted velocities into a velocity per cell
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn resolve_grid_velocities(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= scalar_grid_cell_count()) { return; }
+
+    let weight = scalar_grid_cell_weight(i);
+    var velocity = vec2(0f, 0f);
+    if (weight > GRID_EMPTY_WEIGHT)
+    {
+        velocity = vec2(
+            f32(atomicLoad(&scalar_grid_accumulation[i * 4u + 0u])),
+            f32(atomicLoad(&scalar_grid_accumulation[i * 4u + 1u]))
+        ) / GRID_FIXED_POINT_SCALE / weight;
+    }
+    scalar_grid_velocities[i] = velocity;
+}
+
+// semi-Lagrangian advect the scalar field from one half into the other
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn advect_scalar_field(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    let cell_count = scalar_grid_cell_count();
+    if (i >= cell_count) { return; }
+
+    let read_offset = (config.frame_count % 2u) * cell_count;
+    let write_offset = ((config.frame_count + 1u) % 2u) * cell_count;
+
+    let velocity = scalar_grid_velocities[i];
+    let dye = f32(atomicLoad(&scalar_grid_accumulation[i * 4u + 3u])) / GRID_FIXED_POINT_SCALE;
+
+    // trace back along the cell velocity and sample where the scalar came from
+    let cell = vec2<f32>(scalar_grid_cell(i));
+    let source = cell - velocity * config.fixed_delta_time / config.scalar_grid_cell_size;
+    let advected = sample_scalar_field(source, read_offset);
+
+    scalar_field[write_offset + i] = (advected + dye * config.smoke_injection * config.fixed_delta_time) * config.smoke_dissipation;
+}
+
+/* ----------------------------------- FLIP/PIC PROJECTION -----------------------------------*/
+// central difference divergence of the resolved velocities, zero in empty cells
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn compute_grid_divergence(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    let cell_count = scalar_grid_cell_count();
+    if (i >= cell_count) { return; }
+
+    var divergence = 0f;
+    if (scalar_grid_cell_weight(i) > GRID_EMPTY_WEIGHT)
+    {
+        let cell = scalar_grid_cell(i);
+        let right = scalar_grid_velocities[scalar_grid_neighbor(cell, vec2(1, 0))].x;
+        let left = scalar_grid_velocities[scalar_grid_neighbor(cell, vec2(-1, 0))].x;
+        let top = scalar_grid_velocities[scalar_grid_neighbor(cell, vec2(0, 1))].y;
+        let bottom = scalar_grid_velocities[scalar_grid_neighbor(cell, vec2(0, -1))].y;
+        divergence = (right - left + top - bottom) / (2f * config.scalar_grid_cell_size);
+    }
+
+    grid_pressure[i] = divergence;
+    grid_pressure[cell_count + i] = 0f;         // pressure guess
+    grid_pressure[2u * cell_count + i] = 0f;
+}
+
+// one Jacobi iteration of the pressure poisson equation, empty cells are a free surface (p = 0)
+fn jacobi_pressure(i: u32, read_offset: u32, write_offset: u32)
+{
+    let cell_count = scalar_grid_cell_count();
+    if (scalar_grid_cell_weight(i) <= GRID_EMPTY_WEIGHT)
+    {
+        grid_pressure[write_offset + i] = 0f;
+        return;
+    }
+
+    let cell = scalar_grid_cell(i);
+    var neighbor_sum = 0f;
+    var offsets = array<vec2<i32>, 4>(vec2(1, 0), vec2(-1, 0), vec2(0, 1), vec2(0, -1));
+    for (var k: u32 = 0u; k < 4u; k++)
+    {
+        let neighbor = scalar_grid_neighbor(cell, offsets[k]);
+        if (neighbor == i) { neighbor_sum += grid_pressure[read_offset + i]; }     // domain wall
+        else if (scalar_grid_cell_weight(neighbor) > GRID_EMPTY_WEIGHT) { neighbor_sum += grid_pressure[read_offset + neighbor]; }
+    }
+
+    let h = config.scalar_grid_cell_size;
+    grid_pressure[write_offset + i] = (neighbor_sum - grid_pressure[i] * h * h) * 0.25;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn jacobi_pressure_forward(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let cell_count = scalar_grid_cell_count();
+    if (id.x >= cell_count) { return; }
+    jacobi_pressure(id.x, cell_count, 2u * cell_count);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn jacobi_pressure_backward(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let cell_count = scalar_grid_cell_count();
+    if (id.x >= cell_count) { return; }
+    jacobi_pressure(id.x, 2u * cell_count, cell_count);
+}
+
+// subtract the pressure gradient, writing divergence free velocities to the second half
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn project_grid_velocities(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    let cell_count = scalar_grid_cell_count();
+    if (i >= cell_count) { return; }
+
+    var velocity = scalar_grid_velocities[i];
+    if (scalar_grid_cell_weight(i) > GRID_EMPTY_WEIGHT)
+    {
+        // iterations run in forward/backward pairs, so the result is always in the first pressure field
+        let cell = scalar_grid_cell(i);
+        let right = grid_pressure[cell_count + scalar_grid_neighbor(cell, vec2(1, 0))];
+        let left = grid_pressure[cell_count + scalar_grid_neighbor(cell, vec2(-1, 0))];
+        let top = grid_pressure[cell_count + scalar_grid_neighbor(cell, vec2(0, 1))];
+        let bottom = grid_pressure[cell_count + scalar_grid_neighbor(cell, vec2(0, -1))];
+        velocity -= vec2(right - left, top - bottom) / (2f * config.scalar_grid_cell_size);
+    }
+    scalar_grid_velocities[cell_count + i] = velocity;
+}
+
+// blend the projected grid velocity back onto particles: PIC takes it directly,
+// FLIP adds only the change the projection made
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn transfer_grid_to_particles(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= config.particle_count || is_drained(i)) { return; }
+
+    let grid_position = world_to_scalar_grid(particles[i].position);
+    let resolved = sample_grid_velocity(grid_position, 0u);
+    let projected = sample_grid_velocity(grid_position, scalar_grid_cell_count());
+
+    let pic_velocity = projected;
+    let flip_velocity = particles[i].velocity + projected - resolved;
+    particles[i].velocity = mix(pic_velocity, flip_velocity, config.flip_ratio);
+}
+
+/* ------------------------------ PRESSURE PROBES ------------------------------*/
+// one thread per probe after the sim step, so the samples use the same neighbors and predicted
+// positions as this step's particle densities. Each frame goes into its own row of the ring,
+// tagged with the frame, and the CPU picks up the rows it hasn't seen on every readback.
+@compute @workgroup_size(MAX_PRESSURE_PROBES, 1, 1)
+fn sample_pressure_probes(@builtin(local_invocation_id) local_id: vec3<u32>)
+{
+    let probe = local_id.x;
+    if (probe >= pressure_probes.count) { return; }
+
+    let density = density_at(pressure_probes.positions[probe].xy);
+    let slot = (config.frame_count % PRESSURE_PROBE_HISTORY) * MAX_PRESSURE_PROBES + probe;
+    pressure_probes.samples[slot] = vec4<f32>(density, density_to_pressure(density.x), bitcast<f32>(config.frame_count));
+}
+
+/* ------------------------------ STATS REDUCTION ------------------------------*/
+// Only dispatched when the stats overlay wants a new sample. Each workgroup reduces its particles
+// to two partials in workgroup memory and the CPU adds up the partials after the readback.
+
+var<workgroup> stats_block: array<vec4<f32>, STATS_WORKGROUP_SIZE>;
+var<workgroup> stats_turbulence_block: array<f32, STATS_WORKGROUP_SIZE>;
+#ifdef SUBGROUP_OPERATIONS
+var<workgroup> stats_subgroup_count: atomic<u32>;
+#endif
+
+@compute @workgroup_size(STATS_HISTOGRAM_BINS, 1, 1)
+fn clear_stats_histogram(@builtin(local_invocation_id) local_id: vec3<u32>)
+{
+    atomicStore(&stats.histogram[local_id.x], 0u);
+}
+
+@compute @workgroup_size(STATS_WORKGROUP_SIZE, 1, 1)
+fn reduce_stats(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+#ifdef SUBGROUP_OPERATIONS
+    @builtin(subgroup_invocation_id) lane: u32,
+#endif
+)
+{
+    let i = id.x;
+    let t = local_id.x;
+
+    var value = vec4<f32>(0.0);
+    var turbulence = 0.0;
+    if (i < config.particle_count)
+    {
+        // drained particles aren't part of the fluid any more
+        if (!is_drained(i))
+        {
+            let speed = length(particles[i].velocity);
+            value = vec4<f32>(particle_densities[i][0], 0.5 * speed * speed, speed, 1.0);
+            turbulence = sqrt(velocity_history[i].w);
+        }
+    }
+
+    // there can be more cells than particles, so each thread bins every cell a dispatch apart
+    for (var cell = i; cell < spatial_grid_cell_count(); cell += num_workgroups.x * STATS_WORKGROUP_SIZE)
+    {
+        let count = atomicLoad(&key_counts[cell]);
+        if (count > 0u)
+        {
+            atomicAdd(&stats.histogram[min(count, STATS_HISTOGRAM_BINS) - 1u], 1u);
+        }
+    }
+#ifdef SUBGROUP_OPERATIONS
+    // each subgroup reduces its lanes in registers, then one thread adds up the subgroups. The
+    // order doesn't matter here, so each subgroup takes the next free slot rather than assuming
+    // how the invocations are laid out. Workgroup memory isn't zero initialized, so the slot
+    // counter is cleared before any subgroup takes one
+    if (t == 0u)
+    {
+        atomicStore(&stats_subgroup_count, 0u);
+    }
+    workgroupBarrier();
+
+    let subgroup_value = vec4<f32>(subgroupAdd(value.xy), subgroupMax(value.z), subgroupAdd(value.w));
+    let subgroup_turbulence = subgroupAdd(turbulence);
+    if (lane == subgroupBroadcastFirst(lane))
+    {
+        let subgroup = atomicAdd(&stats_subgroup_count, 1u);
+        stats_block[subgroup] = subgroup_value;
+        stats_turbulence_block[subgroup] = subgroup_turbulence;
+    }
+    workgroupBarrier();
+
+    if (t == 0u)
+    {
+        var total = stats_block[0];
+        var turbulence_total = stats_turbulence_block[0];
+        for (var s = 1u; s < atomicLoad(&stats_subgroup_count); s++)
+        {
+            let other = stats_block[s];
+            total = vec4<f32>(total.xy + other.xy, max(total.z, other.z), total.w + other.w);
+            turbulence_total += stats_turbulence_block[s];
+        }
+        stats.partials[group_id.x * 2u] = total;
+        stats.partials[group_id.x * 2u + 1u] = vec4<f32>(turbulence_total, 0.0, 0.0, 0.0);
+    }
+#else
+    stats_block[t] = value;
+    stats_turbulence_block[t] = turbulence;
+    workgroupBarrier();
+
+    // tree reduction: sums for density, energy and count, max for speed
+    for (var stride = STATS_WORKGROUP_SIZE / 2u; stride > 0u; stride >>= 1u)
+    {
+        if (t < stride)
+        {
+            let current = stats_block[t];
+            let other = stats_block[t + stride];
+            stats_block[t] = vec4<f32>(current.xy + other.xy, max(current.z, other.z), current.w + other.w);
+            stats_turbulence_block[t] += stats_turbulence_block[t + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (t == 0u)
+    {
+        stats.partials[group_id.x * 2u] = stats_block[0];
+        stats.partials[group_id.x * 2u + 1u] = vec4<f32>(stats_turbulence_block[0], 0.0, 0.0, 0.0);
+    }
+#endif
+}
+
+/* ------------------------------ MAX SPEED REDUCTION ------------------------------*/
+// Only dispatched on the main system while adaptive substepping is on, after the frame's last
+// substep. Non-negative floats order the same as their bits, so an atomicMax on the bits per
+// workgroup and then globally is enough.
+var<workgroup> workgroup_max_speed: atomic<u32>;
+
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn reduce_max_speed(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) t: u32,
+)
+{
+    // workgroup memory isn't zero initialized for these pipelines
+    if (t == 0u) { atomicStore(&workgroup_max_speed, 0u); }
+    workgroupBarrier();
+
+    let i = id.x;
+    if (i < config.particle_count && !is_drained(i))
+    {
+        atomicMax(&workgroup_max_speed, bitcast<u32>(length(particles[i].velocity)));
+    }
+    workgroupBarrier();
+
+    if (t == 0u) { atomicMax(&max_speed, atomicLoad(&workgroup_max_speed)); }
+}
+
+/* ------------------------------ TRAILS ------------------------------*/
+// Once a frame after the last substep, while trails are drawn: the render shader reads the ring
+// back from this frame's slot.
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn record_trail_positions(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let i = id.x;
+    if (i >= config.particle_count) { return; }
+
+    trail_positions[i * TRAIL_HISTORY + config.frame_count % TRAIL_HISTORY] = particles[i].position;
+}
+
+/* ------------------------------ VELOCITY FIELD ------------------------------*/
+// samples per side, velocity_field_spacing apart across the screen bounds and capped to the buffer;
+// must match the render shader's
+fn velocity_field_dims() -> vec2<u32>
+{
+    let extent = vec2(config.screen_bounds[1] - config.screen_bounds[0], config.screen_bounds[3] - config.screen_bounds[2]);
+    let samples = ceil(extent / max(config.velocity_field_spacing, 1.0));
+    return clamp(vec2<u32>(samples), vec2(1u), vec2(VELOCITY_FIELD_MAX_SIDE));
+}
+
+// Once a frame after the last substep, while the field is drawn: the density kernel weighted
+// average of the particle velocities within a smoothing radius of each sample, zero where there's
+// no fluid.
+@compute @workgroup_size(WORKGROUP_SIZE, 1, 1)
+fn sample_velocity_field(@builtin(global_invocation_id) id: vec3<u32>)
+{
+    let dims = velocity_field_dims();
+    let sample_index = id.x;
+    if (sample_index >= dims.x * dims.y) { return; }
+
+    let origin = vec2(config.screen_bounds[0], config.screen_bounds[2]);
+    let extent = vec2(config.screen_bounds[1], config.screen_bounds[3]) - origin;
+    let position = origin + (vec2<f32>(vec2(sample_index % dims.x, sample_index / dims.x)) + vec2(0.5)) * extent / vec2<f32>(dims);
+
+    var velocity = vec2(0.0);
+    var weight = 0.0;
+
+    let cell = position_to_cell_coord(position);
+    let sqr_radius = config.smoothing_radius * config.smoothing_radius;
+
+    for (var i: u32; i < 9u; i++)
+    {
+        let neighbor_cell = cell + GRID_OFFSETS[i];
+        if (!in_spatial_grid(neighbor_cell)) { continue; }
+
+        let curr_cell_key = get_cell_key(neighbor_cell);
+        let start_idx = spatial_lookup_offsets[curr_cell_key];
+
+        for (var i: u32 = start_idx; i < config.particle_count; i++)
+        {
+            if (spatial_lookup[i][0] != curr_cell_key) { break; }
+
+            let other_particle_index = spatial_lookup[i][1];
+            let delta = position - predicted_positions[other_particle_index];
+            let sqr_distance = dot(delta, delta);
+            if (sqr_distance > sqr_radius) { continue; }
+
+            let kernel = density_kernel(sqrt(sqr_distance));
+            velocity += particles[other_particle_index].velocity * kernel;
+            weight += kernel;
+        }
+    }
+
+    velocity_field[sample_index] = select(vec2(0.0), velocity / weight, weight > 1e-12);
+}
@@ -7,18 +7,124 @@ struct Config {
     damping_factor: f32,            // 4 bytes
     fixed_delta_time: f32,          // 4 bytes
     frame_count: u32,               // 4 bytes
-    gravity: f32,                   // 4 bytes
+    velocity_history_frames: f32,   // 4 bytes     length of the velocity variance window
+
+    gravity: vec2<f32>,             // 8 bytes     pixels/s^2, (0, -g) pulls straight down
+    _gravity_padding: vec2<f32>,    // 8 bytes
 
     density_kernel_norm: f32,       // 4 bytes
     near_density_kernel_norm: f32,  // 4 bytes
     viscocity_kernel_norm: f32,     // 4 bytes
-    _padding: f32,                  // 4 bytes
+    paused: u32,                    // 4 bytes
     
     target_density: f32,            // 4 bytes
     pressure_multiplier: f32,       // 4 bytes
     viscocity_strength: f32,        // 4 bytes
     near_density_multiplier: f32,   // 4 bytes
 
+    spatial_grid_width: u32,        // 4 bytes     neighbor search cells, one smoothing radius across
+    spatial_grid_height: u32,       // 4 bytes
+    _spatial_grid_padding: vec2<f32>,// 8 bytes
+
+    scalar_grid_width: u32,         // 4 bytes
+    scalar_grid_height: u32,        // 4 bytes
+    scalar_grid_cell_size: f32,     // 4 bytes
+    smoke_enabled: u32,             // 4 bytes
+
+    smoke_injection: f32,           // 4 bytes
+    smoke_dissipation: f32,         // 4 bytes
+    smoke_opacity: f32,             // 4 bytes
+    divergence_view: u32,           // 4 bytes
+
+    flip_enabled: u32,              // 4 bytes
+    flip_ratio: f32,                // 4 bytes
+    pressure_iterations: u32,       // 4 bytes
+    divergence_range: f32,          // 4 bytes
+
+    color_field: u32,               // 4 bytes
+    colormap: u32,                  // 4 bytes
+    color_min: f32,                 // 4 bytes
+    color_max: f32,                 // 4 bytes
+
+    interaction_position: vec2<f32>,// 8 bytes
+    interaction_strength: f32,      // 4 bytes     > 0 attracts, < 0 repels, 0 when idle
+    interaction_radius: f32,        // 4 bytes
+
+    impulse_position: vec2<f32>,    // 8 bytes
+    impulse_strength: f32,          // 4 bytes     > 0 pushes out, < 0 pulls in, 0 outside the frame it fires
+    impulse_radius: f32,            // 4 bytes
+
+    fan_origin: vec2<f32>,          // 8 bytes
+    fan_direction: vec2<f32>,       // 8 bytes     unit vector
+    fan_strength: f32,              // 4 bytes     0 while the fan is off
+    fan_reach: f32,                 // 4 bytes
+    fan_cos_half_angle: f32,        // 4 bytes
+    _fan_padding: f32,              // 4 bytes
+
+    render_mode: u32,               // 4 bytes     0 draws particles, 1 a smoothed liquid surface, 2 a density heatmap
+    surface_threshold: f32,         // 4 bytes     blurred thickness at the liquid's edge
+    surface_blur_radius: f32,       // 4 bytes     in pixels
+    heatmap_range: f32,             // 4 bytes     density at the top of the heatmap, in target densities
+
+    surrogate_enabled: u32,         // 4 bytes     a learned velocity correction is in surrogate_correction
+    surrogate_strength: f32,        // 4 bytes
+    compensated_summation: u32,     // 4 bytes     picks the COMPENSATED_SUMMATION pipelines, not read here
+    deterministic_order: u32,       // 4 bytes     picks the order_cell_contents pass, not read here
+
+    temperature_enabled: u32,       // 4 bytes
+    ambient_temperature: f32,       // 4 bytes
+    heat_diffusion: f32,            // 4 bytes     per second, towards the neighbors' temperature
+    cooling_rate: f32,              // 4 bytes     per second, towards the ambient temperature
+
+    thermal_expansion: f32,         // 4 bytes     buoyancy against gravity per degree above ambient
+    heater_temperature: f32,        // 4 bytes
+    heater_height: f32,             // 4 bytes     how far above the floor the heaters reach
+    heater_count: u32,              // 4 bytes
+
+    solver: u32,                    // 4 bytes     picks the pbf_* passes, not read here
+    pbf_iterations: u32,            // 4 bytes     constraint projections per step
+    pbf_relaxation: f32,            // 4 bytes     softens the density constraint where gradients vanish
+    _solver_padding: f32,           // 4 bytes
+
+    substeps: u32,                  // 4 bytes     particle steps this frame, the grid passes still run once
+    substep_delta_time: f32,        // 4 bytes     fixed_delta_time / substeps, the particle passes' time step
+    cfl_number: f32,                // 4 bytes     not read here
+    max_substeps: u32,              // 4 bytes     not read here
+
+    trail_length: u32,              // 4 bytes     recorded positions drawn per trail, 0 or 1 for none
+    trail_opacity: f32,             // 4 bytes     alpha of the newest segment, fading to 0 at the oldest
+    trail_width: f32,               // 4 bytes
+    _trail_padding: f32,            // 4 bytes
+
+    tonemapping: u32,               // 4 bytes     applied to every color written to the screen
+    exposure: f32,                  // 4 bytes     scales linear color before the tonemap
+    _tonemapping_padding: vec2<f32>,// 8 bytes
+
+    density_alpha_enabled: u32,     // 4 bytes     fade particles by local density
+    density_alpha_min: f32,         // 4 bytes     alpha of a particle with no neighbours
+    density_alpha_range: f32,       // 4 bytes     density reaching full alpha, in target densities
+    density_alpha_gamma: f32,       // 4 bytes     curve between the two, above 1 keeps the spray fainter
+
+    velocity_field_mode: u32,       // 4 bytes     off, arrows or streamlines
+    velocity_field_spacing: f32,    // 4 bytes     distance between samples of the velocity field
+    velocity_field_full_speed: f32, // 4 bytes     speed drawn a full sample spacing long
+    _velocity_field_padding: f32,   // 4 bytes
+
+    outline_enabled: u32,           // 4 bytes     silhouette outline from the surface texture
+    outline_width: f32,             // 4 bytes     in pixels
+    _outline_padding: vec2<f32>,    // 8 bytes
+    outline_color: vec4<f32>,       // 16 bytes    linear rgb, alpha
+
+    retro_enabled: u32,             // 4 bytes     quantize the system's drawing to the palette in a post pass
+    palette_size: u32,              // 4 bytes     colors used from the start of `palette`
+    palette_dither: f32,            // 4 bytes     ordered dither strength, 0 for flat bands
+    retro_pixel_size: f32,          // 4 bytes     screen pixels per retro pixel
+    palette: array<vec4<f32>, MAX_PALETTE_COLORS>,   // 256 bytes   srgb, the w unused
+
+    heater_spans: array<vec4<f32>, 2>,  // 32 bytes     start, end pairs along the floor as fractions of its width
+
+    boundary_modes: vec4<u32>,      // 16 bytes     BOUNDARY_* of the left, right, bottom and top edges
+
     screen_bounds: vec4<f32>,       // 16 bytes     [x_min, x_max, y_min, y_max]
     view_proj: mat4x4<f32>,         // 64 bytes
 }
@@ -42,11 +148,207 @@ struct VertexOutput {
 }
 
 @group(0) @binding(0)
-var<storage, read_write> particles: array<Particle>;
+var<storage, read> particles: array<Particle>;
 
 @group(0) @binding(1)
 var<uniform> config: Config;
 
+@group(0) @binding(5)
+var<storage, read> particle_densities: array<vec2<f32>>;  // density, near_density
+
+@group(0) @binding(9)
+var<storage, read> scalar_field: array<f32>;  // two halves, ping-ponged by frame parity
+
+@group(0) @binding(10)
+var<storage, read> grid_pressure: array<f32>;  // divergence, then two ping-ponged pressure fields
+
+@group(0) @binding(17)
+var<storage, read> velocity_history: array<vec4<f32>>;  // mean velocity x, y, mean squared speed, variance
+
+@group(0) @binding(20)
+var<storage, read> particle_temperatures: array<vec2<f32>>;  // current, next
+
+@group(0) @binding(22)
+var<storage, read> trail_positions: array<vec2<f32>>;  // TRAIL_HISTORY per particle, a ring indexed by frame count
+
+@group(0) @binding(23)
+var<storage, read> velocity_field: array<vec2<f32>>;  // averaged velocity per velocity field sample, row major
+
+@group(1) @binding(0)
+var surface_texture: texture_2d<f32>;   // thickness, or its blurred copy
+
+@group(1) @binding(1)
+var surface_sampler: sampler;
+
+const SURFACE_SPLAT_SCALE: f32 = 3.0;   // splats are wider than the particles so neighbours overlap
+const SURFACE_NORMAL_STRENGTH: f32 = 40.0;
+const TRAIL_HISTORY: u32 = 16u;             // must match particle_render.rs
+const TRAIL_BREAK_RADII: f32 = 4.0;         // longer segments are jumps (drains, respawns, unfilled slots), not motion
+const VELOCITY_FIELD_MAX_SIDE: u32 = 64u;   // must match particle_render.rs
+const STREAMLINE_STEPS: u32 = 12u;          // must match particle_render.rs
+const STREAMLINE_WIDTH: f32 = 0.06;         // in sample spacings
+const MAX_PALETTE_COLORS: u32 = 16u;        // must match retro_render.rs
+
+const COLOR_FIELD_SPEED: u32 = 0u;
+const COLOR_FIELD_DENSITY: u32 = 1u;
+const COLOR_FIELD_PRESSURE: u32 = 2u;
+const COLOR_FIELD_TURBULENCE: u32 = 3u;
+const COLOR_FIELD_TEMPERATURE: u32 = 4u;
+const COLORMAP_VIRIDIS: u32 = 0u;
+const COLORMAP_PLASMA: u32 = 1u;
+const COLORMAP_BLUE_RED: u32 = 2u;
+
+const TONEMAPPING_REINHARD: u32 = 1u;
+const TONEMAPPING_ACES: u32 = 2u;
+
+const VELOCITY_FIELD_STREAMLINES: u32 = 2u;
+
+const RENDER_MODE_HEATMAP: u32 = 2u;        // must match heatmap_render.rs
+const OUTLINE_DIRECTIONS: u32 = 8u;         // samples on each ring around a pixel
+const PI: f32 = 3.14159;
+
+struct OverlayOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// =============================================================================
+// COLORMAPS
+// =============================================================================
+
+// polynomial fits of matplotlib's viridis and plasma, t in [0, 1]
+fn viridis(t: f32) -> vec3<f32>
+{
+    let c0 = vec3(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+fn plasma(t: f32) -> vec3<f32>
+{
+    let c0 = vec3(0.05873234392399702, 0.02333670892565664, 0.5433401826748754);
+    let c1 = vec3(2.176514634195958, 0.2383834171260182, 0.7539604599784036);
+    let c2 = vec3(-2.689460476458034, -7.455851135738909, 3.110799939717086);
+    let c3 = vec3(6.130348345893603, 42.3461881477227, -28.51885465332158);
+    let c4 = vec3(-11.10743619062271, -82.66631109428045, 60.13984767418263);
+    let c5 = vec3(10.02306557647065, 71.41361770095349, -54.07218655560067);
+    let c6 = vec3(-3.658713842777788, -22.93153465461149, 18.19190778539828);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// diverging blue -> white -> red, so the middle of the range reads as neutral
+fn blue_red(t: f32) -> vec3<f32>
+{
+    let blue = vec3(0.2, 0.4, 1.0);
+    let white = vec3(1.0, 1.0, 1.0);
+    let red = vec3(1.0, 0.25, 0.2);
+    if (t < 0.5) {
+        return mix(blue, white, t * 2.0);
+    }
+    return mix(white, red, (t - 0.5) * 2.0);
+}
+
+// the selected per-particle quantity, matching the pressure the compute shader solves with
+fn color_field_value(i: u32) -> f32
+{
+    switch config.color_field {
+        case COLOR_FIELD_DENSITY: {
+            return particle_densities[i][0];
+        }
+        case COLOR_FIELD_PRESSURE: {
+            return (particle_densities[i][0] - config.target_density) * config.pressure_multiplier;
+        }
+        case COLOR_FIELD_TURBULENCE: {
+            return sqrt(velocity_history[i].w);
+        }
+        case COLOR_FIELD_TEMPERATURE: {
+            return particle_temperatures[i].x;
+        }
+        default: {
+            return length(particles[i].velocity);
+        }
+    }
+}
+
+// the selected colormap, t in [0, 1]. The fits and blue_red are sRGB, linearized for the lighting and blending
+fn colormap(t: f32) -> vec3<f32>
+{
+    var rgb: vec3<f32>;
+    switch config.colormap {
+        case COLORMAP_PLASMA: {
+            rgb = plasma(t);
+        }
+        case COLORMAP_BLUE_RED: {
+            rgb = blue_red(t);
+        }
+        default: {
+            rgb = viridis(t);
+        }
+    }
+    return srgb_to_linear(clamp(rgb, vec3(0.0), vec3(1.0)));
+}
+
+// sparse spray translucent, the dense interior opaque
+fn density_alpha(i: u32) -> f32
+{
+    if (config.density_alpha_enabled == 0u) {
+        return 1.0;
+    }
+    let opaque_density = max(config.target_density * config.density_alpha_range, 1e-6);
+    let t = clamp(particle_densities[i][0] / opaque_density, 0.0, 1.0);
+    return mix(config.density_alpha_min, 1.0, pow(t, config.density_alpha_gamma));
+}
+
+fn particle_color(i: u32) -> vec4<f32>
+{
+    let range = max(config.color_max - config.color_min, 1e-6);
+    let t = clamp((color_field_value(i) - config.color_min) / range, 0.0, 1.0);
+    return vec4(colormap(t), density_alpha(i));
+}
+
+// =============================================================================
+// DISPLAY COLOR
+// =============================================================================
+
+// the view target is an sRGB format, so fragments write linear color and the hardware encodes it.
+// Colors picked by eye (the colormaps, overlay tints) are sRGB and go through srgb_to_linear first
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32>
+{
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, c <= vec3(0.04045));
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(c: vec3<f32>) -> vec3<f32>
+{
+    return (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14);
+}
+
+// linear scene color to linear display color in [0, 1]
+fn display_color(c: vec3<f32>) -> vec3<f32>
+{
+    let exposed = max(c, vec3(0.0)) * config.exposure;
+    var mapped: vec3<f32>;
+    switch config.tonemapping {
+        case TONEMAPPING_REINHARD: {
+            mapped = exposed / (exposed + vec3(1.0));
+        }
+        case TONEMAPPING_ACES: {
+            mapped = aces(exposed);
+        }
+        default: {
+            mapped = exposed;
+        }
+    }
+    return clamp(mapped, vec3(0.0), vec3(1.0));
+}
+
 // =============================================================================
 // VERTEX SHADER
 // =============================================================================
@@ -70,8 +372,13 @@ fn vertex_main(input: VertexInput) -> VertexOutput {
     // Transform to clip space using view-projection matrix from uniform buffer
     output.position = config.view_proj * world_position_4d;
 
+    // drained particles (zero alpha) are parked off screen, collapse them rather than rely on it
+    if (particle.color.a == 0.0) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+
     output.uv = input.uv;
-    output.color = particle.color;
+    output.color = particle_color(input.instance_id);
 
     return output;
 }
@@ -97,5 +404,502 @@ fn fragment_main(input: VertexOutput) -> @location(0) vec4<f32>
         discard;
     }
 
-    return input.color; 
-}
\ No newline at end of file
+    return vec4(display_color(input.color.rgb), input.color.a);
+}
+
+// =============================================================================
+// TRAILS
+// =============================================================================
+
+struct LineOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// recorded position `age` frames back, 0 being this frame's
+fn trail_position(i: u32, age: u32) -> vec2<f32>
+{
+    let slot = (config.frame_count + TRAIL_HISTORY - age) % TRAIL_HISTORY;
+    return trail_positions[i * TRAIL_HISTORY + slot];
+}
+
+// one instance per segment: particle i's segment from `age` to `age + 1` frames back, as a quad
+// of trail_width across, fading with age
+@vertex
+fn trail_vertex(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance_index: u32) -> LineOutput {
+    var output: LineOutput;
+
+    let segments = config.trail_length - 1u;
+    let i = instance_index / segments;
+    let age = instance_index % segments;
+
+    // along the segment (0 newer end, 1 older end) and across it
+    var corners = array<vec2<f32>, 6>(
+        vec2(0.0, -0.5), vec2(1.0, -0.5), vec2(0.0, 0.5),
+        vec2(1.0, -0.5), vec2(1.0, 0.5), vec2(0.0, 0.5),
+    );
+    let corner = corners[vertex_index];
+
+    let newer = trail_position(i, age);
+    let older = trail_position(i, age + 1u);
+    let segment = older - newer;
+    let segment_length = length(segment);
+
+    // collapse drained particles and segments that aren't motion
+    if (particles[i].color.a == 0.0 || segment_length < 1e-4 || segment_length > TRAIL_BREAK_RADII * config.smoothing_radius) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return output;
+    }
+
+    let across = vec2(-segment.y, segment.x) / segment_length;
+    let world_position = newer + segment * corner.x + across * corner.y * config.trail_width;
+    output.position = config.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+
+    let fade = 1.0 - (f32(age) + corner.x) / f32(segments);
+    output.color = vec4(particle_color(i).rgb, config.trail_opacity * fade);
+
+    return output;
+}
+
+@fragment
+fn line_fragment(input: LineOutput) -> @location(0) vec4<f32>
+{
+    return vec4(display_color(input.color.rgb), input.color.a);
+}
+
+// =============================================================================
+// VELOCITY FIELD
+// =============================================================================
+
+// samples per side, velocity_field_spacing apart across the screen bounds and capped to the buffer;
+// must match the compute shader's
+fn velocity_field_dims() -> vec2<u32>
+{
+    let extent = vec2(config.screen_bounds[1] - config.screen_bounds[0], config.screen_bounds[3] - config.screen_bounds[2]);
+    let samples = ceil(extent / max(config.velocity_field_spacing, 1.0));
+    return clamp(vec2<u32>(samples), vec2(1u), vec2(VELOCITY_FIELD_MAX_SIDE));
+}
+
+// continuous sample coordinates with the samples at integer values
+fn world_to_velocity_field(position: vec2<f32>, dims: vec2<u32>) -> vec2<f32>
+{
+    let origin = vec2(config.screen_bounds[0], config.screen_bounds[2]);
+    let extent = vec2(config.screen_bounds[1], config.screen_bounds[3]) - origin;
+    return (position - origin) / extent * vec2<f32>(dims) - vec2(0.5);
+}
+
+fn velocity_field_sample_position(sample_index: u32, dims: vec2<u32>) -> vec2<f32>
+{
+    let origin = vec2(config.screen_bounds[0], config.screen_bounds[2]);
+    let extent = vec2(config.screen_bounds[1], config.screen_bounds[3]) - origin;
+    return origin + (vec2<f32>(vec2(sample_index % dims.x, sample_index / dims.x)) + vec2(0.5)) * extent / vec2<f32>(dims);
+}
+
+// bilinear between the four nearest samples, the ones off the grid counting as still fluid
+fn velocity_field_at(position: vec2<f32>, dims: vec2<u32>) -> vec2<f32>
+{
+    let grid = world_to_velocity_field(position, dims);
+    let base = vec2<i32>(floor(grid));
+    let t = grid - floor(grid);
+
+    var velocity = vec2(0.0);
+    for (var corner = 0u; corner < 4u; corner++)
+    {
+        let offset = vec2(corner & 1u, corner >> 1u);
+        let cell = base + vec2<i32>(offset);
+        if (any(cell < vec2(0)) || any(cell >= vec2<i32>(dims))) { continue; }
+
+        let weights = select(vec2(1.0) - t, t, offset == vec2(1u));
+        velocity += velocity_field[u32(cell.y) * dims.x + u32(cell.x)] * weights.x * weights.y;
+    }
+    return velocity;
+}
+
+// Arrows: one instance per sample, a shaft quad and a head triangle centred on it, a sample spacing
+// long at velocity_field_full_speed. Streamlines: STREAMLINE_STEPS instances per sample, each a quad
+// along one step of the path traced through the field from it, fading towards the end. The node
+// draws the largest grid, the samples past this one collapse.
+@vertex
+fn velocity_field_vertex(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance_index: u32) -> LineOutput {
+    var output: LineOutput;
+
+    let dims = velocity_field_dims();
+    let streamlines = config.velocity_field_mode == VELOCITY_FIELD_STREAMLINES;
+    let sample_index = select(instance_index, instance_index / STREAMLINE_STEPS, streamlines);
+    if (sample_index >= dims.x * dims.y) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return output;
+    }
+
+    // world units per unit of velocity
+    let length_scale = config.velocity_field_spacing / max(config.velocity_field_full_speed, 1e-6);
+
+    var start = velocity_field_sample_position(sample_index, dims);
+    var segment: vec2<f32>;
+    var width: f32;
+    var corner: vec2<f32>;      // along the segment (0 start, 1 end) and across it
+    var alpha = 0.9;
+    if (streamlines)
+    {
+        // forward Euler, half a sample spacing per step at full speed
+        let step_index = instance_index % STREAMLINE_STEPS;
+        for (var k = 0u; k < step_index; k++)
+        {
+            start += velocity_field_at(start, dims) * length_scale * 0.5;
+        }
+        segment = velocity_field_at(start, dims) * length_scale * 0.5;
+        width = config.velocity_field_spacing * STREAMLINE_WIDTH;
+
+        var corners = array<vec2<f32>, 6>(
+            vec2(0.0, -0.5), vec2(1.0, -0.5), vec2(0.0, 0.5),
+            vec2(1.0, -0.5), vec2(1.0, 0.5), vec2(0.0, 0.5),
+        );
+        corner = corners[vertex_index];
+        alpha *= 1.0 - (f32(step_index) + corner.x) / f32(STREAMLINE_STEPS);
+    }
+    else
+    {
+        // capped at the spacing so neighbouring arrows don't overlap
+        let velocity = velocity_field[sample_index];
+        let speed = length(velocity);
+        let arrow_length = min(speed * length_scale, config.velocity_field_spacing) * 0.9;
+        segment = velocity / max(speed, 1e-6) * arrow_length;
+        start -= segment * 0.5;
+        width = arrow_length;   // the head keeps its proportions at any length
+
+        var corners = array<vec2<f32>, 9>(
+            vec2(0.0, -0.05), vec2(0.6, -0.05), vec2(0.0, 0.05),
+            vec2(0.6, -0.05), vec2(0.6, 0.05), vec2(0.0, 0.05),
+            vec2(0.6, -0.2), vec2(1.0, 0.0), vec2(0.6, 0.2),
+        );
+        corner = corners[vertex_index];
+    }
+
+    // collapse still fluid and samples with none
+    let segment_length = length(segment);
+    if (segment_length < 1e-3) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+        return output;
+    }
+
+    let across = vec2(-segment.y, segment.x) / segment_length;
+    let world_position = start + segment * corner.x + across * corner.y * width;
+    output.position = config.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+    output.color = vec4(srgb_to_linear(vec3(0.95, 0.95, 0.95)), alpha);
+
+    return output;
+}
+
+// =============================================================================
+// SMOKE OVERLAY
+// =============================================================================
+
+@vertex
+fn scalar_overlay_vertex(@builtin(vertex_index) vertex_index: u32) -> OverlayOutput {
+    var output: OverlayOutput;
+
+    // two triangles spanning the screen bounds
+    var corners = array<vec2<f32>, 6>(
+        vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0),
+        vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0),
+    );
+    let uv = corners[vertex_index];
+
+    let x = mix(config.screen_bounds[0], config.screen_bounds[1], uv.x);
+    let y = mix(config.screen_bounds[2], config.screen_bounds[3], uv.y);
+    output.position = config.view_proj * vec4<f32>(x, y, 0.0, 1.0);
+    output.uv = uv;
+
+    return output;
+}
+
+fn overlay_cell_index(uv: vec2<f32>) -> u32
+{
+    let cell_x = min(u32(uv.x * f32(config.scalar_grid_width)), config.scalar_grid_width - 1u);
+    let cell_y = min(u32(uv.y * f32(config.scalar_grid_height)), config.scalar_grid_height - 1u);
+    return cell_y * config.scalar_grid_width + cell_x;
+}
+
+@fragment
+fn scalar_overlay_fragment(input: OverlayOutput) -> @location(0) vec4<f32>
+{
+    // read the half the compute pass wrote this frame
+    let cell_count = config.scalar_grid_width * config.scalar_grid_height;
+    let read_offset = ((config.frame_count + 1u) % 2u) * cell_count;
+    let value = scalar_field[read_offset + overlay_cell_index(input.uv)];
+
+    let alpha = clamp(value, 0.0, 1.0) * config.smoke_opacity;
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    return vec4<f32>(display_color(srgb_to_linear(vec3(0.85, 0.9, 1.0))), alpha);
+}
+
+// diverging colormap: blue where the fluid compresses, red where it expands
+@fragment
+fn divergence_overlay_fragment(input: OverlayOutput) -> @location(0) vec4<f32>
+{
+    let divergence = grid_pressure[overlay_cell_index(input.uv)];
+    let t = clamp(divergence / config.divergence_range, -1.0, 1.0);
+
+    let alpha = abs(t) * 0.8;
+    if (alpha < 0.01) {
+        discard;
+    }
+
+    let color = select(vec3<f32>(0.2, 0.4, 1.0), vec3<f32>(1.0, 0.25, 0.2), t > 0.0);
+    return vec4<f32>(display_color(srgb_to_linear(color)), alpha);
+}
+
+// =============================================================================
+// LIQUID SURFACE
+// =============================================================================
+
+struct SurfaceOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// gaussian blob per particle, added up into the thickness texture
+@vertex
+fn surface_splat_vertex(input: VertexInput) -> OverlayOutput {
+    var output: OverlayOutput;
+
+    let particle = particles[input.instance_id];
+    let world_position = particle.position + input.quad_pos * config.particle_size * SURFACE_SPLAT_SCALE;
+    output.position = config.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+    if (particle.color.a == 0.0) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);   // drained
+    }
+    output.uv = input.uv;
+
+    return output;
+}
+
+@fragment
+fn surface_splat_fragment(input: OverlayOutput) -> @location(0) vec4<f32>
+{
+    let r = length(input.uv - vec2(0.5)) * 2.0;
+    if (r > 1.0) {
+        discard;
+    }
+    return vec4<f32>(exp(-4.0 * r * r), 0.0, 0.0, 1.0);
+}
+
+// one triangle covering the whole target
+@vertex
+fn surface_fullscreen_vertex(@builtin(vertex_index) vertex_index: u32) -> SurfaceOutput {
+    var output: SurfaceOutput;
+
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    output.position = vec4<f32>(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    output.uv = uv;
+
+    return output;
+}
+
+fn surface_thickness(uv: vec2<f32>) -> f32
+{
+    return textureSampleLevel(surface_texture, surface_sampler, uv, 0.0).r;
+}
+
+// 9 tap gaussian along one axis, taps spread over the blur radius
+fn surface_blur(uv: vec2<f32>, axis: vec2<f32>) -> f32
+{
+    let texel = axis / vec2<f32>(textureDimensions(surface_texture));
+    let spacing = config.surface_blur_radius / 4.0;
+
+    var total = 0.0;
+    var weight_total = 0.0;
+    for (var tap = -4; tap <= 4; tap++) {
+        let offset = f32(tap);
+        let weight = exp(-offset * offset / 8.0);
+        total += surface_thickness(uv + texel * offset * spacing) * weight;
+        weight_total += weight;
+    }
+    return total / weight_total;
+}
+
+@fragment
+fn surface_blur_horizontal(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    return vec4<f32>(surface_blur(input.uv, vec2(1.0, 0.0)), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn surface_blur_vertical(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    return vec4<f32>(surface_blur(input.uv, vec2(0.0, 1.0)), 0.0, 0.0, 1.0);
+}
+
+// threshold the blurred thickness and light it with normals taken from its gradient
+@fragment
+fn surface_composite_fragment(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    let thickness = surface_thickness(input.uv);
+    if (thickness < config.surface_threshold) {
+        discard;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(surface_texture));
+    let dx = surface_thickness(input.uv + vec2(texel.x, 0.0)) - surface_thickness(input.uv - vec2(texel.x, 0.0));
+    let dy = surface_thickness(input.uv + vec2(0.0, texel.y)) - surface_thickness(input.uv - vec2(0.0, texel.y));
+    // uv y points down the screen
+    let slope = SURFACE_NORMAL_STRENGTH / config.surface_threshold;
+    let normal = normalize(vec3<f32>(-dx * slope, dy * slope, 1.0));
+
+    let light = normalize(vec3<f32>(-0.4, 0.6, 1.0));
+    let diffuse = max(dot(normal, light), 0.0);
+    let specular = pow(max(dot(reflect(-light, normal), vec3(0.0, 0.0, 1.0)), 0.0), 32.0);
+
+    // deeper water is darker
+    let depth = clamp((thickness - config.surface_threshold) / config.surface_threshold, 0.0, 1.0);
+    let base = mix(srgb_to_linear(vec3<f32>(0.35, 0.7, 1.0)), srgb_to_linear(vec3<f32>(0.05, 0.25, 0.6)), depth);
+    // the highlight can go past white, the tonemap decides how it rolls off
+    let color = base * (0.35 + 0.65 * diffuse) + vec3(specular * 0.6);
+
+    let alpha = smoothstep(config.surface_threshold, config.surface_threshold * 1.2, thickness);
+    return vec4<f32>(display_color(color), 0.6 + 0.35 * alpha);
+}
+
+// =============================================================================
+// DENSITY HEATMAP
+// =============================================================================
+
+// a quad spanning the particle's smoothing radius, splatted into the surface texture
+@vertex
+fn heatmap_splat_vertex(input: VertexInput) -> OverlayOutput {
+    var output: OverlayOutput;
+
+    let particle = particles[input.instance_id];
+    let world_position = particle.position + input.quad_pos * config.smoothing_radius;
+    output.position = config.view_proj * vec4<f32>(world_position, 0.0, 1.0);
+    if (particle.color.a == 0.0) {
+        output.position = vec4<f32>(0.0, 0.0, 0.0, 0.0);   // drained
+    }
+    output.uv = input.uv;
+
+    return output;
+}
+
+// the compute shader's density kernel, so the summed splats are the density it measures
+@fragment
+fn heatmap_splat_fragment(input: OverlayOutput) -> @location(0) vec4<f32>
+{
+    let distance = length(input.uv - vec2(0.5)) * 2.0 * config.smoothing_radius;
+    if (distance >= config.smoothing_radius) {
+        discard;
+    }
+    let v = config.smoothing_radius - distance;
+    return vec4<f32>(config.density_kernel_norm * v * v, 0.0, 0.0, 1.0);
+}
+
+// zero to heatmap_range target densities through the colormap, fading out where there's no fluid
+@fragment
+fn heatmap_composite_fragment(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    let density = surface_thickness(input.uv);
+    let max_density = max(config.target_density * config.heatmap_range, 1e-6);
+    let alpha = smoothstep(0.0, 0.05 * max_density, density);
+    if (alpha < 0.01) {
+        discard;
+    }
+    return vec4<f32>(display_color(colormap(clamp(density / max_density, 0.0, 1.0))), alpha);
+}
+
+// =============================================================================
+// OUTLINE
+// =============================================================================
+
+// where the fluid's edge is in the surface texture: the surface threshold on the blurred thickness,
+// or on the density where the heatmap composite starts to show
+fn silhouette_threshold() -> f32
+{
+    if (config.render_mode == RENDER_MODE_HEATMAP) {
+        return 0.025 * max(config.target_density * config.heatmap_range, 1e-6);
+    }
+    return config.surface_threshold;
+}
+
+// a band outline_width pixels wide just outside the silhouette: pixels outside it with fluid on
+// either of two rings of samples around them
+@fragment
+fn outline_fragment(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    let threshold = silhouette_threshold();
+    if (surface_thickness(input.uv) >= threshold) {
+        discard;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(surface_texture));
+    var edge = false;
+    for (var i = 0u; i < OUTLINE_DIRECTIONS; i++) {
+        let angle = f32(i) * 2.0 * PI / f32(OUTLINE_DIRECTIONS);
+        let offset = vec2(cos(angle), sin(angle)) * texel * config.outline_width;
+        edge = edge
+            || surface_thickness(input.uv + offset) >= threshold
+            || surface_thickness(input.uv + offset * 0.5) >= threshold;
+    }
+    if (!edge) {
+        discard;
+    }
+
+    return vec4<f32>(display_color(config.outline_color.rgb), config.outline_color.a);
+}
+
+// =============================================================================
+// RETRO PALETTE
+// =============================================================================
+
+// 4x4 ordered dither thresholds, 0..15
+var<private> BAYER_4X4: array<u32, 16> = array<u32, 16>(
+    0u, 8u, 2u, 10u,
+    12u, 4u, 14u, 6u,
+    3u, 11u, 1u, 9u,
+    15u, 7u, 13u, 5u,
+);
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32>
+{
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3(0.0031308));
+}
+
+// The system drew into the retro texture instead of the view, already tonemapped and blended
+// over transparent. Each retro_pixel_size block reads its centre, covers the block when at least
+// half opaque and takes the nearest palette color in srgb, both nudged by the Bayer threshold so
+// the gradients between palette colors and the soft edges come out dithered.
+@fragment
+fn palette_fragment(input: SurfaceOutput) -> @location(0) vec4<f32>
+{
+    let pixel_size = max(config.retro_pixel_size, 1.0);
+    let block = floor(input.position.xy / pixel_size);
+    let uv = (block + vec2(0.5)) * pixel_size / vec2<f32>(textureDimensions(surface_texture));
+    let color = textureSampleLevel(surface_texture, surface_sampler, uv, 0.0);
+
+    let cell = vec2<u32>(block) % vec2(4u);
+    let threshold = (f32(BAYER_4X4[cell.y * 4u + cell.x]) + 0.5) / 16.0 - 0.5;
+    let dither = threshold * config.palette_dither;
+    if (color.a + dither < 0.5) {
+        discard;
+    }
+
+    // blending over transparent premultiplied the color, the spread is about one palette step
+    let palette_size = clamp(config.palette_size, 1u, MAX_PALETTE_COLORS);
+    let spread = 1.0 / f32(max(palette_size, 2u) - 1u);
+    let wanted = linear_to_srgb(color.rgb / max(color.a, 1e-6)) + vec3(dither * spread);
+
+    var best = config.palette[0].rgb;
+    var best_distance = distance(wanted, best);
+    for (var i = 1u; i < palette_size; i++) {
+        let candidate = config.palette[i].rgb;
+        let candidate_distance = distance(wanted, candidate);
+        if (candidate_distance < best_distance) {
+            best = candidate;
+            best_distance = candidate_distance;
+        }
+    }
+    return vec4<f32>(srgb_to_linear(best), 1.0);
+}
@@ -0,0 +1,176 @@
+use bevy::{
+    input::mouse::MouseWheel,
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::parameter_gui::{ColorField, Colormap, GUIConfig, GravityPreset};
+use crate::hud::HudSettings;
+use crate::training_data::TrainingData;
+use crate::units::Seconds;
+
+const ATTRACT_IDLE_SECONDS: f32 = 120.0;    // default idle time before attract mode starts
+const SCENE_SECONDS: f32 = 20.0;            // time each scene is shown
+
+// a look the attract loop cycles through, applied on top of the params the user left behind
+struct AttractScene
+{
+    name: &'static str,
+    apply: fn(&mut GUIConfig),
+}
+
+const ATTRACT_SCENES: [AttractScene; 4] = [
+    AttractScene { name: "Zero-G Smoke", apply: |params| {
+        params.gravity = GravityPreset::ZeroG.gravity();
+        params.oscillate_gravity = false;
+        params.smoke_enabled = true;
+        params.colormap = Colormap::Plasma as u32;
+    }},
+    AttractScene { name: "Sloshing Tank", apply: |params| {
+        params.gravity = GravityPreset::Earth.gravity();
+        params.oscillate_gravity = true;
+        params.gravity_oscillation_period = Seconds(6.0);
+        params.colormap = Colormap::Viridis as u32;
+    }},
+    AttractScene { name: "Divergence", apply: |params| {
+        params.gravity = GravityPreset::Moon.gravity();
+        params.oscillate_gravity = true;
+        params.gravity_oscillation_period = Seconds(10.0);
+        params.divergence_view = true;
+    }},
+    AttractScene { name: "Jupiter Pressure", apply: |params| {
+        params.gravity = GravityPreset::Jupiter.gravity();
+        params.oscillate_gravity = false;
+        params.color_field = ColorField::Pressure as u32;
+        params.colormap = Colormap::BlueRed as u32;
+        (params.color_min, params.color_max) = ColorField::Pressure.default_range(params.target_density);
+    }},
+];
+
+#[derive(Resource)]
+pub struct AttractMode
+{
+    pub enabled: bool,
+    pub idle_seconds: f32,
+    idle_timer: f32,
+    scene_timer: f32,
+    scene_index: usize,
+    saved: Option<(GUIConfig, bool)>,  // params and parameter window visibility to restore, set while active
+}
+
+impl Default for AttractMode
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            enabled: false,
+            idle_seconds: ATTRACT_IDLE_SECONDS,
+            idle_timer: 0.0,
+            scene_timer: 0.0,
+            scene_index: 0,
+            saved: None,
+        }
+    }
+}
+
+impl AttractMode
+{
+    pub fn is_active(&self) -> bool
+    {
+        self.saved.is_some()
+    }
+
+    fn apply_scene(&self, gui_config: &mut GUIConfig)
+    {
+        let Some((saved_params, _)) = self.saved else { return; };
+        *gui_config = saved_params;
+        (ATTRACT_SCENES[self.scene_index].apply)(gui_config);
+        gui_config.applied_changes = true;
+    }
+}
+
+// start cycling scenes after the idle timeout, and hand control straight back on any input
+pub fn update_attract_mode(
+    time: Res<Time<Real>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut attract: ResMut<AttractMode>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut hud_settings: ResMut<HudSettings>,
+    training: Res<TrainingData>,
+)
+{
+    let had_input = keyboard_input.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || cursor_moved.read().count() > 0
+        || mouse_wheel.read().count() > 0;
+
+    // a training data run is unattended on purpose, it shouldn't be taken over
+    if had_input || !attract.enabled || training.is_running()
+    {
+        attract.idle_timer = 0.0;
+        if let Some((saved_params, params_visible)) = attract.saved.take()
+        {
+            *gui_config = GUIConfig { applied_changes: true, ..saved_params };
+            hud_settings.params_visible = params_visible;
+        }
+        return;
+    }
+
+    let delta = time.delta_secs();
+    if !attract.is_active()
+    {
+        attract.idle_timer += delta;
+        if attract.idle_timer < attract.idle_seconds { return; }
+
+        attract.saved = Some((*gui_config, hud_settings.params_visible));
+        hud_settings.params_visible = false;
+        attract.scene_index = 0;
+        attract.scene_timer = 0.0;
+        attract.apply_scene(&mut gui_config);
+        return;
+    }
+
+    attract.scene_timer += delta;
+    if attract.scene_timer >= SCENE_SECONDS
+    {
+        attract.scene_timer = 0.0;
+        attract.scene_index = (attract.scene_index + 1) % ATTRACT_SCENES.len();
+        attract.apply_scene(&mut gui_config);
+    }
+}
+
+// scene title and a hint that the display is interactive
+pub fn attract_mode_overlay(
+    mut contexts: EguiContexts,
+    attract: Res<AttractMode>,
+) -> Result
+{
+    if !attract.is_active() { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    egui::Area::new(egui::Id::new("attract_mode_overlay"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.label(egui::RichText::new(ATTRACT_SCENES[attract.scene_index].name).heading());
+                ui.label("Move the mouse or press any key to play");
+            });
+        });
+    Ok(())
+}
+
+pub fn attract_mode_settings(ui: &mut egui::Ui, attract: &mut AttractMode)
+{
+    ui.checkbox(&mut attract.enabled, "Attract Mode When Idle");
+    if attract.enabled
+    {
+        ui.add(egui::Slider::new(&mut attract.idle_seconds, 10.0..=600.0)
+            .text("Idle Time (s)")
+            .logarithmic(true));
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::path::Path;
+
+use crate::parameter_gui::GUIConfig;
+use crate::headless::HeadlessRun;
+use crate::device_recovery::RESUME_ARG;
+use crate::scene::{SceneIo, SceneShared};
+
+const RECOVERY_PARAMS_PATH: &str = "recovery_params.txt";
+const RECOVERY_SCENE_PATH: &str = "recovery.pscn";
+const DEFAULT_AUTOSAVE_INTERVAL: f32 = 30.0;    // seconds
+
+// a session that didn't exit cleanly, found at launch
+struct Recovery
+{
+    params: String,
+    has_scene: bool,
+    age: Option<std::time::Duration>,   // since the params were last saved
+    after_device_loss: bool,            // written by a session that restarted this one, restored without asking
+}
+
+// Every interval the params (and optionally the particles) are written to recovery files,
+// which a clean exit removes. If they're still there at launch the last session crashed, so
+// restoring them is offered before autosave starts overwriting them.
+#[derive(Resource)]
+pub struct Autosave
+{
+    pub enabled: bool,
+    pub include_scene: bool,
+    pub interval: f32,
+    since_save: f32,
+    saved_params: Option<String>,   // what's in the recovery file, so unchanged params aren't rewritten
+    recovery: Option<Recovery>,
+    keep_recovery: bool,            // the files were written for a relaunch, so exiting leaves them
+    status: Option<String>,
+}
+
+impl Default for Autosave
+{
+    fn default() -> Self
+    {
+        let recovery = std::fs::read_to_string(RECOVERY_PARAMS_PATH).ok().map(|params| Recovery
+        {
+            params,
+            has_scene: Path::new(RECOVERY_SCENE_PATH).exists(),
+            age: std::fs::metadata(RECOVERY_PARAMS_PATH).and_then(|metadata| metadata.modified()).ok()
+                .and_then(|modified| modified.elapsed().ok()),
+            after_device_loss: std::env::args().any(|arg| arg == RESUME_ARG),
+        });
+        Self
+        {
+            enabled: true,
+            include_scene: false,
+            interval: DEFAULT_AUTOSAVE_INTERVAL,
+            since_save: 0.0,
+            saved_params: None,
+            recovery,
+            keep_recovery: false,
+            status: None,
+        }
+    }
+}
+
+// write beside the file and rename over it, so a crash mid-write leaves the previous save
+fn write_atomically(path: &str, contents: impl AsRef<[u8]>) -> std::io::Result<()>
+{
+    let temporary = format!("{}.tmp", path);
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)
+}
+
+fn remove_recovery_files()
+{
+    for path in [RECOVERY_PARAMS_PATH, RECOVERY_SCENE_PATH]
+    {
+        if let Err(error) = std::fs::remove_file(path)
+        {
+            if error.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!("[Autosave] Failed to remove {}: {}", path, error);
+            }
+        }
+    }
+}
+
+impl Autosave
+{
+    // write the recovery files right away for the next launch to restore, leaving out the
+    // particles when there's no scene
+    pub fn write_recovery(&mut self, params: &str, scene: Option<&[u8]>) -> std::io::Result<()>
+    {
+        write_atomically(RECOVERY_PARAMS_PATH, params)?;
+        match scene {
+            Some(scene) => write_atomically(RECOVERY_SCENE_PATH, scene)?,
+            None => match std::fs::remove_file(RECOVERY_SCENE_PATH) {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            },
+        }
+        self.keep_recovery = true;
+        Ok(())
+    }
+}
+
+pub fn update_autosave(
+    time: Res<Time<Real>>,
+    gui_config: Res<GUIConfig>,
+    scene_shared: Res<SceneShared>,
+    headless: Option<Res<HeadlessRun>>,
+    mut scene_io: ResMut<SceneIo>,
+    mut autosave: ResMut<Autosave>,
+)
+{
+    // batch runs have nothing to recover, and the recovery files are left alone until the
+    // prompt is answered
+    if headless.is_some() || !autosave.enabled || autosave.recovery.is_some() { return; }
+
+    autosave.since_save += time.delta_secs();
+    if autosave.since_save < autosave.interval { return; }
+    autosave.since_save = 0.0;
+
+    let params = gui_config.to_text();
+    if autosave.saved_params.as_ref() != Some(&params)
+    {
+        match write_atomically(RECOVERY_PARAMS_PATH, &params) {
+            Ok(()) => {
+                autosave.saved_params = Some(params);
+                autosave.status = None;
+            }
+            Err(error) => autosave.status = Some(format!("Failed to write {}: {}", RECOVERY_PARAMS_PATH, error)),
+        }
+    }
+    // the particles always move, so the scene is written every interval
+    if autosave.include_scene
+    {
+        scene_io.request_save_to(RECOVERY_SCENE_PATH.to_string(), &scene_shared);
+    }
+}
+
+// a clean exit has nothing to recover; runs last so it sees exits requested during the frame
+pub fn clear_recovery_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    autosave: Res<Autosave>,
+)
+{
+    if exit_events.read().next().is_some() && autosave.recovery.is_none() && !autosave.keep_recovery
+    {
+        remove_recovery_files();
+    }
+}
+
+pub fn autosave_gui(
+    mut contexts: EguiContexts,
+    mut autosave: ResMut<Autosave>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut scene_io: ResMut<SceneIo>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+
+    if let Some(recovery) = autosave.recovery.take()
+    {
+        let mut restore = recovery.after_device_loss;
+        let mut discard = false;
+        if !restore
+        {
+            egui::Window::new("Recover Previous Session")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui: &mut egui::Ui| {
+                    ui.label("The last session didn't exit cleanly.");
+                    let age = recovery.age.map_or("an unknown time".to_string(), |age| format!("{} min", age.as_secs() / 60));
+                    let contents = if recovery.has_scene { "parameters and particles" } else { "parameters" };
+                    ui.label(format!("Its {} were autosaved {} ago.", contents, age));
+                    ui.horizontal(|ui| {
+                        restore = ui.button("Restore").clicked();
+                        discard = ui.button("Discard").clicked();
+                    });
+                });
+        }
+
+        if restore
+        {
+            autosave.status = Some(match gui_config.apply_text(&recovery.params) {
+                Ok(()) if recovery.after_device_loss => "Restarted from the last snapshot after the GPU device was lost".to_string(),
+                Ok(()) => "Restored the previous session".to_string(),
+                Err(error) => format!("Failed to restore {}: {}", RECOVERY_PARAMS_PATH, error),
+            });
+            if recovery.has_scene
+            {
+                scene_io.request_load_from(RECOVERY_SCENE_PATH.to_string());
+            }
+        }
+        else if !discard
+        {
+            autosave.recovery = Some(recovery);
+        }
+    }
+
+    egui::Window::new("Autosave")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1350.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.checkbox(&mut autosave.enabled, "Autosave for crash recovery");
+            ui.add_enabled_ui(autosave.enabled, |ui| {
+                ui.checkbox(&mut autosave.include_scene, "Include particles");
+                ui.add(egui::Slider::new(&mut autosave.interval, 5.0..=300.0)
+                    .text("Interval (s)")
+                    .logarithmic(true));
+            });
+            ui.label(format!("Saved to {} until a clean exit", RECOVERY_PARAMS_PATH));
+            if let Some(status) = &autosave.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
+use rand::Rng;
+
+use crate::ParticleConfig;
+use crate::emitter::SpawnQueue;
+use crate::gui_scale::GuiScale;
+use crate::interaction::InteractionTool;
+use crate::particle::Particle;
+use crate::sim_rng::SimRng;
+
+const BRUSH_RADIUS: f32 = 30.0;
+const BRUSH_RATE: f32 = 2000.0;     // particles per second while dragging
+
+// paints particles under the cursor while the left button is held; like the emitters each one
+// replaces the oldest slot in the particle ring
+#[derive(Resource)]
+pub struct BrushSettings
+{
+    pub radius: f32,
+    pub rate: f32,          // particles per second
+    pub velocity: Vec2,     // initial velocity of painted particles
+}
+
+impl Default for BrushSettings
+{
+    fn default() -> Self
+    {
+        Self { radius: BRUSH_RADIUS, rate: BRUSH_RATE, velocity: Vec2::ZERO }
+    }
+}
+
+pub fn brush_settings(ui: &mut egui::Ui, brush: &mut BrushSettings)
+{
+    ui.label("Left drag paints particles");
+    ui.add(egui::Slider::new(&mut brush.radius, 2.0..=200.0)
+        .text("Radius"));
+    ui.add(egui::Slider::new(&mut brush.rate, 10.0..=50000.0)
+        .text("Density (particles/s)")
+        .logarithmic(true));
+    ui.add(egui::Slider::new(&mut brush.velocity.x, -1000.0..=1000.0)
+        .text("Velocity X"));
+    ui.add(egui::Slider::new(&mut brush.velocity.y, -1000.0..=1000.0)
+        .text("Velocity Y"));
+}
+
+pub fn cursor_world_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) -> Option<Vec2>
+{
+    let cursor = windows.single().ok()?.cursor_position()?;
+    let (camera, transform) = camera_query.single().ok()?;
+    camera.viewport_to_world_2d(transform, cursor).ok()
+}
+
+// queue the particles painted this frame, spread evenly over the brush's disc
+pub fn update_brush(
+    time: Res<Time>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut contexts: EguiContexts,
+    tool: Res<InteractionTool>,
+    brush: Res<BrushSettings>,
+    config: Res<ParticleConfig>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut sim_rng: ResMut<SimRng>,
+    mut spawn_debt: Local<f32>,
+    mut painting: Local<bool>,
+)
+{
+    // only strokes that start on the fluid paint, and they keep painting over the gui
+    let pointer_over_gui = contexts.ctx_mut()
+        .map(|ctx| ctx.is_pointer_over_area() || ctx.wants_pointer_input())
+        .unwrap_or(false);
+    if mouse_buttons.just_pressed(MouseButton::Left)
+    {
+        *painting = !pointer_over_gui;
+    }
+    if *tool != InteractionTool::Brush || !mouse_buttons.pressed(MouseButton::Left) || config.paused != 0
+    {
+        *painting = false;
+    }
+    if !*painting
+    {
+        *spawn_debt = 0.0;
+        return;
+    }
+    let Some(center) = cursor_world_position(&windows, &camera_query) else { return; };
+
+    let delta = if sim_rng.is_seeded() { config.fixed_delta_time } else { time.delta_secs() };
+    *spawn_debt += brush.rate * delta;
+    let count = spawn_debt.floor();
+    *spawn_debt -= count;
+
+    let rng = sim_rng.rng();
+    spawn_queue.0.extend((0..count as u32).map(|_| {
+        // sqrt of the radius keeps the disc uniformly covered instead of bunched at its center
+        let offset = Vec2::from_angle(rng.random_range(0.0..std::f32::consts::TAU)) * brush.radius * rng.random::<f32>().sqrt();
+        Particle
+        {
+            position: (center + offset).to_array(),
+            velocity: brush.velocity.to_array(),
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }));
+}
+
+// outline the brush at the cursor while it's the selected tool
+pub fn draw_brush(
+    mut contexts: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    tool: Res<InteractionTool>,
+    brush: Res<BrushSettings>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if *tool != InteractionTool::Brush { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let Some(center) = cursor_world_position(&windows, &camera_query) else { return Ok(()); };
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    if let (Some(screen_center), Some(screen_edge)) = (to_screen(center), to_screen(center + Vec2::X * brush.radius))
+    {
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        let stroke = egui::Stroke::new(1.5, egui::Color32::from_rgb(120, 200, 255));
+        painter.circle_stroke(screen_center, screen_edge.x - screen_center.x, stroke);
+    }
+    Ok(())
+}
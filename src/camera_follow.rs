@@ -0,0 +1,181 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::particle_probe::ParticleProbe;
+use crate::gui_scale::GuiScale;
+
+const DEFAULT_FOLLOW_SMOOTHING: f32 = 0.3;      // seconds for the camera to cover most of the gap
+const PICK_RADIUS: f32 = 30.0;                  // world units around the cursor a middle click picks from
+const MARKER_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 220, 60);
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum FollowMode
+{
+    #[default]
+    Off,            // the camera eases back to the domain's center
+    CenterOfMass,   // particles have unit mass, so this is the average position
+    Particle,       // the particle picked with a middle click
+}
+
+impl FollowMode
+{
+    pub const ALL: [FollowMode; 3] = [FollowMode::Off, FollowMode::CenterOfMass, FollowMode::Particle];
+
+    pub fn label(self) -> &'static str
+    {
+        match self {
+            FollowMode::Off => "Off",
+            FollowMode::CenterOfMass => "Center of Mass",
+            FollowMode::Particle => "Selected Particle",
+        }
+    }
+}
+
+// Positions come from the particle probe, so the target only moves every few frames; the
+// smoothing hides the steps. C cycles the modes.
+#[derive(Resource)]
+pub struct CameraFollow
+{
+    pub mode: FollowMode,
+    pub smoothing: f32,             // seconds, 0 snaps to the target
+    pub selected: Option<usize>,    // index into the main system's particle buffer
+    pending_pick: Option<Vec2>,     // world position of a middle click waiting for fresh positions
+    target: Vec2,
+}
+
+impl Default for CameraFollow
+{
+    fn default() -> Self
+    {
+        Self { mode: FollowMode::Off, smoothing: DEFAULT_FOLLOW_SMOOTHING, selected: None, pending_pick: None, target: Vec2::ZERO }
+    }
+}
+
+fn nearest_particle(positions: &[Vec2], point: Vec2) -> Option<usize>
+{
+    positions.iter()
+        .enumerate()
+        .map(|(index, position)| (index, position.distance_squared(point)))
+        .filter(|(_, distance_squared)| *distance_squared <= PICK_RADIUS * PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+pub fn update_camera_follow(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    mut follow: ResMut<CameraFollow>,
+    mut probe: ResMut<ParticleProbe>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut Transform), With<Camera2d>>,
+)
+{
+    let (pointer_over_gui, typing) = contexts.ctx_mut()
+        .map(|ctx| (ctx.is_pointer_over_area() || ctx.wants_pointer_input(), ctx.wants_keyboard_input()))
+        .unwrap_or((false, false));
+    let Ok((camera, camera_transform, mut transform)) = camera_query.single_mut() else { return; };
+
+    if keyboard_input.just_pressed(KeyCode::KeyC) && !typing
+    {
+        let index = FollowMode::ALL.iter().position(|mode| *mode == follow.mode).unwrap_or(0);
+        follow.mode = FollowMode::ALL[(index + 1) % FollowMode::ALL.len()];
+    }
+
+    if mouse_buttons.just_pressed(MouseButton::Middle) && !pointer_over_gui
+    {
+        follow.pending_pick = windows.single().ok()
+            .and_then(|window| window.cursor_position())
+            .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok());
+    }
+    if follow.mode != FollowMode::Off || follow.pending_pick.is_some()
+    {
+        probe.wanted = true;
+    }
+
+    if let Some(positions) = probe.positions.as_deref().filter(|_| probe.updated)
+    {
+        // picks wait for a fresh readback so they match what's on screen
+        if let Some(pick) = follow.pending_pick.take()
+        {
+            if let Some(index) = nearest_particle(positions, pick)
+            {
+                follow.selected = Some(index);
+                follow.mode = FollowMode::Particle;
+            }
+        }
+
+        let target = match follow.mode {
+            FollowMode::Off => None,
+            FollowMode::CenterOfMass => (!positions.is_empty())
+                .then(|| positions.iter().copied().sum::<Vec2>() / positions.len() as f32),
+            FollowMode::Particle => follow.selected.and_then(|index| positions.get(index).copied()),
+        };
+        // a particle index past the end (the count shrank) leaves the camera where it is
+        if let Some(target) = target
+        {
+            follow.target = target;
+        }
+    }
+    if follow.mode == FollowMode::Off
+    {
+        follow.target = Vec2::ZERO;
+    }
+
+    let blend = if follow.smoothing > 0.0 { 1.0 - (-time.delta_secs() / follow.smoothing).exp() } else { 1.0 };
+    let position = transform.translation.truncate().lerp(follow.target, blend);
+    transform.translation = position.extend(transform.translation.z);
+}
+
+// ring around the followed particle at its last probed position
+pub fn draw_followed_particle(
+    mut contexts: EguiContexts,
+    follow: Res<CameraFollow>,
+    probe: Res<ParticleProbe>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if follow.mode != FollowMode::Particle { return Ok(()); }
+    let Some(position) = follow.selected.and_then(|index| probe.positions.as_ref()?.get(index).copied()) else { return Ok(()); };
+
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let Ok(viewport) = camera.world_to_viewport(camera_transform, position.extend(0.0)) else { return Ok(()); };
+
+    let center = egui::pos2(viewport.x, viewport.y) / gui_scale.applied;
+    ctx.layer_painter(egui::LayerId::background())
+        .circle_stroke(center, 10.0, egui::Stroke::new(1.5, MARKER_COLOR));
+    Ok(())
+}
+
+pub fn camera_follow_gui(
+    mut contexts: EguiContexts,
+    mut follow: ResMut<CameraFollow>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Camera")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1150.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.label("Follow (C)");
+                for mode in FollowMode::ALL {
+                    ui.radio_value(&mut follow.mode, mode, mode.label());
+                }
+            });
+            ui.add(egui::Slider::new(&mut follow.smoothing, 0.0..=2.0)
+                .text("Smoothing (s)"));
+            match follow.selected {
+                Some(index) => ui.label(format!("Selected particle: {}", index)),
+                None => ui.label("Middle click a particle to follow it"),
+            };
+        });
+    Ok(())
+}
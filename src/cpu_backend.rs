@@ -0,0 +1,468 @@
+use bevy::{
+    prelude::*,
+    render::{
+        Render, RenderApp, RenderSet,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        renderer::RenderQueue,
+    },
+};
+
+use crate::{ParticleConfig, ParticleSystem};
+use crate::emitter::{upload_emitted_particles, EmittedParticles};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+
+// Which backend steps the main particle system, picked once through ParticlePlugin's settings or
+// `--sim-backend <name>`. The CPU backend runs the same grid build, neighbor search and SPH
+// forces as the compute shader with rayon, only available with `--features cpu_backend`, and
+// writes its particles into the GPU particle buffer every step so rendering doesn't change.
+// It covers gravity, pressure, viscosity, the cursor, fan and impulse forces and the domain
+// edges; obstacles, force fields, temperature, the background grid passes, the surrogate and the
+// GPU readbacks (stats, probes, densities) only run on the GPU. Extra particle systems stay on
+// the GPU, and the CPU backend always uses the SPH solver whatever the Solver setting says.
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Default)]
+pub enum SimBackend
+{
+    #[default]
+    Gpu,
+    Cpu,    // for CI and devices without compute shaders
+}
+
+impl SimBackend
+{
+    pub const NAMED: [(&str, SimBackend); 2] = [
+        ("gpu", SimBackend::Gpu),
+        ("cpu", SimBackend::Cpu),
+    ];
+
+    pub fn from_args() -> Self
+    {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(name) = args.iter().position(|arg| arg == "--sim-backend").and_then(|index| args.get(index + 1)) else {
+            return Self::default();
+        };
+        Self::NAMED.iter().find(|(known, _)| known == name).map(|(_, backend)| *backend).unwrap_or_else(|| {
+            let known: Vec<&str> = Self::NAMED.iter().map(|(known, _)| *known).collect();
+            warn!("[Backend] Unknown sim backend `{}`, expected one of {}", name, known.join(", "));
+            Self::default()
+        })
+    }
+
+    // the backend that will actually run, the GPU when this build can't step on the CPU
+    pub fn available(self) -> Self
+    {
+        if self == SimBackend::Cpu && !cfg!(feature = "cpu_backend")
+        {
+            warn!("[Backend] Built without the cpu_backend feature, simulating on the GPU");
+            return SimBackend::Gpu;
+        }
+        self
+    }
+}
+
+// the main system's particles while the CPU backend steps them; `batch` increases with every
+// change so the render world writes each one once, however often it is extracted
+#[derive(ExtractResource, Resource, Clone, Default)]
+pub struct CpuParticles
+{
+    batch: u64,
+    particle_count: u32,
+    generation: u32,                // ParticleSystem generation the particles were loaded from
+    particles: Vec<[f32; 8]>,       // position, velocity, color
+}
+
+pub fn add_cpu_backend(app: &mut App)
+{
+    app.add_plugins(ExtractResourcePlugin::<CpuParticles>::default());
+    app.init_resource::<CpuParticles>();
+    app.add_systems(PostUpdate, step_cpu_particles.after(crate::update_derived_params));
+
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app.add_systems(Render, upload_cpu_particles.in_set(RenderSet::Prepare).after(upload_emitted_particles));
+}
+
+#[cfg(feature = "cpu_backend")]
+mod solver
+{
+    use bevy::prelude::*;
+    use rayon::prelude::*;
+
+    use crate::ParticleConfig;
+    use crate::parameter_gui::BoundaryMode;
+
+    const SHADER_DELAY: u32 = 5;            // must match compute_shader.wgsl
+    const DRAINED_OFFSET: f32 = 100000.0;   // must match compute_shader.wgsl
+    const GRID_OFFSETS: [IVec2; 9] = [
+        IVec2::new(-1, -1), IVec2::new(-1, 0), IVec2::new(-1, 1),
+        IVec2::new(0, -1), IVec2::new(0, 0), IVec2::new(0, 1),
+        IVec2::new(1, -1), IVec2::new(1, 0), IVec2::new(1, 1),
+    ];
+
+    fn position(particle: &[f32; 8]) -> Vec2 { Vec2::new(particle[0], particle[1]) }
+    fn velocity(particle: &[f32; 8]) -> Vec2 { Vec2::new(particle[2], particle[3]) }
+    fn is_drained(particle: &[f32; 8]) -> bool { particle[7] == 0.0 }
+
+    // the particles counting sorted by spatial grid cell, like the compute shader's spatial lookup
+    struct SpatialGrid
+    {
+        origin: Vec2,
+        cell_size: f32,
+        size: IVec2,
+        offsets: Vec<u32>,      // start of each cell's run in `indices`, one past the end last
+        indices: Vec<u32>,
+    }
+
+    impl SpatialGrid
+    {
+        fn build(positions: &[Vec2], config: &ParticleConfig) -> Self
+        {
+            let mut grid = Self
+            {
+                origin: Vec2::new(config.screen_bounds[0], config.screen_bounds[2]),
+                cell_size: config.smoothing_radius,
+                size: IVec2::new(config.spatial_grid_width as i32, config.spatial_grid_height as i32).max(IVec2::ONE),
+                offsets: Vec::new(),
+                indices: Vec::new(),
+            };
+            let keys: Vec<usize> = positions.par_iter().map(|position| grid.key(grid.cell(*position))).collect();
+
+            let mut offsets = vec![0u32; (grid.size.x * grid.size.y) as usize + 1];
+            for key in &keys
+            {
+                offsets[key + 1] += 1;
+            }
+            for key in 1..offsets.len()
+            {
+                offsets[key] += offsets[key - 1];
+            }
+
+            // scattering in particle order keeps every cell's run sorted by index
+            let mut next = offsets.clone();
+            let mut indices = vec![0u32; positions.len()];
+            for (index, key) in keys.iter().enumerate()
+            {
+                indices[next[*key] as usize] = index as u32;
+                next[*key] += 1;
+            }
+            grid.offsets = offsets;
+            grid.indices = indices;
+            grid
+        }
+
+        // positions past the bounds are clamped into the edge cells, as in the shader
+        fn cell(&self, position: Vec2) -> IVec2
+        {
+            ((position - self.origin) / self.cell_size).floor().as_ivec2().clamp(IVec2::ZERO, self.size - 1)
+        }
+
+        fn key(&self, cell: IVec2) -> usize
+        {
+            (cell.y * self.size.x + cell.x) as usize
+        }
+
+        // every particle in the 3x3 cells around the position
+        fn neighbors(&self, position: Vec2) -> impl Iterator<Item = usize> + '_
+        {
+            let cell = self.cell(position);
+            GRID_OFFSETS.iter()
+                .map(move |offset| cell + *offset)
+                .filter(move |neighbor| neighbor.cmpge(IVec2::ZERO).all() && neighbor.cmplt(self.size).all())
+                .flat_map(move |neighbor| {
+                    let key = self.key(neighbor);
+                    self.indices[self.offsets[key] as usize..self.offsets[key + 1] as usize].iter().map(|index| *index as usize)
+                })
+        }
+    }
+
+    struct Kernels<'a>
+    {
+        config: &'a ParticleConfig,
+    }
+
+    impl Kernels<'_>
+    {
+        fn density(&self, distance: f32) -> f32
+        {
+            let v = (self.config.smoothing_radius - distance).max(0.0);
+            self.config.density_kernel_norm * v * v
+        }
+
+        fn density_derivative(&self, distance: f32) -> f32
+        {
+            let v = (self.config.smoothing_radius - distance).max(0.0);
+            -2.0 * self.config.density_kernel_norm * v
+        }
+
+        fn near_density(&self, distance: f32) -> f32
+        {
+            let v = (self.config.smoothing_radius - distance).max(0.0);
+            self.config.near_density_kernel_norm * v * v * v
+        }
+
+        fn near_density_derivative(&self, distance: f32) -> f32
+        {
+            let v = (self.config.smoothing_radius - distance).max(0.0);
+            -3.0 * self.config.near_density_kernel_norm * v * v
+        }
+
+        fn viscosity(&self, distance: f32) -> f32
+        {
+            let radius = self.config.smoothing_radius;
+            if distance >= radius { return 0.0; }
+            let v = radius * radius - distance * distance;
+            self.config.viscocity_kernel_norm * v * v * v
+        }
+
+        fn pressure(&self, density: f32) -> f32
+        {
+            (density - self.config.target_density) * self.config.pressure_multiplier
+        }
+
+        fn near_pressure(&self, near_density: f32) -> f32
+        {
+            near_density * self.config.near_density_multiplier
+        }
+    }
+
+    // position and velocity along one axis after its low and high edges, modes in the same order
+    fn resolve_axis(position: f32, velocity: f32, low: f32, high: f32, modes: [u32; 2], damping_factor: f32) -> (f32, f32)
+    {
+        let (mode, edge, sign) = if position <= low {
+            (modes[0], low, 1.0)
+        } else if position >= high {
+            (modes[1], high, -1.0)
+        } else {
+            return (position, velocity);
+        };
+        match mode {
+            mode if mode == BoundaryMode::Wrap as u32 => (position + sign * (high - low), velocity),
+            mode if mode == BoundaryMode::Open as u32 => (position, velocity),
+            _ => (edge, sign * velocity.abs() * damping_factor),
+        }
+    }
+
+    fn crosses_drain(position: f32, low: f32, high: f32, modes: [u32; 2]) -> bool
+    {
+        let drain = BoundaryMode::Drain as u32;
+        (position <= low && modes[0] == drain) || (position >= high && modes[1] == drain)
+    }
+
+    fn check_screen_bounds(particle: &mut [f32; 8], config: &ParticleConfig)
+    {
+        let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+        let [left, right, bottom, top] = config.boundary_modes;
+        let (position, velocity) = (position(particle), velocity(particle));
+
+        if crosses_drain(position.x, x_min, x_max, [left, right]) || crosses_drain(position.y, y_min, y_max, [bottom, top])
+        {
+            let parked = Vec2::new(x_min, y_min) - Vec2::splat(DRAINED_OFFSET);
+            particle[..4].copy_from_slice(&[parked.x, parked.y, 0.0, 0.0]);
+            particle[7] = 0.0;
+            return;
+        }
+
+        let (x, velocity_x) = resolve_axis(position.x, velocity.x, x_min, x_max, [left, right], config.damping_factor);
+        let (y, velocity_y) = resolve_axis(position.y, velocity.y, y_min, y_max, [bottom, top], config.damping_factor);
+        particle[..4].copy_from_slice(&[x, y, velocity_x, velocity_y]);
+    }
+
+    // radial falloff towards the edge of a disc, zero outside it and at its center
+    fn radial_falloff(offset: Vec2, radius: f32) -> Option<(Vec2, f32)>
+    {
+        let distance = offset.length();
+        if distance >= radius || distance < 0.0001 { return None; }
+        Some((offset / distance, 1.0 - distance / radius))
+    }
+
+    fn external_forces(position: Vec2, config: &ParticleConfig) -> Vec2
+    {
+        let delta_time = config.fixed_delta_time;
+        let mut velocity_change = Vec2::ZERO;
+
+        if config.interaction_strength != 0.0
+        {
+            let offset = Vec2::from(config.interaction_position) - position;
+            if let Some((direction, falloff)) = radial_falloff(offset, config.interaction_radius)
+            {
+                velocity_change += direction * config.interaction_strength * falloff * delta_time;
+            }
+        }
+
+        if config.fan_strength != 0.0
+        {
+            let offset = position - Vec2::from(config.fan_origin);
+            let distance = offset.length();
+            let fan_direction = Vec2::from(config.fan_direction);
+            let cos_angle = offset.dot(fan_direction) / distance;
+            if distance < config.fan_reach && distance >= 0.0001 && cos_angle > config.fan_cos_half_angle
+            {
+                let edge_falloff = (cos_angle - config.fan_cos_half_angle) / (1.0 - config.fan_cos_half_angle);
+                let falloff = (1.0 - distance / config.fan_reach) * edge_falloff;
+                velocity_change += fan_direction * config.fan_strength * falloff * delta_time;
+            }
+        }
+
+        // one-frame kick, not scaled by the time step
+        if config.impulse_strength != 0.0
+        {
+            let offset = position - Vec2::from(config.impulse_position);
+            if let Some((direction, falloff)) = radial_falloff(offset, config.impulse_radius)
+            {
+                velocity_change += direction * config.impulse_strength * falloff;
+            }
+        }
+        velocity_change
+    }
+
+    // one sim step, the compute shader's pre_simulation_step then simulation_step. Forces read the
+    // velocities from after gravity, where the shader reads whatever its neighbors have reached.
+    pub fn step(particles: &mut [[f32; 8]], config: &ParticleConfig)
+    {
+        if config.frame_count < SHADER_DELAY { return; }
+        let delta_time = config.fixed_delta_time;
+        let gravity = Vec2::from(config.gravity);
+        let kernels = Kernels { config };
+        let sqr_radius = config.smoothing_radius * config.smoothing_radius;
+
+        particles.par_iter_mut().filter(|particle| !is_drained(particle)).for_each(|particle| {
+            particle[2] += gravity.x * delta_time;
+            particle[3] += gravity.y * delta_time;
+        });
+        let predicted: Vec<Vec2> = particles.par_iter()
+            .map(|particle| position(particle) + velocity(particle) * delta_time)
+            .collect();
+        let velocities: Vec<Vec2> = particles.par_iter().map(velocity).collect();
+        let grid = SpatialGrid::build(&predicted, config);
+        let (grid, predicted) = (&grid, &predicted);
+
+        let neighbors_within = |i: usize| {
+            grid.neighbors(predicted[i]).filter_map(move |other| {
+                let delta = predicted[other] - predicted[i];
+                let sqr_distance = delta.length_squared();
+                (sqr_distance <= sqr_radius).then(|| (other, delta, sqr_distance.sqrt()))
+            })
+        };
+
+        // density and near density from the predicted positions
+        let densities: Vec<Vec2> = (0..particles.len()).into_par_iter()
+            .map(|i| {
+                if is_drained(&particles[i]) { return Vec2::ZERO; }
+                neighbors_within(i)
+                    .map(|(_, _, distance)| Vec2::new(kernels.density(distance), kernels.near_density(distance)))
+                    .sum()
+            })
+            .collect();
+
+        particles.par_iter_mut().enumerate().filter(|(_, particle)| !is_drained(particle)).for_each(|(i, particle)| {
+            let [density, near_density] = densities[i].to_array();
+            let pressure = kernels.pressure(density);
+            let near_pressure = kernels.near_pressure(near_density);
+
+            let mut pressure_force = Vec2::ZERO;
+            let mut viscosity = Vec2::ZERO;
+            for (other, delta, distance) in neighbors_within(i).filter(|(other, _, _)| *other != i)
+            {
+                let direction = if distance > 0.0001 { delta / distance } else { Vec2::Y };
+
+                // symmetric SPH formulation, as in calculate_pressure_force
+                let [neighbor_density, neighbor_near_density] = densities[other].to_array();
+                let pressure_term = pressure / (density * density)
+                    + kernels.pressure(neighbor_density) / (neighbor_density * neighbor_density);
+                let near_pressure_term = near_pressure / (density * density)
+                    + kernels.near_pressure(neighbor_near_density) / (neighbor_density * neighbor_near_density);
+                pressure_force += direction * pressure_term * kernels.density_derivative(distance);
+                pressure_force += direction * near_pressure_term * kernels.near_density_derivative(distance);
+
+                viscosity += (velocities[other] - velocities[i]) * kernels.viscosity(distance);
+            }
+
+            let mut velocity = velocities[i]
+                + pressure_force * delta_time
+                + viscosity * config.viscocity_strength * delta_time;
+            velocity += external_forces(position(particle), config);
+            let position = position(particle) + velocity * delta_time;
+            particle[..4].copy_from_slice(&[position.x, position.y, velocity.x, velocity.y]);
+
+            check_screen_bounds(particle, config);
+        });
+    }
+}
+
+// without the feature SimBackend::available never picks the CPU, so there is nothing to step
+#[cfg(not(feature = "cpu_backend"))]
+mod solver
+{
+    use crate::ParticleConfig;
+
+    pub fn step(_particles: &mut [[f32; 8]], _config: &ParticleConfig) {}
+}
+
+// Step the main system on the CPU, after every param change of the frame. Its particles start
+// over from the extracted scatter whenever the GPU copy would be replaced, and take the frame's
+// emitted batch into the same ring slots.
+pub fn step_cpu_particles(
+    config: Res<ParticleConfig>,
+    emitted: Res<EmittedParticles>,
+    particle_system_query: Query<&ParticleSystem, Without<ParticleSystemConfig>>,
+    mut cpu_particles: ResMut<CpuParticles>,
+    mut last_emitted_batch: Local<u64>,
+)
+{
+    let Ok(particle_system) = particle_system_query.single() else { return; };
+
+    if cpu_particles.particle_count != config.particle_count || cpu_particles.generation != particle_system.generation
+    {
+        if particle_system.particles.len() != config.particle_count as usize { return; }
+        cpu_particles.particles = particle_system.particles.iter()
+            .map(|particle| {
+                let [x, y] = particle.position;
+                let [vx, vy] = particle.velocity;
+                let [r, g, b, a] = particle.color;
+                [x, y, vx, vy, r, g, b, a]
+            })
+            .collect();
+        cpu_particles.particle_count = config.particle_count;
+        cpu_particles.generation = particle_system.generation;
+        cpu_particles.batch += 1;
+        return;
+    }
+
+    let new_batch = emitted.newer_than(*last_emitted_batch);
+    if config.paused != 0 && new_batch.is_none() { return; }
+
+    if let Some((batch, first_index, particles)) = new_batch
+    {
+        *last_emitted_batch = batch;
+        let ring = &mut cpu_particles.particles;
+        if ring.is_empty() { return; }
+        for (offset, particle) in particles.iter().enumerate()
+        {
+            let index = (first_index as usize + offset) % ring.len();
+            ring[index] = *particle;
+        }
+    }
+    if config.paused == 0
+    {
+        solver::step(&mut cpu_particles.particles, &config);
+    }
+    cpu_particles.batch += 1;
+}
+
+// replace the main system's particle buffer with the latest CPU step, once its upload is done
+pub fn upload_cpu_particles(
+    render_queue: Res<RenderQueue>,
+    cpu_particles: Res<CpuParticles>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+    mut last_batch: Local<u64>,
+)
+{
+    if cpu_particles.batch == *last_batch { return; }
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        if pipeline_buffers.particle_count != cpu_particles.particle_count || pipeline_buffers.generation != cpu_particles.generation { continue; }
+
+        render_queue.write_buffer(&pipeline_buffers.particle_buffer, 0, bytemuck::cast_slice(&cpu_particles.particles));
+        *last_batch = cpu_particles.batch;
+    }
+}
@@ -56,9 +56,9 @@ impl Node for ParticleDebugNode
             //             device, 
             //             queue, 
             //             &pipeline_buffers.spatial_lookup_offsets_buffer, 
-            //             particle_count
+            //             config.spatial_grid_cells()
             //         );
-            //         print_spatial_lookup_offsets(spatial_lookup_offsets, particle_count);
+            //         print_spatial_lookup_offsets(spatial_lookup_offsets, config.spatial_grid_cells());
             //         let densities = read_particle_densities_from_gpu(
             //             device, 
             //             queue, 
@@ -104,13 +104,69 @@ fn print_config(config: ParticleConfig)
 
     println!("fixed_delta_time: {}", config.fixed_delta_time);
     println!("frame_count: {}", config.frame_count);
-    println!("gravity: {}", config.gravity);
+    println!("velocity_history_frames: {}", config.velocity_history_frames);
+    println!("gravity: {:?}", config.gravity);
+    println!("paused: {}", config.paused);
 
     println!("target_density: {}", config.target_density);
     println!("pressure_multiplier: {}", config.pressure_multiplier);
     println!("viscocity_strength: {}", config.viscocity_strength);
     println!("near_density_multiplier: {}", config.near_density_multiplier);
 
+    println!("spatial_grid: {}x{}", config.spatial_grid_width, config.spatial_grid_height);
+    println!("scalar_grid: {}x{} (cell size {})", config.scalar_grid_width, config.scalar_grid_height, config.scalar_grid_cell_size);
+    println!("smoke_enabled: {}", config.smoke_enabled);
+    println!("smoke_injection: {}", config.smoke_injection);
+    println!("smoke_dissipation: {}", config.smoke_dissipation);
+    println!("smoke_opacity: {}", config.smoke_opacity);
+
+    println!("flip_enabled: {}", config.flip_enabled);
+    println!("flip_ratio: {}", config.flip_ratio);
+    println!("pressure_iterations: {}", config.pressure_iterations);
+    println!("divergence_view: {}", config.divergence_view);
+    println!("divergence_range: {}", config.divergence_range);
+
+    println!("color_field: {}", config.color_field);
+    println!("colormap: {}", config.colormap);
+    println!("color_range: {} to {}", config.color_min, config.color_max);
+
+    println!("interaction_position: {:?}", config.interaction_position);
+    println!("interaction_strength: {}", config.interaction_strength);
+    println!("interaction_radius: {}", config.interaction_radius);
+
+    println!("impulse_position: {:?}", config.impulse_position);
+    println!("impulse_strength: {}", config.impulse_strength);
+    println!("impulse_radius: {}", config.impulse_radius);
+
+    println!("fan_origin: {:?}", config.fan_origin);
+    println!("fan_direction: {:?}", config.fan_direction);
+    println!("fan_strength: {}", config.fan_strength);
+    println!("fan_reach: {}", config.fan_reach);
+    println!("fan_cos_half_angle: {}", config.fan_cos_half_angle);
+    println!("render_mode: {}", config.render_mode);
+    println!("surface_threshold: {}", config.surface_threshold);
+    println!("surface_blur_radius: {}", config.surface_blur_radius);
+    println!("heatmap_range: {}", config.heatmap_range);
+    println!("surrogate_enabled: {}", config.surrogate_enabled);
+    println!("surrogate_strength: {}", config.surrogate_strength);
+    println!("compensated_summation: {}", config.compensated_summation);
+    println!("deterministic_order: {}", config.deterministic_order);
+    println!("temperature_enabled: {}", config.temperature_enabled);
+    println!("ambient_temperature: {}", config.ambient_temperature);
+    println!("heat_diffusion: {}", config.heat_diffusion);
+    println!("cooling_rate: {}", config.cooling_rate);
+    println!("thermal_expansion: {}", config.thermal_expansion);
+    println!("heaters: {} at {}, {} high, {:?}", config.heater_count, config.heater_temperature, config.heater_height, config.heater_spans);
+    println!("solver: {} ({} iterations, relaxation {})", config.solver, config.pbf_iterations, config.pbf_relaxation);
+    println!("trails: {} frames, opacity {}, width {}", config.trail_length, config.trail_opacity, config.trail_width);
+    println!("tonemapping: {}, exposure {}", config.tonemapping, config.exposure);
+    println!("density alpha: {}, min {}, range {}, gamma {}", config.density_alpha_enabled, config.density_alpha_min, config.density_alpha_range, config.density_alpha_gamma);
+    println!("velocity field: {}, spacing {}, full speed {}", config.velocity_field_mode, config.velocity_field_spacing, config.velocity_field_full_speed);
+    println!("outline: {}, width {}, color {:?}", config.outline_enabled, config.outline_width, config.outline_color);
+    println!("retro: {}, {} palette colors, dither {}, pixel size {}", config.retro_enabled, config.palette_size, config.palette_dither, config.retro_pixel_size);
+    println!("substeps: {} of {} (cfl {}, up to {})", config.substeps, config.substep_delta_time, config.cfl_number, config.max_substeps);
+    println!("boundary_modes: {:?}", config.boundary_modes);
+
     println!("screen_bounds: {:?}", config.screen_bounds);
     println!("view_proj:");
     for row in &config.view_proj {
@@ -118,6 +174,9 @@ fn print_config(config: ParticleConfig)
     }
 }
 
+// Blocking readbacks for debugging. The browser only runs map callbacks once control returns to
+// its event loop, so waiting on one there never finishes and these are desktop only.
+#[cfg(not(target_arch = "wasm32"))]
 pub fn read_spatial_lookup_buffer_from_gpu(
     device: &RenderDevice,
     queue: &RenderQueue, 
@@ -174,13 +233,14 @@ pub fn validate_spatial_lookup(
     //println!("ARRAY IS SORTED!!!");
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn read_grid_start_idxs_from_gpu(
     device: &RenderDevice,
     queue: &RenderQueue, 
     source_buffer: &Buffer,
-    particle_count: u32
+    cell_count: u32
 ) -> Vec<u32> {
-    let buffer_size = (particle_count as u64) * std::mem::size_of::<u32>() as u64;
+    let buffer_size = (cell_count as u64) * std::mem::size_of::<u32>() as u64;
     
     // Create staging buffer
     let staging_buffer = device.create_buffer(&BufferDescriptor {
@@ -219,6 +279,7 @@ pub fn read_grid_start_idxs_from_gpu(
     result
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn read_particle_densities_from_gpu(
     device: &RenderDevice,
     queue: &RenderQueue, 
@@ -277,10 +338,10 @@ pub fn print_densities(
 
 pub fn print_spatial_lookup_offsets(
     array: Vec<u32>,
-    particle_count: u32,
+    cell_count: u32,
 ) {
     println!("SPATIAL LOOKUP OFFSETS");
-    for i in 0..particle_count as usize {
+    for i in 0..cell_count as usize {
         println!("Index {}: Value {}", i, array[i]);
     }
     //println!("ARRAY IS SORTED!!!");
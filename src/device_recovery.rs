@@ -0,0 +1,136 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::ParticleConfig;
+use crate::autosave::Autosave;
+use crate::gpu_readback::GpuReadback;
+use crate::headless::HeadlessRun;
+use crate::parameter_gui::GUIConfig;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::scene::encode_scene;
+
+pub const RESUME_ARG: &str = "--resume-after-device-loss";
+const SNAPSHOT_INTERVAL: u32 = 300;     // sim frames between particle snapshots
+
+type ParticleRecord = [f32; 8];
+
+// shared between main and render worlds: wgpu raises the flag when the device is lost, the
+// render world hands back a particle snapshot every SNAPSHOT_INTERVAL frames
+#[derive(Resource, Clone, Default)]
+pub struct DeviceRecoveryShared
+{
+    device_lost: Arc<AtomicBool>,
+    particles: Arc<Mutex<Option<Vec<ParticleRecord>>>>,
+}
+
+// render world side of the snapshot readback
+#[derive(Resource)]
+pub struct DeviceSnapshotReadback
+{
+    particles: GpuReadback,
+    requested_frame: Option<u32>,
+}
+
+impl Default for DeviceSnapshotReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("device_snapshot_readback_buffer"), requested_frame: None }
+    }
+}
+
+// Bevy can't swap a new device into a running renderer, so a lost device (driver reset, resume
+// from suspend) is only noted here and restart_from_snapshot does the rest. Uncaptured errors
+// are left to wgpu's and Bevy's own handling.
+pub fn watch_for_device_loss(render_device: &RenderDevice, shared: &DeviceRecoveryShared)
+{
+    let device_lost = shared.device_lost.clone();
+    render_device.wgpu_device().set_device_lost_callback(move |reason, message| {
+        error!("[Device] GPU device lost ({:?}): {}", reason, message);
+        device_lost.store(true, Ordering::Relaxed);
+    });
+}
+
+// the most recent particles to restart from if the device is lost
+pub fn snapshot_particles_for_recovery(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<DeviceRecoveryShared>,
+    mut snapshot_readback: ResMut<DeviceSnapshotReadback>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if shared.device_lost.load(Ordering::Relaxed) { return; }
+
+    if let Some(particles) = snapshot_readback.particles.try_read::<ParticleRecord>(&render_device)
+    {
+        *shared.particles.lock().unwrap() = Some(particles);
+    }
+
+    if !snapshot_readback.particles.is_idle() { return; }
+    let due = snapshot_readback.requested_frame.is_none_or(|frame| config.frame_count.wrapping_sub(frame) >= SNAPSHOT_INTERVAL);
+    if !due { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        snapshot_readback.requested_frame = Some(config.frame_count);
+        snapshot_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            (std::mem::size_of::<ParticleRecord>() * pipeline_buffers.particle_count as usize) as u64,
+        );
+    }
+}
+
+// Not an in-process recovery: once the device is lost, write the params and last snapshot to
+// the autosave's recovery files and restart the app with the same arguments. The new process
+// gets a fresh device and restores the files without asking. Headless runs have no session to
+// resume and just fail, as does the browser, which can't start a process or write the files,
+// so the page has to be reloaded by hand.
+pub fn restart_from_snapshot(
+    shared: Res<DeviceRecoveryShared>,
+    gui_config: Res<GUIConfig>,
+    headless: Option<Res<HeadlessRun>>,
+    mut autosave: ResMut<Autosave>,
+    mut exit: EventWriter<AppExit>,
+    mut recovering: Local<bool>,
+)
+{
+    if *recovering || !shared.device_lost.load(Ordering::Relaxed) { return; }
+    *recovering = true;
+
+    if headless.is_some() || cfg!(target_arch = "wasm32")
+    {
+        error!("[Device] Stopping, the GPU device was lost and this run can't restart itself");
+        exit.write(AppExit::error());
+        return;
+    }
+
+    let params = gui_config.to_text();
+    let scene = shared.particles.lock().unwrap().take().map(|particles| encode_scene(&params, &particles));
+    if let Err(error) = autosave.write_recovery(&params, scene.as_deref())
+    {
+        error!("[Device] Failed to write the recovery files: {}", error);
+        exit.write(AppExit::error());
+        return;
+    }
+
+    // every original argument is passed on, only the resume flag is added
+    let restarted = std::env::current_exe().and_then(|executable| {
+        std::process::Command::new(executable)
+            .args(std::env::args_os().skip(1).filter(|arg| arg != RESUME_ARG))
+            .arg(RESUME_ARG)
+            .spawn()
+    });
+    match restarted {
+        Ok(_) => info!("[Device] Restarting from the last snapshot on a new device"),
+        Err(error) => error!("[Device] Failed to restart, restore the recovery files on the next launch: {}", error),
+    }
+    exit.write(AppExit::Success);
+}
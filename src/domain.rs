@@ -0,0 +1,244 @@
+use bevy::{
+    prelude::*,
+    render::camera::ScalingMode,
+    window::{PrimaryWindow, WindowResized},
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{centered_bounds, get_screen_bounds, setup_particles_scatter, size_scalar_grid, ParticleConfig, ParticleSystem};
+use crate::gui_scale::GuiScale;
+use crate::initial_layout::InitialLayout;
+use crate::sim_rng::SimRng;
+
+const DOMAIN_PRESETS: [(&str, Vec2); 4] = [
+    ("16:9", Vec2::new(1600.0, 900.0)),
+    ("4:3", Vec2::new(1200.0, 900.0)),
+    ("21:9", Vec2::new(2100.0, 900.0)),
+    ("Square", Vec2::new(900.0, 900.0)),
+];
+const LETTERBOX_COLOR: egui::Color32 = egui::Color32::from_gray(12);
+const BORDER_COLOR: egui::Color32 = egui::Color32::from_gray(160);
+const DEFAULT_BORDER_WIDTH: f32 = 2.0;
+
+// Size of the simulated region in world units, centered on the origin. Without one the domain
+// is whatever the window shows at one world unit per pixel, so scenes depend on the monitor's
+// aspect ratio; with one the camera fits the domain in the window and the rest is letterboxed.
+// `--domain <width>x<height>` sets it at launch.
+#[derive(Resource, Default)]
+pub struct Domain
+{
+    pub size: Option<Vec2>,
+}
+
+impl Domain
+{
+    pub fn from_args() -> Self
+    {
+        let args: Vec<String> = std::env::args().collect();
+        let size = args.iter().position(|arg| arg == "--domain")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|value| parse_size(value));
+        Self { size }
+    }
+
+    pub fn bounds(&self) -> Option<[f32; 4]>
+    {
+        self.size.map(|size| [-size.x / 2.0, size.x / 2.0, -size.y / 2.0, size.y / 2.0])
+    }
+
+    fn scaling_mode(&self) -> ScalingMode
+    {
+        match self.size {
+            Some(size) => ScalingMode::AutoMin { min_width: size.x, min_height: size.y },
+            None => ScalingMode::WindowSize,
+        }
+    }
+}
+
+// how the domain's extent is shown, kept apart from Domain so toggling it doesn't rescatter
+#[derive(Resource)]
+pub struct DomainFrame
+{
+    pub letterbox: bool,
+    pub border: bool,
+    pub border_width: f32,      // egui points
+}
+
+impl Default for DomainFrame
+{
+    fn default() -> Self
+    {
+        Self { letterbox: true, border: true, border_width: DEFAULT_BORDER_WIDTH }
+    }
+}
+
+fn parse_size(value: &str) -> Option<Vec2>
+{
+    let (width, height) = value.split_once('x')?;
+    let size = Vec2::new(width.trim().parse().ok()?, height.trim().parse().ok()?);
+    (size.x > 0.0 && size.y > 0.0).then_some(size)
+}
+
+// fit the camera to the domain, and move the sim into the new bounds when it changes after setup
+pub fn update_domain(
+    domain: Res<Domain>,
+    layout: Res<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
+    mut projection_query: Query<&mut Projection, With<Camera2d>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut particle_config: ResMut<ParticleConfig>,
+    mut particle_system_query: Query<&mut ParticleSystem>,
+)
+{
+    if !domain.is_changed() { return; }
+
+    for mut projection in projection_query.iter_mut()
+    {
+        if let Projection::Orthographic(orthographic) = projection.as_mut()
+        {
+            orthographic.scaling_mode = domain.scaling_mode();
+        }
+    }
+
+    // setup_particles hasn't run yet, it picks up the domain itself
+    if particle_config.screen_bounds == [0.0; 4] { return; }
+
+    let Some(bounds) = domain.bounds().or_else(|| get_screen_bounds(&camera_query)) else { return; };
+    particle_config.screen_bounds = bounds;
+    size_scalar_grid(&mut particle_config);
+
+    // the grids are sized from the bounds, so every system gets new buffers along with its particles
+    for mut particle_system in particle_system_query.iter_mut()
+    {
+        let particle_count = particle_system.particles.len() as u32;
+        particle_system.particles = setup_particles_scatter(&layout, &mut sim_rng, bounds, particle_count);
+        particle_system.generation = particle_system.generation.wrapping_add(1);
+    }
+}
+
+// Without a fixed domain the bounds track the window, so particles aren't clipped or left
+// outside after a resize or leaving fullscreen. The particles stay where they are; the bounds
+// pass pushes any that end up outside back in. A fixed domain is refit by the camera instead.
+pub fn fit_bounds_to_window(
+    mut resized_events: EventReader<WindowResized>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    domain: Res<Domain>,
+    mut particle_config: ResMut<ParticleConfig>,
+)
+{
+    let Some(resized) = resized_events.read()
+        .filter(|resized| primary_window.get(resized.window).is_ok())
+        .last() else { return; };
+
+    // setup_particles hasn't run yet, or the domain doesn't depend on the window
+    if particle_config.screen_bounds == [0.0; 4] || domain.size.is_some() { return; }
+    if resized.width <= 0.0 || resized.height <= 0.0 { return; }   // minimized
+
+    // the camera's viewport isn't updated until PostUpdate, so size from the event
+    let bounds = centered_bounds(Vec2::new(resized.width, resized.height));
+    if bounds == particle_config.screen_bounds { return; }
+    particle_config.screen_bounds = bounds;
+    // the render world regrows the grid buffers if the new size needs more cells
+    size_scalar_grid(&mut particle_config);
+}
+
+// dark bars over the parts of the window outside the sim bounds and a frame around them
+pub fn draw_domain_frame(
+    mut contexts: EguiContexts,
+    config: Res<ParticleConfig>,
+    frame: Res<DomainFrame>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if !frame.letterbox && !frame.border { return Ok(()); }
+    if config.screen_bounds == [0.0; 4] { return Ok(()); }
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+    // viewport y runs down, so the top left corner is the world's (x_min, y_max)
+    let (Some(top_left), Some(bottom_right)) = (to_screen(Vec2::new(x_min, y_max)), to_screen(Vec2::new(x_max, y_min))) else { return Ok(()); };
+
+    let screen = ctx.screen_rect();
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    if frame.letterbox
+    {
+        for bar in [
+            egui::Rect::from_min_max(screen.min, egui::pos2(screen.max.x, top_left.y)),
+            egui::Rect::from_min_max(egui::pos2(screen.min.x, bottom_right.y), screen.max),
+            egui::Rect::from_min_max(egui::pos2(screen.min.x, top_left.y), egui::pos2(top_left.x, bottom_right.y)),
+            egui::Rect::from_min_max(egui::pos2(bottom_right.x, top_left.y), egui::pos2(screen.max.x, bottom_right.y)),
+        ]
+        {
+            if bar.is_positive()
+            {
+                painter.rect_filled(bar, 0.0, LETTERBOX_COLOR);
+            }
+        }
+    }
+
+    // drawn just outside the bounds so it never covers particles at the edge
+    if frame.border
+    {
+        let domain_rect = egui::Rect::from_min_max(top_left, bottom_right);
+        painter.rect_stroke(domain_rect, 0.0, egui::Stroke::new(frame.border_width, BORDER_COLOR), egui::StrokeKind::Outside);
+    }
+    Ok(())
+}
+
+// edits are kept locally until applied, since applying rescatters every system
+pub fn domain_gui(
+    mut contexts: EguiContexts,
+    mut domain: ResMut<Domain>,
+    mut frame: ResMut<DomainFrame>,
+    mut edit: Local<Option<(bool, Vec2)>>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let (fixed, size) = edit.get_or_insert((domain.size.is_some(), domain.size.unwrap_or(DOMAIN_PRESETS[0].1)));
+
+    egui::Window::new("Domain")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 700.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(fixed, false, "Fit Window");
+                ui.radio_value(fixed, true, "Fixed Size");
+            });
+            ui.add_enabled_ui(*fixed, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut size.x).range(100.0..=10000.0).prefix("W "));
+                    ui.add(egui::DragValue::new(&mut size.y).range(100.0..=10000.0).prefix("H "));
+                });
+                ui.horizontal(|ui| {
+                    for (name, preset) in DOMAIN_PRESETS {
+                        if ui.button(name).clicked() {
+                            *size = preset;
+                        }
+                    }
+                });
+            });
+
+            let wanted = fixed.then_some(*size);
+            if ui.add_enabled(wanted != domain.size, egui::Button::new("Apply (rescatters particles)")).clicked() {
+                domain.size = wanted;
+            }
+            match domain.size {
+                Some(size) => ui.label(format!("Current: {} x {} world units", size.x, size.y)),
+                None => ui.label("Current: window size"),
+            };
+
+            ui.separator();
+            ui.checkbox(&mut frame.letterbox, "Letterbox Bars");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut frame.border, "Border");
+                ui.add_enabled(frame.border, egui::Slider::new(&mut frame.border_width, 0.5..=8.0).text("Width"));
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        renderer::RenderQueue,
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+use rand::Rng;
+
+use crate::ParticleConfig;
+use crate::particle::Particle;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::sim_rng::SimRng;
+
+const EMITTER_RATE: f32 = 500.0;        // particles per second
+const EMITTER_SPEED: f32 = 200.0;       // pixels per second
+const EMITTER_SPREAD: f32 = 15.0;       // full cone angle in degrees
+const EMITTER_JITTER: f32 = 2.0;        // spawn position noise so particles don't stack exactly
+
+// injects particles over time; the particle buffer is a fixed size ring, so each new
+// particle replaces the oldest slot
+#[derive(Component, Clone, Copy)]
+pub struct Emitter
+{
+    pub position: Vec2,
+    pub direction: Vec2,    // unit vector
+    pub rate: f32,          // particles per second
+    pub speed: f32,         // initial speed
+    pub spread: f32,        // full cone angle in radians
+    spawn_debt: f32,        // fractional particles carried over between frames
+}
+
+impl Emitter
+{
+    pub fn new(position: Vec2, direction: Vec2, rate: f32, speed: f32, spread: f32) -> Self
+    {
+        Self { position, direction: direction.normalize_or(Vec2::X), rate, speed, spread, spawn_debt: 0.0 }
+    }
+
+    fn spawn(&self, rng: &mut impl Rng) -> Particle
+    {
+        let angle = rng.random_range(-0.5..=0.5) * self.spread;
+        let velocity = Vec2::from_angle(angle).rotate(self.direction) * self.speed;
+        let jitter = Vec2::new(rng.random_range(-1.0..=1.0), rng.random_range(-1.0..=1.0)) * EMITTER_JITTER;
+        Particle
+        {
+            position: (self.position + jitter).to_array(),
+            velocity: velocity.to_array(),
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+// particles emitted this frame and where they start in the ring; `batch` increases with every
+// non-empty batch so the render world writes each one once, however often it is extracted
+#[derive(ExtractResource, Resource, Clone, Default)]
+pub struct EmittedParticles
+{
+    batch: u64,
+    first_index: u32,
+    particles: Vec<[f32; 8]>,   // position, velocity, color
+}
+
+impl EmittedParticles
+{
+    // the newest batch if it came after `batch`, with where it starts in the ring
+    pub fn newer_than(&self, batch: u64) -> Option<(u64, u32, &[[f32; 8]])>
+    {
+        (self.batch > batch).then_some((self.batch, self.first_index, self.particles.as_slice()))
+    }
+}
+
+// particles other tools (the brush) want appended this frame; update_emitters takes them into
+// the same batch as the emitters' so they share its ring slots
+#[derive(Resource, Default)]
+pub struct SpawnQueue(pub Vec<Particle>);
+
+// next slot to overwrite, and the count it wraps at
+#[derive(Resource, Default)]
+pub struct EmitterRing
+{
+    cursor: u32,
+    particle_count: u32,
+}
+
+#[derive(Resource)]
+pub struct EmitterSettings
+{
+    pub rate: f32,
+    pub speed: f32,
+    pub spread: f32,    // degrees
+}
+
+impl Default for EmitterSettings
+{
+    fn default() -> Self
+    {
+        Self { rate: EMITTER_RATE, speed: EMITTER_SPEED, spread: EMITTER_SPREAD }
+    }
+}
+
+pub fn update_emitters(
+    time: Res<Time>,
+    config: Res<ParticleConfig>,
+    mut ring: ResMut<EmitterRing>,
+    mut emitted: ResMut<EmittedParticles>,
+    mut spawn_queue: ResMut<SpawnQueue>,
+    mut sim_rng: ResMut<SimRng>,
+    mut emitter_query: Query<&mut Emitter>,
+)
+{
+    let queued = std::mem::take(&mut spawn_queue.0);
+
+    // the ring starts over whenever the buffers are resized
+    if ring.particle_count != config.particle_count
+    {
+        ring.cursor = 0;
+        ring.particle_count = config.particle_count;
+    }
+    if config.paused != 0 || config.particle_count == 0 { return; }
+
+    // seeded runs emit per sim step so the count doesn't depend on the frame rate
+    let delta = if sim_rng.is_seeded() { config.fixed_delta_time } else { time.delta_secs() };
+    let rng = sim_rng.rng();
+    let mut particles = queued;
+    for mut emitter in emitter_query.iter_mut()
+    {
+        emitter.spawn_debt += emitter.rate * delta;
+        let count = emitter.spawn_debt.floor();
+        emitter.spawn_debt -= count;
+        particles.extend((0..count as u32).map(|_| emitter.spawn(rng)));
+    }
+    if particles.is_empty() { return; }
+
+    // more than a full ring in one frame would only overwrite itself
+    let skip = particles.len().saturating_sub(config.particle_count as usize);
+    let particles: Vec<[f32; 8]> = particles[skip..].iter()
+        .map(|particle| {
+            let [x, y] = particle.position;
+            let [vx, vy] = particle.velocity;
+            let [r, g, b, a] = particle.color;
+            [x, y, vx, vy, r, g, b, a]
+        })
+        .collect();
+
+    emitted.batch += 1;
+    emitted.first_index = ring.cursor;
+    ring.cursor = ((ring.cursor as usize + particles.len()) % config.particle_count as usize) as u32;
+    emitted.particles = particles;
+}
+
+// write the newest batch into the particle buffer, split in two where it wraps around the ring
+pub fn upload_emitted_particles(
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    emitted: Res<EmittedParticles>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+    mut last_batch: Local<u64>,
+)
+{
+    if emitted.batch == *last_batch { return; }
+    *last_batch = emitted.batch;
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        let particle_count = pipeline_buffers.particle_count as usize;
+        if pipeline_buffers.particle_count != config.particle_count || emitted.first_index as usize >= particle_count { continue; }
+
+        let first_index = emitted.first_index as usize;
+        let (before_wrap, after_wrap) = emitted.particles.split_at(emitted.particles.len().min(particle_count - first_index));
+        let stride = std::mem::size_of::<[f32; 8]>() as u64;
+        render_queue.write_buffer(&pipeline_buffers.particle_buffer, first_index as u64 * stride, bytemuck::cast_slice(before_wrap));
+        if !after_wrap.is_empty()
+        {
+            render_queue.write_buffer(&pipeline_buffers.particle_buffer, 0, bytemuck::cast_slice(after_wrap));
+        }
+    }
+}
+
+// add emitters at the middle of the screen and tune the ones that get added next
+pub fn emitter_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut settings: ResMut<EmitterSettings>,
+    emitter_query: Query<Entity, With<Emitter>>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let center = Vec2::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+
+    egui::Window::new("Emitters")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 900.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("{} emitters", emitter_query.iter().count()));
+            ui.add(egui::Slider::new(&mut settings.rate, 1.0..=20000.0)
+                .text("Rate (particles/s)")
+                .logarithmic(true));
+            ui.add(egui::Slider::new(&mut settings.speed, 0.0..=2000.0)
+                .text("Speed"));
+            ui.add(egui::Slider::new(&mut settings.spread, 0.0..=180.0)
+                .text("Spread (deg)"));
+            ui.horizontal(|ui| {
+                if ui.button("Add at Center").clicked() {
+                    commands.spawn(Emitter::new(center, Vec2::X, settings.rate, settings.speed, settings.spread.to_radians()));
+                }
+                if ui.button("Clear").clicked() {
+                    for entity in emitter_query.iter() {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            });
+        });
+    Ok(())
+}
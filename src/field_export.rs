@@ -0,0 +1,255 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+
+const FIELD_MAGIC: &[u8; 4] = b"PFLD";
+const FIELD_VERSION: u32 = 1;
+const DEFAULT_EXPORT_DIRECTORY: &str = "field_export";
+const DEFAULT_CELL_SIZE: f32 = 10.0;
+
+// Field file layout (one per exported frame), little endian:
+//   magic, version, sim frame, grid width, grid height (u32 each)
+//   cell size, x_min, y_min (f32 each), grid origin at the bottom left of the screen bounds
+//   density, width * height f32 row by row from the bottom, particles per unit area
+//   velocity, width * height [vx, vy] f32 pairs in the same order, mass weighted mean per cell
+type ParticleRecord = [f32; 8];
+
+// particles rasterized onto a regular grid
+pub struct FieldGrid
+{
+    pub width: u32,
+    pub height: u32,
+    pub cell_size: f32,
+    pub origin: Vec2,
+    pub density: Vec<f32>,
+    pub velocity: Vec<[f32; 2]>,
+}
+
+impl FieldGrid
+{
+    // cloud-in-cell: every particle is spread bilinearly over the four nearest cell centers
+    pub fn rasterize(particles: &[ParticleRecord], screen_bounds: [f32; 4], cell_size: f32) -> Self
+    {
+        let [x_min, x_max, y_min, y_max] = screen_bounds;
+        let width = ((x_max - x_min) / cell_size).ceil().max(1.0) as u32;
+        let height = ((y_max - y_min) / cell_size).ceil().max(1.0) as u32;
+        let origin = Vec2::new(x_min, y_min);
+
+        let cell_count = (width * height) as usize;
+        let mut weights = vec![0.0f32; cell_count];
+        let mut momentum = vec![Vec2::ZERO; cell_count];
+
+        for particle in particles
+        {
+            let position = Vec2::new(particle[0], particle[1]);
+            let velocity = Vec2::new(particle[2], particle[3]);
+
+            // grid coordinates relative to cell centers
+            let grid = (position - origin) / cell_size - 0.5;
+            let base = grid.floor();
+            let fraction = grid - base;
+
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)]
+            {
+                let x = base.x as i32 + dx;
+                let y = base.y as i32 + dy;
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 { continue; }
+
+                let weight_x = if dx == 0 { 1.0 - fraction.x } else { fraction.x };
+                let weight_y = if dy == 0 { 1.0 - fraction.y } else { fraction.y };
+                let weight = weight_x * weight_y;
+                let index = (y as u32 * width + x as u32) as usize;
+                weights[index] += weight;
+                momentum[index] += velocity * weight;
+            }
+        }
+
+        let cell_area = cell_size * cell_size;
+        let density = weights.iter().map(|weight| weight / cell_area).collect();
+        let velocity = weights.iter().zip(&momentum)
+            .map(|(weight, momentum)| if *weight > 0.0 { (*momentum / *weight).to_array() } else { [0.0; 2] })
+            .collect();
+
+        Self { width, height, cell_size, origin, density, velocity }
+    }
+
+    fn encode(&self, sim_frame: u32) -> Vec<u8>
+    {
+        let mut bytes = Vec::with_capacity(32 + 12 * self.density.len());
+        bytes.extend_from_slice(FIELD_MAGIC);
+        for word in [FIELD_VERSION, sim_frame, self.width, self.height]
+        {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for value in [self.cell_size, self.origin.x, self.origin.y]
+            .iter()
+            .chain(&self.density)
+            .chain(self.velocity.iter().flatten())
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+// shared between main and render worlds: the main world turns recording on, the render
+// world hands back each particle buffer readback along with the sim frame it was taken at
+#[derive(Resource, Clone, Default)]
+pub struct FieldExportShared
+{
+    recording: Arc<AtomicBool>,
+    particles: Arc<Mutex<Option<(u32, Vec<ParticleRecord>)>>>,
+}
+
+// render world side of the export readback
+#[derive(Resource)]
+pub struct FieldExportReadback
+{
+    particles: GpuReadback,
+    requested_frame: u32,
+}
+
+impl Default for FieldExportReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("field_export_readback_buffer"), requested_frame: 0 }
+    }
+}
+
+#[derive(Resource)]
+pub struct FieldExport
+{
+    pub directory: String,
+    pub cell_size: f32,
+    pub recording: bool,
+    pub frames_written: u32,
+    pub status: Option<String>,
+}
+
+impl Default for FieldExport
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            directory: DEFAULT_EXPORT_DIRECTORY.to_string(),
+            cell_size: DEFAULT_CELL_SIZE,
+            recording: false,
+            frames_written: 0,
+            status: None,
+        }
+    }
+}
+
+// a readback is requested as soon as the previous one lands, so while the sim runs faster
+// than the copies some frames are skipped; the sim frame in each file tells which
+pub fn read_back_field_export(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<FieldExportShared>,
+    mut export_readback: ResMut<FieldExportReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(particles) = export_readback.particles.try_read::<ParticleRecord>(&render_device)
+    {
+        *shared.particles.lock().unwrap() = Some((export_readback.requested_frame, particles));
+    }
+
+    if !export_readback.particles.is_idle() || !shared.recording.load(Ordering::Relaxed) { return; }
+    // the previous frame hasn't been written out yet
+    if shared.particles.lock().unwrap().is_some() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        let size = (std::mem::size_of::<ParticleRecord>() * pipeline_buffers.particle_count as usize) as u64;
+        if !budget.try_acquire("field_export", size, 1) { return; }
+
+        export_readback.requested_frame = config.frame_count;
+        export_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            size,
+        );
+    }
+}
+
+// rasterize and write each readback that arrives while recording
+pub fn update_field_export(
+    shared: Res<FieldExportShared>,
+    config: Res<ParticleConfig>,
+    mut export: ResMut<FieldExport>,
+)
+{
+    shared.recording.store(export.recording, Ordering::Relaxed);
+
+    let Some((sim_frame, particles)) = shared.particles.lock().unwrap().take() else { return; };
+    if !export.recording { return; }
+
+    let grid = FieldGrid::rasterize(&particles, config.screen_bounds, export.cell_size);
+    let path = PathBuf::from(&export.directory).join(format!("fields_{:06}.pfld", sim_frame));
+    let written = std::fs::create_dir_all(&export.directory)
+        .and_then(|_| std::fs::write(&path, grid.encode(sim_frame)));
+
+    match written
+    {
+        Ok(()) => {
+            export.frames_written += 1;
+            export.status = Some(format!("{} frames, {}x{} cells", export.frames_written, grid.width, grid.height));
+        }
+        Err(error) => {
+            // stop rather than failing again every frame
+            export.recording = false;
+            export.status = Some(format!("Failed to write {}: {}", path.display(), error));
+            warn!("[FieldExport] {}", export.status.as_ref().unwrap());
+        }
+    }
+}
+
+pub fn field_export_gui(
+    mut contexts: EguiContexts,
+    mut export: ResMut<FieldExport>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Field Export")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 850.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label("Density and velocity grids, one raw f32 file per frame");
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                ui.add_enabled(!export.recording, egui::TextEdit::singleline(&mut export.directory));
+            });
+            ui.add_enabled(!export.recording, egui::Slider::new(&mut export.cell_size, 2.0..=100.0)
+                .text("Cell Size")
+                .logarithmic(true));
+
+            let label = if export.recording { "Stop Recording" } else { "Start Recording" };
+            if ui.button(label).clicked() {
+                export.recording = !export.recording;
+                if export.recording {
+                    export.frames_written = 0;
+                    export.status = None;
+                }
+            }
+            if let Some(status) = &export.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+
+const READBACK_INTERVAL: u32 = 30;          // frames between density readbacks
+const DRIFT_HISTORY_LEN: usize = 120;       // samples kept for the drift plot
+const DRIFT_ALERT_THRESHOLD: f32 = 0.1;     // fractional volume drift treated as significant
+const DRIFT_ALERT_SAMPLES: usize = 10;      // consecutive samples past the threshold before alerting
+
+// latest average density read back from the GPU, shared between main and render worlds
+#[derive(Resource, Clone, Default)]
+pub struct DensitySample
+{
+    average_density: Arc<Mutex<Option<f32>>>,
+}
+
+// render world side of the density readback
+#[derive(Resource)]
+pub struct DensityReadback
+{
+    readback: GpuReadback,
+    frame_count: u32,
+}
+
+impl Default for DensityReadback
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            readback: GpuReadback::new("density_readback_buffer"),
+            frame_count: 0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct FluidVolumeStats
+{
+    pub rest_area: f32,         // particle count * rest spacing^2
+    pub measured_area: f32,     // particle count / average density
+    pub drift_history: VecDeque<f32>,
+    pub alert: bool,
+}
+
+impl FluidVolumeStats
+{
+    pub fn drift(&self) -> f32
+    {
+        self.drift_history.back().copied().unwrap_or(0.0)
+    }
+}
+
+// copies the density buffer back every READBACK_INTERVAL frames and publishes the average
+pub fn read_back_densities(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    sample: Res<DensitySample>,
+    mut density_readback: ResMut<DensityReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, Without<ParticleSystemConfig>>,
+)
+{
+    if let Some(densities) = density_readback.readback.try_read::<[f32; 2]>(&render_device)
+    {
+        if !densities.is_empty()
+        {
+            let total: f32 = densities.iter().map(|density| density[0]).sum();
+            *sample.average_density.lock().unwrap() = Some(total / densities.len() as f32);
+        }
+    }
+
+    // keeps asking every frame once the interval has passed, until the budget lets the copy through
+    density_readback.frame_count += 1;
+    if density_readback.frame_count < READBACK_INTERVAL || !density_readback.readback.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        let size = (std::mem::size_of::<[f32; 2]>() * pipeline_buffers.particle_count as usize) as u64;
+        if !budget.try_acquire("densities", size, 1) { return; }
+
+        density_readback.frame_count = 0;
+        density_readback.readback.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_densities_buffer,
+            size,
+        );
+    }
+}
+
+// turn the latest density sample into a volume drift measurement
+pub fn update_fluid_volume(
+    sample: Res<DensitySample>,
+    config: Res<ParticleConfig>,
+    mut stats: ResMut<FluidVolumeStats>,
+)
+{
+    let Some(average_density) = sample.average_density.lock().unwrap().take() else { return; };
+    if average_density <= 0.0 || config.target_density <= 0.0 || config.particle_count == 0 { return; }
+
+    // mass is 1 per particle, so the rest spacing^2 is 1 / target density
+    let particle_count = config.particle_count as f32;
+    stats.rest_area = particle_count / config.target_density;
+    stats.measured_area = particle_count / average_density;
+
+    let drift = stats.measured_area / stats.rest_area - 1.0;
+    stats.drift_history.push_back(drift);
+    if stats.drift_history.len() > DRIFT_HISTORY_LEN
+    {
+        stats.drift_history.pop_front();
+    }
+
+    // only alert on systematic drift, not a single noisy sample
+    let recent = stats.drift_history.iter().rev().take(DRIFT_ALERT_SAMPLES);
+    let alert = stats.drift_history.len() >= DRIFT_ALERT_SAMPLES
+        && (recent.clone().all(|d| *d > DRIFT_ALERT_THRESHOLD) || recent.clone().all(|d| *d < -DRIFT_ALERT_THRESHOLD));
+    stats.alert = alert;
+}
+
+pub fn fluid_volume_gui(
+    mut contexts: EguiContexts,
+    stats: Res<FluidVolumeStats>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Fluid Volume")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 10.0])  // Upper left corner
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("Rest Area: {:.0}", stats.rest_area));
+            ui.label(format!("Measured Area: {:.0}", stats.measured_area));
+            ui.label(format!("Drift: {:+.1}%", stats.drift() * 100.0));
+
+            // drift over time, centered on zero, scaled so the alert threshold sits at half height
+            let (response, painter) = ui.allocate_painter(egui::vec2(200.0, 60.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::DARK_GRAY), egui::StrokeKind::Inside);
+            painter.hline(rect.x_range(), rect.center().y, egui::Stroke::new(1.0, egui::Color32::GRAY));
+
+            let points: Vec<egui::Pos2> = stats.drift_history.iter().enumerate().map(|(i, drift)| {
+                let x = rect.left() + rect.width() * i as f32 / (DRIFT_HISTORY_LEN - 1) as f32;
+                let y = rect.center().y - (drift / (2.0 * DRIFT_ALERT_THRESHOLD)).clamp(-1.0, 1.0) * rect.height() * 0.5;
+                egui::pos2(x, y)
+            }).collect();
+            let color = if stats.alert { egui::Color32::RED } else { egui::Color32::LIGHT_GREEN };
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, color)));
+
+            if stats.alert
+            {
+                let direction = if stats.drift() > 0.0 { "gain" } else { "loss" };
+                ui.colored_label(egui::Color32::RED,
+                    format!("Systematic volume {direction}: check pressure multiplier and timestep"));
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        renderer::RenderQueue,
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+use bytemuck::{Pod, Zeroable};
+use std::f32::consts::TAU;
+
+use crate::ParticleConfig;
+use crate::particle_buffers::GPUPipelineBuffers;
+
+pub const MAX_FORCE_FIELDS: usize = 16;     // must match MAX_FORCE_FIELDS in compute_shader.wgsl
+const FORCE_FIELD_HEADER_SIZE: u64 = 8;     // field count, padded to the alignment of the array
+pub const FORCE_FIELD_BUFFER_SIZE: u64 = FORCE_FIELD_HEADER_SIZE + (std::mem::size_of::<GpuForceField>() * MAX_FORCE_FIELDS) as u64;
+
+const FORCE_FIELD_STRENGTH: f32 = 200.0;    // pixels/s^2
+const FORCE_FIELD_RADIUS: f32 = 150.0;
+const WAVE_LENGTH: f32 = 200.0;
+const WAVE_PERIOD: f32 = 2.0;               // seconds
+
+// values match the compute shader's FORCE_FIELD_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum ForceFieldKind
+{
+    Wind,       // constant push along the direction everywhere
+    Wave,       // push along the direction, sinusoidal in space and time, travelling along it
+    Vortex,     // swirl around the position, counter clockwise for positive strength
+    Attractor,  // pull towards the position, negative strength repels
+}
+
+impl ForceFieldKind
+{
+    pub const ALL: [ForceFieldKind; 4] = [
+        ForceFieldKind::Wind,
+        ForceFieldKind::Wave,
+        ForceFieldKind::Vortex,
+        ForceFieldKind::Attractor,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            ForceFieldKind::Wind => "Wind",
+            ForceFieldKind::Wave => "Wave",
+            ForceFieldKind::Vortex => "Vortex",
+            ForceFieldKind::Attractor => "Attractor",
+        }
+    }
+
+    // wind and waves act everywhere, the others fade out to nothing at their radius
+    fn is_local(&self) -> bool
+    {
+        matches!(self, ForceFieldKind::Vortex | ForceFieldKind::Attractor)
+    }
+}
+
+// analytic acceleration added to every particle it reaches, in world units
+#[derive(ExtractComponent, Component, Clone, Copy)]
+pub struct ForceField
+{
+    pub kind: ForceFieldKind,
+    pub position: Vec2,
+    pub direction: f32,     // degrees counter clockwise from +x, wind and waves only
+    pub strength: f32,      // peak acceleration
+    pub radius: f32,        // vortices and attractors only
+    pub wavelength: f32,    // waves only
+    pub period: f32,        // waves only, seconds
+    phase: f32,             // radians the wave has travelled, advanced with the sim
+}
+
+impl ForceField
+{
+    pub fn new(kind: ForceFieldKind, position: Vec2) -> Self
+    {
+        Self
+        {
+            kind,
+            position,
+            direction: 0.0,
+            strength: FORCE_FIELD_STRENGTH,
+            radius: FORCE_FIELD_RADIUS,
+            wavelength: WAVE_LENGTH,
+            period: WAVE_PERIOD,
+            phase: 0.0,
+        }
+    }
+
+    fn to_gpu(&self) -> GpuForceField
+    {
+        GpuForceField
+        {
+            position: self.position.to_array(),
+            direction: Vec2::from_angle(self.direction.to_radians()).to_array(),
+            kind: self.kind as u32,
+            strength: self.strength,
+            radius: self.radius.max(1e-3),
+            wavenumber: TAU / self.wavelength.max(1e-3),
+            phase: self.phase,
+            _padding: 0,
+        }
+    }
+}
+
+// mirrors the ForceField struct in compute_shader.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuForceField
+{
+    position: [f32; 2],
+    direction: [f32; 2],    // unit vector
+    kind: u32,
+    strength: f32,
+    radius: f32,
+    wavenumber: f32,        // radians per world unit along the direction
+    phase: f32,
+    _padding: u32,
+}
+
+// advance the waves with the sim, so they hold still while it's paused
+pub fn update_force_fields(
+    config: Res<ParticleConfig>,
+    mut field_query: Query<&mut ForceField>,
+)
+{
+    if config.paused != 0 { return; }
+
+    for mut field in field_query.iter_mut()
+    {
+        if field.kind != ForceFieldKind::Wave { continue; }
+        let phase = field.phase + TAU * config.fixed_delta_time / field.period.max(1e-3);
+        field.phase = phase % TAU;
+    }
+}
+
+// pack the extracted force fields into each system's force field buffer
+pub fn prepare_force_fields(
+    render_queue: Res<RenderQueue>,
+    field_query: Query<&ForceField>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers>,
+    mut warned: Local<bool>,
+)
+{
+    let field_count = field_query.iter().count();
+    if field_count > MAX_FORCE_FIELDS && !*warned
+    {
+        warn!("[Force Fields] {} force fields spawned, only the first {} apply", field_count, MAX_FORCE_FIELDS);
+        *warned = true;
+    }
+
+    let fields: Vec<GpuForceField> = field_query.iter()
+        .take(MAX_FORCE_FIELDS)
+        .map(|field| field.to_gpu())
+        .collect();
+    let header = [fields.len() as u32, 0u32];
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        render_queue.write_buffer(&pipeline_buffers.force_field_buffer, 0, bytemuck::bytes_of(&header));
+        if !fields.is_empty()
+        {
+            render_queue.write_buffer(&pipeline_buffers.force_field_buffer, FORCE_FIELD_HEADER_SIZE, bytemuck::cast_slice(&fields));
+        }
+    }
+}
+
+// add force fields at the middle of the screen, tune and remove them
+pub fn force_field_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut field_query: Query<(Entity, &mut ForceField)>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let center = Vec2::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+    let field_count = field_query.iter().count();
+
+    egui::Window::new("Force Fields")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 660.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("{} / {} force fields", field_count, MAX_FORCE_FIELDS));
+            ui.add_enabled_ui(field_count < MAX_FORCE_FIELDS, |ui| {
+                ui.horizontal(|ui| {
+                    for kind in ForceFieldKind::ALL {
+                        if ui.button(kind.name()).clicked() {
+                            commands.spawn(ForceField::new(kind, center));
+                        }
+                    }
+                });
+            });
+
+            for (index, (entity, mut field)) in field_query.iter_mut().enumerate()
+            {
+                let field = field.as_mut();
+                egui::CollapsingHeader::new(format!("#{} {}", index, field.kind.name()))
+                    .id_salt(entity)
+                    .show(ui, |ui| {
+                        ui.add(egui::Slider::new(&mut field.strength, -1000.0..=1000.0).text("Strength"));
+                        if field.kind.is_local()
+                        {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut field.position.x).speed(1.0).prefix("x "));
+                                ui.add(egui::DragValue::new(&mut field.position.y).speed(1.0).prefix("y "));
+                            });
+                            ui.add(egui::Slider::new(&mut field.radius, 10.0..=1000.0).text("Radius"));
+                        }
+                        else
+                        {
+                            ui.add(egui::Slider::new(&mut field.direction, -180.0..=180.0).text("Direction (deg)"));
+                        }
+                        if field.kind == ForceFieldKind::Wave
+                        {
+                            ui.add(egui::Slider::new(&mut field.wavelength, 20.0..=2000.0).text("Wavelength").logarithmic(true));
+                            ui.add(egui::Slider::new(&mut field.period, 0.1..=10.0).text("Period (s)").logarithmic(true));
+                        }
+                        if ui.small_button("Remove").clicked() {
+                            commands.entity(entity).despawn();
+                        }
+                    });
+            }
+
+            if ui.button("Clear").clicked() {
+                for (entity, _) in field_query.iter() {
+                    commands.entity(entity).despawn();
+                }
+            }
+        });
+    Ok(())
+}
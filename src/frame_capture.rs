@@ -0,0 +1,219 @@
+use bevy::{
+    prelude::*,
+    render::view::screenshot::{Screenshot, ScreenshotCaptured},
+    tasks::IoTaskPool,
+};
+use bevy_egui::{egui, EguiContexts};
+use image::{DynamicImage, ImageFormat};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const DEFAULT_CAPTURE_DIRECTORY: &str = "captures";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CaptureFormat
+{
+    Png,
+    Exr,    // 32 bit float, linear
+}
+
+impl CaptureFormat
+{
+    pub const ALL: [CaptureFormat; 2] = [CaptureFormat::Png, CaptureFormat::Exr];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            CaptureFormat::Png => "PNG",
+            CaptureFormat::Exr => "EXR",
+        }
+    }
+
+    fn extension(&self) -> &'static str
+    {
+        match self {
+            CaptureFormat::Png => "png",
+            CaptureFormat::Exr => "exr",
+        }
+    }
+}
+
+// Screenshots of the primary window, egui included. Bevy copies the view target into a
+// readback buffer and hands back the image a few frames later; it's encoded and written on the
+// IO task pool so recording doesn't stall the sim. F12 captures a frame, Shift+F12 starts and
+// stops recording every frame to a numbered sequence.
+#[derive(Resource)]
+pub struct FrameCapture
+{
+    pub format: CaptureFormat,
+    pub directory: String,
+    pub recording: bool,
+    sequence: u32,                          // recordings so far, each gets its own prefix
+    next_frame: u32,                        // frame number within the current recording
+    last_error: Arc<Mutex<Option<String>>>, // set by the writer tasks
+    status: Option<String>,
+}
+
+impl Default for FrameCapture
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            format: CaptureFormat::Png,
+            directory: DEFAULT_CAPTURE_DIRECTORY.to_string(),
+            recording: false,
+            sequence: 0,
+            next_frame: 0,
+            last_error: Arc::new(Mutex::new(None)),
+            status: None,
+        }
+    }
+}
+
+impl FrameCapture
+{
+    fn capture(&mut self, commands: &mut Commands, name: String)
+    {
+        let directory = PathBuf::from(&self.directory);
+        if let Err(error) = std::fs::create_dir_all(&directory)
+        {
+            self.status = Some(format!("Failed to create {}: {}", directory.display(), error));
+            self.recording = false;
+            return;
+        }
+
+        let path = directory.join(format!("{}.{}", name, self.format.extension()));
+        let format = self.format;
+        let last_error = self.last_error.clone();
+        commands.spawn(Screenshot::primary_window())
+            .observe(move |trigger: Trigger<ScreenshotCaptured>| {
+                let image = trigger.event().0.clone();
+                let path = path.clone();
+                let last_error = last_error.clone();
+                IoTaskPool::get().spawn(async move {
+                    if let Err(error) = write_image(image, &path, format)
+                    {
+                        error!("[Capture] Failed to write {}: {}", path.display(), error);
+                        *last_error.lock().unwrap() = Some(format!("Failed to write {}: {}", path.display(), error));
+                    }
+                }).detach();
+            });
+    }
+
+    fn start_recording(&mut self)
+    {
+        self.recording = true;
+        self.sequence += 1;
+        self.next_frame = 0;
+    }
+
+    fn stop_recording(&mut self)
+    {
+        self.recording = false;
+        self.status = Some(format!("Recorded {} frames to {}", self.next_frame, self.directory));
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32
+{
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+// the window is an 8 bit sRGB surface, so EXR frames get the same pixels in linear float
+fn write_image(image: Image, path: &Path, format: CaptureFormat) -> std::result::Result<(), String>
+{
+    let dynamic = image.try_into_dynamic().map_err(|error| error.to_string())?;
+    let written = match format {
+        CaptureFormat::Png => dynamic.to_rgb8().save_with_format(path, ImageFormat::Png),
+        CaptureFormat::Exr => {
+            let mut linear = dynamic.to_rgb32f();
+            for channel in linear.iter_mut()
+            {
+                *channel = srgb_to_linear(*channel);
+            }
+            DynamicImage::ImageRgb32F(linear).save_with_format(path, ImageFormat::OpenExr)
+        }
+    };
+    written.map_err(|error| error.to_string())
+}
+
+pub fn update_frame_capture(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut capture: ResMut<FrameCapture>,
+    mut screenshot_count: Local<u32>,
+)
+{
+    let typing = contexts.ctx_mut().map(|ctx| ctx.wants_keyboard_input()).unwrap_or(false);
+    if keyboard_input.just_pressed(KeyCode::F12) && !typing
+    {
+        let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if shift && capture.recording {
+            capture.stop_recording();
+        } else if shift {
+            capture.start_recording();
+        } else {
+            let name = format!("screenshot_{:04}", *screenshot_count);
+            *screenshot_count += 1;
+            capture.status = Some(format!("Captured {}", name));
+            capture.capture(&mut commands, name);
+        }
+    }
+
+    if capture.recording
+    {
+        let name = format!("sequence_{:02}_frame_{:06}", capture.sequence, capture.next_frame);
+        capture.next_frame += 1;
+        capture.capture(&mut commands, name);
+    }
+
+    if let Some(error) = capture.last_error.lock().unwrap().take()
+    {
+        capture.status = Some(error);
+    }
+}
+
+pub fn frame_capture_gui(
+    mut contexts: EguiContexts,
+    mut capture: ResMut<FrameCapture>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Frame Capture")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1300.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.label("Format");
+                for format in CaptureFormat::ALL {
+                    ui.radio_value(&mut capture.format, format, format.name());
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                ui.text_edit_singleline(&mut capture.directory);
+            });
+            ui.label("F12 captures a frame, Shift+F12 starts/stops recording");
+
+            let label = if capture.recording { "Stop Recording" } else { "Record" };
+            if ui.button(label).clicked()
+            {
+                if capture.recording {
+                    capture.stop_recording();
+                } else {
+                    capture.start_recording();
+                }
+            }
+            if capture.recording
+            {
+                ui.colored_label(egui::Color32::RED, format!("Recording: {} frames", capture.next_frame));
+            }
+            if let Some(status) = &capture.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use crate::particle_probe::ParticleProbe;
+
+// a fixed set of particles, by particle buffer index (the buffer is never reordered)
+#[derive(Component, Clone, Default)]
+pub struct ParticleGroup
+{
+    pub indices: Vec<u32>,
+}
+
+impl ParticleGroup
+{
+    // the particles currently inside a region
+    pub fn from_region(positions: &[Vec2], region: Rect) -> Self
+    {
+        let indices = positions.iter().enumerate()
+            .filter(|(_, position)| region.contains(**position))
+            .map(|(index, _)| index as u32)
+            .collect();
+        Self { indices }
+    }
+}
+
+// axis aligned region that keeps track of how many particles are inside it, optionally only
+// counting the particles of a ParticleGroup entity; refreshed whenever the particle probe updates
+#[derive(Component, Clone)]
+pub struct GoalRegion
+{
+    pub region: Rect,
+    pub group: Option<Entity>,
+    pub inside: u32,
+    pub total: u32,
+    pub fraction: f32,
+}
+
+impl GoalRegion
+{
+    pub fn new(region: Rect) -> Self
+    {
+        Self { region, group: None, inside: 0, total: 0, fraction: 0.0 }
+    }
+
+    pub fn for_group(region: Rect, group: Entity) -> Self
+    {
+        Self { group: Some(group), ..Self::new(region) }
+    }
+}
+
+// sent for every goal region each time its count is refreshed
+#[derive(Event)]
+pub struct GoalRegionUpdated
+{
+    pub region: Entity,
+    pub inside: u32,
+    pub total: u32,
+    pub fraction: f32,
+}
+
+pub fn update_goal_regions(
+    mut probe: ResMut<ParticleProbe>,
+    mut region_query: Query<(Entity, &mut GoalRegion)>,
+    group_query: Query<&ParticleGroup>,
+    mut updated_events: EventWriter<GoalRegionUpdated>,
+)
+{
+    if region_query.is_empty() { return; }
+    probe.wanted = true;
+
+    if !probe.updated { return; }
+    let Some(positions) = probe.positions.as_ref() else { return; };
+
+    for (entity, mut goal) in region_query.iter_mut()
+    {
+        let region = goal.region;
+        let is_inside = |index: usize| positions.get(index).is_some_and(|position| region.contains(*position));
+
+        let (inside, total) = match goal.group.map(|group| group_query.get(group)) {
+            Some(Ok(group)) => {
+                let inside = group.indices.iter().filter(|index| is_inside(**index as usize)).count();
+                (inside, group.indices.len())
+            }
+            // the group entity is gone, nothing left to count
+            Some(Err(_)) => (0, 0),
+            None => ((0..positions.len()).filter(|index| is_inside(*index)).count(), positions.len()),
+        };
+
+        goal.inside = inside as u32;
+        goal.total = total as u32;
+        goal.fraction = inside as f32 / total.max(1) as f32;
+        updated_events.write(GoalRegionUpdated { region: entity, inside: goal.inside, total: goal.total, fraction: goal.fraction });
+    }
+}
@@ -0,0 +1,331 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::*,
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+use bytemuck::Pod;
+use std::sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}};
+
+pub const DEFAULT_READBACK_KILOBYTES: u32 = 1024;    // per frame
+pub const DEFAULT_READBACK_TRANSFERS: u32 = 2;      // per frame
+const MAX_DEFERRED_FRAMES: u32 = 30;                // a request waiting this long goes through regardless
+
+// Non-blocking copy of a GPU buffer back to the CPU. A copy is requested once,
+// then polled on later frames until the staging buffer has been mapped, so the
+// render world never waits on the GPU (unlike the blocking reads in debug.rs).
+pub struct GpuReadback
+{
+    label: &'static str,
+    staging_buffer: Option<Buffer>,
+    size: u64,
+    pending: bool,
+    map_result: Arc<Mutex<Option<bool>>>,   // set by the map_async callback
+}
+
+impl GpuReadback
+{
+    pub fn new(label: &'static str) -> Self
+    {
+        Self
+        {
+            label,
+            staging_buffer: None,
+            size: 0,
+            pending: false,
+            map_result: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // true when no copy is in flight and a new one can be requested
+    pub fn is_idle(&self) -> bool
+    {
+        !self.pending
+    }
+
+    // copy the first `size` bytes of `source_buffer` to the staging buffer and start mapping it
+    pub fn request(
+        &mut self,
+        render_device: &RenderDevice,
+        render_queue: &RenderQueue,
+        source_buffer: &Buffer,
+        size: u64,
+    )
+    {
+        if self.pending || size == 0 { return; }
+
+        // (re)create the staging buffer if the requested copy no longer fits
+        if self.staging_buffer.as_ref().is_none_or(|buffer| buffer.size() < size)
+        {
+            self.staging_buffer = Some(render_device.create_buffer(&BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+        }
+        let staging_buffer = self.staging_buffer.as_ref().unwrap();
+
+        let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.copy_buffer_to_buffer(source_buffer, 0, staging_buffer, 0, size);
+        render_queue.submit(std::iter::once(encoder.finish()));
+
+        *self.map_result.lock().unwrap() = None;
+        let map_result = self.map_result.clone();
+        staging_buffer.slice(..size).map_async(MapMode::Read, move |result| {
+            *map_result.lock().unwrap() = Some(result.is_ok());
+        });
+
+        self.size = size;
+        self.pending = true;
+    }
+
+    // returns the copied data once the staging buffer is mapped, None while still in flight
+    pub fn try_read<T: Pod>(&mut self, render_device: &RenderDevice) -> Option<Vec<T>>
+    {
+        if !self.pending { return None; }
+
+        let _ = render_device.poll(Maintain::Poll);
+
+        let mapped = self.map_result.lock().unwrap().take()?;
+        self.pending = false;
+
+        let staging_buffer = self.staging_buffer.as_ref()?;
+        if !mapped
+        {
+            warn!("[Readback] Failed to map staging buffer '{}'", self.label);
+            return None;
+        }
+
+        // copy into a typed vec rather than casting the mapped bytes, which may be misaligned for T
+        let mut result = vec![T::zeroed(); self.size as usize / std::mem::size_of::<T>()];
+        {
+            let data = staging_buffer.slice(..self.size).get_mapped_range();
+            let len = result.len() * std::mem::size_of::<T>();
+            bytemuck::cast_slice_mut::<T, u8>(&mut result).copy_from_slice(&data[..len]);
+        }
+        staging_buffer.unmap();
+
+        Some(result)
+    }
+}
+
+// shared between main and render worlds: the statistics window sets the per frame budget and
+// shows how many requests are waiting on it
+#[derive(Resource, Clone)]
+pub struct ReadbackBudgetShared
+{
+    pub max_kilobytes: Arc<AtomicU32>,
+    pub max_transfers: Arc<AtomicU32>,
+    pub waiting: Arc<AtomicU32>,
+}
+
+impl Default for ReadbackBudgetShared
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            max_kilobytes: Arc::new(AtomicU32::new(DEFAULT_READBACK_KILOBYTES)),
+            max_transfers: Arc::new(AtomicU32::new(DEFAULT_READBACK_TRANSFERS)),
+            waiting: Arc::new(AtomicU32::new(0)),
+        }
+    }
+}
+
+struct WaitingReadback
+{
+    label: &'static str,
+    size: u64,
+    transfers: u32,
+    frames: u32,    // frames turned down so far
+    asked: bool,    // asked again this frame, dropped from the queue otherwise
+}
+
+// Render world side. The inspection features (stats, probes, exports, timings) ask for a grant
+// before requesting their copies, so together they stay within a per frame byte and transfer
+// budget. Requests turned down are queued oldest first and a newer one only goes through if the
+// budget still covers everything queued ahead of it, so the features take turns instead of
+// whichever runs first winning every frame. Readbacks the sim itself depends on (adaptive
+// substeps, rigid bodies, the surrogate, device recovery snapshots, training data) don't go
+// through the budget.
+#[derive(Resource, Default)]
+pub struct ReadbackBudget
+{
+    max_bytes: u64,
+    max_transfers: u32,
+    bytes: u64,
+    transfers: u32,
+    waiting: Vec<WaitingReadback>,
+}
+
+impl ReadbackBudget
+{
+    // true if `label` may make `transfers` copies totalling `size` bytes this frame, otherwise
+    // it's queued to ask again next frame
+    pub fn try_acquire(&mut self, label: &'static str, size: u64, transfers: u32) -> bool
+    {
+        let position = self.waiting.iter().position(|waiting| waiting.label == label);
+        let ahead = &self.waiting[..position.unwrap_or(self.waiting.len())];
+        let ahead_bytes: u64 = ahead.iter().map(|waiting| waiting.size).sum();
+        let ahead_transfers: u32 = ahead.iter().map(|waiting| waiting.transfers).sum();
+        let frames = position.map_or(0, |index| self.waiting[index].frames);
+
+        let fits = self.transfers + ahead_transfers + transfers <= self.max_transfers
+            && self.bytes + ahead_bytes + size <= self.max_bytes;
+        // the first grant of the frame always goes through, so one larger than the budget still lands
+        let first = self.transfers == 0 && ahead.is_empty();
+
+        if fits || first || frames >= MAX_DEFERRED_FRAMES
+        {
+            if let Some(index) = position
+            {
+                self.waiting.remove(index);
+            }
+            self.bytes += size;
+            self.transfers += transfers;
+            return true;
+        }
+
+        match position {
+            Some(index) => {
+                self.waiting[index].size = size;
+                self.waiting[index].transfers = transfers;
+                self.waiting[index].asked = true;
+            }
+            None => self.waiting.push(WaitingReadback { label, size, transfers, frames: 0, asked: true }),
+        }
+        false
+    }
+
+    // start a frame's budget, forgetting queued requests that weren't asked for again
+    fn begin_frame(&mut self, max_bytes: u64, max_transfers: u32)
+    {
+        self.max_bytes = max_bytes;
+        self.max_transfers = max_transfers.max(1);
+        self.bytes = 0;
+        self.transfers = 0;
+
+        self.waiting.retain(|waiting| waiting.asked);
+        for waiting in self.waiting.iter_mut()
+        {
+            waiting.frames += 1;
+            waiting.asked = false;
+        }
+    }
+}
+
+pub fn reset_readback_budget(
+    shared: Res<ReadbackBudgetShared>,
+    mut budget: ResMut<ReadbackBudget>,
+)
+{
+    budget.begin_frame(
+        shared.max_kilobytes.load(Ordering::Relaxed) as u64 * 1024,
+        shared.max_transfers.load(Ordering::Relaxed),
+    );
+    shared.waiting.store(budget.waiting.len() as u32, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn new_budget(max_bytes: u64, max_transfers: u32) -> ReadbackBudget
+    {
+        let mut budget = ReadbackBudget::default();
+        budget.begin_frame(max_bytes, max_transfers);
+        budget
+    }
+
+    #[test]
+    fn grants_requests_that_fit()
+    {
+        let mut budget = new_budget(1000, 4);
+        assert!(budget.try_acquire("a", 400, 1));
+        assert!(budget.try_acquire("b", 400, 1));
+        assert!(!budget.try_acquire("c", 400, 1), "over the byte budget");
+
+        let mut budget = new_budget(1000, 2);
+        assert!(budget.try_acquire("a", 100, 1));
+        assert!(budget.try_acquire("b", 100, 1));
+        assert!(!budget.try_acquire("c", 100, 1), "over the transfer budget");
+    }
+
+    #[test]
+    fn counts_every_transfer_of_a_grant()
+    {
+        let mut budget = new_budget(1000, 2);
+        assert!(budget.try_acquire("pair", 200, 2));
+        assert!(!budget.try_acquire("single", 10, 1));
+    }
+
+    #[test]
+    fn first_grant_of_the_frame_always_goes_through()
+    {
+        let mut budget = new_budget(100, 1);
+        assert!(budget.try_acquire("large", 500, 2));
+        assert!(!budget.try_acquire("small", 10, 1));
+
+        // the queued request is now ahead, so the large one waits its turn
+        budget.begin_frame(100, 1);
+        assert!(!budget.try_acquire("large", 500, 2));
+        assert!(budget.try_acquire("small", 10, 1));
+    }
+
+    #[test]
+    fn older_requests_go_first()
+    {
+        let mut budget = new_budget(1000, 4);
+        assert!(budget.try_acquire("a", 900, 1));
+        assert!(!budget.try_acquire("b", 800, 1));
+        assert!(!budget.try_acquire("c", 300, 1));
+
+        // c asks first but would leave no room for b, which was turned down before it
+        budget.begin_frame(1000, 4);
+        assert!(!budget.try_acquire("c", 300, 1));
+        assert!(budget.try_acquire("b", 800, 1));
+        assert!(!budget.try_acquire("a", 900, 1));
+
+        budget.begin_frame(1000, 4);
+        assert!(budget.try_acquire("c", 300, 1));
+        assert!(!budget.try_acquire("a", 900, 1), "over the byte budget behind c's grant");
+    }
+
+    #[test]
+    fn requests_waiting_too_long_go_through_regardless()
+    {
+        let mut budget = new_budget(100, 1);
+        assert!(budget.try_acquire("a", 100, 1));
+        assert!(!budget.try_acquire("b", 100, 1));
+        for _ in 1..MAX_DEFERRED_FRAMES
+        {
+            budget.begin_frame(100, 1);
+            budget.transfers = 1;   // spent by a grant outside the queue
+            assert!(!budget.try_acquire("b", 100, 1));
+        }
+
+        budget.begin_frame(100, 1);
+        budget.transfers = 1;
+        assert!(budget.try_acquire("b", 100, 1));
+        assert!(budget.waiting.is_empty());
+    }
+
+    #[test]
+    fn drops_requests_not_asked_for_again()
+    {
+        let mut budget = new_budget(100, 1);
+        assert!(budget.try_acquire("a", 100, 1));
+        assert!(!budget.try_acquire("b", 100, 1));
+
+        budget.begin_frame(100, 1);
+        assert_eq!(budget.waiting.len(), 1);
+
+        // b turned its feature off and didn't ask this frame
+        budget.begin_frame(100, 1);
+        assert!(budget.waiting.is_empty());
+        assert!(budget.try_acquire("c", 100, 1));
+    }
+}
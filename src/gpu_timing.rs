@@ -0,0 +1,250 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+    },
+};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU32, Ordering}};
+
+use crate::ParticleConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+
+const MAX_TIMED_SUBSTEPS: u32 = 32;     // the Max Substeps slider's top, later substeps go untimed
+const COMPUTE_QUERIES_PER_SUBSTEP: u32 = 2 * GpuPhase::COMPUTE.len() as u32;
+const RENDER_QUERY: u32 = 0;            // the render pass's pair comes first, then each substep's phases
+const QUERY_COUNT: u32 = 2 + MAX_TIMED_SUBSTEPS * COMPUTE_QUERIES_PER_SUBSTEP;
+
+// the main system's work timed on the GPU, each compute phase summed over the frame's substeps
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GpuPhase
+{
+    Grid,       // clear the key counts, count particles per cell
+    Offsets,    // prefix sum of the key counts
+    Sort,       // scatter into the spatial lookup, and order the cells' contents when deterministic
+    PreSim,     // predicted positions and densities, or the PBF prediction
+    Sim,        // forces and integration, or the PBF constraint iterations
+    Render,     // the system's render pass
+}
+
+impl GpuPhase
+{
+    pub const COMPUTE: [GpuPhase; 5] = [GpuPhase::Grid, GpuPhase::Offsets, GpuPhase::Sort, GpuPhase::PreSim, GpuPhase::Sim];
+    pub const ALL: [GpuPhase; 6] = [GpuPhase::Grid, GpuPhase::Offsets, GpuPhase::Sort, GpuPhase::PreSim, GpuPhase::Sim, GpuPhase::Render];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            GpuPhase::Grid => "Grid",
+            GpuPhase::Offsets => "Offsets",
+            GpuPhase::Sort => "Sort",
+            GpuPhase::PreSim => "Pre-Sim",
+            GpuPhase::Sim => "Sim",
+            GpuPhase::Render => "Render",
+        }
+    }
+
+    fn first_query(&self, substep: u32) -> u32
+    {
+        match self {
+            GpuPhase::Render => RENDER_QUERY,
+            _ => 2 + substep * COMPUTE_QUERIES_PER_SUBSTEP + 2 * (*self as u32),
+        }
+    }
+}
+
+// milliseconds per phase, indexed like GpuPhase::ALL
+#[derive(Clone, Copy, Default)]
+pub struct GpuTimings
+{
+    pub sim_frame: u32,
+    pub substeps: u32,
+    pub milliseconds: [f32; GpuPhase::ALL.len()],
+}
+
+// shared between main and render worlds: the stats overlay asks for timings while it's open
+// and takes the latest ones the render world read back
+#[derive(Resource, Clone, Default)]
+pub struct GpuTimingShared
+{
+    pub wanted: Arc<AtomicBool>,
+    pub supported: Arc<AtomicBool>,
+    pub timings: Arc<Mutex<Option<GpuTimings>>>,
+}
+
+// Render world side. Timestamps are written at the start and end of each phase's first and last
+// pass, so only the TIMESTAMP_QUERY feature is needed. A frame is only resolved once every phase
+// and the render pass wrote theirs, and the next one is timed once its readback has landed.
+#[derive(Resource)]
+pub struct GpuTiming
+{
+    query_set: Option<QuerySet>,  // None without timestamp query support
+    resolve_buffer: Buffer,
+    readback: GpuReadback,
+    timestamp_period: f32,              // nanoseconds per tick
+    active: AtomicBool,                 // timing this frame
+    timed_substeps: AtomicU32,          // substeps the compute node timed in full, 0 when any phase was skipped
+    render_timed: AtomicBool,
+    resolved: AtomicBool,
+    requested_substeps: u32,
+    requested_frame: u32,
+}
+
+impl FromWorld for GpuTiming
+{
+    fn from_world(world: &mut World) -> Self
+    {
+        let render_device = world.resource::<RenderDevice>();
+        let query_set = render_device.features().contains(WgpuFeatures::TIMESTAMP_QUERY).then(|| {
+            render_device.wgpu_device().create_query_set(&QuerySetDescriptor {
+                label: Some("gpu_timing_query_set"),
+                ty: QueryType::Timestamp,
+                count: QUERY_COUNT,
+            })
+        });
+        if query_set.is_none()
+        {
+            info!("[GPU Timing] The device doesn't support timestamp queries, per pass timings are off");
+        }
+        world.resource::<GpuTimingShared>().supported.store(query_set.is_some(), Ordering::Relaxed);
+
+        let resolve_buffer = world.resource::<RenderDevice>().create_buffer(&BufferDescriptor {
+            label: Some("gpu_timing_resolve_buffer"),
+            size: (std::mem::size_of::<u64>() as u32 * QUERY_COUNT) as u64,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        Self
+        {
+            query_set,
+            resolve_buffer,
+            readback: GpuReadback::new("gpu_timing_readback_buffer"),
+            timestamp_period: world.resource::<RenderQueue>().get_timestamp_period(),
+            active: AtomicBool::new(false),
+            timed_substeps: AtomicU32::new(0),
+            render_timed: AtomicBool::new(false),
+            resolved: AtomicBool::new(false),
+            requested_substeps: 0,
+            requested_frame: 0,
+        }
+    }
+}
+
+impl GpuTiming
+{
+    // timestamp writes for pass `index` of a run of passes tagged with `phases`: the start of the
+    // first pass of each phase and the end of its last
+    pub fn compute_pass_writes(&self, phases: &[GpuPhase], index: usize, substep: u32) -> Option<ComputePassTimestampWrites<'_>>
+    {
+        let (begin, end) = self.pass_writes(phases, index, substep)?;
+        Some(ComputePassTimestampWrites {
+            query_set: self.query_set.as_ref()?,
+            beginning_of_pass_write_index: begin,
+            end_of_pass_write_index: end,
+        })
+    }
+
+    pub fn render_pass_writes(&self) -> Option<RenderPassTimestampWrites<'_>>
+    {
+        let (begin, end) = self.pass_writes(&[GpuPhase::Render], 0, 0)?;
+        Some(RenderPassTimestampWrites {
+            query_set: self.query_set.as_ref()?,
+            beginning_of_pass_write_index: begin,
+            end_of_pass_write_index: end,
+        })
+    }
+
+    fn pass_writes(&self, phases: &[GpuPhase], index: usize, substep: u32) -> Option<(Option<u32>, Option<u32>)>
+    {
+        if !self.active.load(Ordering::Relaxed) || substep >= MAX_TIMED_SUBSTEPS { return None; }
+
+        let phase = phases[index];
+        let first = index == 0 || phases[index - 1] != phase;
+        let last = index + 1 == phases.len() || phases[index + 1] != phase;
+        if !first && !last { return None; }
+        let query = phase.first_query(substep);
+        Some((first.then_some(query), last.then_some(query + 1)))
+    }
+
+    pub fn is_active(&self) -> bool
+    {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    // the compute node timed every phase of the first `substeps` substeps
+    pub fn mark_compute_timed(&self, substeps: u32)
+    {
+        self.timed_substeps.store(substeps.min(MAX_TIMED_SUBSTEPS), Ordering::Relaxed);
+    }
+
+    pub fn mark_render_timed(&self)
+    {
+        self.render_timed.store(true, Ordering::Relaxed);
+    }
+
+    // after the frame's last timed pass, copy the written timestamps into the resolve buffer
+    pub fn resolve(&self, render_context: &mut RenderContext)
+    {
+        let Some(query_set) = self.query_set.as_ref() else { return; };
+        let substeps = self.timed_substeps.load(Ordering::Relaxed);
+        if !self.is_active() || substeps == 0 || !self.render_timed.load(Ordering::Relaxed) { return; }
+
+        let query_count = 2 + substeps * COMPUTE_QUERIES_PER_SUBSTEP;
+        render_context.command_encoder().resolve_query_set(query_set, 0..query_count, &self.resolve_buffer, 0);
+        self.resolved.store(true, Ordering::Relaxed);
+    }
+}
+
+// time this frame if the overlay wants timings and the last ones have been read back
+pub fn begin_gpu_timing(
+    shared: Res<GpuTimingShared>,
+    gpu_timing: Res<GpuTiming>,
+)
+{
+    let active = gpu_timing.query_set.is_some() && shared.wanted.load(Ordering::Relaxed) && gpu_timing.readback.is_idle();
+    gpu_timing.active.store(active, Ordering::Relaxed);
+    gpu_timing.timed_substeps.store(0, Ordering::Relaxed);
+    gpu_timing.render_timed.store(false, Ordering::Relaxed);
+    gpu_timing.resolved.store(false, Ordering::Relaxed);
+}
+
+// copy the resolved timestamps on frames that were timed in full, and turn the landed ones into
+// milliseconds per phase
+pub fn read_back_gpu_timing(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<GpuTimingShared>,
+    mut gpu_timing: ResMut<GpuTiming>,
+    mut budget: ResMut<ReadbackBudget>,
+)
+{
+    if let Some(ticks) = gpu_timing.readback.try_read::<u64>(&render_device)
+    {
+        let to_milliseconds = gpu_timing.timestamp_period as f64 / 1_000_000.0;
+        let elapsed = |phase: GpuPhase, substep: u32| {
+            let query = phase.first_query(substep) as usize;
+            ticks[query + 1].saturating_sub(ticks[query]) as f64 * to_milliseconds
+        };
+
+        let mut timings = GpuTimings { sim_frame: gpu_timing.requested_frame, substeps: gpu_timing.requested_substeps, ..default() };
+        for (phase, milliseconds) in GpuPhase::ALL.iter().zip(timings.milliseconds.iter_mut())
+        {
+            let substeps = if *phase == GpuPhase::Render { 1 } else { gpu_timing.requested_substeps };
+            *milliseconds = (0..substeps).map(|substep| elapsed(*phase, substep)).sum::<f64>() as f32;
+        }
+        *shared.timings.lock().unwrap() = Some(timings);
+    }
+
+    if !gpu_timing.resolved.swap(false, Ordering::Relaxed) { return; }
+
+    // turned down, the next frame is timed and resolved again
+    let substeps = gpu_timing.timed_substeps.load(Ordering::Relaxed);
+    let size = (std::mem::size_of::<u64>() as u32 * (2 + substeps * COMPUTE_QUERIES_PER_SUBSTEP)) as u64;
+    if !budget.try_acquire("gpu_timing", size, 1) { return; }
+
+    gpu_timing.requested_substeps = substeps;
+    gpu_timing.requested_frame = config.frame_count;
+    let gpu_timing = gpu_timing.as_mut();
+    gpu_timing.readback.request(&render_device, &render_queue, &gpu_timing.resolve_buffer, size);
+}
@@ -0,0 +1,73 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts, EguiContextSettings};
+
+const REFERENCE_HEIGHT: f32 = 1080.0;   // logical window height the gui is laid out for
+const AUTO_SCALE_STEP: f32 = 0.25;      // auto detected scales snap to this so text stays crisp
+
+// extra egui scale on top of the window's own scale factor, for displays that
+// report a scale factor of 1 at 4K and leave the panels tiny
+#[derive(Resource)]
+pub struct GuiScale
+{
+    pub auto: bool,
+    pub scale: f32,     // used when auto is off
+    pub applied: f32,   // scale currently in effect
+}
+
+impl Default for GuiScale
+{
+    fn default() -> Self
+    {
+        Self { auto: true, scale: 1.0, applied: 1.0 }
+    }
+}
+
+// guess a scale from the logical window height, never shrinking below 1
+fn detect_scale(window: &Window) -> f32
+{
+    let scale = (window.height() / REFERENCE_HEIGHT).max(1.0);
+    (scale / AUTO_SCALE_STEP).round() * AUTO_SCALE_STEP
+}
+
+// push the chosen scale into egui, waiting for the pointer to be released so the
+// panel doesn't rescale underneath a slider drag
+pub fn apply_gui_scale(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    mut gui_scale: ResMut<GuiScale>,
+    mut context_settings: Query<&mut EguiContextSettings>,
+)
+{
+    let Ok(window) = windows.single() else { return; };
+    let scale = if gui_scale.auto { detect_scale(window) } else { gui_scale.scale };
+    if scale == gui_scale.applied { return; }
+
+    let dragging = contexts.ctx_mut()
+        .map(|ctx| ctx.input(|input| input.pointer.any_down()))
+        .unwrap_or(false);
+    if dragging { return; }
+
+    for mut settings in context_settings.iter_mut()
+    {
+        settings.scale_factor = scale;
+    }
+    gui_scale.applied = scale;
+}
+
+pub fn gui_scale_settings(ui: &mut egui::Ui, gui_scale: &mut GuiScale)
+{
+    ui.checkbox(&mut gui_scale.auto, "Auto Detect");
+    if gui_scale.auto
+    {
+        ui.label(format!("GUI Scale: {:.2}", gui_scale.applied));
+    }
+    else
+    {
+        ui.add(egui::Slider::new(&mut gui_scale.scale, 0.5..=3.0)
+            .text("GUI Scale")
+            .step_by(0.05));
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+use crate::ParticleConfig;
+use crate::parameter_gui::GUIConfig;
+use crate::pipeline_status::SimulationReadiness;
+use crate::scene::{SceneIo, SceneShared};
+use crate::sim_clock::SimClock;
+
+const DEFAULT_STEPS: u32 = 1000;
+const DEFAULT_SNAPSHOT_INTERVAL: u32 = 100;
+const DEFAULT_OUTPUT_DIRECTORY: &str = "headless_output";
+pub const DEFAULT_HEADLESS_DOMAIN: Vec2 = Vec2::new(1600.0, 900.0);    // without a window, --domain or this
+
+fn arg_value(name: &str) -> Option<String>
+{
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == name).and_then(|index| args.get(index + 1).cloned())
+}
+
+// `--headless` runs without a window for `--steps` fixed steps, writing a scene snapshot (the
+// same .pscn files F5 saves) every `--snapshot-every` steps and after the last one into
+// `--output`, then exits. `--params <file>` applies parameter text like the Sim Params window's
+// before the first step, and a scene path as the first argument is loaded as usual. Only
+// particle state is written; without a window there is no view for the render node to draw.
+#[derive(Resource)]
+pub struct HeadlessRun
+{
+    pub steps: u32,
+    pub snapshot_interval: u32,
+    pub output: PathBuf,
+    params_path: Option<String>,
+    start_frame: Option<u32>,       // sim frame once the pipelines were ready
+    snapshot_step: Option<u32>,     // last step a snapshot was requested for
+}
+
+impl HeadlessRun
+{
+    pub fn from_args() -> Option<Self>
+    {
+        if !std::env::args().any(|arg| arg == "--headless") { return None; }
+
+        let parse = |name: &str, default: u32| {
+            arg_value(name).map_or(default, |value| value.parse().unwrap_or_else(|_| {
+                warn!("[Headless] {} expects a number, using {}", name, default);
+                default
+            }))
+        };
+        Some(Self
+        {
+            steps: parse("--steps", DEFAULT_STEPS),
+            snapshot_interval: parse("--snapshot-every", DEFAULT_SNAPSHOT_INTERVAL).max(1),
+            output: PathBuf::from(arg_value("--output").unwrap_or_else(|| DEFAULT_OUTPUT_DIRECTORY.to_string())),
+            params_path: arg_value("--params"),
+            start_frame: None,
+            snapshot_step: None,
+        })
+    }
+}
+
+// apply the --params file once, before anything is scattered
+pub fn apply_headless_params(
+    mut run: ResMut<HeadlessRun>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut exit: EventWriter<AppExit>,
+)
+{
+    let Some(path) = run.params_path.take() else { return; };
+    let applied = std::fs::read_to_string(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| gui_config.apply_text(&text));
+    match applied {
+        Ok(()) => {
+            gui_config.applied_changes = true;
+            info!("[Headless] Applied parameters from {}", path);
+        }
+        Err(error) => {
+            error!("[Headless] Failed to apply {}: {}", path, error);
+            exit.write(AppExit::error());
+        }
+    }
+}
+
+// Hold the clock until the pipelines are ready, then step. The clock is also held while a
+// snapshot is in flight so each one is taken exactly at its step; the readback only copies a
+// fully uploaded particle buffer, so the step 0 snapshot also waits out the initial upload.
+pub fn run_headless(
+    readiness: Res<SimulationReadiness>,
+    sim_config: Res<ParticleConfig>,
+    scene_shared: Res<SceneShared>,
+    mut scene_io: ResMut<SceneIo>,
+    mut clock: ResMut<SimClock>,
+    mut run: ResMut<HeadlessRun>,
+    mut exit: EventWriter<AppExit>,
+)
+{
+    if !readiness.ready || scene_io.save_pending()
+    {
+        clock.paused = true;
+        return;
+    }
+
+    if run.start_frame.is_none()
+    {
+        info!("[Headless] Running {} steps, snapshots every {} into {}", run.steps, run.snapshot_interval, run.output.display());
+        run.start_frame = Some(sim_config.frame_count);
+    }
+    let start_frame = run.start_frame.unwrap();
+    let step = sim_config.frame_count.wrapping_sub(start_frame);
+
+    let snapshot_due = step % run.snapshot_interval == 0 || step >= run.steps;
+    if snapshot_due && run.snapshot_step != Some(step)
+    {
+        if let Err(error) = std::fs::create_dir_all(&run.output)
+        {
+            error!("[Headless] Failed to create {}: {}", run.output.display(), error);
+            exit.write(AppExit::error());
+            return;
+        }
+        run.snapshot_step = Some(step);
+        scene_io.path = run.output.join(format!("step_{:06}.pscn", step)).to_string_lossy().into_owned();
+        scene_io.request_save(&scene_shared);
+        clock.paused = true;
+        return;
+    }
+
+    if step >= run.steps
+    {
+        info!("[Headless] Finished {} steps", step);
+        exit.write(AppExit::Success);
+        return;
+    }
+    clock.paused = false;
+}
@@ -0,0 +1,33 @@
+use bevy::render::{
+    render_resource::*,
+    renderer::RenderContext,
+};
+
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::particle_render::ParticleRenderPipeline;
+use crate::surface_render::{surface_pass, SurfaceTextures};
+
+pub const RENDER_MODE_HEATMAP: u32 = 2;
+
+// Splat every particle's density kernel into the surface texture, which leaves the SPH density
+// the solver would measure at each pixel, so the field between particles shows too. Returns the
+// bind group the heatmap composite should read.
+pub fn render_density_heatmap<'a>(
+    render_context: &mut RenderContext,
+    pipeline_cache: &PipelineCache,
+    pipeline: &ParticleRenderPipeline,
+    pipeline_buffers: &GPUPipelineBuffers,
+    surface_textures: &'a SurfaceTextures,
+) -> Option<&'a BindGroup>
+{
+    let splat_pipeline = pipeline_cache.get_render_pipeline(pipeline.heatmap_splat_pipeline_id)?;
+    pipeline_cache.get_render_pipeline(pipeline.heatmap_composite_pipeline_id)?;
+
+    let mut render_pass = surface_pass(render_context, "heatmap_splat_pass", &surface_textures.thickness.view);
+    render_pass.set_render_pipeline(splat_pipeline);
+    render_pass.set_bind_group(0, &pipeline_buffers.render_bind_group, &[]);
+    render_pass.set_vertex_buffer(0, pipeline_buffers.vertex_buffer.slice(..));
+    render_pass.draw(0..6, 0..pipeline_buffers.particle_count);
+
+    Some(&surface_textures.thickness.bind_group)
+}
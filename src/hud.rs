@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+use crate::sim_clock::SimClock;
+
+const FPS_SMOOTHING: f32 = 0.05;    // weight of the newest frame in the fps moving average
+
+// the compact hud and the full parameter window are toggled separately, so a demo
+// can keep just the hud on screen
+#[derive(Resource)]
+pub struct HudSettings
+{
+    pub hud_visible: bool,
+    pub params_visible: bool,
+    pub stats_visible: bool,
+}
+
+impl Default for HudSettings
+{
+    fn default() -> Self
+    {
+        Self { hud_visible: true, params_visible: true, stats_visible: false }
+    }
+}
+
+// H toggles the hud, P toggles the parameter window, T the statistics overlay
+pub fn toggle_hud(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut settings: ResMut<HudSettings>,
+)
+{
+    let typing = contexts.ctx_mut().map(|ctx| ctx.wants_keyboard_input()).unwrap_or(false);
+    if typing { return; }
+
+    if keyboard_input.just_pressed(KeyCode::KeyH)
+    {
+        settings.hud_visible = !settings.hud_visible;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyP)
+    {
+        settings.params_visible = !settings.params_visible;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyT)
+    {
+        settings.stats_visible = !settings.stats_visible;
+    }
+}
+
+pub fn hud_system(
+    mut contexts: EguiContexts,
+    time: Res<Time<Real>>,
+    settings: Res<HudSettings>,
+    sim_clock: Res<SimClock>,
+    config: Res<ParticleConfig>,
+    mut smoothed_fps: Local<Option<f32>>,
+) -> Result
+{
+    let delta = time.delta_secs();
+    if delta > 0.0
+    {
+        let fps = 1.0 / delta;
+        *smoothed_fps = Some(smoothed_fps.map_or(fps, |smoothed| smoothed + FPS_SMOOTHING * (fps - smoothed)));
+    }
+
+    if !settings.hud_visible { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let mut text = format!("{:.0} FPS  |  {} particles", smoothed_fps.unwrap_or(0.0), config.particle_count);
+    if sim_clock.paused
+    {
+        text.push_str("  |  PAUSED");
+    }
+    else if sim_clock.suspended
+    {
+        text.push_str("  |  SUSPENDED");
+    }
+    if sim_clock.time_scale != 1.0
+    {
+        text.push_str(&format!("  |  {:.2}x", sim_clock.time_scale));
+    }
+
+    egui::Area::new(egui::Id::new("hud"))
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 6.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(egui::RichText::new(text).monospace());
+            });
+        });
+    Ok(())
+}
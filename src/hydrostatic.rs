@@ -0,0 +1,357 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::{ParticleConfig, ParticleSystem};
+use crate::particle::Particle;
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+use crate::parameter_gui::{BoundaryMode, GUIConfig, GravityPreset, SolverKind};
+use crate::initial_layout::InitialLayout;
+use crate::sim_rng::SimRng;
+use crate::obstacle::Obstacle;
+use crate::rigid_body::RigidBody;
+use crate::emitter::Emitter;
+use crate::force_field::ForceField;
+use crate::obstacle_course::CourseEntity;
+
+const READBACK_INTERVAL: u32 = 60;      // frames between profile measurements
+const HEIGHT_BINS: usize = 32;          // horizontal slices the domain is averaged over
+
+// per-height density averages measured on the GPU state
+#[derive(Clone, Default)]
+pub struct HydrostaticProfile
+{
+    pub bin_densities: Vec<f32>,    // average density per bin, bottom to top (0 when empty)
+    pub y_min: f32,
+    pub bin_height: f32,
+    pub surface_height: f32,        // highest particle, where pressure is taken to be zero
+}
+
+// shared between main and render worlds so the render world only reads back while the check runs
+#[derive(Resource, Clone, Default)]
+pub struct HydrostaticShared
+{
+    enabled: Arc<AtomicBool>,
+    profile: Arc<Mutex<Option<HydrostaticProfile>>>,
+}
+
+// render world side of the profile readback
+#[derive(Resource)]
+pub struct HydrostaticReadback
+{
+    particles: GpuReadback,
+    densities: GpuReadback,
+    pending_particles: Option<Vec<[f32; 8]>>,  // position, velocity, color
+    pending_densities: Option<Vec<[f32; 2]>>,
+    frame_count: u32,
+}
+
+impl Default for HydrostaticReadback
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            particles: GpuReadback::new("hydrostatic_particle_readback_buffer"),
+            densities: GpuReadback::new("hydrostatic_density_readback_buffer"),
+            pending_particles: None,
+            pending_densities: None,
+            frame_count: 0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct HydrostaticCheck
+{
+    pub enabled: bool,
+    pub profile: Option<HydrostaticProfile>,
+    saved: Option<SavedSetup>,  // while the scenario is loaded
+}
+
+// what the verification scenario replaced, put back when the check is turned off
+struct SavedSetup
+{
+    gui_config: GUIConfig,
+    layout: InitialLayout,
+    particles: Vec<Particle>,
+    obstacles: Vec<(Obstacle, Option<RigidBody>)>,
+    emitters: Vec<Emitter>,
+    force_fields: Vec<ForceField>,
+}
+
+// bin particle densities by height once both readbacks for a frame have arrived
+fn build_profile(
+    particles: &[[f32; 8]],
+    densities: &[[f32; 2]],
+    config: &ParticleConfig,
+) -> HydrostaticProfile
+{
+    let y_min = config.screen_bounds[2];
+    let y_max = config.screen_bounds[3];
+    let bin_height = (y_max - y_min) / HEIGHT_BINS as f32;
+
+    let mut totals = vec![0.0f32; HEIGHT_BINS];
+    let mut counts = vec![0u32; HEIGHT_BINS];
+    let mut surface_height = y_min;
+
+    for (particle, density) in particles.iter().zip(densities.iter())
+    {
+        let y = particle[1];
+        let bin = (((y - y_min) / bin_height) as usize).min(HEIGHT_BINS - 1);
+        totals[bin] += density[0];
+        counts[bin] += 1;
+        surface_height = surface_height.max(y);
+    }
+
+    let bin_densities = totals.iter().zip(counts.iter())
+        .map(|(total, count)| if *count > 0 { total / *count as f32 } else { 0.0 })
+        .collect();
+
+    HydrostaticProfile { bin_densities, y_min, bin_height, surface_height }
+}
+
+pub fn read_back_hydrostatic_profile(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<HydrostaticShared>,
+    mut hydrostatic_readback: ResMut<HydrostaticReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, Without<ParticleSystemConfig>>,
+)
+{
+    let readback = &mut *hydrostatic_readback;
+    if let Some(particles) = readback.particles.try_read::<[f32; 8]>(&render_device)
+    {
+        readback.pending_particles = Some(particles);
+    }
+    if let Some(densities) = readback.densities.try_read::<[f32; 2]>(&render_device)
+    {
+        readback.pending_densities = Some(densities);
+    }
+    if readback.pending_particles.is_some() && readback.pending_densities.is_some()
+    {
+        let particles = readback.pending_particles.take().unwrap();
+        let densities = readback.pending_densities.take().unwrap();
+        *shared.profile.lock().unwrap() = Some(build_profile(&particles, &densities, &config));
+    }
+
+    if !shared.enabled.load(Ordering::Relaxed) { return; }
+
+    readback.frame_count += 1;
+    if readback.frame_count < READBACK_INTERVAL
+        || !readback.particles.is_idle()
+        || !readback.densities.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        let particle_count = pipeline_buffers.particle_count as usize;
+        let particles_size = (std::mem::size_of::<[f32; 8]>() * particle_count) as u64;
+        let densities_size = (std::mem::size_of::<[f32; 2]>() * particle_count) as u64;
+        // both copies are granted together, the profile needs the pair
+        if !budget.try_acquire("hydrostatic", particles_size + densities_size, 2) { return; }
+
+        readback.frame_count = 0;
+        readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            particles_size,
+        );
+        readback.densities.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_densities_buffer,
+            densities_size,
+        );
+    }
+}
+
+// Load the verification scenario when the check is turned on: a column of fluid resting on the
+// floor of a closed box under constant earth gravity, solved with the SPH state equation the
+// analytic profile assumes, with no obstacles, emitters, force fields or heaters. Turning it off
+// restores everything it replaced.
+pub fn update_hydrostatic_check(
+    mut commands: Commands,
+    shared: Res<HydrostaticShared>,
+    mut check: ResMut<HydrostaticCheck>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut layout: ResMut<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
+    config: Res<ParticleConfig>,
+    mut particle_system_query: Query<&mut ParticleSystem, Without<ParticleSystemConfig>>,
+    obstacle_query: Query<(Entity, &Obstacle, Option<&RigidBody>), Without<CourseEntity>>,
+    emitter_query: Query<(Entity, &Emitter)>,
+    force_field_query: Query<(Entity, &ForceField)>,
+)
+{
+    if check.enabled && check.saved.is_none()
+    {
+        let Ok(mut particle_system) = particle_system_query.single_mut() else { return; };
+
+        check.saved = Some(SavedSetup
+        {
+            gui_config: *gui_config,
+            layout: *layout,
+            particles: particle_system.particles.clone(),
+            obstacles: obstacle_query.iter().map(|(_, obstacle, body)| (*obstacle, body.copied())).collect(),
+            emitters: emitter_query.iter().map(|(_, emitter)| *emitter).collect(),
+            force_fields: force_field_query.iter().map(|(_, field)| *field).collect(),
+        });
+        let entities = obstacle_query.iter().map(|(entity, _, _)| entity)
+            .chain(emitter_query.iter().map(|(entity, _)| entity))
+            .chain(force_field_query.iter().map(|(entity, _)| entity));
+        for entity in entities
+        {
+            commands.entity(entity).despawn();
+        }
+
+        gui_config.gravity = GravityPreset::Earth.gravity();
+        gui_config.gravity_angle = 0.0;
+        gui_config.oscillate_gravity = false;
+        gui_config.boundary_modes = [BoundaryMode::Bounce as u32; 4];
+        gui_config.solver = SolverKind::Sph as u32;
+        gui_config.flip_enabled = false;
+        gui_config.temperature_enabled = false;
+        gui_config.applied_changes = true;
+
+        *layout = InitialLayout::Column;
+        let particle_count = particle_system.particles.len() as u32;
+        particle_system.particles = layout.particles(sim_rng.rng(), config.screen_bounds, particle_count);
+        particle_system.generation = particle_system.generation.wrapping_add(1);
+        check.profile = None;
+    }
+    else if !check.enabled
+    {
+        if let Some(saved) = check.saved.take()
+        {
+            *gui_config = saved.gui_config;
+            gui_config.applied_changes = true;
+            *layout = saved.layout;
+            if let Ok(mut particle_system) = particle_system_query.single_mut()
+            {
+                particle_system.particles = saved.particles;
+                particle_system.generation = particle_system.generation.wrapping_add(1);
+            }
+            for (obstacle, body) in saved.obstacles
+            {
+                match body {
+                    Some(body) => commands.spawn((obstacle, body)),
+                    None => commands.spawn(obstacle),
+                };
+            }
+            for emitter in saved.emitters
+            {
+                commands.spawn(emitter);
+            }
+            for field in saved.force_fields
+            {
+                commands.spawn(field);
+            }
+        }
+    }
+
+    shared.enabled.store(check.enabled, Ordering::Relaxed);
+    if let Some(profile) = shared.profile.lock().unwrap().take()
+    {
+        check.profile = Some(profile);
+    }
+}
+
+// analytic density at height y for the linear state equation p = k * (density - target_density):
+// dp/dy = -density * g  =>  density(y) = target_density * exp(g * (surface - y) / k), g the downward component
+fn analytic_density(y: f32, surface_height: f32, config: &ParticleConfig) -> f32
+{
+    if y > surface_height || config.pressure_multiplier <= 0.0 { return 0.0; }
+    config.target_density * (-config.gravity[1] * (surface_height - y) / config.pressure_multiplier).exp()
+}
+
+pub fn hydrostatic_gui(
+    mut contexts: EguiContexts,
+    mut check: ResMut<HydrostaticCheck>,
+    config: Res<ParticleConfig>,
+    course_query: Query<(), With<CourseEntity>>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Hydrostatic Check")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 200.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            // update_hydrostatic_check loads the scenario, and restores the previous setup after.
+            // The obstacle course owns its entities, so it has to be reset first.
+            let course_running = !course_query.is_empty();
+            ui.add_enabled_ui(!course_running || check.enabled, |ui| {
+                ui.checkbox(&mut check.enabled, "Run Verification");
+            });
+            if course_running && !check.enabled
+            {
+                ui.label("Reset the obstacle course to run the verification");
+                return;
+            }
+            if !check.enabled { return; }
+
+            let Some(profile) = check.profile.as_ref() else {
+                ui.label("Waiting for the column to settle...");
+                return;
+            };
+
+            let heights: Vec<f32> = (0..profile.bin_densities.len())
+                .map(|bin| profile.y_min + (bin as f32 + 0.5) * profile.bin_height)
+                .collect();
+            let analytic: Vec<f32> = heights.iter()
+                .map(|y| analytic_density(*y, profile.surface_height, &config))
+                .collect();
+
+            // scale to the measured data, letting the analytic curve run off the plot if it diverges
+            let max_measured = profile.bin_densities.iter().cloned().fold(0.0f32, f32::max);
+            let max_density = (max_measured * 1.25).max(config.target_density * 2.0);
+
+            // density on the x axis, height on the y axis
+            let (response, painter) = ui.allocate_painter(egui::vec2(220.0, 220.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::DARK_GRAY), egui::StrokeKind::Inside);
+
+            let to_screen = |density: f32, bin: usize| {
+                let x = rect.left() + rect.width() * (density / max_density).clamp(0.0, 1.0);
+                let y = rect.bottom() - rect.height() * (bin as f32 + 0.5) / heights.len() as f32;
+                egui::pos2(x, y)
+            };
+
+            let analytic_points: Vec<egui::Pos2> = analytic.iter().enumerate()
+                .filter(|(_, density)| **density > 0.0)
+                .map(|(bin, density)| to_screen(*density, bin))
+                .collect();
+            painter.add(egui::Shape::line(analytic_points, egui::Stroke::new(1.5, egui::Color32::YELLOW)));
+
+            for (bin, density) in profile.bin_densities.iter().enumerate()
+            {
+                if *density > 0.0
+                {
+                    painter.circle_filled(to_screen(*density, bin), 2.5, egui::Color32::LIGHT_BLUE);
+                }
+            }
+
+            ui.colored_label(egui::Color32::YELLOW, "Analytic");
+            ui.colored_label(egui::Color32::LIGHT_BLUE, "Measured");
+
+            // error over bins below the surface that actually hold particles
+            let (error_total, error_count) = profile.bin_densities.iter().zip(analytic.iter())
+                .filter(|(measured, expected)| **measured > 0.0 && **expected > 0.0)
+                .fold((0.0f32, 0u32), |(total, count), (measured, expected)| {
+                    (total + ((measured - expected) / expected).abs(), count + 1)
+                });
+            if error_count > 0
+            {
+                ui.label(format!("Mean Relative Error: {:.1}%", 100.0 * error_total / error_count as f32));
+            }
+        });
+    Ok(())
+}
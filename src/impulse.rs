@@ -0,0 +1,117 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+
+const IMPULSE_STRENGTH: f32 = 800.0;    // velocity added at the center of the blast, in pixels/s
+const IMPULSE_RADIUS: f32 = 200.0;
+
+#[derive(Resource)]
+pub struct ImpulseSettings
+{
+    pub strength: f32,
+    pub radius: f32,
+}
+
+impl Default for ImpulseSettings
+{
+    fn default() -> Self
+    {
+        Self { strength: IMPULSE_STRENGTH, radius: IMPULSE_RADIUS }
+    }
+}
+
+// impulse waiting for an unpaused frame: world position and signed strength
+#[derive(Resource, Default)]
+pub struct PendingImpulse(pub Option<(Vec2, f32)>);
+
+// E explodes and I implodes at the cursor, falling back to the middle of the screen
+// when the cursor is outside the window; the impulse lives in the config for exactly
+// one simulated frame
+pub fn update_impulse(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    settings: Res<ImpulseSettings>,
+    mut pending: ResMut<PendingImpulse>,
+    mut sim_config: ResMut<ParticleConfig>,
+)
+{
+    let typing = contexts.ctx_mut().map(|ctx| ctx.wants_keyboard_input()).unwrap_or(false);
+    let direction = if typing {
+        0.0
+    } else if keyboard_input.just_pressed(KeyCode::KeyE) {
+        1.0
+    } else if keyboard_input.just_pressed(KeyCode::KeyI) {
+        -1.0
+    } else {
+        0.0
+    };
+
+    if direction != 0.0
+    {
+        let cursor_world_position = windows.single().ok()
+            .and_then(|window| window.cursor_position())
+            .and_then(|cursor| {
+                let (camera, transform) = camera_query.single().ok()?;
+                camera.viewport_to_world_2d(transform, cursor).ok()
+            });
+        let position = cursor_world_position.unwrap_or_else(|| screen_center(&sim_config));
+        pending.0 = Some((position, direction * settings.strength));
+    }
+
+    // hold the impulse through paused frames so it isn't lost before the sim sees it
+    if sim_config.paused != 0 { return; }
+
+    if let Some((position, strength)) = pending.0.take()
+    {
+        sim_config.impulse_position = position.to_array();
+        sim_config.impulse_strength = strength;
+        sim_config.impulse_radius = settings.radius;
+    }
+    else if sim_config.impulse_strength != 0.0
+    {
+        sim_config.impulse_strength = 0.0;
+    }
+}
+
+fn screen_center(config: &ParticleConfig) -> Vec2
+{
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    Vec2::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0)
+}
+
+pub fn impulse_gui(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<ImpulseSettings>,
+    mut pending: ResMut<PendingImpulse>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Impulses")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 700.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label("E explodes, I implodes at the cursor");
+            ui.add(egui::Slider::new(&mut settings.strength, 10.0..=5000.0)
+                .text("Strength (px/s)")
+                .logarithmic(true));
+            ui.add(egui::Slider::new(&mut settings.radius, 10.0..=1000.0)
+                .text("Radius"));
+            ui.horizontal(|ui| {
+                if ui.button("Explode").clicked() {
+                    pending.0 = Some((screen_center(&config), settings.strength));
+                }
+                if ui.button("Implode").clicked() {
+                    pending.0 = Some((screen_center(&config), -settings.strength));
+                }
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use crate::particle::Particle;
+
+const GOLDEN_ANGLE: f32 = 2.399_963;   // radians, spreads spiral points evenly over a disc
+
+// lays out `particle_count` particles inside the given screen bounds; seeded runs only replay
+// exactly if it's deterministic
+pub type LayoutFn = fn([f32; 4], u32) -> Vec<Particle>;
+
+// Where the particles start whenever the fluid is scattered: at launch, when the count or domain
+// changes, and for new systems. Insert the resource before the app runs to pick one in code,
+// Custom included, or pass `--layout <name>` with one of the names below.
+#[derive(Resource, Clone, Copy, Default)]
+pub enum InitialLayout
+{
+    #[default]
+    Scatter,        // uniform across the width, normally distributed about the middle height
+    DamBreak,       // a block of fluid in the bottom left corner
+    UniformGrid,    // a lattice filling the whole domain
+    Disk,           // a centered disc
+    DoubleBlob,     // two discs side by side
+    Ring,           // a centered annulus
+    Column,         // the bottom half filled wall to wall, the hydrostatic check's resting column
+    #[allow(dead_code)]     // only picked in code, there's no flag for it
+    Custom(LayoutFn),
+}
+
+impl InitialLayout
+{
+    pub const NAMED: [(&str, InitialLayout); 7] = [
+        ("scatter", InitialLayout::Scatter),
+        ("dam-break", InitialLayout::DamBreak),
+        ("grid", InitialLayout::UniformGrid),
+        ("disk", InitialLayout::Disk),
+        ("double-blob", InitialLayout::DoubleBlob),
+        ("ring", InitialLayout::Ring),
+        ("column", InitialLayout::Column),
+    ];
+
+    pub fn from_args() -> Self
+    {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(name) = args.iter().position(|arg| arg == "--layout").and_then(|index| args.get(index + 1)) else {
+            return Self::default();
+        };
+        Self::NAMED.iter().find(|(known, _)| known == name).map(|(_, layout)| *layout).unwrap_or_else(|| {
+            let known: Vec<&str> = Self::NAMED.iter().map(|(known, _)| *known).collect();
+            warn!("[Layout] Unknown layout `{}`, expected one of {}", name, known.join(", "));
+            Self::default()
+        })
+    }
+
+    pub fn particles(&self, rng: &mut impl Rng, screen_bounds: [f32; 4], particle_count: u32) -> Vec<Particle>
+    {
+        let [x_min, x_max, y_min, y_max] = screen_bounds;
+        let (width, height) = (x_max - x_min, y_max - y_min);
+        let center = Vec2::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+        let size = width.min(height);
+
+        let positions = match self {
+            InitialLayout::Scatter => return scatter(rng, screen_bounds, particle_count),
+            InitialLayout::Custom(layout) => return layout(screen_bounds, particle_count),
+            InitialLayout::DamBreak => fill_rect([x_min, x_min + 0.4 * width, y_min, y_min + 0.8 * height], particle_count),
+            InitialLayout::UniformGrid => fill_rect(screen_bounds, particle_count),
+            InitialLayout::Disk => spiral(center, 0.0, 0.35 * size, particle_count),
+            InitialLayout::DoubleBlob => {
+                let offset = Vec2::new(width / 4.0, 0.0);
+                let mut positions = spiral(center - offset, 0.0, 0.2 * size, particle_count / 2);
+                positions.extend(spiral(center + offset, 0.0, 0.2 * size, particle_count - particle_count / 2));
+                positions
+            }
+            InitialLayout::Ring => spiral(center, 0.25 * size, 0.4 * size, particle_count),
+            InitialLayout::Column => fill_rect([x_min, x_max, y_min, y_min + 0.5 * height], particle_count),
+        };
+        positions.into_iter()
+            .map(|position| Particle { position: position.to_array(), velocity: [0.0, 0.0], color: [1.0, 1.0, 1.0, 1.0] })
+            .collect()
+    }
+}
+
+fn scatter(
+    rng: &mut impl Rng,
+    screen_bounds: [f32; 4],
+    particle_count: u32,
+) -> Vec<Particle>
+{
+    let [x_min, x_max, y_min, y_max] = screen_bounds;
+
+    // Y-distribution: mean at center
+    let y_center = (y_min + y_max) / 2.0;
+    let y_std_dev = (y_max - y_min) * 0.125;
+    let y_dist = Normal::new(y_center, y_std_dev).unwrap();
+
+    let mut particles = Vec::with_capacity(particle_count as usize);
+
+    for i in 0..particle_count {
+        // Uniformly distribute x across visible width
+        let t = i as f32 / particle_count as f32;
+        let x = x_min + t * (x_max - x_min);
+
+        // Sample y and clamp to bounds
+        let mut y = y_dist.sample(rng);
+        y = y.clamp(y_min, y_max);
+
+        particles.push(Particle {
+            position: [x, y],
+            velocity: [0.0, 0.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+        });
+    }
+
+    particles
+}
+
+// rows of evenly spaced points filling the rect from the bottom, the columns chosen so the
+// spacing comes out about the same both ways
+fn fill_rect(rect: [f32; 4], count: u32) -> Vec<Vec2>
+{
+    let [x_min, x_max, y_min, y_max] = rect;
+    let (width, height) = ((x_max - x_min).max(f32::EPSILON), (y_max - y_min).max(f32::EPSILON));
+    let columns = ((count as f32 * width / height).sqrt().ceil() as u32).max(1);
+    let rows = count.div_ceil(columns).max(1);
+    let spacing = Vec2::new(width / columns as f32, height / rows as f32);
+
+    (0..count)
+        .map(|i| Vec2::new(x_min, y_min) + Vec2::new((i % columns) as f32 + 0.5, (i / columns) as f32 + 0.5) * spacing)
+        .collect()
+}
+
+// a sunflower spiral covering the annulus between the radii at even density, a disc when the
+// inner radius is zero
+fn spiral(center: Vec2, inner_radius: f32, outer_radius: f32, count: u32) -> Vec<Vec2>
+{
+    let (inner_squared, outer_squared) = (inner_radius * inner_radius, outer_radius * outer_radius);
+    (0..count)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / count as f32;
+            let radius = (inner_squared + t * (outer_squared - inner_squared)).sqrt();
+            center + Vec2::from_angle(i as f32 * GOLDEN_ANGLE) * radius
+        })
+        .collect()
+}
@@ -0,0 +1,141 @@
+use bevy::{
+    prelude::*,
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+use crate::parameter_gui::GUIConfig;
+use crate::gui_scale::GuiScale;
+
+const MIN_FAN_REACH: f32 = 10.0;    // shorter drags don't aim the fan yet
+
+// what the mouse does to the fluid
+#[derive(Resource, Default, Clone, Copy, PartialEq)]
+pub enum InteractionTool
+{
+    #[default]
+    Force,  // radial attract / repel around the cursor
+    Fan,    // cone shaped push from where the drag started towards the cursor
+    Brush,  // paints new particles under the cursor, see brush.rs
+    Drag,   // moves obstacles, see obstacle_drag.rs
+}
+
+// push the cursor position and mouse button state into the sim config every frame
+pub fn update_mouse_interaction(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    gui_config: Res<GUIConfig>,
+    mut tool: ResMut<InteractionTool>,
+    mut sim_config: ResMut<ParticleConfig>,
+    mut fan_origin: Local<Option<Vec2>>,
+)
+{
+    // clicks on the gui shouldn't stir the fluid underneath it
+    let (pointer_over_gui, typing) = contexts.ctx_mut()
+        .map(|ctx| (ctx.is_pointer_over_area() || ctx.wants_pointer_input(), ctx.wants_keyboard_input()))
+        .unwrap_or((false, false));
+
+    if keyboard_input.just_pressed(KeyCode::KeyF) && !typing
+    {
+        *tool = if *tool == InteractionTool::Fan { InteractionTool::Force } else { InteractionTool::Fan };
+    }
+
+    let cursor_world_position = windows.single().ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor| {
+            let (camera, transform) = camera_query.single().ok()?;
+            camera.viewport_to_world_2d(transform, cursor).ok()
+        });
+
+    let mut strength = 0.0;
+    let mut fan_strength = 0.0;
+    if let Some(position) = cursor_world_position
+    {
+        sim_config.interaction_position = position.to_array();
+        match *tool
+        {
+            InteractionTool::Force =>
+            {
+                if pointer_over_gui {
+                    // leave the fluid alone
+                } else if mouse_buttons.pressed(MouseButton::Left) {
+                    strength = gui_config.interaction_strength;
+                } else if mouse_buttons.pressed(MouseButton::Right) {
+                    strength = -gui_config.interaction_strength;
+                }
+            }
+            InteractionTool::Fan =>
+            {
+                // the drag that started on the fluid keeps aiming the fan even over the gui
+                if mouse_buttons.just_pressed(MouseButton::Left) && !pointer_over_gui
+                {
+                    *fan_origin = Some(position);
+                }
+                if let Some(origin) = fan_origin.filter(|_| mouse_buttons.pressed(MouseButton::Left))
+                {
+                    let aim = position - origin;
+                    if aim.length() >= MIN_FAN_REACH
+                    {
+                        sim_config.fan_origin = origin.to_array();
+                        sim_config.fan_direction = aim.normalize().to_array();
+                        sim_config.fan_reach = aim.length();
+                        sim_config.fan_cos_half_angle = gui_config.fan_angle.to_radians().cos();
+                        fan_strength = gui_config.fan_strength;
+                    }
+                }
+            }
+            InteractionTool::Brush | InteractionTool::Drag => {}
+        }
+    }
+    if !mouse_buttons.pressed(MouseButton::Left)
+    {
+        *fan_origin = None;
+    }
+
+    sim_config.interaction_strength = strength;
+    sim_config.interaction_radius = gui_config.interaction_radius.0;
+    sim_config.fan_strength = fan_strength;
+}
+
+// outline the fan's cone while it blows
+pub fn draw_fan(
+    mut contexts: EguiContexts,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    sim_config: Res<ParticleConfig>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if sim_config.fan_strength == 0.0 { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    let origin = Vec2::from_array(sim_config.fan_origin);
+    let direction = Vec2::from_array(sim_config.fan_direction);
+    let half_angle = sim_config.fan_cos_half_angle.clamp(-1.0, 1.0).acos();
+    let reach = direction * sim_config.fan_reach;
+
+    let points: Option<Vec<egui::Pos2>> = [
+        origin + Vec2::from_angle(half_angle).rotate(reach),
+        origin,
+        origin + Vec2::from_angle(-half_angle).rotate(reach),
+    ].iter().map(|point| to_screen(*point)).collect();
+    let tip = to_screen(origin + reach);
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let stroke = egui::Stroke::new(1.5, egui::Color32::from_rgb(120, 200, 255));
+    if let (Some(points), Some(tip)) = (points, tip)
+    {
+        painter.arrow(points[1], tip - points[1], stroke);
+        painter.add(egui::Shape::line(points, stroke));
+    }
+    Ok(())
+}
@@ -4,11 +4,12 @@ use bevy::{
         extract_component::ExtractComponent, 
         extract_resource::ExtractResource, 
     },
-    window::WindowMode,
+    app::ScheduleRunnerPlugin,
+    window::ExitCondition,
+    winit::WinitPlugin,
 };
-use rand_distr::{Distribution, Normal};
 use bytemuck::{Pod, Zeroable};
-use bevy_egui::{EguiPlugin, EguiPrimaryContextPass};
+use bevy_egui::{EguiGlobalSettings, EguiPlugin, EguiPrimaryContextPass};
 
 use std::f32::consts::PI;
 
@@ -19,12 +20,108 @@ mod util;
 mod debug;
 mod particle_buffers;
 mod parameter_gui;
+mod gpu_readback;
+mod fluid_volume;
+mod hydrostatic;
+mod pipeline_status;
+mod sim_clock;
+mod interaction;
+mod parameter_history;
+mod obstacle;
+mod gui_scale;
+mod hud;
+mod attract_mode;
+mod impulse;
+mod scene;
+mod emitter;
+mod particle_probe;
+mod goal_region;
+mod obstacle_course;
+mod surface_render;
+mod heatmap_render;
+mod field_export;
+mod particle_systems;
+mod training_data;
+mod surrogate;
+mod rigid_body;
+mod stats;
+mod domain;
+mod presets;
+mod camera_follow;
+mod pressure_probe;
+mod obstacle_force;
+mod param_suggestion;
+mod headless;
+mod radius_gauge;
+mod frame_capture;
+mod autosave;
+mod device_recovery;
+mod temperature;
+mod power_saving;
+mod brush;
+mod initial_layout;
+mod session_log;
+mod sim_rng;
+mod sort_backend;
+mod cpu_backend;
+mod units;
+mod param_migration;
+mod substeps;
+mod obstacle_drag;
+mod retro_render;
+mod force_field;
+mod gpu_timing;
 use particle::Particle;
-use parameter_gui::{gui_system, apply_gui_updates, GUIConfig};
+use parameter_gui::{gui_system, apply_gui_updates, oscillate_gravity, ramp_params, tilt_gravity, store_gui_defaults, GUIConfig, ParamRamp, RetroPalette};
+use fluid_volume::{fluid_volume_gui, update_fluid_volume, FluidVolumeStats};
+use hydrostatic::{hydrostatic_gui, update_hydrostatic_check, HydrostaticCheck};
+use pipeline_status::{announce_simulation_ready, pipeline_progress_overlay, SimulationReadiness, SimulationReady};
+use sim_clock::{advance_frame_count, update_delta_time, update_sim_clock, SimClock};
+use interaction::{draw_fan, update_mouse_interaction, InteractionTool};
+use parameter_history::{parameter_history_system, ParameterHistory};
+use obstacle::{draw_obstacles, obstacle_gui};
+use gui_scale::{apply_gui_scale, GuiScale};
+use hud::{hud_system, toggle_hud, HudSettings};
+use impulse::{impulse_gui, update_impulse, ImpulseSettings, PendingImpulse};
+use scene::{scene_gui, update_scene_io, SceneIo};
+use field_export::{field_export_gui, update_field_export, FieldExport};
+use particle_systems::{particle_systems_gui, ParticleSystemConfig};
+use training_data::{training_data_gui, update_training_data, TrainingData};
+use surrogate::{surrogate_gui, update_surrogate, Surrogate};
+use rigid_body::{rigid_body_gui, update_rigid_bodies, RigidBodies};
+use stats::{stats_overlay, StatsOverlay};
+use domain::{domain_gui, draw_domain_frame, fit_bounds_to_window, update_domain, Domain, DomainFrame};
+use presets::{load_presets, presets_gui, Presets};
+use camera_follow::{camera_follow_gui, draw_followed_particle, update_camera_follow, CameraFollow};
+use obstacle_force::{draw_obstacle_forces, obstacle_force_gui, update_obstacle_forces, ObstacleForces};
+use frame_capture::{frame_capture_gui, update_frame_capture, FrameCapture};
+use autosave::{autosave_gui, clear_recovery_on_exit, update_autosave, Autosave};
+use device_recovery::restart_from_snapshot;
+use temperature::draw_heaters;
+use power_saving::{power_saving_gui, update_power_saving, PowerSaving};
+use brush::{draw_brush, update_brush, BrushSettings};
+use initial_layout::InitialLayout;
+use sim_rng::SimRng;
+use cpu_backend::SimBackend;
+use session_log::{session_log_gui, update_session_log, write_session_log_on_exit, SessionLog};
+use radius_gauge::{draw_smoothing_radius_gauge, SmoothingRadiusGauge};
+use headless::{apply_headless_params, run_headless, HeadlessRun, DEFAULT_HEADLESS_DOMAIN};
+use pressure_probe::{draw_pressure_probes, pressure_probe_gui, update_pressure_probes, PressureProbes};
+use emitter::{emitter_gui, update_emitters, EmittedParticles, EmitterRing, EmitterSettings, SpawnQueue};
+use particle_probe::{update_particle_probe, ParticleProbe};
+use goal_region::{update_goal_regions, GoalRegionUpdated};
+use obstacle_course::{obstacle_course_gui, update_obstacle_course, CourseCompleted, ObstacleCourse};
+use attract_mode::{attract_mode_overlay, update_attract_mode, AttractMode};
+use units::{Density, Seconds, WorldLength};
+use substeps::update_substeps;
+use obstacle_drag::update_obstacle_drag;
+use retro_render::MAX_PALETTE_COLORS;
+use force_field::{force_field_gui, update_force_fields};
 
 const PARTICLE_COUNT: u32 = 50000;
 const PARTICLE_SIZE: f32 = 3.0;
 const SMOOTHING_RADIUS: f32 = PARTICLE_SIZE * PARTICLE_SIZE;
+pub const MIN_SMOOTHING_RADIUS: f32 = 1.0;    // spatial grid cells are never narrower than this
 const GRAVITY: f32 = 0.0;
 const TARGET_DENSITY: f32 = 0.011;
 const PRESSURE_MULTIPLIER: f32 = 10000.0;
@@ -33,11 +130,54 @@ const VISCOCITY_STRENGTH: f32 = 5.0;
 const DAMPING_FACTOR: f32 = 0.1;
 const FIXED_DELTA_TIME: f32 = 1.0 / 100.0;
 const MAX_ENERGY: f32 = 2000.0;
+const GRAVITY_OSCILLATION_PERIOD: f32 = 5.0;
+const MAX_DELTA_TIME: f32 = 1.0 / 60.0;
+const SCALAR_GRID_CELL_SIZE: f32 = 8.0;
+const SMOKE_INJECTION: f32 = 5.0;
+const SMOKE_DISSIPATION: f32 = 0.99;
+const SMOKE_OPACITY: f32 = 0.6;
+const FLIP_RATIO: f32 = 0.95;
+const PRESSURE_ITERATIONS: u32 = 20;
+const DIVERGENCE_RANGE: f32 = 10.0;
+const INTERACTION_STRENGTH: f32 = 5000.0;
+const INTERACTION_RADIUS: f32 = 100.0;
+const FAN_STRENGTH: f32 = 3000.0;
+const FAN_ANGLE: f32 = 20.0;
+const SURFACE_THRESHOLD: f32 = 0.5;
+const SURFACE_BLUR_RADIUS: f32 = 8.0;
+const HEATMAP_RANGE: f32 = 2.0;
+const COLOR_MIN: f32 = 0.0;
+const COLOR_MAX: f32 = 100.0;
+const VELOCITY_HISTORY_FRAMES: f32 = 60.0;
+const AMBIENT_TEMPERATURE: f32 = 20.0;
+const HEAT_DIFFUSION: f32 = 2.0;
+const COOLING_RATE: f32 = 0.05;
+const THERMAL_EXPANSION: f32 = 0.01;
+const HEATER_TEMPERATURE: f32 = 80.0;
+const HEATER_HEIGHT: f32 = 20.0;
+const PBF_ITERATIONS: u32 = 4;
+const PBF_RELAXATION: f32 = 0.01;
+const CFL_NUMBER: f32 = 0.4;
+const MAX_SUBSTEPS: u32 = 8;
+const TRAIL_OPACITY: f32 = 0.5;
+const TRAIL_WIDTH: f32 = 2.0;
+const DENSITY_ALPHA_MIN: f32 = 0.15;
+const DENSITY_ALPHA_RANGE: f32 = 1.0;
+const DENSITY_ALPHA_GAMMA: f32 = 1.0;
+const VELOCITY_FIELD_SPACING: f32 = 24.0;
+const VELOCITY_FIELD_FULL_SPEED: f32 = 100.0;
+const OUTLINE_WIDTH: f32 = 3.0;
+const OUTLINE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const PALETTE_DITHER: f32 = 0.5;
+const RETRO_PIXEL_SIZE: f32 = 3.0;
+#[cfg(target_arch = "wasm32")]
+const WEB_CANVAS: &str = "#particle-canvas";   // must match web/index.html
 
 #[derive(ExtractComponent, Component, Default, Clone)]
 pub struct ParticleSystem 
 {
     pub particles: Vec<Particle>,
+    pub generation: u32,    // bumped when particles are replaced with the count unchanged, so the GPU copy is re-uploaded
 }
 
 #[repr(C)]
@@ -51,34 +191,201 @@ pub struct ParticleConfig {
     pub damping_factor: f32,            // 4 bytes
     pub fixed_delta_time: f32,          // 4 bytes
     pub frame_count: u32,               // 4 bytes
-    pub gravity: f32,                   // 4 bytes
+    pub velocity_history_frames: f32,   // 4 bytes     length of the velocity variance window
+
+    pub gravity: [f32; 2],              // 8 bytes     pixels/s^2, (0, -g) pulls straight down
+    pub _gravity_padding: [f32; 2],     // 8 bytes
 
     pub density_kernel_norm: f32,       // 4 bytes
     pub near_density_kernel_norm: f32,  // 4 bytes
     pub viscocity_kernel_norm: f32,     // 4 bytes
-    pub _padding: f32,                  // 4 bytes
+    pub paused: u32,                    // 4 bytes     sim passes are skipped while set
 
     pub target_density: f32,            // 4 bytes
     pub pressure_multiplier: f32,       // 4 bytes
     pub viscocity_strength: f32,        // 4 bytes
     pub near_density_multiplier: f32,   // 4 bytes
 
+    pub spatial_grid_width: u32,        // 4 bytes     neighbor search cells, one smoothing radius across
+    pub spatial_grid_height: u32,       // 4 bytes
+    pub _spatial_grid_padding: [f32; 2],// 8 bytes
+
+    pub scalar_grid_width: u32,         // 4 bytes
+    pub scalar_grid_height: u32,        // 4 bytes
+    pub scalar_grid_cell_size: f32,     // 4 bytes
+    pub smoke_enabled: u32,             // 4 bytes
+
+    pub smoke_injection: f32,           // 4 bytes
+    pub smoke_dissipation: f32,         // 4 bytes
+    pub smoke_opacity: f32,             // 4 bytes
+    pub divergence_view: u32,           // 4 bytes
+
+    pub flip_enabled: u32,              // 4 bytes
+    pub flip_ratio: f32,                // 4 bytes
+    pub pressure_iterations: u32,       // 4 bytes
+    pub divergence_range: f32,          // 4 bytes
+
+    pub color_field: u32,               // 4 bytes     speed, density, pressure, turbulence or temperature
+    pub colormap: u32,                  // 4 bytes     viridis, plasma or blue-red
+    pub color_min: f32,                 // 4 bytes     field value at the bottom of the colormap
+    pub color_max: f32,                 // 4 bytes     field value at the top of the colormap
+
+    pub interaction_position: [f32; 2], // 8 bytes
+    pub interaction_strength: f32,      // 4 bytes     > 0 attracts, < 0 repels, 0 when idle
+    pub interaction_radius: f32,        // 4 bytes
+
+    pub impulse_position: [f32; 2],     // 8 bytes
+    pub impulse_strength: f32,          // 4 bytes     > 0 pushes out, < 0 pulls in, 0 outside the frame it fires
+    pub impulse_radius: f32,            // 4 bytes
+
+    pub fan_origin: [f32; 2],           // 8 bytes
+    pub fan_direction: [f32; 2],        // 8 bytes     unit vector
+    pub fan_strength: f32,              // 4 bytes     0 while the fan is off
+    pub fan_reach: f32,                 // 4 bytes
+    pub fan_cos_half_angle: f32,        // 4 bytes
+    pub _fan_padding: f32,              // 4 bytes
+
+    pub render_mode: u32,               // 4 bytes     0 draws particles, 1 a smoothed liquid surface, 2 a density heatmap
+    pub surface_threshold: f32,         // 4 bytes     blurred thickness at the liquid's edge
+    pub surface_blur_radius: f32,       // 4 bytes     pixels
+    pub heatmap_range: f32,             // 4 bytes     density at the top of the heatmap, in target densities
+
+    pub surrogate_enabled: u32,         // 4 bytes     a learned velocity correction is in surrogate_correction
+    pub surrogate_strength: f32,        // 4 bytes
+    pub compensated_summation: u32,     // 4 bytes     Kahan summed density and force accumulators
+    pub deterministic_order: u32,       // 4 bytes     sort each cell's particles by index, for seeded runs
+
+    pub temperature_enabled: u32,       // 4 bytes
+    pub ambient_temperature: f32,       // 4 bytes
+    pub heat_diffusion: f32,            // 4 bytes     per second, towards the neighbors' temperature
+    pub cooling_rate: f32,              // 4 bytes     per second, towards the ambient temperature
+
+    pub thermal_expansion: f32,         // 4 bytes     buoyancy against gravity per degree above ambient
+    pub heater_temperature: f32,        // 4 bytes
+    pub heater_height: f32,             // 4 bytes     how far above the floor the heaters reach
+    pub heater_count: u32,              // 4 bytes
+
+    pub solver: u32,                    // 4 bytes     SolverKind
+    pub pbf_iterations: u32,            // 4 bytes     constraint projections per step
+    pub pbf_relaxation: f32,            // 4 bytes     softens the density constraint where gradients vanish
+    pub _solver_padding: f32,           // 4 bytes
+
+    pub substeps: u32,                  // 4 bytes     particle steps this frame, see substeps.rs
+    pub substep_delta_time: f32,        // 4 bytes     fixed_delta_time / substeps
+    pub cfl_number: f32,                // 4 bytes     smoothing radii the fastest particle may cross per substep
+    pub max_substeps: u32,              // 4 bytes     1 while adaptive substepping is off
+
+    pub trail_length: u32,              // 4 bytes     recorded positions drawn per trail, 0 or 1 for none
+    pub trail_opacity: f32,             // 4 bytes     alpha of the newest segment
+    pub trail_width: f32,               // 4 bytes
+    pub _trail_padding: f32,            // 4 bytes
+
+    pub tonemapping: u32,               // 4 bytes     Tonemapping, applied to every color written to the screen
+    pub exposure: f32,                  // 4 bytes     scales linear color before the tonemap
+    pub _tonemapping_padding: [f32; 2], // 8 bytes
+
+    pub density_alpha_enabled: u32,     // 4 bytes     fade particles by local density
+    pub density_alpha_min: f32,         // 4 bytes     alpha of a particle with no neighbours
+    pub density_alpha_range: f32,       // 4 bytes     density reaching full alpha, in target densities
+    pub density_alpha_gamma: f32,       // 4 bytes     curve between the two, above 1 keeps the spray fainter
+
+    pub velocity_field_mode: u32,       // 4 bytes     VelocityFieldMode
+    pub velocity_field_spacing: f32,    // 4 bytes     distance between samples of the velocity field
+    pub velocity_field_full_speed: f32, // 4 bytes     speed drawn a full sample spacing long
+    pub _velocity_field_padding: f32,   // 4 bytes
+
+    pub outline_enabled: u32,           // 4 bytes     silhouette outline from the surface texture
+    pub outline_width: f32,             // 4 bytes     in pixels
+    pub _outline_padding: [f32; 2],     // 8 bytes
+    pub outline_color: [f32; 4],        // 16 bytes    linear rgb, alpha
+
+    pub retro_enabled: u32,             // 4 bytes     quantize the system's drawing to the palette in a post pass
+    pub palette_size: u32,              // 4 bytes     colors used from the start of `palette`
+    pub palette_dither: f32,            // 4 bytes     ordered dither strength, 0 for flat bands
+    pub retro_pixel_size: f32,          // 4 bytes     screen pixels per retro pixel
+    pub palette: [[f32; 4]; MAX_PALETTE_COLORS],   // 256 bytes   srgb, the w unused
+
+    pub heater_spans: [[f32; 4]; 2],    // 32 bytes     start, end pairs along the floor as fractions of its width
+
+    pub boundary_modes: [u32; 4],       // 16 bytes     BoundaryMode of the left, right, bottom and top edges
+
     pub screen_bounds: [f32; 4],        // 16 bytes     [x_min, x_max, y_min, y_max]
 
     pub view_proj: [[f32; 4]; 4],       // 64 bytes
 }
 
+impl ParticleConfig
+{
+    // The smoothing radius is the support of every kernel and the spatial grid's cell size, and
+    // the kernels are normalized by powers of it, so changing it means changing those too. Set
+    // it through here; update_derived_params catches direct writes, and bounds changes, a frame later.
+    pub fn set_smoothing_radius(&mut self, smoothing_radius: WorldLength)
+    {
+        self.smoothing_radius = smoothing_radius.0.max(MIN_SMOOTHING_RADIUS);
+        [self.density_kernel_norm, self.near_density_kernel_norm, self.viscocity_kernel_norm] = kernel_norms(self.smoothing_radius);
+        [self.spatial_grid_width, self.spatial_grid_height] = spatial_grid_size(self.screen_bounds, self.smoothing_radius);
+    }
+
+    pub fn spatial_grid_cells(&self) -> u32
+    {
+        self.spatial_grid_width.saturating_mul(self.spatial_grid_height)
+    }
+}
+
+// cells one smoothing radius across covering the screen bounds, so every cell has its own key
+pub fn spatial_grid_size(screen_bounds: [f32; 4], smoothing_radius: f32) -> [u32; 2]
+{
+    let [x_min, x_max, y_min, y_max] = screen_bounds;
+    [
+        ((x_max - x_min) / smoothing_radius).ceil().max(1.0) as u32,
+        ((y_max - y_min) / smoothing_radius).ceil().max(1.0) as u32,
+    ]
+}
+
+// normalizations of the density, near density and viscosity kernels in 2D
+fn kernel_norms(smoothing_radius: f32) -> [f32; 3]
+{
+    [
+        10.0 / (PI * smoothing_radius.powf(5.0)),
+        15.0 / (PI * smoothing_radius.powf(6.0)),
+        4.0 / (PI * smoothing_radius.powf(8.0)),
+    ]
+}
+
 fn main() 
 {
-    App::new()
-    .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                mode: WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
-                ..default()
-            }),
+    let headless = HeadlessRun::from_args();
+    let mut domain = Domain::from_args();
+    let layout = InitialLayout::from_args();
+    let sim_rng = SimRng::from_args();
+    let mut app = App::new();
+
+    if headless.is_some()
+    {
+        // no window or event loop, the runner steps the app as fast as it can and egui never
+        // gets a context, so the gui systems don't run
+        app.add_plugins(DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .disable::<WinitPlugin>())
+        // ScheduleRunnerPlugin isn't part of DefaultPlugins while bevy_winit is enabled, so it's
+        // added on its own to drive the update loop in winit's place
+        .add_plugins(ScheduleRunnerPlugin::run_loop(std::time::Duration::ZERO))
+        .insert_resource(EguiGlobalSettings { auto_create_primary_context: false, ..default() });
+        domain.size.get_or_insert(DEFAULT_HEADLESS_DOMAIN);
+    }
+    else
+    {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(primary_window()),
             ..default()
-        }))
-    .add_plugins(particle::ParticlePlugin)
+        }));
+    }
+
+    app.add_plugins(particle::ParticlePlugin { backend: SimBackend::from_args() })
     .add_plugins(EguiPlugin::default())
 
     // Actual simulation parameters used in compute shader
@@ -91,65 +398,403 @@ fn main()
         damping_factor: DAMPING_FACTOR,
         fixed_delta_time: FIXED_DELTA_TIME,
         frame_count: 0,
-        gravity: GRAVITY,
+        velocity_history_frames: VELOCITY_HISTORY_FRAMES,
 
-        density_kernel_norm: 10.0 / (PI * SMOOTHING_RADIUS.powf(5.0)),
-        near_density_kernel_norm: 15.0 / (PI * SMOOTHING_RADIUS.powf(6.0)),
-        viscocity_kernel_norm: 4.0 / (PI * SMOOTHING_RADIUS.powf(8.0)),
-        _padding: 0.0,
+        gravity: [0.0, -GRAVITY],
+        _gravity_padding: [0.0; 2],
+
+        density_kernel_norm: kernel_norms(SMOOTHING_RADIUS)[0],
+        near_density_kernel_norm: kernel_norms(SMOOTHING_RADIUS)[1],
+        viscocity_kernel_norm: kernel_norms(SMOOTHING_RADIUS)[2],
+        paused: 0,
 
         target_density: TARGET_DENSITY,
         pressure_multiplier: PRESSURE_MULTIPLIER,
         viscocity_strength: VISCOCITY_STRENGTH,
         near_density_multiplier: NEAR_DENSITY_MULTIPLIER,
 
+        spatial_grid_width: 1,  // sized from the screen bounds once they're known
+        spatial_grid_height: 1,
+        _spatial_grid_padding: [0.0; 2],
+
+        scalar_grid_width: 0,   // sized from the screen bounds during setup
+        scalar_grid_height: 0,
+        scalar_grid_cell_size: SCALAR_GRID_CELL_SIZE,
+        smoke_enabled: 0,
+
+        smoke_injection: SMOKE_INJECTION,
+        smoke_dissipation: SMOKE_DISSIPATION,
+        smoke_opacity: SMOKE_OPACITY,
+        divergence_view: 0,
+
+        flip_enabled: 0,
+        flip_ratio: FLIP_RATIO,
+        pressure_iterations: PRESSURE_ITERATIONS,
+        divergence_range: DIVERGENCE_RANGE,
+
+        color_field: 0,
+        colormap: 0,
+        color_min: COLOR_MIN,
+        color_max: COLOR_MAX,
+
+        interaction_position: [0.0; 2],
+        interaction_strength: 0.0,
+        interaction_radius: INTERACTION_RADIUS,
+
+        impulse_position: [0.0; 2],
+        impulse_strength: 0.0,
+        impulse_radius: 0.0,
+
+        fan_origin: [0.0; 2],
+        fan_direction: [1.0, 0.0],
+        fan_strength: 0.0,
+        fan_reach: 0.0,
+        fan_cos_half_angle: 1.0,
+        _fan_padding: 0.0,
+
+        render_mode: 0,
+        surface_threshold: SURFACE_THRESHOLD,
+        surface_blur_radius: SURFACE_BLUR_RADIUS,
+        heatmap_range: HEATMAP_RANGE,
+
+        surrogate_enabled: 0,
+        surrogate_strength: 1.0,
+        compensated_summation: 0,
+        deterministic_order: sim_rng.is_seeded() as u32,
+
+        temperature_enabled: 0,
+        ambient_temperature: AMBIENT_TEMPERATURE,
+        heat_diffusion: HEAT_DIFFUSION,
+        cooling_rate: COOLING_RATE,
+
+        thermal_expansion: THERMAL_EXPANSION,
+        heater_temperature: HEATER_TEMPERATURE,
+        heater_height: HEATER_HEIGHT,
+        heater_count: 1,
+
+        solver: 0,
+        pbf_iterations: PBF_ITERATIONS,
+        pbf_relaxation: PBF_RELAXATION,
+        _solver_padding: 0.0,
+
+        substeps: 1,
+        substep_delta_time: FIXED_DELTA_TIME,
+        cfl_number: CFL_NUMBER,
+        max_substeps: 1,
+
+        trail_length: 0,
+        trail_opacity: TRAIL_OPACITY,
+        trail_width: TRAIL_WIDTH,
+        _trail_padding: 0.0,
+
+        tonemapping: 0,
+        exposure: 1.0,
+        _tonemapping_padding: [0.0; 2],
+
+        density_alpha_enabled: 0,
+        density_alpha_min: DENSITY_ALPHA_MIN,
+        density_alpha_range: DENSITY_ALPHA_RANGE,
+        density_alpha_gamma: DENSITY_ALPHA_GAMMA,
+
+        velocity_field_mode: 0,
+        velocity_field_spacing: VELOCITY_FIELD_SPACING,
+        velocity_field_full_speed: VELOCITY_FIELD_FULL_SPEED,
+        _velocity_field_padding: 0.0,
+
+        outline_enabled: 0,
+        outline_width: OUTLINE_WIDTH,
+        _outline_padding: [0.0; 2],
+        outline_color: OUTLINE_COLOR,
+
+        retro_enabled: 0,
+        palette_size: RetroPalette::GameBoy.colors().len() as u32,
+        palette_dither: PALETTE_DITHER,
+        retro_pixel_size: RETRO_PIXEL_SIZE,
+        palette: RetroPalette::GameBoy.gpu_colors(),
+
+        heater_spans: [[0.4, 0.6, 0.0, 0.0], [0.0; 4]],
+
+        boundary_modes: [0; 4],
+
         screen_bounds: [0.0; 4],
         view_proj: Mat4::IDENTITY.to_cols_array_2d(),
     })
     
     // GUI modifiable sim params
     .insert_resource(GUIConfig {
-        fixed_delta_time: FIXED_DELTA_TIME,
-        smoothing_radius: SMOOTHING_RADIUS,
+        particle_count: PARTICLE_COUNT,
+        fixed_delta_time: Seconds(FIXED_DELTA_TIME),
+        smoothing_radius: WorldLength(SMOOTHING_RADIUS),
         max_energy: MAX_ENERGY,
 
         gravity: GRAVITY,
+        gravity_angle: 0.0,
         damping_factor: DAMPING_FACTOR,
-        target_density: TARGET_DENSITY,
+        target_density: Density(TARGET_DENSITY),
         pressure_multiplier: PRESSURE_MULTIPLIER,
         
         viscocity_strength: VISCOCITY_STRENGTH,
         near_density_multiplier: NEAR_DENSITY_MULTIPLIER,
+
+        smoke_enabled: false,
+        smoke_injection: SMOKE_INJECTION,
+        smoke_dissipation: SMOKE_DISSIPATION,
+        smoke_opacity: SMOKE_OPACITY,
+
+        flip_enabled: false,
+        flip_ratio: FLIP_RATIO,
+        pressure_iterations: PRESSURE_ITERATIONS,
+
+        divergence_view: false,
+        divergence_range: DIVERGENCE_RANGE,
+
+        color_field: 0,
+        colormap: 0,
+        color_min: COLOR_MIN,
+        color_max: COLOR_MAX,
+
+        render_mode: 0,
+        surface_threshold: SURFACE_THRESHOLD,
+        surface_blur_radius: SURFACE_BLUR_RADIUS,
+        heatmap_range: HEATMAP_RANGE,
+
+        velocity_history_frames: VELOCITY_HISTORY_FRAMES,
+        compensated_summation: false,
+
+        temperature_enabled: false,
+        ambient_temperature: AMBIENT_TEMPERATURE,
+        heat_diffusion: HEAT_DIFFUSION,
+        cooling_rate: COOLING_RATE,
+        thermal_expansion: THERMAL_EXPANSION,
+        heater_temperature: HEATER_TEMPERATURE,
+        heater_height: HEATER_HEIGHT,
+        heater_count: 1,
+        heater_spans: [[0.4, 0.6], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0]],
+
+        solver: 0,
+        pbf_iterations: PBF_ITERATIONS,
+        pbf_relaxation: PBF_RELAXATION,
+
+        adaptive_substeps: false,
+        cfl_number: CFL_NUMBER,
+        max_substeps: MAX_SUBSTEPS,
+
+        trail_length: 0,
+        trail_opacity: TRAIL_OPACITY,
+        trail_width: TRAIL_WIDTH,
+
+        tonemapping: 0,
+        exposure: 1.0,
+
+        density_alpha_enabled: false,
+        density_alpha_min: DENSITY_ALPHA_MIN,
+        density_alpha_range: DENSITY_ALPHA_RANGE,
+        density_alpha_gamma: DENSITY_ALPHA_GAMMA,
+
+        velocity_field_mode: 0,
+        velocity_field_spacing: VELOCITY_FIELD_SPACING,
+        velocity_field_full_speed: VELOCITY_FIELD_FULL_SPEED,
+
+        outline_enabled: false,
+        outline_width: OUTLINE_WIDTH,
+        outline_color: OUTLINE_COLOR,
+
+        retro_enabled: false,
+        palette: 0,
+        palette_dither: PALETTE_DITHER,
+        retro_pixel_size: RETRO_PIXEL_SIZE,
+
+        boundary_modes: [0; 4],
+
+        interaction_strength: INTERACTION_STRENGTH,
+        interaction_radius: WorldLength(INTERACTION_RADIUS),
+        fan_strength: FAN_STRENGTH,
+        fan_angle: FAN_ANGLE,
+
+        variable_delta_time: false,
+        max_delta_time: Seconds(MAX_DELTA_TIME),
+
+        oscillate_gravity: false,
+        gravity_oscillation_period: Seconds(GRAVITY_OSCILLATION_PERIOD),
         applied_changes: false,  
     })  
 
-    
-
-    .add_systems(Startup, setup_camera)
+    .init_resource::<FluidVolumeStats>()
+    .init_resource::<HydrostaticCheck>()
+    .init_resource::<SimulationReadiness>()
+    .init_resource::<ParameterHistory>()
+    .init_resource::<SimClock>()
+    .init_resource::<GuiScale>()
+    .init_resource::<HudSettings>()
+    .init_resource::<AttractMode>()
+    .init_resource::<ImpulseSettings>()
+    .init_resource::<PendingImpulse>()
+    .init_resource::<SceneIo>()
+    .init_resource::<FieldExport>()
+    .init_resource::<TrainingData>()
+    .init_resource::<Surrogate>()
+    .init_resource::<RigidBodies>()
+    .init_resource::<StatsOverlay>()
+    .insert_resource(domain)
+    .insert_resource(layout)
+    .insert_resource(sim_rng)
+    .init_resource::<DomainFrame>()
+    .init_resource::<Presets>()
+    .init_resource::<CameraFollow>()
+    .init_resource::<PressureProbes>()
+    .init_resource::<ObstacleForces>()
+    .init_resource::<SmoothingRadiusGauge>()
+    .init_resource::<FrameCapture>()
+    .init_resource::<Autosave>()
+    .init_resource::<PowerSaving>()
+    .init_resource::<SessionLog>()
+    .init_resource::<InteractionTool>()
+    .init_resource::<EmittedParticles>()
+    .init_resource::<EmitterRing>()
+    .init_resource::<EmitterSettings>()
+    .init_resource::<SpawnQueue>()
+    .init_resource::<BrushSettings>()
+    .init_resource::<ParticleProbe>()
+    .init_resource::<ObstacleCourse>()
+    .add_event::<SimulationReady>()
+    .add_event::<CourseCompleted>()
+    .add_event::<GoalRegionUpdated>()
+    .add_event::<ParamRamp>()
+
+    .add_systems(Startup, (setup_camera, store_gui_defaults, load_presets))
     .add_systems(PreUpdate, apply_gui_updates)
     .add_systems(EguiPrimaryContextPass, gui_system)
+    .add_systems(EguiPrimaryContextPass, parameter_history_system.after(gui_system))
+    .add_systems(EguiPrimaryContextPass, draw_smoothing_radius_gauge.after(gui_system))
+    .add_systems(EguiPrimaryContextPass, fluid_volume_gui)
+    .add_systems(EguiPrimaryContextPass, hydrostatic_gui)
+    .add_systems(EguiPrimaryContextPass, pipeline_progress_overlay)
+    .add_systems(EguiPrimaryContextPass, obstacle_gui)
+    .add_systems(EguiPrimaryContextPass, force_field_gui)
+    .add_systems(EguiPrimaryContextPass, rigid_body_gui)
+    .add_systems(EguiPrimaryContextPass, hud_system)
+    .add_systems(EguiPrimaryContextPass, stats_overlay)
+    .add_systems(EguiPrimaryContextPass, draw_domain_frame)
+    .add_systems(EguiPrimaryContextPass, domain_gui)
+    .add_systems(EguiPrimaryContextPass, presets_gui)
+    .add_systems(EguiPrimaryContextPass, camera_follow_gui)
+    .add_systems(EguiPrimaryContextPass, draw_followed_particle)
+    .add_systems(EguiPrimaryContextPass, pressure_probe_gui)
+    .add_systems(EguiPrimaryContextPass, draw_pressure_probes)
+    .add_systems(EguiPrimaryContextPass, obstacle_force_gui)
+    .add_systems(EguiPrimaryContextPass, draw_obstacle_forces)
+    .add_systems(EguiPrimaryContextPass, frame_capture_gui)
+    .add_systems(EguiPrimaryContextPass, autosave_gui)
+    .add_systems(EguiPrimaryContextPass, power_saving_gui)
+    .add_systems(EguiPrimaryContextPass, session_log_gui)
+    .add_systems(EguiPrimaryContextPass, draw_obstacles)
+    .add_systems(EguiPrimaryContextPass, draw_heaters)
+    .add_systems(EguiPrimaryContextPass, draw_fan)
+    .add_systems(EguiPrimaryContextPass, draw_brush)
+    .add_systems(EguiPrimaryContextPass, attract_mode_overlay)
+    .add_systems(EguiPrimaryContextPass, impulse_gui)
+    .add_systems(EguiPrimaryContextPass, scene_gui)
+    .add_systems(EguiPrimaryContextPass, field_export_gui)
+    .add_systems(EguiPrimaryContextPass, particle_systems_gui)
+    .add_systems(EguiPrimaryContextPass, training_data_gui)
+    .add_systems(EguiPrimaryContextPass, surrogate_gui)
+    .add_systems(EguiPrimaryContextPass, emitter_gui)
+    .add_systems(EguiPrimaryContextPass, obstacle_course_gui)
+    .add_systems(Update, update_fluid_volume)
+    .add_systems(Update, update_hydrostatic_check)
+    .add_systems(Update, announce_simulation_ready)
+    .add_systems(Update, oscillate_gravity)
+    .add_systems(Update, ramp_params)
+    .add_systems(Update, tilt_gravity)
+    .add_systems(Update, update_delta_time.after(update_sim_clock))
+    .add_systems(Update, update_sim_clock)
+    .add_systems(Update, apply_gui_scale)
+    .add_systems(Update, toggle_hud)
+    .add_systems(Update, update_attract_mode.after(toggle_hud))
+    .add_systems(Update, advance_frame_count.after(update_sim_clock))
+    .add_systems(Update, update_mouse_interaction)
+    .add_systems(Update, update_impulse.after(update_sim_clock))
     .add_systems(Update, setup_particles)
-    .add_systems(Update, exit_on_escape)
-    .run();
+    .add_systems(Update, update_domain.before(resize_particle_system))
+    .add_systems(Update, fit_bounds_to_window.after(update_domain))
+    .add_systems(Update, resize_particle_system)
+    .add_systems(PostUpdate, update_derived_params)
+    .add_systems(PostUpdate, update_substeps)
+    .add_systems(Update, update_scene_io.before(resize_particle_system))
+    .add_systems(Update, update_field_export)
+    .add_systems(Update, update_surrogate)
+    .add_systems(Update, update_rigid_bodies.after(update_sim_clock).after(update_delta_time))
+    .add_systems(Update, update_obstacle_drag.after(update_rigid_bodies))
+    .add_systems(Update, update_force_fields)
+    .add_systems(Update, update_training_data.before(update_sim_clock).before(resize_particle_system))
+    .add_systems(Update, update_emitters.after(update_sim_clock))
+    .add_systems(Update, update_brush.after(update_sim_clock).before(update_emitters))
+    .add_systems(Update, update_particle_probe)
+    .add_systems(Update, update_goal_regions.after(update_particle_probe))
+    .add_systems(Update, update_camera_follow.after(update_particle_probe))
+    .add_systems(Update, update_pressure_probes)
+    .add_systems(Update, update_obstacle_forces)
+    .add_systems(Update, update_frame_capture)
+    .add_systems(Update, update_autosave.before(update_scene_io))
+    .add_systems(Last, clear_recovery_on_exit)
+    .add_systems(Update, update_session_log)
+    .add_systems(Last, write_session_log_on_exit)
+    .add_systems(Update, restart_from_snapshot)
+    .add_systems(Update, update_power_saving.before(update_sim_clock))
+    .add_systems(Update, update_obstacle_course.after(update_goal_regions))
+    .add_systems(Update, exit_on_escape);
+
+    if let Some(run) = headless
+    {
+        app.insert_resource(run)
+        .add_systems(Startup, apply_headless_params)
+        .add_systems(Update, run_headless.before(update_sim_clock));
+    }
+    app.run();
 }
 
 fn get_screen_bounds(
     camera_query: &Query<(&Camera, &GlobalTransform), With<Camera2d>>,
 ) -> Option<[f32; 4]> 
 {
-    let (camera, transform) = camera_query.single().ok()?;
+    let (camera, _) = camera_query.single().ok()?;
     let viewport_size = camera.logical_viewport_size()?;
+    Some(centered_bounds(viewport_size))
+}
 
-    let center = transform.translation().truncate();
-    let half_width = viewport_size.x / 2.0;
-    let half_height = viewport_size.y / 2.0;
+// bounds of the given size around the origin, where the camera starts; the domain stays
+// put when the camera follows the fluid elsewhere
+fn centered_bounds(size: Vec2) -> [f32; 4]
+{
+    let half_width = size.x / 2.0;
+    let half_height = size.y / 2.0;
+
+    [
+        -half_width, // x_min
+        half_width, // x_max
+        -half_height, // y_min
+        half_height, // y_max
+    ]
+}
 
-    Some([
-        center.x - half_width, // x_min
-        center.x + half_width, // x_max
-        center.y - half_height, // y_min
-        center.y + half_height, // y_max
-    ])
+#[cfg(not(target_arch = "wasm32"))]
+fn primary_window() -> Window
+{
+    Window {
+        mode: bevy::window::WindowMode::BorderlessFullscreen(MonitorSelection::Primary),
+        ..default()
+    }
+}
+
+// in the browser the app draws into the demo page's canvas (web/index.html) and follows its size
+#[cfg(target_arch = "wasm32")]
+fn primary_window() -> Window
+{
+    Window {
+        canvas: Some(WEB_CANVAS.to_string()),
+        fit_canvas_to_parent: true,
+        ..default()
+    }
 }
 
 fn setup_camera(mut commands : Commands)
@@ -158,61 +803,96 @@ fn setup_camera(mut commands : Commands)
 }
 
 fn setup_particles(
-    commands: Commands,
+    mut commands: Commands,
     mut particle_config: ResMut<ParticleConfig>,
     camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    domain: Res<Domain>,
+    layout: Res<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
     mut ran: Local<bool>
 ) {
     if !*ran
     {
         *ran = true;
 
-        // Get and store screen bounds
-        if let Some(bounds) = get_screen_bounds(&camera_query) {
+        // Get and store screen bounds, a fixed domain takes the place of the window
+        if let Some(bounds) = domain.bounds().or_else(|| get_screen_bounds(&camera_query)) {
             particle_config.screen_bounds = bounds;
         } else {
             warn!("[Setup] Failed to retrieve screen bounds from camera query");
             return; // Exit setup early if bounds are unavailable
         }
 
-        setup_particles_scatter(particle_config, commands);
+        size_scalar_grid(&mut particle_config);
+
+        // Spawn particle system
+        let particles = setup_particles_scatter(&layout, &mut sim_rng, particle_config.screen_bounds, particle_config.particle_count);
+        commands.spawn(ParticleSystem { particles, ..default() });
     }
 }
 
-fn setup_particles_scatter(
-    particle_config: ResMut<ParticleConfig>,
-    mut commands: Commands,
-)
+// Size the smoke grid to cover the screen
+fn size_scalar_grid(particle_config: &mut ParticleConfig)
 {
     let [x_min, x_max, y_min, y_max] = particle_config.screen_bounds;
-    let mut rng = rand::rng();
-
-    // Y-distribution: mean at center
-    let y_center = (y_min + y_max) / 2.0;
-    let y_std_dev = (y_max - y_min) * 0.125;
-    let y_dist = Normal::new(y_center, y_std_dev).unwrap();
-
-    let mut particles = Vec::with_capacity(PARTICLE_COUNT as usize);
-
-    // for i in 0..total_particles {
-    for i in 0..PARTICLE_COUNT {
-        // Uniformly distribute x across visible width
-        let t = i as f32 / PARTICLE_COUNT as f32;
-        let x = x_min + t * (x_max - x_min);
-
-        // Sample y and clamp to bounds
-        let mut y = y_dist.sample(&mut rng);
-        y = y.clamp(y_min, y_max);
-
-        particles.push(Particle {
-            position: [x, y],
-            velocity: [0.0, 0.0], 
-            color: [1.0, 1.0, 1.0, 1.0],
-        });
+    particle_config.scalar_grid_width = ((x_max - x_min) / SCALAR_GRID_CELL_SIZE).ceil().max(1.0) as u32;
+    particle_config.scalar_grid_height = ((y_max - y_min) / SCALAR_GRID_CELL_SIZE).ceil().max(1.0) as u32;
+}
+
+// recompute the kernel normalizations and spatial grid of any config whose smoothing radius or
+// bounds were written directly, only writing when they're stale so change detection stays quiet
+fn update_derived_params(
+    mut particle_config: ResMut<ParticleConfig>,
+    mut local_config_query: Query<&mut ParticleSystemConfig, Changed<ParticleSystemConfig>>,
+)
+{
+    let stale = |config: &ParticleConfig| {
+        [config.density_kernel_norm, config.near_density_kernel_norm, config.viscocity_kernel_norm] != kernel_norms(config.smoothing_radius)
+            || [config.spatial_grid_width, config.spatial_grid_height] != spatial_grid_size(config.screen_bounds, config.smoothing_radius)
+    };
+    if particle_config.is_changed() && stale(&particle_config)
+    {
+        let smoothing_radius = WorldLength(particle_config.smoothing_radius);
+        particle_config.set_smoothing_radius(smoothing_radius);
+    }
+    for mut local_config in local_config_query.iter_mut()
+    {
+        if stale(&local_config.0)
+        {
+            let smoothing_radius = WorldLength(local_config.0.smoothing_radius);
+            local_config.0.set_smoothing_radius(smoothing_radius);
+        }
+    }
+}
+
+// rescatter the particles whenever the configured count no longer matches the system,
+// the render world reallocates its buffers once the new particles are extracted
+fn resize_particle_system(
+    particle_config: Res<ParticleConfig>,
+    layout: Res<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
+    mut particle_system_query: Query<(&mut ParticleSystem, Option<&ParticleSystemConfig>)>,
+)
+{
+    for (mut particle_system, local_config) in particle_system_query.iter_mut()
+    {
+        let particle_count = local_config.map_or(particle_config.particle_count, |local_config| local_config.0.particle_count);
+        if particle_system.particles.len() != particle_count as usize
+        {
+            particle_system.particles = setup_particles_scatter(&layout, &mut sim_rng, particle_config.screen_bounds, particle_count);
+        }
     }
+}
 
-    // Spawn particle system and camera
-    commands.spawn(ParticleSystem { particles });
+// lay out the particles of a new or resized system
+fn setup_particles_scatter(
+    layout: &InitialLayout,
+    sim_rng: &mut SimRng,
+    screen_bounds: [f32; 4],
+    particle_count: u32,
+) -> Vec<Particle>
+{
+    layout.particles(sim_rng.rng(), screen_bounds, particle_count)
 }
 
 fn exit_on_escape(
@@ -0,0 +1,240 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        renderer::RenderQueue,
+        sync_world::MainEntity,
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+use bytemuck::{Pod, Zeroable};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::gui_scale::GuiScale;
+use crate::rigid_body::RigidBody;
+use crate::obstacle_drag::DraggedObstacle;
+
+pub const MAX_OBSTACLES: usize = 64;    // must match MAX_OBSTACLES in compute_shader.wgsl
+const OBSTACLE_HEADER_SIZE: u64 = 8;    // obstacle count, padded to the alignment of the array
+pub const OBSTACLE_BUFFER_SIZE: u64 = OBSTACLE_HEADER_SIZE + (std::mem::size_of::<GpuObstacle>() * MAX_OBSTACLES) as u64;
+
+const OBSTACLE_SHAPE_CIRCLE: u32 = 0;
+const OBSTACLE_SHAPE_BOX: u32 = 1;
+const NO_BODY: u32 = u32::MAX;
+
+#[derive(Clone, Copy)]
+pub enum ObstacleShape
+{
+    Circle { radius: f32 },
+    Aabb { half_extents: Vec2 },
+    OrientedBox { half_extents: Vec2, rotation: f32 },  // rotation in radians, counter clockwise
+}
+
+// collider the fluid flows around, in world units. Static unless it is also a RigidBody.
+#[derive(ExtractComponent, Component, Clone, Copy)]
+pub struct Obstacle
+{
+    pub position: Vec2,
+    pub shape: ObstacleShape,
+}
+
+impl Obstacle
+{
+    pub fn circle(position: Vec2, radius: f32) -> Self
+    {
+        Self { position, shape: ObstacleShape::Circle { radius } }
+    }
+
+    pub fn aabb(position: Vec2, half_extents: Vec2) -> Self
+    {
+        Self { position, shape: ObstacleShape::Aabb { half_extents } }
+    }
+
+    pub fn oriented_box(position: Vec2, half_extents: Vec2, rotation: f32) -> Self
+    {
+        Self { position, shape: ObstacleShape::OrientedBox { half_extents, rotation } }
+    }
+
+    // whether a world point is inside the shape, for picking
+    pub fn contains(&self, point: Vec2) -> bool
+    {
+        let offset = point - self.position;
+        let (half_extents, rotation) = match self.shape {
+            ObstacleShape::Circle { radius } => return offset.length_squared() <= radius * radius,
+            ObstacleShape::Aabb { half_extents } => (half_extents, 0.0),
+            ObstacleShape::OrientedBox { half_extents, rotation } => (half_extents, rotation),
+        };
+        let local = Vec2::from_angle(-rotation).rotate(offset);
+        local.x.abs() <= half_extents.x && local.y.abs() <= half_extents.y
+    }
+
+    fn to_gpu(&self, body: Option<&RigidBody>, dragged: Option<&DraggedObstacle>) -> GpuObstacle
+    {
+        // an AABB is just a box with no rotation
+        let (shape, half_extents, rotation) = match self.shape {
+            ObstacleShape::Circle { radius } => (OBSTACLE_SHAPE_CIRCLE, Vec2::new(radius, radius), 0.0),
+            ObstacleShape::Aabb { half_extents } => (OBSTACLE_SHAPE_BOX, half_extents, 0.0),
+            ObstacleShape::OrientedBox { half_extents, rotation } => (OBSTACLE_SHAPE_BOX, half_extents, rotation),
+        };
+        GpuObstacle
+        {
+            center: self.position.to_array(),
+            half_extents: half_extents.to_array(),
+            rotation: [rotation.cos(), rotation.sin()],
+            velocity: body.map(|body| body.velocity).or(dragged.map(|dragged| dragged.velocity)).unwrap_or(Vec2::ZERO).to_array(),
+            shape,
+            body: body.map_or(NO_BODY, |body| body.slot),
+            angular_velocity: body.map_or(0.0, |body| body.angular_velocity),
+            _padding: 0,
+        }
+    }
+}
+
+// mirrors the Obstacle struct in compute_shader.wgsl
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct GpuObstacle
+{
+    center: [f32; 2],
+    half_extents: [f32; 2],     // radius in both for circles
+    rotation: [f32; 2],         // cos, sin
+    velocity: [f32; 2],
+    shape: u32,
+    body: u32,                  // rigid body slot, NO_BODY for static obstacles
+    angular_velocity: f32,
+    _padding: u32,
+}
+
+// main world obstacle entities in the order they were packed into the obstacle buffer
+#[derive(Resource, Default)]
+pub struct ObstacleOrder(pub Vec<Entity>);
+
+// pack the extracted obstacles into each system's obstacle buffer
+pub fn prepare_obstacles(
+    render_queue: Res<RenderQueue>,
+    obstacle_query: Query<(&MainEntity, &Obstacle, Option<&RigidBody>, Option<&DraggedObstacle>)>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers>,
+    mut obstacle_order: ResMut<ObstacleOrder>,
+    mut warned: Local<bool>,
+)
+{
+    let obstacle_count = obstacle_query.iter().count();
+    if obstacle_count > MAX_OBSTACLES && !*warned
+    {
+        warn!("[Obstacles] {} obstacles spawned, only the first {} collide", obstacle_count, MAX_OBSTACLES);
+        *warned = true;
+    }
+
+    let obstacles: Vec<GpuObstacle> = obstacle_query.iter()
+        .take(MAX_OBSTACLES)
+        .map(|(_, obstacle, body, dragged)| obstacle.to_gpu(body, dragged))
+        .collect();
+    obstacle_order.0 = obstacle_query.iter()
+        .take(MAX_OBSTACLES)
+        .map(|(main_entity, _, _, _)| main_entity.id())
+        .collect();
+    let header = [obstacles.len() as u32, 0u32];
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        render_queue.write_buffer(&pipeline_buffers.obstacle_buffer, 0, bytemuck::bytes_of(&header));
+        if !obstacles.is_empty()
+        {
+            render_queue.write_buffer(&pipeline_buffers.obstacle_buffer, OBSTACLE_HEADER_SIZE, bytemuck::cast_slice(&obstacles));
+        }
+    }
+}
+
+// outline obstacles behind the egui windows so the empty regions in the fluid are visible
+pub fn draw_obstacles(
+    mut contexts: EguiContexts,
+    obstacle_query: Query<(&Obstacle, Has<DraggedObstacle>)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    // viewport coordinates are logical pixels, egui points are those divided by the gui scale
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for (obstacle, dragged) in obstacle_query.iter()
+    {
+        // the one under the mouse stands out
+        let stroke = if dragged {
+            egui::Stroke::new(2.5, egui::Color32::from_rgb(120, 200, 255))
+        } else {
+            egui::Stroke::new(1.5, egui::Color32::from_gray(200))
+        };
+        let (half_extents, rotation) = match obstacle.shape {
+            ObstacleShape::Circle { radius } => {
+                let edge = to_screen(obstacle.position + Vec2::new(radius, 0.0));
+                if let (Some(center), Some(edge)) = (to_screen(obstacle.position), edge)
+                {
+                    painter.circle_stroke(center, center.distance(edge), stroke);
+                }
+                continue;
+            }
+            ObstacleShape::Aabb { half_extents } => (half_extents, 0.0),
+            ObstacleShape::OrientedBox { half_extents, rotation } => (half_extents, rotation),
+        };
+
+        let rotation = Vec2::from_angle(rotation);
+        let corners: Option<Vec<egui::Pos2>> = [
+            Vec2::new(-half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, -half_extents.y),
+            Vec2::new(half_extents.x, half_extents.y),
+            Vec2::new(-half_extents.x, half_extents.y),
+        ].iter().map(|corner| to_screen(obstacle.position + rotation.rotate(*corner))).collect();
+
+        if let Some(corners) = corners
+        {
+            painter.add(egui::Shape::closed_line(corners, stroke));
+        }
+    }
+    Ok(())
+}
+
+// spawn and clear obstacles from the GUI, placed at the middle of the screen
+pub fn obstacle_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    obstacle_query: Query<Entity, With<Obstacle>>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let center = Vec2::new((x_min + x_max) / 2.0, (y_min + y_max) / 2.0);
+    let size = ((x_max - x_min).min(y_max - y_min) * 0.08).max(10.0);
+
+    egui::Window::new("Obstacles")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 600.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("{} / {} obstacles", obstacle_query.iter().count(), MAX_OBSTACLES));
+            ui.horizontal(|ui| {
+                if ui.button("Circle").clicked() {
+                    commands.spawn(Obstacle::circle(center, size));
+                }
+                if ui.button("Box").clicked() {
+                    commands.spawn(Obstacle::aabb(center, Vec2::splat(size)));
+                }
+                if ui.button("Rotated Box").clicked() {
+                    commands.spawn(Obstacle::oriented_box(center, Vec2::new(size * 2.0, size * 0.5), std::f32::consts::FRAC_PI_4));
+                }
+            });
+            if ui.button("Clear").clicked() {
+                for entity in obstacle_query.iter() {
+                    commands.entity(entity).despawn();
+                }
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+use crate::obstacle::Obstacle;
+use crate::particle_probe::ParticleProbe;
+use crate::goal_region::{GoalRegion, ParticleGroup};
+use crate::gui_scale::GuiScale;
+
+const REGION_WIDTH: f32 = 0.15;     // spawn and goal regions as a fraction of the screen width
+const WIN_FRACTION: f32 = 0.5;      // share of the blob that has to reach the goal
+
+// sent when enough of the blob reaches the goal
+#[derive(Event)]
+pub struct CourseCompleted
+{
+    pub time: f32,
+}
+
+// obstacles, blob group and goal spawned by the course, removed again when it is reset
+#[derive(Component)]
+pub struct CourseEntity;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CourseState
+{
+    Idle,
+    WaitingForBlob,     // the next probe readback picks the blob out of the spawn region
+    Running,            // a GoalRegion counts the blob's particles in the goal
+    Finished,
+}
+
+#[derive(Resource)]
+pub struct ObstacleCourse
+{
+    state: CourseState,
+    blob_size: usize,
+    in_goal_fraction: f32,
+    elapsed: f32,
+}
+
+impl Default for ObstacleCourse
+{
+    fn default() -> Self
+    {
+        Self { state: CourseState::Idle, blob_size: 0, in_goal_fraction: 0.0, elapsed: 0.0 }
+    }
+}
+
+// spawn region on the left, goal on the right, full height, in world units
+fn course_regions(config: &ParticleConfig) -> (Rect, Rect)
+{
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let region_width = (x_max - x_min) * REGION_WIDTH;
+    let spawn = Rect::new(x_min, y_min, x_min + region_width, y_max);
+    let goal = Rect::new(x_max - region_width, y_min, x_max, y_max);
+    (spawn, goal)
+}
+
+// a staggered row of walls between the spawn and goal regions
+fn spawn_course_obstacles(commands: &mut Commands, config: &ParticleConfig)
+{
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let width = x_max - x_min;
+    let height = y_max - y_min;
+    let wall = Vec2::new(width * 0.01, height * 0.3);
+
+    for (x, y) in [(0.35, 0.3), (0.5, 0.7), (0.65, 0.3)]
+    {
+        let position = Vec2::new(x_min + width * x, y_min + height * y);
+        commands.spawn((Obstacle::aabb(position, wall), CourseEntity));
+    }
+    commands.spawn((Obstacle::circle(Vec2::new(x_min + width * 0.5, y_min + height * 0.25), height * 0.08), CourseEntity));
+}
+
+pub fn update_obstacle_course(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<ParticleConfig>,
+    mut course: ResMut<ObstacleCourse>,
+    mut probe: ResMut<ParticleProbe>,
+    goal_query: Query<&GoalRegion, With<CourseEntity>>,
+    mut completed_events: EventWriter<CourseCompleted>,
+)
+{
+    match course.state
+    {
+        CourseState::Idle | CourseState::Finished => {}
+        CourseState::WaitingForBlob => {
+            probe.wanted = true;
+            if !probe.updated { return; }
+            let Some(positions) = probe.positions.as_ref() else { return; };
+
+            let (spawn, goal) = course_regions(&config);
+            let blob = ParticleGroup::from_region(positions, spawn);
+            course.blob_size = blob.indices.len();
+            let group = commands.spawn((blob, CourseEntity)).id();
+            commands.spawn((GoalRegion::for_group(goal, group), CourseEntity));
+
+            course.state = CourseState::Running;
+            course.elapsed = 0.0;
+        }
+        CourseState::Running => {
+            if config.paused == 0
+            {
+                course.elapsed += time.delta_secs();
+            }
+            let Ok(goal) = goal_query.single() else { return; };
+            course.in_goal_fraction = goal.fraction;
+
+            if goal.total > 0 && goal.fraction >= WIN_FRACTION
+            {
+                course.state = CourseState::Finished;
+                completed_events.write(CourseCompleted { time: course.elapsed });
+                info!("[Course] Completed in {:.1}s", course.elapsed);
+            }
+        }
+    }
+}
+
+pub fn obstacle_course_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut course: ResMut<ObstacleCourse>,
+    course_entities: Query<Entity, With<CourseEntity>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    config: Res<ParticleConfig>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+
+    egui::Window::new("Obstacle Course")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1000.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("Guide the fluid from the spawn region into the goal ({:.0}% needed)", WIN_FRACTION * 100.0));
+            ui.horizontal(|ui| {
+                if ui.button("Start").clicked() {
+                    for entity in course_entities.iter() {
+                        commands.entity(entity).despawn();
+                    }
+                    spawn_course_obstacles(&mut commands, &config);
+                    *course = ObstacleCourse { state: CourseState::WaitingForBlob, ..default() };
+                }
+                if ui.add_enabled(course.state != CourseState::Idle, egui::Button::new("Reset")).clicked() {
+                    for entity in course_entities.iter() {
+                        commands.entity(entity).despawn();
+                    }
+                    *course = ObstacleCourse::default();
+                }
+            });
+
+            match course.state {
+                CourseState::Idle => {}
+                CourseState::WaitingForBlob => { ui.label("Finding the fluid in the spawn region..."); }
+                CourseState::Running if course.blob_size == 0 => {
+                    ui.label("No fluid in the spawn region, reset and try again");
+                }
+                CourseState::Running => {
+                    ui.label(format!("In Goal: {:.1}%", course.in_goal_fraction * 100.0));
+                    ui.label(format!("Time: {:.1}s", course.elapsed));
+                }
+                CourseState::Finished => {
+                    ui.label(format!("Completed in {:.1}s!", course.elapsed));
+                }
+            }
+        });
+
+    // outline the regions behind the windows while a course is on
+    if course.state == CourseState::Idle { return Ok(()); }
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let (spawn, goal) = course_regions(&config);
+    for (region, color) in [(spawn, egui::Color32::from_rgb(120, 200, 255)), (goal, egui::Color32::from_rgb(255, 200, 80))]
+    {
+        if let (Some(a), Some(b)) = (to_screen(region.min), to_screen(region.max))
+        {
+            painter.rect_stroke(egui::Rect::from_two_pos(a, b), 0.0, egui::Stroke::new(2.0, color), egui::StrokeKind::Inside);
+        }
+    }
+    Ok(())
+}
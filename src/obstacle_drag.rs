@@ -0,0 +1,88 @@
+use bevy::{
+    prelude::*,
+    render::extract_component::ExtractComponent,
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiContexts;
+
+use crate::ParticleConfig;
+use crate::obstacle::Obstacle;
+use crate::rigid_body::RigidBody;
+use crate::interaction::InteractionTool;
+use crate::brush::cursor_world_position;
+
+// An obstacle held by the mouse. It follows the cursor at the offset it was grabbed with, and the
+// collision pass pushes particles with `velocity` so a dragged paddle stirs the fluid instead of
+// just teleporting through it. Rigid bodies take the drag velocity as their own, so they keep it
+// when let go.
+#[derive(ExtractComponent, Component, Clone, Copy)]
+pub struct DraggedObstacle
+{
+    pub grab_offset: Vec2,  // obstacle position minus the cursor when it was grabbed
+    pub velocity: Vec2,
+}
+
+// grab the topmost obstacle under the cursor on a left click with the drag tool, move it with the
+// cursor while the button is held. Runs after the rigid bodies so the drag overrides their step.
+pub fn update_obstacle_drag(
+    mut commands: Commands,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut contexts: EguiContexts,
+    tool: Res<InteractionTool>,
+    sim_config: Res<ParticleConfig>,
+    mut obstacle_query: Query<(Entity, &mut Obstacle, Option<&mut DraggedObstacle>, Option<&mut RigidBody>)>,
+)
+{
+    let cursor = cursor_world_position(&windows, &camera_query);
+
+    // let go when the button comes up, the tool changes or the cursor leaves the window
+    let held = *tool == InteractionTool::Drag && mouse_buttons.pressed(MouseButton::Left) && cursor.is_some();
+    if !held
+    {
+        for (entity, _, dragged, _) in obstacle_query.iter()
+        {
+            if dragged.is_some() {
+                commands.entity(entity).remove::<DraggedObstacle>();
+            }
+        }
+        return;
+    }
+    let Some(cursor) = cursor else { return; };
+
+    if mouse_buttons.just_pressed(MouseButton::Left)
+    {
+        let pointer_over_gui = contexts.ctx_mut()
+            .map(|ctx| ctx.is_pointer_over_area() || ctx.wants_pointer_input())
+            .unwrap_or(false);
+        if pointer_over_gui { return; }
+
+        // later obstacles are drawn over earlier ones
+        if let Some((entity, obstacle, _, _)) = obstacle_query.iter().filter(|(_, obstacle, _, _)| obstacle.contains(cursor)).last()
+        {
+            commands.entity(entity).insert(DraggedObstacle { grab_offset: obstacle.position - cursor, velocity: Vec2::ZERO });
+        }
+        return;
+    }
+
+    for (_, mut obstacle, dragged, body) in obstacle_query.iter_mut()
+    {
+        let Some(mut dragged) = dragged else { continue; };
+
+        // the sim moves fixed_delta_time per frame, so that's the time the move took in its terms
+        let target = cursor + dragged.grab_offset;
+        dragged.velocity = if sim_config.paused != 0 {
+            Vec2::ZERO
+        } else {
+            (target - obstacle.position) / sim_config.fixed_delta_time
+        };
+        obstacle.position = target;
+
+        if let Some(mut body) = body
+        {
+            body.velocity = dragged.velocity;
+            body.angular_velocity = 0.0;
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::ParticleConfig;
+use crate::obstacle::{Obstacle, ObstacleOrder, MAX_OBSTACLES};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+use crate::gui_scale::GuiScale;
+
+const FORCE_FIXED_POINT_SCALE: f32 = 16.0;          // must match BODY_FIXED_POINT_SCALE in compute_shader.wgsl
+const TORQUE_FIXED_POINT_SCALE: f32 = 1.0;          // must match BODY_ANGULAR_FIXED_POINT_SCALE in compute_shader.wgsl
+pub const OBSTACLE_FORCE_BUFFER_SIZE: u64 = (std::mem::size_of::<[i32; 3]>() * MAX_OBSTACLES) as u64;
+const ARROW_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 140, 60);
+
+// average force the fluid put on one obstacle over the last readback window
+#[derive(Clone, Copy, Default)]
+pub struct ObstacleForce
+{
+    pub force: Vec2,    // particles have unit mass, so this is the summed impulse per second
+    pub torque: f32,    // about the obstacle's center, counter clockwise positive
+}
+
+// shared between main and render worlds: the main world asks for forces while obstacles exist,
+// the render world hands back the latest averages keyed by main world entity
+#[derive(Resource, Clone, Default)]
+pub struct ObstacleForceShared
+{
+    wanted: Arc<AtomicBool>,
+    forces: Arc<Mutex<Option<Vec<(Entity, ObstacleForce)>>>>,
+}
+
+// render world side of the force readback
+#[derive(Resource)]
+pub struct ObstacleForceReadback
+{
+    readback: GpuReadback,
+    elapsed: f32,                   // sim seconds summed into the buffer since the last clear
+    requested_elapsed: f32,         // sim seconds covered by the copy in flight
+    requested_order: Vec<Entity>,   // obstacle order the copy in flight was packed in
+}
+
+impl Default for ObstacleForceReadback
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            readback: GpuReadback::new("obstacle_force_readback_buffer"),
+            elapsed: 0.0,
+            requested_elapsed: 0.0,
+            requested_order: Vec::new(),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ObstacleForces
+{
+    pub forces: HashMap<Entity, ObstacleForce>,
+    pub show_arrows: bool,
+    pub arrow_scale: f32,   // world units per unit of force, 0 picks one from the largest force
+}
+
+impl ObstacleForces
+{
+    fn arrow_scale(&self) -> f32
+    {
+        if self.arrow_scale > 0.0 { return self.arrow_scale; }
+        let largest = self.forces.values().map(|force| force.force.length()).fold(0.0, f32::max);
+        if largest > 0.0 { 100.0 / largest } else { 0.0 }
+    }
+}
+
+// copy the main system's impulses and clear them like the body impulses, then divide by the
+// sim time they were summed over
+pub fn read_back_obstacle_forces(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    obstacle_order: Res<ObstacleOrder>,
+    shared: Res<ObstacleForceShared>,
+    mut force_readback: ResMut<ObstacleForceReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if config.paused == 0
+    {
+        force_readback.elapsed += config.fixed_delta_time;
+    }
+
+    if let Some(impulses) = force_readback.readback.try_read::<[i32; 3]>(&render_device)
+    {
+        // nothing moved while paused, so keep showing the last forces
+        if force_readback.requested_elapsed > 0.0
+        {
+            let elapsed = force_readback.requested_elapsed;
+            let forces = force_readback.requested_order.iter()
+                .zip(&impulses)
+                .map(|(entity, impulse)| {
+                    let force = ObstacleForce
+                    {
+                        force: Vec2::new(impulse[0] as f32, impulse[1] as f32) / FORCE_FIXED_POINT_SCALE / elapsed,
+                        torque: impulse[2] as f32 / TORQUE_FIXED_POINT_SCALE / elapsed,
+                    };
+                    (*entity, force)
+                })
+                .collect();
+            *shared.forces.lock().unwrap() = Some(forces);
+        }
+    }
+
+    if !force_readback.readback.is_idle() || !shared.wanted.load(Ordering::Relaxed) { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        // impulses keep accumulating while the copy waits its turn, averaged over the longer time
+        if !budget.try_acquire("obstacle_forces", OBSTACLE_FORCE_BUFFER_SIZE, 1) { return; }
+
+        force_readback.readback.request(&render_device, &render_queue, &pipeline_buffers.obstacle_force_buffer, OBSTACLE_FORCE_BUFFER_SIZE);
+        // the copy is already submitted, so the clear lands after it and before the next step
+        render_queue.write_buffer(&pipeline_buffers.obstacle_force_buffer, 0, &[0u8; OBSTACLE_FORCE_BUFFER_SIZE as usize]);
+
+        force_readback.requested_elapsed = std::mem::take(&mut force_readback.elapsed);
+        force_readback.requested_order = obstacle_order.0.clone();
+    }
+}
+
+pub fn update_obstacle_forces(
+    shared: Res<ObstacleForceShared>,
+    obstacle_query: Query<Entity, With<Obstacle>>,
+    mut obstacle_forces: ResMut<ObstacleForces>,
+)
+{
+    shared.wanted.store(!obstacle_query.is_empty(), Ordering::Relaxed);
+    if let Some(forces) = shared.forces.lock().unwrap().take()
+    {
+        obstacle_forces.forces = forces.into_iter().collect();
+    }
+    // despawned obstacles drop out right away rather than on the next readback
+    obstacle_forces.forces.retain(|entity, _| obstacle_query.contains(*entity));
+}
+
+// force arrows from each obstacle's center behind the egui windows
+pub fn draw_obstacle_forces(
+    mut contexts: EguiContexts,
+    obstacle_forces: Res<ObstacleForces>,
+    obstacle_query: Query<&Obstacle>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if !obstacle_forces.show_arrows { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let scale = obstacle_forces.arrow_scale();
+    for (entity, force) in &obstacle_forces.forces
+    {
+        let Ok(obstacle) = obstacle_query.get(*entity) else { continue; };
+        if let (Some(start), Some(end)) = (to_screen(obstacle.position), to_screen(obstacle.position + force.force * scale))
+        {
+            painter.arrow(start, end - start, egui::Stroke::new(2.0, ARROW_COLOR));
+        }
+    }
+    Ok(())
+}
+
+pub fn obstacle_force_gui(
+    mut contexts: EguiContexts,
+    mut obstacle_forces: ResMut<ObstacleForces>,
+    obstacle_query: Query<Entity, With<Obstacle>>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Obstacle Forces")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1250.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut obstacle_forces.show_arrows, "Show Arrows");
+                ui.add(egui::DragValue::new(&mut obstacle_forces.arrow_scale)
+                    .speed(0.001)
+                    .range(0.0..=f32::MAX)
+                    .prefix("scale "));
+                ui.label("(0 = auto)");
+            });
+
+            if obstacle_query.is_empty()
+            {
+                ui.label("Spawn an obstacle to measure the fluid's force on it");
+                return;
+            }
+
+            egui::Grid::new("obstacle_force_grid").num_columns(5).striped(true).show(ui, |ui| {
+                for heading in ["Obstacle", "Fx", "Fy", "|F|", "Torque"]
+                {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+
+                // spawn order, so the numbering stays put as forces come and go
+                let mut entities: Vec<Entity> = obstacle_query.iter().collect();
+                entities.sort();
+                for (index, entity) in entities.iter().enumerate()
+                {
+                    let force = obstacle_forces.forces.get(entity).copied().unwrap_or_default();
+                    ui.label(format!("#{}", index));
+                    ui.label(format!("{:.1}", force.force.x));
+                    ui.label(format!("{:.1}", force.force.y));
+                    ui.label(format!("{:.1}", force.force.length()));
+                    ui.label(format!("{:.1}", force.torque));
+                    ui.end_row();
+                }
+            });
+        });
+    Ok(())
+}
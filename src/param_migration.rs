@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use crate::presets::ParamValue;
+
+// Version of the named parameter set, written into the param text, presets.ron and through
+// the text into scenes. Bump it with a migration below whenever a param is renamed or retyped,
+// or added with a default that behaves differently from builds that didn't have it.
+pub const PARAMS_VERSION: u32 = 5;
+pub const UNVERSIONED_PARAMS: u32 = 1;     // anything saved before the version was written
+
+// what changed on the way to a version, applied in this order
+struct ParamMigration
+{
+    version: u32,
+    converted: &'static [(&'static str, &'static str, fn(ParamValue) -> ParamValue)],   // old name, new name, new value
+    added: &'static [(&'static str, ParamValue)],       // the value that behaves like older files did
+}
+
+const MIGRATIONS: [ParamMigration; 4] = [
+    // unversioned files came from builds with and without these, a build without one behaved
+    // as if it were set to this
+    ParamMigration
+    {
+        version: 2,
+        converted: &[],
+        added: &[
+            ("gravity_angle", ParamValue::Float(0.0)),
+            ("compensated_summation", ParamValue::Bool(false)),
+            ("surface_mode", ParamValue::Bool(false)),
+            ("boundary_left", ParamValue::Integer(0)),
+            ("boundary_right", ParamValue::Integer(0)),
+            ("boundary_bottom", ParamValue::Integer(0)),
+            ("boundary_top", ParamValue::Integer(0)),
+        ],
+    },
+    // the surface toggle became a render mode when the density heatmap was added
+    ParamMigration
+    {
+        version: 3,
+        converted: &[("surface_mode", "render_mode", surface_mode_to_render_mode)],
+        added: &[],
+    },
+    // the temperature layer, off so older scenes keep their isothermal behavior
+    ParamMigration
+    {
+        version: 4,
+        converted: &[],
+        added: &[("temperature_enabled", ParamValue::Bool(false))],
+    },
+    // position based fluids, older files ran the state equation solver
+    ParamMigration
+    {
+        version: 5,
+        converted: &[],
+        added: &[("solver", ParamValue::Integer(0))],
+    },
+];
+
+fn surface_mode_to_render_mode(surface_mode: ParamValue) -> ParamValue
+{
+    ParamValue::Integer((surface_mode == ParamValue::Bool(true)) as u32)
+}
+
+// newer files may have params this build can't place, so they're refused rather than half applied
+pub fn check_params_version(version: u32) -> std::result::Result<(), String>
+{
+    if version > PARAMS_VERSION
+    {
+        return Err(format!("parameters are version {version}, this build reads up to {PARAMS_VERSION}"));
+    }
+    Ok(())
+}
+
+// bring a param set saved at the version up to PARAMS_VERSION
+pub fn migrate_params(version: u32, params: &mut BTreeMap<String, ParamValue>)
+{
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > version)
+    {
+        for (old, new, convert) in migration.converted
+        {
+            if let Some(value) = params.remove(*old)
+            {
+                params.insert(new.to_string(), convert(value));
+            }
+        }
+        for (name, value) in migration.added
+        {
+            params.entry(name.to_string()).or_insert(*value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn params(entries: &[(&str, ParamValue)]) -> BTreeMap<String, ParamValue>
+    {
+        entries.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+    }
+
+    #[test]
+    fn unversioned_params_get_every_default()
+    {
+        let mut migrated = params(&[("gravity", ParamValue::Float(-9.8))]);
+        migrate_params(UNVERSIONED_PARAMS, &mut migrated);
+
+        assert_eq!(migrated, params(&[
+            ("gravity", ParamValue::Float(-9.8)),
+            ("gravity_angle", ParamValue::Float(0.0)),
+            ("compensated_summation", ParamValue::Bool(false)),
+            ("render_mode", ParamValue::Integer(0)),
+            ("boundary_left", ParamValue::Integer(0)),
+            ("boundary_right", ParamValue::Integer(0)),
+            ("boundary_bottom", ParamValue::Integer(0)),
+            ("boundary_top", ParamValue::Integer(0)),
+            ("temperature_enabled", ParamValue::Bool(false)),
+            ("solver", ParamValue::Integer(0)),
+        ]));
+    }
+
+    #[test]
+    fn saved_values_are_kept()
+    {
+        let mut migrated = params(&[("gravity_angle", ParamValue::Float(90.0)), ("boundary_top", ParamValue::Integer(2))]);
+        migrate_params(UNVERSIONED_PARAMS, &mut migrated);
+
+        assert_eq!(migrated["gravity_angle"], ParamValue::Float(90.0));
+        assert_eq!(migrated["boundary_top"], ParamValue::Integer(2));
+    }
+
+    #[test]
+    fn version_2_surface_mode_becomes_a_render_mode()
+    {
+        for (surface_mode, render_mode) in [(true, 1), (false, 0)]
+        {
+            let mut migrated = params(&[("surface_mode", ParamValue::Bool(surface_mode))]);
+            migrate_params(2, &mut migrated);
+
+            assert!(!migrated.contains_key("surface_mode"));
+            assert_eq!(migrated["render_mode"], ParamValue::Integer(render_mode));
+            assert_eq!(migrated["temperature_enabled"], ParamValue::Bool(false));
+            assert_eq!(migrated["solver"], ParamValue::Integer(0));
+        }
+    }
+
+    #[test]
+    fn version_3_gets_the_temperature_layer_and_solver()
+    {
+        let mut migrated = params(&[("render_mode", ParamValue::Integer(2))]);
+        migrate_params(3, &mut migrated);
+
+        assert_eq!(migrated, params(&[
+            ("render_mode", ParamValue::Integer(2)),
+            ("temperature_enabled", ParamValue::Bool(false)),
+            ("solver", ParamValue::Integer(0)),
+        ]));
+    }
+
+    #[test]
+    fn version_4_gets_the_solver()
+    {
+        let mut migrated = params(&[("temperature_enabled", ParamValue::Bool(true))]);
+        migrate_params(4, &mut migrated);
+
+        assert_eq!(migrated, params(&[
+            ("temperature_enabled", ParamValue::Bool(true)),
+            ("solver", ParamValue::Integer(0)),
+        ]));
+    }
+
+    #[test]
+    fn current_params_are_left_alone()
+    {
+        let mut migrated = params(&[("solver", ParamValue::Integer(1))]);
+        migrate_params(PARAMS_VERSION, &mut migrated);
+
+        assert_eq!(migrated, params(&[("solver", ParamValue::Integer(1))]));
+    }
+
+    #[test]
+    fn newer_versions_are_refused()
+    {
+        assert!(check_params_version(PARAMS_VERSION).is_ok());
+        assert!(check_params_version(UNVERSIONED_PARAMS).is_ok());
+        assert!(check_params_version(PARAMS_VERSION + 1).is_err());
+    }
+}
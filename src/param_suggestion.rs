@@ -0,0 +1,185 @@
+use bevy_egui::egui;
+
+use crate::{ParticleConfig, MIN_SMOOTHING_RADIUS};
+use crate::parameter_gui::GUIConfig;
+use crate::units::{Density, Seconds, WorldLength};
+
+const SPACING_TO_RADIUS: f32 = 2.0;         // kernel support in rest spacings, ~12 neighbors in 2D
+const MACH_RATIO: f32 = 10.0;               // sound speed over the fastest flow, ~1% density variation
+const MIN_SOUND_SPEED: f32 = 100.0;         // pixels/s, keeps the fluid stiff without gravity
+const CFL_NUMBER: f32 = 0.4;
+const FORCE_STEP_FACTOR: f32 = 0.25;        // dt <= factor * sqrt(h / g)
+const SMOOTHING_RADIUS_RANGE: (f32, f32) = (MIN_SMOOTHING_RADIUS, 30.0);         // the Sim Params slider ranges
+const PRESSURE_MULTIPLIER_RANGE: (f32, f32) = (1.0, 100_000.0);
+const DELTA_TIME_RANGE: (f32, f32) = (0.0015, 0.015);
+
+// solver params consistent with the particle size, target density and gravity
+#[derive(Clone, Copy)]
+pub struct SuggestedParams
+{
+    pub smoothing_radius: WorldLength,
+    pub pressure_multiplier: f32,
+    pub delta_time: Seconds,
+    pub rest_spacing: WorldLength,
+    pub sound_speed: f32,
+    pub clamped: bool,      // the heuristics asked for something outside the slider ranges
+}
+
+impl SuggestedParams
+{
+    // Weakly compressible SPH rules of thumb. Particles have unit mass, so the rest spacing is
+    // 1 / sqrt(target density), but never less than the particle size or they'd overlap on
+    // screen. The pressure multiplier is the square of the sound speed, which is set to
+    // MACH_RATIO times the speed of fluid falling the height of its own column.
+    pub fn new(particle_size: f32, target_density: Density, gravity: f32, particle_count: u32, screen_bounds: [f32; 4]) -> Self
+    {
+        let rest_spacing = target_density.rest_spacing().0.max(particle_size);
+        let smoothing_radius = SPACING_TO_RADIUS * rest_spacing;
+
+        let [x_min, x_max, y_min, y_max] = screen_bounds;
+        let width = (x_max - x_min).max(1.0);
+        let column_height = (particle_count as f32 * rest_spacing * rest_spacing / width).min(y_max - y_min);
+        let max_flow_speed = (2.0 * gravity.abs() * column_height).sqrt();
+        let sound_speed = (MACH_RATIO * max_flow_speed).max(MIN_SOUND_SPEED);
+        let pressure_multiplier = sound_speed * sound_speed;
+
+        let mut delta_time = CFL_NUMBER * smoothing_radius / (sound_speed + max_flow_speed);
+        if gravity.abs() > 0.0
+        {
+            delta_time = delta_time.min(FORCE_STEP_FACTOR * (smoothing_radius / gravity.abs()).sqrt());
+        }
+
+        let clamp = |value: f32, (min, max): (f32, f32)| value.clamp(min, max);
+        let suggested = Self
+        {
+            smoothing_radius: WorldLength(clamp(smoothing_radius, SMOOTHING_RADIUS_RANGE)),
+            pressure_multiplier: clamp(pressure_multiplier, PRESSURE_MULTIPLIER_RANGE),
+            delta_time: Seconds(clamp(delta_time, DELTA_TIME_RANGE)),
+            rest_spacing: WorldLength(rest_spacing),
+            sound_speed,
+            clamped: false,
+        };
+        Self
+        {
+            clamped: suggested.smoothing_radius.0 != smoothing_radius
+                || suggested.pressure_multiplier != pressure_multiplier
+                || suggested.delta_time.0 > delta_time,   // a shorter step than asked for is fine
+            ..suggested
+        }
+    }
+
+    // the step goes to whichever delta time is in use
+    fn apply(&self, gui_config: &mut GUIConfig)
+    {
+        gui_config.smoothing_radius = self.smoothing_radius;
+        gui_config.pressure_multiplier = self.pressure_multiplier;
+        if gui_config.variable_delta_time {
+            gui_config.max_delta_time = self.delta_time;
+        } else {
+            gui_config.fixed_delta_time = self.delta_time;
+        }
+    }
+}
+
+// suggested values next to the current ones, returns true when they were applied
+pub fn param_suggestion_settings(ui: &mut egui::Ui, gui_config: &mut GUIConfig, sim_config: &ParticleConfig) -> bool
+{
+    let suggested = SuggestedParams::new(
+        sim_config.particle_size,
+        gui_config.target_density,
+        gui_config.gravity,
+        gui_config.particle_count,
+        sim_config.screen_bounds,
+    );
+    let delta_time = if gui_config.variable_delta_time { gui_config.max_delta_time } else { gui_config.fixed_delta_time };
+
+    ui.label(format!("From particle size {:.1}, target density {:.4} and gravity {:.0}",
+        sim_config.particle_size, gui_config.target_density.0, gui_config.gravity));
+    egui::Grid::new("param_suggestion_grid").num_columns(3).striped(true).show(ui, |ui| {
+        ui.strong("");
+        ui.strong("Current");
+        ui.strong("Suggested");
+        ui.end_row();
+        for (name, current, value) in [
+            ("Smoothing Radius", gui_config.smoothing_radius.0, suggested.smoothing_radius.0),
+            ("Pressure Multiplier", gui_config.pressure_multiplier, suggested.pressure_multiplier),
+            ("Delta Time", delta_time.0, suggested.delta_time.0),
+        ] {
+            ui.label(name);
+            ui.label(format!("{:.4}", current));
+            ui.label(format!("{:.4}", value));
+            ui.end_row();
+        }
+    });
+    ui.label(format!("Rest spacing {:.1}, sound speed {:.0}", suggested.rest_spacing.0, suggested.sound_speed));
+    if suggested.clamped
+    {
+        ui.colored_label(egui::Color32::YELLOW, "Clamped to the slider ranges: expect some compression");
+    }
+
+    if ui.button("Apply Suggested").clicked()
+    {
+        suggested.apply(gui_config);
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SCREEN_BOUNDS: [f32; 4] = [-500.0, 500.0, -300.0, 300.0];
+
+    #[test]
+    fn radius_follows_the_rest_spacing()
+    {
+        let suggested = SuggestedParams::new(2.0, Density(0.04), 10.0, 1000, SCREEN_BOUNDS);
+        assert!((suggested.rest_spacing.0 - 5.0).abs() < 1e-4);
+        assert!((suggested.smoothing_radius.0 - SPACING_TO_RADIUS * 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn particles_never_overlap()
+    {
+        let suggested = SuggestedParams::new(2.0, Density(1.0), 10.0, 1000, SCREEN_BOUNDS);
+        assert_eq!(suggested.rest_spacing, WorldLength(2.0));
+    }
+
+    #[test]
+    fn pressure_comes_from_the_column_height()
+    {
+        // 1000 particles 5 apart across a 1000 wide screen stand 25 high
+        let suggested = SuggestedParams::new(2.0, Density(0.04), 10.0, 1000, SCREEN_BOUNDS);
+        let sound_speed = MACH_RATIO * (2.0f32 * 10.0 * 25.0).sqrt();
+        assert!((suggested.sound_speed - sound_speed).abs() < 1e-2);
+        assert!((suggested.pressure_multiplier - sound_speed * sound_speed).abs() < 1.0);
+        assert!(!suggested.clamped);
+    }
+
+    #[test]
+    fn stays_stiff_without_gravity()
+    {
+        let suggested = SuggestedParams::new(2.0, Density(0.04), 0.0, 1000, SCREEN_BOUNDS);
+        assert_eq!(suggested.sound_speed, MIN_SOUND_SPEED);
+        assert_eq!(suggested.pressure_multiplier, MIN_SOUND_SPEED * MIN_SOUND_SPEED);
+    }
+
+    #[test]
+    fn shorter_steps_than_asked_are_not_clamping()
+    {
+        // no gravity allows a step longer than the slider goes
+        let suggested = SuggestedParams::new(2.0, Density(0.04), 0.0, 1000, SCREEN_BOUNDS);
+        assert_eq!(suggested.delta_time, Seconds(DELTA_TIME_RANGE.1));
+        assert!(!suggested.clamped);
+    }
+
+    #[test]
+    fn out_of_range_radius_is_clamped()
+    {
+        let suggested = SuggestedParams::new(2.0, Density(0.0001), 10.0, 1000, SCREEN_BOUNDS);
+        assert_eq!(suggested.smoothing_radius, WorldLength(SMOOTHING_RADIUS_RANGE.1));
+        assert!(suggested.clamped);
+    }
+}
@@ -1,69 +1,1416 @@
 use bevy::{prelude::*};
 use bevy_egui::{egui, EguiContexts};
-use crate::ParticleConfig;
+use crate::{ParticleConfig, MIN_SMOOTHING_RADIUS};
+use crate::sim_clock::SimClock;
+use crate::power_saving::PowerSaving;
+use crate::sim_rng::SimRng;
+use crate::gui_scale::{gui_scale_settings, GuiScale};
+use crate::hud::HudSettings;
+use crate::attract_mode::{attract_mode_settings, AttractMode};
+use crate::interaction::InteractionTool;
+use crate::brush::{brush_settings, BrushSettings};
+use crate::presets::ParamValue;
+use crate::param_migration::{check_params_version, migrate_params, PARAMS_VERSION, UNVERSIONED_PARAMS};
+use std::collections::BTreeMap;
+use crate::param_suggestion::param_suggestion_settings;
+use crate::radius_gauge::SmoothingRadiusGauge;
+use crate::units::{Density, Seconds, WorldLength};
+use crate::particle_render::{SupportedMsaa, TRAIL_HISTORY};
+use crate::retro_render::MAX_PALETTE_COLORS;
+
+const PIXELS_PER_METER: f32 = 40.0;     // world units (pixels) per simulated meter
+const CHANGED_PARAM_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 200, 80);   // params that differ from the defaults
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GravityPreset
+{
+    ZeroG,
+    Moon,
+    Earth,
+    Jupiter,
+}
+
+impl GravityPreset
+{
+    pub const ALL: [GravityPreset; 4] = [
+        GravityPreset::ZeroG,
+        GravityPreset::Moon,
+        GravityPreset::Earth,
+        GravityPreset::Jupiter,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            GravityPreset::ZeroG => "Zero-G",
+            GravityPreset::Moon => "Moon",
+            GravityPreset::Earth => "Earth",
+            GravityPreset::Jupiter => "Jupiter",
+        }
+    }
+
+    // surface gravity converted from m/s^2 to pixels/s^2
+    pub fn gravity(&self) -> f32
+    {
+        let meters_per_second_sq = match self {
+            GravityPreset::ZeroG => 0.0,
+            GravityPreset::Moon => 1.62,
+            GravityPreset::Earth => 9.81,
+            GravityPreset::Jupiter => 24.79,
+        };
+        meters_per_second_sq * PIXELS_PER_METER
+    }
+}
+
+const GRAVITY_RAMP_DURATION: f32 = 0.75;   // seconds for a gravity preset to take hold
+
+pub const MAX_HEATERS: usize = 4;     // must fit heater_spans in ParticleConfig
+
+// per-particle quantity mapped onto the colormap, values match the shader's COLOR_FIELD_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorField
+{
+    Speed,
+    Density,
+    Pressure,
+    Turbulence,     // RMS velocity fluctuation over the velocity history window
+    Temperature,
+}
+
+impl ColorField
+{
+    pub const ALL: [ColorField; 5] = [
+        ColorField::Speed,
+        ColorField::Density,
+        ColorField::Pressure,
+        ColorField::Turbulence,
+        ColorField::Temperature,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            ColorField::Speed => "Speed",
+            ColorField::Density => "Density",
+            ColorField::Pressure => "Pressure",
+            ColorField::Turbulence => "Turbulence",
+            ColorField::Temperature => "Temperature",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(ColorField::Speed)
+    }
+
+    // a starting min/max for the field, the fields differ by orders of magnitude
+    pub fn default_range(&self, target_density: Density) -> (f32, f32)
+    {
+        match self {
+            ColorField::Speed => (0.0, 100.0),
+            ColorField::Density => (0.0, 2.0 * target_density.0),
+            ColorField::Pressure => (-100.0, 100.0),
+            ColorField::Turbulence => (0.0, 30.0),
+            ColorField::Temperature => (0.0, 100.0),
+        }
+    }
+}
+
+// what an edge of the domain does to particles that reach it, values match the shader's
+// BOUNDARY_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum BoundaryMode
+{
+    Bounce,     // clamp and reflect with damping
+    Wrap,       // come back in through the opposite edge; kernels don't reach across the seam
+    Drain,      // removed from the sim until the particles are rescattered
+    Open,       // no boundary, particles keep going
+}
+
+impl BoundaryMode
+{
+    pub const ALL: [BoundaryMode; 4] = [
+        BoundaryMode::Bounce,
+        BoundaryMode::Wrap,
+        BoundaryMode::Drain,
+        BoundaryMode::Open,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            BoundaryMode::Bounce => "Bounce",
+            BoundaryMode::Wrap => "Wrap",
+            BoundaryMode::Drain => "Drain",
+            BoundaryMode::Open => "Open",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(BoundaryMode::Bounce)
+    }
+}
+
+// how the fluid is kept near its rest density, values match the shader's SOLVER_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum SolverKind
+{
+    Sph,    // state equation: pressure forces from the density error
+    Pbf,    // position based fluids: iterated density constraint projection, stable at larger time steps
+}
+
+impl SolverKind
+{
+    pub const ALL: [SolverKind; 2] = [
+        SolverKind::Sph,
+        SolverKind::Pbf,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            SolverKind::Sph => "SPH (State Equation)",
+            SolverKind::Pbf => "Position Based Fluids",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(SolverKind::Sph)
+    }
+}
+
+const BOUNDARY_EDGES: [&str; 4] = ["Left", "Right", "Bottom", "Top"];
+
+// how the fluid is drawn, values match RENDER_MODE_SURFACE and RENDER_MODE_HEATMAP
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderMode
+{
+    Particles,
+    Surface,        // thresholded, blurred particle thickness
+    Heatmap,        // the density field through the colormap
+}
+
+impl RenderMode
+{
+    pub const ALL: [RenderMode; 3] = [
+        RenderMode::Particles,
+        RenderMode::Surface,
+        RenderMode::Heatmap,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            RenderMode::Particles => "Particles",
+            RenderMode::Surface => "Surface",
+            RenderMode::Heatmap => "Density Heatmap",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(RenderMode::Particles)
+    }
+}
+
+// values match the shader's COLORMAP_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum Colormap
+{
+    Viridis,
+    Plasma,
+    BlueRed,
+}
+
+impl Colormap
+{
+    pub const ALL: [Colormap; 3] = [
+        Colormap::Viridis,
+        Colormap::Plasma,
+        Colormap::BlueRed,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Plasma => "Plasma",
+            Colormap::BlueRed => "Blue-Red",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(Colormap::Viridis)
+    }
+}
+
+// curve from linear scene color to the display, values match the shader's TONEMAPPING_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tonemapping
+{
+    None,           // clipped at white
+    Reinhard,
+    Aces,
+}
+
+impl Tonemapping
+{
+    pub const ALL: [Tonemapping; 3] = [
+        Tonemapping::None,
+        Tonemapping::Reinhard,
+        Tonemapping::Aces,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Tonemapping::None => "None",
+            Tonemapping::Reinhard => "Reinhard",
+            Tonemapping::Aces => "ACES Filmic",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(Tonemapping::None)
+    }
+}
+
+// values match the render shader's VELOCITY_FIELD_* constants
+#[derive(Clone, Copy, PartialEq)]
+pub enum VelocityFieldMode
+{
+    Off,
+    Arrows,
+    Streamlines,
+}
+
+impl VelocityFieldMode
+{
+    pub const ALL: [VelocityFieldMode; 3] = [
+        VelocityFieldMode::Off,
+        VelocityFieldMode::Arrows,
+        VelocityFieldMode::Streamlines,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            VelocityFieldMode::Off => "Off",
+            VelocityFieldMode::Arrows => "Arrows",
+            VelocityFieldMode::Streamlines => "Streamlines",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(VelocityFieldMode::Off)
+    }
+}
+
+// palettes for the retro mode, each at most MAX_PALETTE_COLORS
+#[derive(Clone, Copy, PartialEq)]
+pub enum RetroPalette
+{
+    GameBoy,
+    Pico8,
+    Cga,
+    Monochrome,
+}
+
+impl RetroPalette
+{
+    pub const ALL: [RetroPalette; 4] = [
+        RetroPalette::GameBoy,
+        RetroPalette::Pico8,
+        RetroPalette::Cga,
+        RetroPalette::Monochrome,
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            RetroPalette::GameBoy => "Game Boy",
+            RetroPalette::Pico8 => "PICO-8",
+            RetroPalette::Cga => "CGA",
+            RetroPalette::Monochrome => "Monochrome",
+        }
+    }
+
+    pub fn from_u32(value: u32) -> Self
+    {
+        Self::ALL.get(value as usize).copied().unwrap_or(RetroPalette::GameBoy)
+    }
+
+    // srgb
+    pub fn colors(&self) -> &'static [[u8; 3]]
+    {
+        match self {
+            RetroPalette::GameBoy => &[
+                [0x0f, 0x38, 0x0f], [0x30, 0x62, 0x30], [0x8b, 0xac, 0x0f], [0x9b, 0xbc, 0x0f],
+            ],
+            RetroPalette::Pico8 => &[
+                [0x00, 0x00, 0x00], [0x1d, 0x2b, 0x53], [0x7e, 0x25, 0x53], [0x00, 0x87, 0x51],
+                [0xab, 0x52, 0x36], [0x5f, 0x57, 0x4f], [0xc2, 0xc3, 0xc7], [0xff, 0xf1, 0xe8],
+                [0xff, 0x00, 0x4d], [0xff, 0xa3, 0x00], [0xff, 0xec, 0x27], [0x00, 0xe4, 0x36],
+                [0x29, 0xad, 0xff], [0x83, 0x76, 0x9c], [0xff, 0x77, 0xa8], [0xff, 0xcc, 0xaa],
+            ],
+            RetroPalette::Cga => &[
+                [0x00, 0x00, 0x00], [0x55, 0xff, 0xff], [0xff, 0x55, 0xff], [0xff, 0xff, 0xff],
+            ],
+            RetroPalette::Monochrome => &[
+                [0x00, 0x00, 0x00], [0xff, 0xff, 0xff],
+            ],
+        }
+    }
+
+    // as ParticleConfig's palette, the unused entries left black
+    pub fn gpu_colors(&self) -> [[f32; 4]; MAX_PALETTE_COLORS]
+    {
+        let mut palette = [[0.0; 4]; MAX_PALETTE_COLORS];
+        for (entry, [r, g, b]) in palette.iter_mut().zip(self.colors()) {
+            *entry = [*r as f32 / 255.0, *g as f32 / 255.0, *b as f32 / 255.0, 1.0];
+        }
+        palette
+    }
+}
 
 #[repr(C)]
 #[derive(Resource, Clone, Copy)]
 pub struct GUIConfig
 {
-    pub fixed_delta_time: f32,          // 4 bytes
-    pub gravity: f32,                   // 4 bytes
+    pub particle_count: u32,
+    pub fixed_delta_time: Seconds,      // 4 bytes
+    pub gravity: f32,                   // 4 bytes     strength
+    pub gravity_angle: f32,             // 4 bytes     tilt from straight down in degrees, positive pulls right
     pub damping_factor: f32,            // 4 bytes
 
-    pub smoothing_radius: f32,          // 4 bytes
+    pub smoothing_radius: WorldLength,  // 4 bytes
     pub max_energy: f32,                // 4 bytes
-    pub target_density: f32,            // 4 bytes
+    pub target_density: Density,        // 4 bytes
     pub pressure_multiplier: f32,       // 4 bytes
          
     pub viscocity_strength: f32,        // 4 bytes
     pub near_density_multiplier: f32,   // 4 bytes
     
+    pub smoke_enabled: bool,
+    pub smoke_injection: f32,
+    pub smoke_dissipation: f32,
+    pub smoke_opacity: f32,
+
+    pub flip_enabled: bool,
+    pub flip_ratio: f32,
+    pub pressure_iterations: u32,
+
+    pub divergence_view: bool,
+    pub divergence_range: f32,
+
+    pub color_field: u32,               // ColorField as u32
+    pub colormap: u32,                  // Colormap as u32
+    pub color_min: f32,
+    pub color_max: f32,
+
+    pub render_mode: u32,               // RenderMode as u32
+    pub surface_threshold: f32,
+    pub surface_blur_radius: f32,
+    pub heatmap_range: f32,             // density at the top of the heatmap, in target densities
+    pub trail_length: u32,              // frames of positions per trail, 0 for none
+    pub trail_opacity: f32,
+    pub trail_width: f32,
+    pub tonemapping: u32,               // Tonemapping as u32
+    pub exposure: f32,
+    pub density_alpha_enabled: bool,
+    pub density_alpha_min: f32,
+    pub density_alpha_range: f32,       // density at full alpha, in target densities
+    pub density_alpha_gamma: f32,
+    pub velocity_field_mode: u32,       // VelocityFieldMode as u32
+    pub velocity_field_spacing: f32,
+    pub velocity_field_full_speed: f32,
+    pub outline_enabled: bool,
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],        // linear rgb, alpha
+    pub retro_enabled: bool,
+    pub palette: u32,                   // RetroPalette as u32
+    pub palette_dither: f32,
+    pub retro_pixel_size: f32,
+
+    pub compensated_summation: bool,    // Kahan summation in the density and force loops, slower
+
+    pub temperature_enabled: bool,
+    pub ambient_temperature: f32,
+    pub heat_diffusion: f32,
+    pub cooling_rate: f32,
+    pub thermal_expansion: f32,         // buoyancy per degree above ambient, as a fraction of gravity
+    pub heater_temperature: f32,
+    pub heater_height: f32,
+    pub heater_count: u32,
+    pub heater_spans: [[f32; 2]; MAX_HEATERS],  // start and end along the floor as fractions of its width
+
+    pub solver: u32,                    // SolverKind as u32
+    pub pbf_iterations: u32,
+    pub pbf_relaxation: f32,
+
+    pub adaptive_substeps: bool,        // split frames by the CFL condition, see substeps.rs
+    pub cfl_number: f32,
+    pub max_substeps: u32,
+
+    pub velocity_history_frames: f32,   // window of the per-particle velocity variance
+
+    pub boundary_modes: [u32; 4],       // BoundaryMode as u32 for the left, right, bottom and top edges
+
+    pub interaction_strength: f32,
+    pub interaction_radius: WorldLength,
+    pub fan_strength: f32,
+    pub fan_angle: f32,                 // half angle of the cone in degrees
+
+    pub variable_delta_time: bool,
+    pub max_delta_time: Seconds,
+
+    pub oscillate_gravity: bool,
+    pub gravity_oscillation_period: Seconds,
+    
     pub applied_changes: bool,          
 }
 
+// baseline params (the GUIConfig as launched) that the reset buttons and change view compare against
+#[derive(Resource, Clone, Copy)]
+pub struct GUIDefaults(pub GUIConfig);
+
+pub fn store_gui_defaults(
+    mut commands: Commands,
+    gui_config: Res<GUIConfig>,
+)
+{
+    commands.insert_resource(GUIDefaults(*gui_config));
+}
+
+impl GUIConfig
+{
+    // gravity in pixels/s^2, the strength pointed down and tilted by the angle
+    pub fn gravity_vector(&self) -> Vec2
+    {
+        let angle = self.gravity_angle.to_radians();
+        Vec2::new(angle.sin(), -angle.cos()) * self.gravity
+    }
+
+    // named float params, shared by the text export and import
+    fn float_params_mut(&mut self) -> [(&'static str, &mut f32); 58]
+    {
+        let [[heater_1_start, heater_1_end], [heater_2_start, heater_2_end], [heater_3_start, heater_3_end], [heater_4_start, heater_4_end]] = &mut self.heater_spans;
+        let [outline_red, outline_green, outline_blue, outline_alpha] = &mut self.outline_color;
+        [
+            ("fixed_delta_time", &mut self.fixed_delta_time.0),
+            ("max_delta_time", &mut self.max_delta_time.0),
+            ("gravity", &mut self.gravity),
+            ("gravity_angle", &mut self.gravity_angle),
+            ("gravity_oscillation_period", &mut self.gravity_oscillation_period.0),
+            ("damping_factor", &mut self.damping_factor),
+            ("smoothing_radius", &mut self.smoothing_radius.0),
+            ("max_energy", &mut self.max_energy),
+            ("target_density", &mut self.target_density.0),
+            ("pressure_multiplier", &mut self.pressure_multiplier),
+            ("viscocity_strength", &mut self.viscocity_strength),
+            ("near_density_multiplier", &mut self.near_density_multiplier),
+            ("interaction_strength", &mut self.interaction_strength),
+            ("interaction_radius", &mut self.interaction_radius.0),
+            ("fan_strength", &mut self.fan_strength),
+            ("fan_angle", &mut self.fan_angle),
+            ("smoke_injection", &mut self.smoke_injection),
+            ("smoke_dissipation", &mut self.smoke_dissipation),
+            ("smoke_opacity", &mut self.smoke_opacity),
+            ("flip_ratio", &mut self.flip_ratio),
+            ("divergence_range", &mut self.divergence_range),
+            ("color_min", &mut self.color_min),
+            ("color_max", &mut self.color_max),
+            ("surface_threshold", &mut self.surface_threshold),
+            ("surface_blur_radius", &mut self.surface_blur_radius),
+            ("heatmap_range", &mut self.heatmap_range),
+            ("velocity_history_frames", &mut self.velocity_history_frames),
+            ("ambient_temperature", &mut self.ambient_temperature),
+            ("heat_diffusion", &mut self.heat_diffusion),
+            ("cooling_rate", &mut self.cooling_rate),
+            ("thermal_expansion", &mut self.thermal_expansion),
+            ("heater_temperature", &mut self.heater_temperature),
+            ("heater_height", &mut self.heater_height),
+            ("heater_1_start", heater_1_start),
+            ("heater_1_end", heater_1_end),
+            ("heater_2_start", heater_2_start),
+            ("heater_2_end", heater_2_end),
+            ("heater_3_start", heater_3_start),
+            ("heater_3_end", heater_3_end),
+            ("heater_4_start", heater_4_start),
+            ("heater_4_end", heater_4_end),
+            ("pbf_relaxation", &mut self.pbf_relaxation),
+            ("cfl_number", &mut self.cfl_number),
+            ("trail_opacity", &mut self.trail_opacity),
+            ("trail_width", &mut self.trail_width),
+            ("exposure", &mut self.exposure),
+            ("density_alpha_min", &mut self.density_alpha_min),
+            ("density_alpha_range", &mut self.density_alpha_range),
+            ("density_alpha_gamma", &mut self.density_alpha_gamma),
+            ("velocity_field_spacing", &mut self.velocity_field_spacing),
+            ("velocity_field_full_speed", &mut self.velocity_field_full_speed),
+            ("outline_width", &mut self.outline_width),
+            ("outline_red", outline_red),
+            ("outline_green", outline_green),
+            ("outline_blue", outline_blue),
+            ("outline_alpha", outline_alpha),
+            ("palette_dither", &mut self.palette_dither),
+            ("retro_pixel_size", &mut self.retro_pixel_size),
+        ]
+    }
+
+    fn bool_params_mut(&mut self) -> [(&'static str, &mut bool); 11]
+    {
+        [
+            ("variable_delta_time", &mut self.variable_delta_time),
+            ("oscillate_gravity", &mut self.oscillate_gravity),
+            ("smoke_enabled", &mut self.smoke_enabled),
+            ("flip_enabled", &mut self.flip_enabled),
+            ("divergence_view", &mut self.divergence_view),
+            ("compensated_summation", &mut self.compensated_summation),
+            ("temperature_enabled", &mut self.temperature_enabled),
+            ("adaptive_substeps", &mut self.adaptive_substeps),
+            ("density_alpha_enabled", &mut self.density_alpha_enabled),
+            ("outline_enabled", &mut self.outline_enabled),
+            ("retro_enabled", &mut self.retro_enabled),
+        ]
+    }
+
+    fn u32_params_mut(&mut self) -> [(&'static str, &mut u32); 17]
+    {
+        let [left, right, bottom, top] = &mut self.boundary_modes;
+        [
+            ("particle_count", &mut self.particle_count),
+            ("pressure_iterations", &mut self.pressure_iterations),
+            ("color_field", &mut self.color_field),
+            ("colormap", &mut self.colormap),
+            ("render_mode", &mut self.render_mode),
+            ("boundary_left", left),
+            ("boundary_right", right),
+            ("boundary_bottom", bottom),
+            ("boundary_top", top),
+            ("heater_count", &mut self.heater_count),
+            ("solver", &mut self.solver),
+            ("pbf_iterations", &mut self.pbf_iterations),
+            ("max_substeps", &mut self.max_substeps),
+            ("trail_length", &mut self.trail_length),
+            ("tonemapping", &mut self.tonemapping),
+            ("velocity_field_mode", &mut self.velocity_field_mode),
+            ("palette", &mut self.palette),
+        ]
+    }
+
+    fn float_param_mut(&mut self, name: &str) -> Option<&mut f32>
+    {
+        self.float_params_mut().into_iter().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    fn bool_param_mut(&mut self, name: &str) -> Option<&mut bool>
+    {
+        self.bool_params_mut().into_iter().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    fn u32_param_mut(&mut self, name: &str) -> Option<&mut u32>
+    {
+        self.u32_params_mut().into_iter().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    // every named parameter with its value, in the same order as the text export
+    pub fn param_values(&self) -> Vec<(&'static str, ParamValue)>
+    {
+        let mut params = *self;
+        let mut values = Vec::new();
+        values.extend(params.float_params_mut().map(|(name, value)| (name, ParamValue::Float(*value))));
+        values.extend(params.bool_params_mut().map(|(name, value)| (name, ParamValue::Bool(*value))));
+        values.extend(params.u32_params_mut().map(|(name, value)| (name, ParamValue::Integer(*value))));
+        values
+    }
+
+    // set a single named parameter, returning false for unknown names or mismatched types;
+    // whole numbers are accepted for float params since RON writes `2.0` and `2` alike
+    pub fn set_param(&mut self, name: &str, value: ParamValue) -> bool
+    {
+        match value {
+            ParamValue::Float(value) => self.float_param_mut(name).map(|field| *field = value).is_some(),
+            ParamValue::Bool(value) => self.bool_param_mut(name).map(|field| *field = value).is_some(),
+            ParamValue::Integer(value) => {
+                if let Some(field) = self.u32_param_mut(name) { *field = value; return true; }
+                self.float_param_mut(name).map(|field| *field = value as f32).is_some()
+            }
+        }
+    }
+
+    // names of the parameters that differ between two configs
+    pub fn diff(&self, other: &GUIConfig) -> Vec<&'static str>
+    {
+        let (mut a, mut b) = (*self, *other);
+        let mut names = Vec::new();
+        for ((name, x), (_, y)) in a.float_params_mut().into_iter().zip(b.float_params_mut())
+        {
+            if *x != *y { names.push(name); }
+        }
+        for ((name, x), (_, y)) in a.bool_params_mut().into_iter().zip(b.bool_params_mut())
+        {
+            if *x != *y { names.push(name); }
+        }
+        for ((name, x), (_, y)) in a.u32_params_mut().into_iter().zip(b.u32_params_mut())
+        {
+            if *x != *y { names.push(name); }
+        }
+        names
+    }
+
+    // display value of a named parameter
+    pub fn param_text(&self, name: &str) -> Option<String>
+    {
+        let mut params = *self;
+        if let Some(value) = params.float_param_mut(name) { return Some(format!("{value:?}")); }
+        if let Some(value) = params.bool_param_mut(name) { return Some(value.to_string()); }
+        params.u32_param_mut(name).map(|value| value.to_string())
+    }
+
+    // copy a single named parameter from another config, returning false for unknown names
+    pub fn copy_param_from(&mut self, name: &str, source: &GUIConfig) -> bool
+    {
+        let mut source = *source;
+        if let (Some(value), Some(source_value)) = (self.float_param_mut(name), source.float_param_mut(name))
+        {
+            *value = *source_value;
+            return true;
+        }
+        if let (Some(value), Some(source_value)) = (self.bool_param_mut(name), source.bool_param_mut(name))
+        {
+            *value = *source_value;
+            return true;
+        }
+        if let (Some(value), Some(source_value)) = (self.u32_param_mut(name), source.u32_param_mut(name))
+        {
+            *value = *source_value;
+            return true;
+        }
+        false
+    }
+
+    // a version line, then one `name = value` line per parameter; floats use the shortest
+    // exact representation
+    pub fn to_text(&self) -> String
+    {
+        let mut params = *self;
+        let mut text = format!("version = {PARAMS_VERSION}\n");
+        for (name, value) in params.float_params_mut()
+        {
+            text.push_str(&format!("{name} = {value:?}\n"));
+        }
+        for (name, value) in params.bool_params_mut()
+        {
+            text.push_str(&format!("{name} = {value}\n"));
+        }
+        for (name, value) in params.u32_params_mut()
+        {
+            text.push_str(&format!("{name} = {value}\n"));
+        }
+        text
+    }
+
+    // parse text produced by to_text; parameters left out keep their current value and
+    // nothing is changed unless every line parses. Text from older versions (or without a
+    // version line) is migrated like presets, so params it couldn't have written get the value
+    // that behaves like that version did.
+    pub fn apply_text(&mut self, text: &str) -> std::result::Result<(), String>
+    {
+        let mut version = UNVERSIONED_PARAMS;
+        let mut params = BTreeMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty())
+        {
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(format!("expected `name = value`, got `{line}`"));
+            };
+            let (name, value) = (name.trim(), value.trim());
+            if name == "version"
+            {
+                version = value.parse().map_err(|_| format!("invalid version: `{value}`"))?;
+                check_params_version(version)?;
+                continue;
+            }
+            let value = ParamValue::parse(value).ok_or_else(|| format!("invalid value for {name}: `{value}`"))?;
+            params.insert(name.to_string(), value);
+        }
+        migrate_params(version, &mut params);
+
+        let mut updated = *self;
+        for (name, value) in params
+        {
+            if updated.set_param(&name, value) { continue; }
+
+            if updated.float_param_mut(&name).is_some() {
+                return Err(format!("expected a number for {name}"));
+            }
+            if updated.bool_param_mut(&name).is_some() {
+                return Err(format!("expected true or false for {name}"));
+            }
+            if updated.u32_param_mut(&name).is_some() {
+                return Err(format!("expected a whole number for {name}"));
+            }
+            return Err(format!("unknown parameter `{name}`"));
+        }
+
+        *self = updated;
+        self.applied_changes = true;
+        Ok(())
+    }
+}
+
+fn msaa_name(msaa: Msaa) -> &'static str
+{
+    match msaa
+    {
+        Msaa::Off => "Off",
+        Msaa::Sample2 => "2x MSAA",
+        Msaa::Sample4 => "4x MSAA",
+        Msaa::Sample8 => "8x MSAA",
+    }
+}
+
+// slider paired with a numeric field for exact entry / fine dragging, and a reset-to-default button;
+// the row is highlighted while the value differs from the default
+fn parameter_slider(
+    ui: &mut egui::Ui,
+    value: &mut f32,
+    default: f32,
+    slider: impl for<'a> FnOnce(&'a mut f32) -> egui::Slider<'a>,
+) -> bool
+{
+    parameter_slider_row(ui, value, default, slider).0
+}
+
+// parameter_slider that also reports whether the slider or field is hovered or being dragged
+fn parameter_slider_row(
+    ui: &mut egui::Ui,
+    value: &mut f32,
+    default: f32,
+    slider: impl for<'a> FnOnce(&'a mut f32) -> egui::Slider<'a>,
+) -> (bool, bool)
+{
+    ui.horizontal(|ui| {
+        if *value != default
+        {
+            ui.visuals_mut().override_text_color = Some(CHANGED_PARAM_COLOR);
+        }
+        let slider_response = ui.add(slider(value).show_value(false));
+        let drag_response = ui.add(egui::DragValue::new(value)
+            .speed((default.abs() * 0.001).max(0.001))
+            .max_decimals(6));
+        let mut changed = slider_response.changed() || drag_response.changed();
+        let active = [&slider_response, &drag_response].iter()
+            .any(|response| response.hovered() || response.dragged());
+        if ui.add_enabled(*value != default, egui::Button::new("Reset").small()).clicked()
+        {
+            *value = default;
+            changed = true;
+        }
+        (changed, active)
+    }).inner
+}
+
 // create the gui system with sliders for useful sim params
 pub fn gui_system(
     mut contexts: EguiContexts,
     mut gui_config: ResMut<GUIConfig>,
+    defaults: Res<GUIDefaults>,
+    mut sim_clock: ResMut<SimClock>,
+    mut gui_scale: ResMut<GuiScale>,
+    hud_settings: Res<HudSettings>,
+    mut attract: ResMut<AttractMode>,
+    mut interaction_tool: ResMut<InteractionTool>,
+    mut brush: ResMut<BrushSettings>,
+    mut radius_gauge: ResMut<SmoothingRadiusGauge>,
+    sim_config: Res<ParticleConfig>,
+    supported_msaa: Res<SupportedMsaa>,
+    mut camera_msaa: Query<&mut Msaa, With<Camera2d>>,
+    mut param_text: Local<String>,
+    mut param_text_error: Local<Option<String>>,
+    mut ramp_events: EventWriter<ParamRamp>,
 ) -> Result
 {
     let ctx = contexts.ctx_mut()?;
+    let defaults = defaults.0;
     gui_config.applied_changes = false;
+    radius_gauge.active = false;
+    if !hud_settings.params_visible { return Ok(()); }
+
     egui::Window::new("Sim Params")
         .collapsible(true)
         .resizable(true)
         .default_pos([ctx.screen_rect().width() - 310.0, 10.0])  // Upper right corner
         .show(ctx, |ui: &mut egui::Ui| {
             let mut changed = false;
-            changed |= ui.add(egui::Slider::new(&mut gui_config.fixed_delta_time, 0.0015..=0.015)
-                .text("Fixed Delta Time")
-                .step_by(0.001)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.gravity, 0.0..=1000.0)
-                .text("Gravity")
-                .step_by(1.0)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.damping_factor, 0.0..=1.0)
-                .text("Damping Factor")
-                .step_by(0.1)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.smoothing_radius, 0.0..=30.0)
-                .text("Smoothing Radius")
-                .step_by(1.0)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.max_energy, 1000.0..=10000.0)
-                .text("Max Energy")).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.target_density, 0.0..=0.1)
-                .text("Target Density")
-                .step_by(0.001)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.pressure_multiplier, 1.0..=100000.0)
-                .text("Pressure Multiplier")
-                .logarithmic(true)
-                .smallest_positive(1.0)
-                .largest_finite(100_000.0)).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.viscocity_strength, 0.0..=10.0)
-                .text("Viscocity Strength")).changed();
-            changed |= ui.add(egui::Slider::new(&mut gui_config.near_density_multiplier, 1.0..=10000.0)
-                .text("Near Density Multiplier")
-                .logarithmic(true)
-                .smallest_positive(1.0)
-                .largest_finite(10_000.0)).changed();
+
+            ui.horizontal(|ui| {
+                if ui.button(if sim_clock.paused { "Resume" } else { "Pause" }).clicked() {
+                    sim_clock.paused = !sim_clock.paused;
+                }
+                if ui.add_enabled(sim_clock.paused, egui::Button::new("Step")).clicked() {
+                    sim_clock.step_requested = true;
+                }
+                ui.label("(Space)");
+            });
+            ui.checkbox(&mut sim_clock.suspend_in_background, "Pause in Background");
+            changed |= ui.add(egui::Slider::new(&mut sim_clock.time_scale, 0.05..=1.0)
+                .text("Time Scale")
+                .logarithmic(true)).changed();
+
+            // reallocating the particle buffers is expensive, so only commit the count once the slider is released
+            ui.horizontal(|ui| {
+                let response = ui.add(egui::Slider::new(&mut gui_config.particle_count, 1000..=500_000)
+                    .text("Particle Count")
+                    .logarithmic(true));
+                if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                    changed = true;
+                }
+                if ui.add_enabled(gui_config.particle_count != defaults.particle_count, egui::Button::new("Reset").small()).clicked() {
+                    gui_config.particle_count = defaults.particle_count;
+                    changed = true;
+                }
+            });
+            changed |= ui.checkbox(&mut gui_config.variable_delta_time, "Follow Frame Time").changed();
+            if gui_config.variable_delta_time {
+                parameter_slider(ui, &mut gui_config.max_delta_time.0, defaults.max_delta_time.0, |value| {
+                    egui::Slider::new(value, 0.002..=0.033)
+                        .text("Max Delta Time")
+                        .step_by(0.001)
+                });
+            } else {
+                changed |= parameter_slider(ui, &mut gui_config.fixed_delta_time.0, defaults.fixed_delta_time.0, |value| {
+                    egui::Slider::new(value, 0.0015..=0.015)
+                        .text("Fixed Delta Time")
+                        .step_by(0.001)
+                });
+            }
+            changed |= parameter_slider(ui, &mut gui_config.gravity, defaults.gravity, |value| {
+                egui::Slider::new(value, 0.0..=1000.0)
+                    .text("Gravity")
+                    .step_by(1.0)
+            });
+            ui.horizontal(|ui| {
+                for preset in GravityPreset::ALL {
+                    if ui.button(preset.name()).clicked() {
+                        ramp_events.write(ParamRamp { name: "gravity", target: preset.gravity(), duration: Seconds(GRAVITY_RAMP_DURATION) });
+                    }
+                }
+            });
+            changed |= parameter_slider(ui, &mut gui_config.gravity_angle, defaults.gravity_angle, |value| {
+                egui::Slider::new(value, -180.0..=180.0)
+                    .text("Gravity Tilt (deg, Left/Right, Down resets)")
+                    .step_by(1.0)
+            });
+            changed |= ui.checkbox(&mut gui_config.oscillate_gravity, "Oscillate Gravity").changed();
+            if gui_config.oscillate_gravity {
+                changed |= parameter_slider(ui, &mut gui_config.gravity_oscillation_period.0, defaults.gravity_oscillation_period.0, |value| {
+                    egui::Slider::new(value, 0.5..=20.0)
+                        .text("Oscillation Period (s)")
+                        .step_by(0.5)
+                });
+            }
+            changed |= parameter_slider(ui, &mut gui_config.damping_factor, defaults.damping_factor, |value| {
+                egui::Slider::new(value, 0.0..=1.0)
+                    .text("Damping Factor")
+                    .step_by(0.1)
+            });
+            let (radius_changed, radius_active) = parameter_slider_row(ui, &mut gui_config.smoothing_radius.0, defaults.smoothing_radius.0, |value| {
+                egui::Slider::new(value, MIN_SMOOTHING_RADIUS..=30.0)
+                    .text("Smoothing Radius")
+                    .step_by(1.0)
+            });
+            changed |= radius_changed;
+            radius_gauge.active = radius_active;
+            changed |= parameter_slider(ui, &mut gui_config.max_energy, defaults.max_energy, |value| {
+                egui::Slider::new(value, 1000.0..=10000.0)
+                    .text("Max Energy")
+            });
+            changed |= parameter_slider(ui, &mut gui_config.target_density.0, defaults.target_density.0, |value| {
+                egui::Slider::new(value, 0.0..=0.1)
+                    .text("Target Density")
+                    .step_by(0.001)
+            });
+            changed |= parameter_slider(ui, &mut gui_config.pressure_multiplier, defaults.pressure_multiplier, |value| {
+                egui::Slider::new(value, 1.0..=100000.0)
+                    .text("Pressure Multiplier")
+                    .logarithmic(true)
+                    .smallest_positive(1.0)
+                    .largest_finite(100_000.0)
+            });
+            changed |= parameter_slider(ui, &mut gui_config.viscocity_strength, defaults.viscocity_strength, |value| {
+                egui::Slider::new(value, 0.0..=10.0)
+                    .text("Viscocity Strength")
+            });
+            changed |= parameter_slider(ui, &mut gui_config.near_density_multiplier, defaults.near_density_multiplier, |value| {
+                egui::Slider::new(value, 1.0..=10000.0)
+                    .text("Near Density Multiplier")
+                    .logarithmic(true)
+                    .smallest_positive(1.0)
+                    .largest_finite(10_000.0)
+            });
+
+            ui.collapsing("Suggest Parameters", |ui| {
+                changed |= param_suggestion_settings(ui, &mut gui_config, &sim_config);
+            });
+
+            ui.collapsing("Mouse Interaction", |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut *interaction_tool, InteractionTool::Force, "Attract/Repel");
+                    ui.radio_value(&mut *interaction_tool, InteractionTool::Fan, "Fan (F)");
+                    ui.radio_value(&mut *interaction_tool, InteractionTool::Brush, "Brush");
+                    ui.radio_value(&mut *interaction_tool, InteractionTool::Drag, "Drag Obstacles");
+                });
+                match *interaction_tool {
+                    InteractionTool::Force => {
+                        ui.label("Left click attracts, right click repels");
+                        parameter_slider(ui, &mut gui_config.interaction_strength, defaults.interaction_strength, |value| {
+                            egui::Slider::new(value, 0.0..=20000.0)
+                                .text("Strength")
+                        });
+                        parameter_slider(ui, &mut gui_config.interaction_radius.0, defaults.interaction_radius.0, |value| {
+                            egui::Slider::new(value, 10.0..=400.0)
+                                .text("Radius")
+                        });
+                    }
+                    InteractionTool::Fan => {
+                        ui.label("Left drag aims the fan, the drag length sets its reach");
+                        parameter_slider(ui, &mut gui_config.fan_strength, defaults.fan_strength, |value| {
+                            egui::Slider::new(value, 0.0..=20000.0)
+                                .text("Strength")
+                        });
+                        parameter_slider(ui, &mut gui_config.fan_angle, defaults.fan_angle, |value| {
+                            egui::Slider::new(value, 1.0..=90.0)
+                                .text("Spread (deg)")
+                        });
+                    }
+                    InteractionTool::Brush => brush_settings(ui, &mut brush),
+                    InteractionTool::Drag => {
+                        ui.label("Left drag moves an obstacle, a thrown rigid body keeps the speed");
+                    }
+                }
+            });
+
+            ui.collapsing("Solver", |ui| {
+                let mut solver = SolverKind::from_u32(gui_config.solver);
+                egui::ComboBox::from_label("Solver")
+                    .selected_text(solver.name())
+                    .show_ui(ui, |ui| {
+                        for kind in SolverKind::ALL {
+                            ui.selectable_value(&mut solver, kind, kind.name());
+                        }
+                    });
+                if solver as u32 != gui_config.solver {
+                    gui_config.solver = solver as u32;
+                    changed = true;
+                }
+                ui.add_enabled_ui(solver == SolverKind::Pbf, |ui| {
+                    changed |= ui.add(egui::Slider::new(&mut gui_config.pbf_iterations, 1..=20)
+                        .text("Constraint Iterations")).changed();
+                    changed |= parameter_slider(ui, &mut gui_config.pbf_relaxation, defaults.pbf_relaxation, |value| {
+                        egui::Slider::new(value, 0.0001..=1.0)
+                            .text("Relaxation")
+                            .logarithmic(true)
+                    });
+                });
+                ui.label("Position based fluids ignore the pressure and near density multipliers and hold up at larger time steps");
+
+                ui.separator();
+                changed |= ui.checkbox(&mut gui_config.adaptive_substeps, "Adaptive Substeps").changed();
+                ui.add_enabled_ui(gui_config.adaptive_substeps, |ui| {
+                    changed |= parameter_slider(ui, &mut gui_config.cfl_number, defaults.cfl_number, |value| {
+                        egui::Slider::new(value, 0.05..=1.0)
+                            .text("CFL Number")
+                    });
+                    changed |= ui.add(egui::Slider::new(&mut gui_config.max_substeps, 2..=32)
+                        .text("Max Substeps")).changed();
+                    ui.label(format!("Substeps this frame: {}", sim_config.substeps));
+                });
+            });
+
+            ui.collapsing("Smoke", |ui| {
+                changed |= ui.checkbox(&mut gui_config.smoke_enabled, "Enable Smoke").changed();
+                changed |= parameter_slider(ui, &mut gui_config.smoke_injection, defaults.smoke_injection, |value| {
+                    egui::Slider::new(value, 0.0..=20.0)
+                        .text("Injection")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.smoke_dissipation, defaults.smoke_dissipation, |value| {
+                    egui::Slider::new(value, 0.9..=1.0)
+                        .text("Dissipation")
+                        .step_by(0.001)
+                });
+                changed |= parameter_slider(ui, &mut gui_config.smoke_opacity, defaults.smoke_opacity, |value| {
+                    egui::Slider::new(value, 0.0..=1.0)
+                        .text("Opacity")
+                });
+            });
+
+            ui.collapsing("Temperature", |ui| {
+                changed |= ui.checkbox(&mut gui_config.temperature_enabled, "Enable Temperature").changed();
+                ui.label("Warm fluid rises against gravity, so convection needs gravity on");
+                changed |= parameter_slider(ui, &mut gui_config.ambient_temperature, defaults.ambient_temperature, |value| {
+                    egui::Slider::new(value, 0.0..=100.0)
+                        .text("Ambient Temperature")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.heat_diffusion, defaults.heat_diffusion, |value| {
+                    egui::Slider::new(value, 0.0..=20.0)
+                        .text("Diffusion Rate (1/s)")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.cooling_rate, defaults.cooling_rate, |value| {
+                    egui::Slider::new(value, 0.0..=1.0)
+                        .text("Cooling Rate (1/s)")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.thermal_expansion, defaults.thermal_expansion, |value| {
+                    egui::Slider::new(value, 0.0..=0.05)
+                        .text("Buoyancy (x gravity per degree)")
+                });
+
+                ui.separator();
+                ui.label("Heated floor regions");
+                changed |= parameter_slider(ui, &mut gui_config.heater_temperature, defaults.heater_temperature, |value| {
+                    egui::Slider::new(value, 0.0..=200.0)
+                        .text("Heater Temperature")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.heater_height, defaults.heater_height, |value| {
+                    egui::Slider::new(value, 1.0..=100.0)
+                        .text("Heater Height")
+                });
+                let mut removed = None;
+                for heater in 0..(gui_config.heater_count as usize).min(MAX_HEATERS) {
+                    let [start, end] = &mut gui_config.heater_spans[heater];
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Heater {}", heater + 1));
+                        changed |= ui.add(egui::DragValue::new(start).range(0.0..=*end).speed(0.005).prefix("from ")).changed();
+                        changed |= ui.add(egui::DragValue::new(end).range(*start..=1.0).speed(0.005).prefix("to ")).changed();
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(heater);
+                        }
+                    });
+                }
+                if let Some(heater) = removed {
+                    gui_config.heater_spans[heater..].rotate_left(1);
+                    gui_config.heater_count -= 1;
+                    changed = true;
+                }
+                if ui.add_enabled((gui_config.heater_count as usize) < MAX_HEATERS, egui::Button::new("Add Heater")).clicked() {
+                    gui_config.heater_spans[gui_config.heater_count as usize] = [0.4, 0.6];
+                    gui_config.heater_count += 1;
+                    changed = true;
+                }
+            });
+
+            ui.collapsing("FLIP/PIC (Experimental)", |ui| {
+                changed |= ui.checkbox(&mut gui_config.flip_enabled, "Enable Grid Projection").changed();
+                changed |= parameter_slider(ui, &mut gui_config.flip_ratio, defaults.flip_ratio, |value| {
+                    egui::Slider::new(value, 0.0..=1.0)
+                        .text("FLIP Ratio (0 = PIC)")
+                });
+                changed |= ui.add(egui::Slider::new(&mut gui_config.pressure_iterations, 2..=100)
+                    .text("Pressure Iterations")
+                    .step_by(2.0)).changed();
+            });
+
+            ui.collapsing("Divergence", |ui| {
+                changed |= ui.checkbox(&mut gui_config.divergence_view, "Show Velocity Divergence").changed();
+                changed |= parameter_slider(ui, &mut gui_config.divergence_range, defaults.divergence_range, |value| {
+                    egui::Slider::new(value, 0.1..=100.0)
+                        .text("Color Range (1/s)")
+                        .logarithmic(true)
+                });
+            });
+
+            ui.collapsing("Velocity Field", |ui| {
+                let mut velocity_field_mode = VelocityFieldMode::from_u32(gui_config.velocity_field_mode);
+                ui.horizontal(|ui| {
+                    for mode in VelocityFieldMode::ALL {
+                        changed |= ui.radio_value(&mut velocity_field_mode, mode, mode.name()).changed();
+                    }
+                });
+                gui_config.velocity_field_mode = velocity_field_mode as u32;
+                ui.add_enabled_ui(velocity_field_mode != VelocityFieldMode::Off, |ui| {
+                    changed |= parameter_slider(ui, &mut gui_config.velocity_field_spacing, defaults.velocity_field_spacing, |value| {
+                        egui::Slider::new(value, 10.0..=100.0)
+                            .text("Sample Spacing")
+                    });
+                    changed |= parameter_slider(ui, &mut gui_config.velocity_field_full_speed, defaults.velocity_field_full_speed, |value| {
+                        egui::Slider::new(value, 1.0..=1000.0)
+                            .text("Full Length Speed")
+                            .logarithmic(true)
+                    });
+                });
+            });
+
+            ui.collapsing("Particle Color", |ui| {
+                let mut color_field = ColorField::from_u32(gui_config.color_field);
+                egui::ComboBox::from_label("Color By")
+                    .selected_text(color_field.name())
+                    .show_ui(ui, |ui| {
+                        for field in ColorField::ALL {
+                            ui.selectable_value(&mut color_field, field, field.name());
+                        }
+                    });
+                if color_field as u32 != gui_config.color_field
+                {
+                    // the old range is meaningless for the new field
+                    gui_config.color_field = color_field as u32;
+                    (gui_config.color_min, gui_config.color_max) = color_field.default_range(gui_config.target_density);
+                    changed = true;
+                }
+
+                let mut colormap = Colormap::from_u32(gui_config.colormap);
+                egui::ComboBox::from_label("Colormap")
+                    .selected_text(colormap.name())
+                    .show_ui(ui, |ui| {
+                        for map in Colormap::ALL {
+                            ui.selectable_value(&mut colormap, map, map.name());
+                        }
+                    });
+                if colormap as u32 != gui_config.colormap
+                {
+                    gui_config.colormap = colormap as u32;
+                    changed = true;
+                }
+
+                if color_field == ColorField::Turbulence {
+                    changed |= parameter_slider(ui, &mut gui_config.velocity_history_frames, defaults.velocity_history_frames, |value| {
+                        egui::Slider::new(value, 2.0..=600.0)
+                            .text("History Window (frames)")
+                            .logarithmic(true)
+                    });
+                }
+
+                // slider spans a few times the field's default range either side
+                let (range_min, range_max) = color_field.default_range(gui_config.target_density);
+                let span = range_max - range_min;
+                changed |= parameter_slider(ui, &mut gui_config.color_min, defaults.color_min, |value| {
+                    egui::Slider::new(value, (range_min - span)..=(range_max + span))
+                        .text("Min")
+                });
+                changed |= parameter_slider(ui, &mut gui_config.color_max, defaults.color_max, |value| {
+                    egui::Slider::new(value, (range_min - span)..=(range_max + span))
+                        .text("Max")
+                });
+            });
+
+            ui.collapsing("Rendering", |ui| {
+                let mut render_mode = RenderMode::from_u32(gui_config.render_mode);
+                ui.horizontal(|ui| {
+                    for mode in RenderMode::ALL {
+                        changed |= ui.radio_value(&mut render_mode, mode, mode.name()).changed();
+                    }
+                });
+                gui_config.render_mode = render_mode as u32;
+                ui.add_enabled_ui(render_mode == RenderMode::Surface, |ui| {
+                    changed |= parameter_slider(ui, &mut gui_config.surface_threshold, defaults.surface_threshold, |value| {
+                        egui::Slider::new(value, 0.01..=5.0)
+                            .text("Surface Threshold")
+                            .logarithmic(true)
+                    });
+                    changed |= parameter_slider(ui, &mut gui_config.surface_blur_radius, defaults.surface_blur_radius, |value| {
+                        egui::Slider::new(value, 0.0..=32.0)
+                            .text("Blur Radius (px)")
+                    });
+                });
+                // drawn with the Particle Color colormap; with Blue-Red and a range of 2, zero pressure is white
+                ui.add_enabled_ui(render_mode == RenderMode::Heatmap, |ui| {
+                    changed |= parameter_slider(ui, &mut gui_config.heatmap_range, defaults.heatmap_range, |value| {
+                        egui::Slider::new(value, 1.0..=10.0)
+                            .text("Heatmap Range (x target density)")
+                    });
+                });
+
+                // only the particle quads fade, the surface and heatmap already show the density
+                ui.add_enabled_ui(render_mode == RenderMode::Particles, |ui| {
+                    changed |= ui.checkbox(&mut gui_config.density_alpha_enabled, "Alpha by Density").changed();
+                    ui.add_enabled_ui(gui_config.density_alpha_enabled, |ui| {
+                        changed |= parameter_slider(ui, &mut gui_config.density_alpha_min, defaults.density_alpha_min, |value| {
+                            egui::Slider::new(value, 0.0..=1.0)
+                                .text("Sparse Alpha")
+                        });
+                        changed |= parameter_slider(ui, &mut gui_config.density_alpha_range, defaults.density_alpha_range, |value| {
+                            egui::Slider::new(value, 0.1..=4.0)
+                                .text("Opaque Density (x target density)")
+                        });
+                        changed |= parameter_slider(ui, &mut gui_config.density_alpha_gamma, defaults.density_alpha_gamma, |value| {
+                            egui::Slider::new(value, 0.25..=4.0)
+                                .text("Curve")
+                                .logarithmic(true)
+                        });
+                    });
+                });
+
+                // traced around the surface texture, which particles mode then fills just for this
+                changed |= ui.checkbox(&mut gui_config.outline_enabled, "Outline").changed();
+                ui.add_enabled_ui(gui_config.outline_enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        changed |= ui.color_edit_button_rgba_unmultiplied(&mut gui_config.outline_color).changed();
+                        ui.label("Outline Color");
+                    });
+                    changed |= parameter_slider(ui, &mut gui_config.outline_width, defaults.outline_width, |value| {
+                        egui::Slider::new(value, 1.0..=16.0)
+                            .text("Outline Width (px)")
+                    });
+                });
+
+                changed |= ui.add(egui::Slider::new(&mut gui_config.trail_length, 0..=TRAIL_HISTORY)
+                    .text("Trail Length (frames)")).changed();
+                ui.add_enabled_ui(gui_config.trail_length > 1, |ui| {
+                    changed |= parameter_slider(ui, &mut gui_config.trail_opacity, defaults.trail_opacity, |value| {
+                        egui::Slider::new(value, 0.0..=1.0)
+                            .text("Trail Opacity")
+                    });
+                    changed |= parameter_slider(ui, &mut gui_config.trail_width, defaults.trail_width, |value| {
+                        egui::Slider::new(value, 0.5..=10.0)
+                            .text("Trail Width")
+                    });
+                });
+
+                // brightness over 1 comes from the surface's specular and the exposure, the tonemap rolls it off
+                let mut tonemapping = Tonemapping::from_u32(gui_config.tonemapping);
+                egui::ComboBox::from_label("Tonemapping")
+                    .selected_text(tonemapping.name())
+                    .show_ui(ui, |ui| {
+                        for curve in Tonemapping::ALL {
+                            ui.selectable_value(&mut tonemapping, curve, curve.name());
+                        }
+                    });
+                if tonemapping as u32 != gui_config.tonemapping
+                {
+                    gui_config.tonemapping = tonemapping as u32;
+                    changed = true;
+                }
+                changed |= parameter_slider(ui, &mut gui_config.exposure, defaults.exposure, |value| {
+                    egui::Slider::new(value, 0.25..=4.0)
+                        .text("Exposure")
+                        .logarithmic(true)
+                });
+
+                // the system is drawn offscreen and quantized onto the screen, the overlays included
+                changed |= ui.checkbox(&mut gui_config.retro_enabled, "Retro Palette").changed();
+                ui.add_enabled_ui(gui_config.retro_enabled, |ui| {
+                    let mut palette = RetroPalette::from_u32(gui_config.palette);
+                    egui::ComboBox::from_label("Palette")
+                        .selected_text(palette.name())
+                        .show_ui(ui, |ui| {
+                            for option in RetroPalette::ALL {
+                                ui.selectable_value(&mut palette, option, option.name());
+                            }
+                        });
+                    if palette as u32 != gui_config.palette
+                    {
+                        gui_config.palette = palette as u32;
+                        changed = true;
+                    }
+                    changed |= parameter_slider(ui, &mut gui_config.palette_dither, defaults.palette_dither, |value| {
+                        egui::Slider::new(value, 0.0..=1.0)
+                            .text("Dither")
+                    });
+                    changed |= parameter_slider(ui, &mut gui_config.retro_pixel_size, defaults.retro_pixel_size, |value| {
+                        egui::Slider::new(value, 1.0..=8.0)
+                            .text("Pixel Size (px)")
+                    });
+                });
+
+                // the camera's setting, the particle pipelines follow it in the render world
+                if let Ok(mut msaa) = camera_msaa.single_mut() {
+                    let mut selected = *msaa;
+                    egui::ComboBox::from_label("Anti-aliasing")
+                        .selected_text(msaa_name(selected))
+                        .show_ui(ui, |ui| {
+                            for option in supported_msaa.0.iter() {
+                                ui.selectable_value(&mut selected, *option, msaa_name(*option));
+                            }
+                        });
+                    if selected != *msaa {
+                        *msaa = selected;
+                    }
+                }
+            });
+
+            ui.collapsing("Boundaries", |ui| {
+                egui::Grid::new("boundary_modes_grid").num_columns(2).show(ui, |ui| {
+                    for (edge, mode) in BOUNDARY_EDGES.iter().zip(gui_config.boundary_modes.iter_mut()) {
+                        let mut selected = BoundaryMode::from_u32(*mode);
+                        ui.label(*edge);
+                        egui::ComboBox::from_id_salt(("boundary_mode", *edge))
+                            .selected_text(selected.name())
+                            .show_ui(ui, |ui| {
+                                for boundary in BoundaryMode::ALL {
+                                    ui.selectable_value(&mut selected, boundary, boundary.name());
+                                }
+                            });
+                        if selected as u32 != *mode {
+                            *mode = selected as u32;
+                            changed = true;
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.label("Drained particles stay out until the particles are rescattered or an emitter reuses their slots");
+            });
+
+            ui.collapsing("Precision", |ui| {
+                changed |= ui.checkbox(&mut gui_config.compensated_summation, "Compensated Summation").changed();
+                ui.label("Kahan sums the density and force accumulators, for less rounding error at high neighbor counts");
+                if gui_config.compensated_summation {
+                    ui.colored_label(egui::Color32::YELLOW, "Slower: roughly four times the adds in the neighbor loops");
+                }
+            });
+
+            ui.collapsing("Display", |ui| {
+                gui_scale_settings(ui, &mut gui_scale);
+                attract_mode_settings(ui, &mut attract);
+            });
+
+            // everything changed during the session, with a per-parameter revert
+            let changes = gui_config.diff(&defaults);
+            egui::CollapsingHeader::new(format!("Changed From Defaults ({})", changes.len()))
+                .id_salt("changed_from_defaults")
+                .show(ui, |ui| {
+                    if changes.is_empty() {
+                        ui.label("All parameters at their defaults");
+                    }
+                    egui::Grid::new("changed_params_grid").striped(true).show(ui, |ui| {
+                        for name in &changes {
+                            ui.colored_label(CHANGED_PARAM_COLOR, *name);
+                            ui.label(format!("{} -> {}",
+                                defaults.param_text(name).unwrap_or_default(),
+                                gui_config.param_text(name).unwrap_or_default()));
+                            if ui.small_button("Revert").clicked() {
+                                changed |= gui_config.copy_param_from(name, &defaults);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+
+            // whole parameter set as text, for sharing exact values between runs
+            ui.collapsing("Parameter Text", |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Copy").clicked() {
+                        ui.ctx().copy_text(gui_config.to_text());
+                    }
+                    if ui.button("Apply Pasted").clicked() {
+                        *param_text_error = gui_config.apply_text(&param_text).err();
+                    }
+                    if ui.button("Reset All").clicked() {
+                        *gui_config = defaults;
+                        changed = true;
+                    }
+                });
+                ui.add(egui::TextEdit::multiline(&mut *param_text)
+                    .hint_text("Paste parameters here")
+                    .code_editor()
+                    .desired_rows(4));
+                if let Some(error) = param_text_error.as_ref() {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
             
             if changed {
                 gui_config.applied_changes = true;
@@ -78,26 +1425,196 @@ use std::f32::consts::PI;
 pub fn apply_gui_updates(
     mut sim_config: ResMut<ParticleConfig>,
     mut gui_config: ResMut<GUIConfig>,
+    sim_clock: Res<SimClock>,
+    power_saving: Res<PowerSaving>,
 )
 {
     if gui_config.applied_changes 
     {
-        sim_config.fixed_delta_time = gui_config.fixed_delta_time;
-        sim_config.gravity = gui_config.gravity;
+        sim_config.particle_count = power_saving.cap_particle_count(gui_config.particle_count).max(1);
+        sim_config.fixed_delta_time = (gui_config.fixed_delta_time * sim_clock.time_scale).0;
+        sim_config.gravity = gui_config.gravity_vector().to_array();
         sim_config.damping_factor = gui_config.damping_factor;
 
-        sim_config.density_kernel_norm = 10.0 / (PI * gui_config.smoothing_radius.powf(5.0));
-        sim_config.near_density_kernel_norm = 15.0 / (PI * gui_config.smoothing_radius.powf(6.0));
-        sim_config.viscocity_kernel_norm = 4.0 / (PI * gui_config.smoothing_radius.powf(8.0));
-        sim_config.smoothing_radius = gui_config.smoothing_radius;
+        sim_config.set_smoothing_radius(gui_config.smoothing_radius);
 
 
         sim_config.max_energy = gui_config.max_energy;
-        sim_config.target_density = gui_config.target_density;
+        sim_config.target_density = gui_config.target_density.0;
         sim_config.pressure_multiplier = gui_config.pressure_multiplier;
         sim_config.viscocity_strength = gui_config.viscocity_strength;
         sim_config.near_density_multiplier = gui_config.near_density_multiplier;
+
+        sim_config.smoke_enabled = gui_config.smoke_enabled as u32;
+        sim_config.smoke_injection = gui_config.smoke_injection;
+        sim_config.smoke_dissipation = gui_config.smoke_dissipation;
+        sim_config.smoke_opacity = gui_config.smoke_opacity;
+
+        sim_config.flip_enabled = gui_config.flip_enabled as u32;
+        sim_config.flip_ratio = gui_config.flip_ratio;
+        sim_config.pressure_iterations = gui_config.pressure_iterations;
+
+        sim_config.divergence_view = gui_config.divergence_view as u32;
+        sim_config.divergence_range = gui_config.divergence_range;
+
+        sim_config.color_field = gui_config.color_field;
+        sim_config.colormap = gui_config.colormap;
+        sim_config.color_min = gui_config.color_min;
+        sim_config.color_max = gui_config.color_max;
+        sim_config.velocity_history_frames = gui_config.velocity_history_frames;
+
+        sim_config.render_mode = gui_config.render_mode;
+        sim_config.surface_threshold = gui_config.surface_threshold;
+        sim_config.surface_blur_radius = gui_config.surface_blur_radius;
+        sim_config.heatmap_range = gui_config.heatmap_range;
+        sim_config.trail_length = gui_config.trail_length.min(TRAIL_HISTORY);
+        sim_config.trail_opacity = gui_config.trail_opacity;
+        sim_config.trail_width = gui_config.trail_width;
+        sim_config.tonemapping = gui_config.tonemapping;
+        sim_config.exposure = gui_config.exposure;
+        sim_config.density_alpha_enabled = gui_config.density_alpha_enabled as u32;
+        sim_config.density_alpha_min = gui_config.density_alpha_min;
+        sim_config.density_alpha_range = gui_config.density_alpha_range;
+        sim_config.density_alpha_gamma = gui_config.density_alpha_gamma;
+        sim_config.velocity_field_mode = gui_config.velocity_field_mode;
+        sim_config.velocity_field_spacing = gui_config.velocity_field_spacing;
+        sim_config.velocity_field_full_speed = gui_config.velocity_field_full_speed;
+        sim_config.outline_enabled = gui_config.outline_enabled as u32;
+        sim_config.outline_width = gui_config.outline_width;
+        sim_config.outline_color = gui_config.outline_color;
+        let palette = RetroPalette::from_u32(gui_config.palette);
+        sim_config.retro_enabled = gui_config.retro_enabled as u32;
+        sim_config.palette_size = palette.colors().len() as u32;
+        sim_config.palette_dither = gui_config.palette_dither;
+        sim_config.retro_pixel_size = gui_config.retro_pixel_size;
+        sim_config.palette = palette.gpu_colors();
+
+        sim_config.compensated_summation = gui_config.compensated_summation as u32;
+
+        sim_config.temperature_enabled = gui_config.temperature_enabled as u32;
+        sim_config.ambient_temperature = gui_config.ambient_temperature;
+        sim_config.heat_diffusion = gui_config.heat_diffusion;
+        sim_config.cooling_rate = gui_config.cooling_rate;
+        sim_config.thermal_expansion = gui_config.thermal_expansion;
+        sim_config.heater_temperature = gui_config.heater_temperature;
+        sim_config.heater_height = gui_config.heater_height;
+        sim_config.heater_count = gui_config.heater_count.min(MAX_HEATERS as u32);
+        // packed two spans to a vec4
+        for (heater, [start, end]) in gui_config.heater_spans.iter().enumerate()
+        {
+            sim_config.heater_spans[heater / 2][heater % 2 * 2] = *start;
+            sim_config.heater_spans[heater / 2][heater % 2 * 2 + 1] = *end;
+        }
+        sim_config.boundary_modes = gui_config.boundary_modes;
+
+        sim_config.solver = gui_config.solver;
+        sim_config.pbf_iterations = gui_config.pbf_iterations.max(1);
+        sim_config.pbf_relaxation = gui_config.pbf_relaxation;
+
+        sim_config.cfl_number = gui_config.cfl_number;
+        sim_config.max_substeps = if gui_config.adaptive_substeps { gui_config.max_substeps.max(1) } else { 1 };
         
         gui_config.applied_changes = false;
     }
+}
+
+// swing gravity between +/- the selected strength while oscillation is enabled, on the sim's
+// clock in seeded runs
+pub fn oscillate_gravity(
+    time: Res<Time>,
+    gui_config: Res<GUIConfig>,
+    sim_rng: Res<SimRng>,
+    mut sim_config: ResMut<ParticleConfig>,
+)
+{
+    if gui_config.oscillate_gravity
+    {
+        let elapsed = if sim_rng.is_seeded() { sim_config.frame_count as f32 * sim_config.fixed_delta_time } else { time.elapsed_secs() };
+        let phase = 2.0 * PI * elapsed / gui_config.gravity_oscillation_period.0;
+        sim_config.gravity = (gui_config.gravity_vector() * phase.cos()).to_array();
+    }
+}
+
+// sent to move a named float param to a new value over a short ramp rather than in one step,
+// ramp_params eases it there
+#[derive(Event, Clone, Copy)]
+pub struct ParamRamp
+{
+    pub name: &'static str,
+    pub target: f32,
+    pub duration: Seconds,
+}
+
+// a ParamRamp in progress
+pub struct ActiveRamp
+{
+    ramp: ParamRamp,
+    start: f32,
+    elapsed: f32,
+    written: f32,   // what the ramp last set, anything else means the param was changed under it
+}
+
+// ease params toward their requested values, giving up on one that's changed in the meantime
+// (a slider, undo or a preset) so the ramp doesn't fight it
+pub fn ramp_params(
+    time: Res<Time>,
+    mut ramp_events: EventReader<ParamRamp>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut ramps: Local<Vec<ActiveRamp>>,
+)
+{
+    for ramp in ramp_events.read()
+    {
+        let Some(start) = gui_config.float_param_mut(ramp.name).map(|value| *value) else {
+            warn!("[Params] Can't ramp unknown param {}", ramp.name);
+            continue;
+        };
+        ramps.retain(|active| active.ramp.name != ramp.name);
+        ramps.push(ActiveRamp { ramp: *ramp, start, elapsed: 0.0, written: start });
+    }
+    if ramps.is_empty() { return; }
+
+    let delta = time.delta_secs();
+    ramps.retain_mut(|active| {
+        let Some(value) = gui_config.float_param_mut(active.ramp.name) else { return false; };
+        if *value != active.written { return false; }
+
+        active.elapsed += delta;
+        let t = (active.elapsed / active.ramp.duration.0.max(f32::EPSILON)).min(1.0);
+        *value = active.start + (active.ramp.target - active.start) * t * t * (3.0 - 2.0 * t);
+        active.written = *value;
+        t < 1.0
+    });
+    gui_config.applied_changes = true;
+}
+
+const GRAVITY_TILT_RATE: f32 = 90.0;    // degrees per second while an arrow key is held
+
+// left/right arrows tilt gravity, down snaps it back to straight down
+pub fn tilt_gravity(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut gui_config: ResMut<GUIConfig>,
+)
+{
+    let typing = contexts.ctx_mut().map(|ctx| ctx.wants_keyboard_input()).unwrap_or(false);
+    if typing { return; }
+
+    let mut tilt = 0.0;
+    if keyboard_input.pressed(KeyCode::ArrowLeft) { tilt -= 1.0; }
+    if keyboard_input.pressed(KeyCode::ArrowRight) { tilt += 1.0; }
+
+    if keyboard_input.just_pressed(KeyCode::ArrowDown)
+    {
+        gui_config.gravity_angle = 0.0;
+        gui_config.applied_changes = true;
+    }
+    else if tilt != 0.0
+    {
+        // wrap into (-180, 180] so the slider keeps up with full turns
+        let angle = gui_config.gravity_angle + tilt * GRAVITY_TILT_RATE * time.delta_secs();
+        gui_config.gravity_angle = -((180.0 - angle).rem_euclid(360.0) - 180.0);
+        gui_config.applied_changes = true;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::parameter_gui::GUIConfig;
+use crate::attract_mode::AttractMode;
+use crate::training_data::TrainingData;
+
+const MAX_HISTORY: usize = 100;     // oldest edits are dropped past this many
+
+struct HistoryEntry
+{
+    label: String,          // parameters touched by the edit
+    params: GUIConfig,      // undo stack: state before the edit, redo stack: state after it
+}
+
+#[derive(Resource, Default)]
+pub struct ParameterHistory
+{
+    undo_stack: Vec<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+    committed: Option<GUIConfig>,   // last state recorded, edits are diffed against it
+}
+
+// params with the one-frame apply flag cleared, so snapshots compare and restore cleanly
+fn settled(params: GUIConfig) -> GUIConfig
+{
+    GUIConfig { applied_changes: false, ..params }
+}
+
+impl ParameterHistory
+{
+    fn restore(&mut self, params: GUIConfig, gui_config: &mut GUIConfig)
+    {
+        *gui_config = GUIConfig { applied_changes: true, ..params };
+        self.committed = Some(settled(params));
+    }
+
+    pub fn undo(&mut self, gui_config: &mut GUIConfig) -> bool
+    {
+        let Some(entry) = self.undo_stack.pop() else { return false; };
+        self.redo_stack.push(HistoryEntry { label: entry.label, params: settled(*gui_config) });
+        self.restore(entry.params, gui_config);
+        true
+    }
+
+    pub fn redo(&mut self, gui_config: &mut GUIConfig) -> bool
+    {
+        let Some(entry) = self.redo_stack.pop() else { return false; };
+        self.undo_stack.push(HistoryEntry { label: entry.label, params: settled(*gui_config) });
+        self.restore(entry.params, gui_config);
+        true
+    }
+
+    // record the settled params if they differ from the last recorded state
+    fn record(&mut self, gui_config: &GUIConfig)
+    {
+        let current = settled(*gui_config);
+        let committed = *self.committed.get_or_insert(current);
+        let changed = committed.diff(&current);
+        if changed.is_empty() { return; }
+
+        self.undo_stack.push(HistoryEntry { label: changed.join(", "), params: committed });
+        if self.undo_stack.len() > MAX_HISTORY
+        {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        self.committed = Some(current);
+    }
+}
+
+// track parameter edits and handle Ctrl+Z / Ctrl+Y; runs after the param panel so the
+// whole frame's edits are seen at once
+pub fn parameter_history_system(
+    mut contexts: EguiContexts,
+    mut gui_config: ResMut<GUIConfig>,
+    mut history: ResMut<ParameterHistory>,
+    attract: Res<AttractMode>,
+    training: Res<TrainingData>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+
+    // text fields keep their own undo, and a drag in progress becomes one edit once released
+    if !ctx.wants_keyboard_input()
+    {
+        let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+        let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+        let redo_alt = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+        // consume the shift variant first, the plain shortcut would also match it
+        if ctx.input_mut(|input| input.consume_shortcut(&redo_alt) || input.consume_shortcut(&redo))
+        {
+            history.redo(&mut gui_config);
+        }
+        else if ctx.input_mut(|input| input.consume_shortcut(&undo))
+        {
+            history.undo(&mut gui_config);
+        }
+    }
+    // attract mode and training data scenes aren't edits, and the params are restored when they end
+    if !ctx.input(|input| input.pointer.any_down()) && !attract.is_active() && !training.is_running()
+    {
+        history.record(&gui_config);
+    }
+
+    egui::Window::new("History")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 400.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!history.undo_stack.is_empty(), egui::Button::new("Undo")).clicked() {
+                    history.undo(&mut gui_config);
+                }
+                if ui.add_enabled(!history.redo_stack.is_empty(), egui::Button::new("Redo")).clicked() {
+                    history.redo(&mut gui_config);
+                }
+            });
+            ui.label("Ctrl+Z / Ctrl+Y");
+            ui.separator();
+
+            // undone edits above, applied edits below newest first; clicking one jumps to it
+            let mut redo_count = 0;
+            let mut undo_count = 0;
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for (index, entry) in history.redo_stack.iter().enumerate() {
+                    let text = egui::RichText::new(&entry.label).weak();
+                    if ui.selectable_label(false, text).clicked() {
+                        redo_count = history.redo_stack.len() - index;
+                    }
+                }
+                for (index, entry) in history.undo_stack.iter().enumerate().rev() {
+                    let is_latest = index + 1 == history.undo_stack.len();
+                    if ui.selectable_label(is_latest, &entry.label).clicked() {
+                        undo_count = history.undo_stack.len() - 1 - index;
+                    }
+                }
+                if history.undo_stack.is_empty() && history.redo_stack.is_empty() {
+                    ui.label("No edits yet");
+                }
+            });
+
+            for _ in 0..redo_count {
+                history.redo(&mut gui_config);
+            }
+            for _ in 0..undo_count {
+                history.undo(&mut gui_config);
+            }
+        });
+    Ok(())
+}
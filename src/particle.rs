@@ -7,24 +7,53 @@ use bevy::{
         graph::CameraDriverLabel, 
         render_graph::RenderGraph, 
         render_resource::*, 
+        renderer::{RenderAdapter, RenderDevice},
         RenderApp, RenderSet,
     },
 };
 
 use crate::{ParticleConfig, ParticleSystem};
-use crate::particle_render::{ParticleRenderNode, ParticleRenderLabel, ParticleRenderPipeline};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::particle_render::{update_render_pipeline_msaa, ParticleRenderNode, ParticleRenderLabel, ParticleRenderPipeline, SupportedMsaa};
 use crate::particle_buffers::prepare_particle_buffers;
 use crate::particle_compute::{ParticleComputeNode, ParticleComputeLabel, ParticleComputePipeline};
 use crate::debug::{ParticleDebugLabel, ParticleDebugNode};
+use crate::fluid_volume::{read_back_densities, DensityReadback, DensitySample};
+use crate::hydrostatic::{read_back_hydrostatic_profile, HydrostaticReadback, HydrostaticShared};
+use crate::obstacle::{prepare_obstacles, Obstacle, ObstacleOrder};
+use crate::obstacle_drag::DraggedObstacle;
+use crate::force_field::{prepare_force_fields, ForceField};
+use crate::emitter::{upload_emitted_particles, EmittedParticles};
+use crate::particle_probe::{read_back_particle_probe, ParticleProbeReadback, ParticleProbeShared};
+use crate::scene::{read_back_scene_particles, SceneReadback, SceneShared};
+use crate::field_export::{read_back_field_export, FieldExportReadback, FieldExportShared};
+use crate::training_data::{read_back_training_data, TrainingDataReadback, TrainingDataShared};
+use crate::surrogate::{read_back_surrogate_particles, upload_surrogate_correction, SurrogateReadback, SurrogateShared};
+use crate::rigid_body::{read_back_body_impulses, RigidBody, RigidBodyReadback, RigidBodyShared};
+use crate::stats::{read_back_stats, schedule_stats_reduction, StatsReadback, StatsShared};
+use crate::obstacle_force::{read_back_obstacle_forces, ObstacleForceReadback, ObstacleForceShared};
+use crate::pressure_probe::{prepare_pressure_probes, read_back_pressure_probes, PressureProbeReadback, PressureProbeShared};
+use crate::surface_render::prepare_surface_textures;
+use crate::retro_render::prepare_retro_textures;
+use crate::pipeline_status::{update_pipeline_progress, PipelineProgress};
+use crate::cpu_backend::{add_cpu_backend, SimBackend};
+use crate::substeps::{read_back_max_speed, MaxSpeedReadback, MaxSpeedSample};
+use crate::gpu_timing::{begin_gpu_timing, read_back_gpu_timing, GpuTiming, GpuTimingShared};
+use crate::gpu_readback::{reset_readback_budget, ReadbackBudget, ReadbackBudgetShared};
+use crate::device_recovery::{snapshot_particles_for_recovery, watch_for_device_loss, DeviceRecoveryShared, DeviceSnapshotReadback};
 
 #[derive(ShaderType, Default, Clone, Copy)] 
 pub struct Particle {
     pub position: [f32; 2],
     pub velocity: [f32; 2], 
-    pub color: [f32; 4],
+    pub color: [f32; 4],       // alpha 0 marks a particle removed by a Drain boundary
 }
 
-pub struct ParticlePlugin;
+#[derive(Default)]
+pub struct ParticlePlugin
+{
+    pub backend: SimBackend,    // steps the main particle system, see cpu_backend.rs
+}
 
 impl Plugin for ParticlePlugin 
 {
@@ -32,12 +61,118 @@ impl Plugin for ParticlePlugin
     {
         // extract particle system to render world
         app.add_plugins(ExtractComponentPlugin::<ParticleSystem>::default());
+        app.add_plugins(ExtractComponentPlugin::<ParticleSystemConfig>::default());
         app.add_plugins(ExtractResourcePlugin::<ParticleConfig>::default());
+        app.add_plugins(ExtractComponentPlugin::<Obstacle>::default());
+        app.add_plugins(ExtractComponentPlugin::<DraggedObstacle>::default());
+        app.add_plugins(ExtractComponentPlugin::<RigidBody>::default());
+        app.add_plugins(ExtractComponentPlugin::<ForceField>::default());
+        app.add_plugins(ExtractResourcePlugin::<EmittedParticles>::default());
+
+        // the compute node skips the main system's sim passes while the CPU steps it
+        let backend = self.backend.available();
+        app.insert_resource(backend);
+        app.sub_app_mut(RenderApp).insert_resource(backend);
+        if backend == SimBackend::Cpu
+        {
+            add_cpu_backend(app);
+        }
+
+        // density samples are written by the render world and read by the main world
+        let density_sample = DensitySample::default();
+        app.insert_resource(density_sample.clone());
+        let hydrostatic_shared = HydrostaticShared::default();
+        app.insert_resource(hydrostatic_shared.clone());
+        let scene_shared = SceneShared::default();
+        app.insert_resource(scene_shared.clone());
+        let field_export_shared = FieldExportShared::default();
+        app.insert_resource(field_export_shared.clone());
+        let training_data_shared = TrainingDataShared::default();
+        app.insert_resource(training_data_shared.clone());
+        let surrogate_shared = SurrogateShared::default();
+        app.insert_resource(surrogate_shared.clone());
+        let rigid_body_shared = RigidBodyShared::default();
+        app.insert_resource(rigid_body_shared.clone());
+        let stats_shared = StatsShared::default();
+        app.insert_resource(stats_shared.clone());
+        let probe_shared = ParticleProbeShared::default();
+        app.insert_resource(probe_shared.clone());
+        let pressure_probe_shared = PressureProbeShared::default();
+        app.insert_resource(pressure_probe_shared.clone());
+        let obstacle_force_shared = ObstacleForceShared::default();
+        app.insert_resource(obstacle_force_shared.clone());
+        let pipeline_progress = PipelineProgress::default();
+        app.insert_resource(pipeline_progress.clone());
+        let device_recovery_shared = DeviceRecoveryShared::default();
+        app.insert_resource(device_recovery_shared.clone());
+        let max_speed_sample = MaxSpeedSample::default();
+        app.insert_resource(max_speed_sample.clone());
+        let gpu_timing_shared = GpuTimingShared::default();
+        app.insert_resource(gpu_timing_shared.clone());
+        let readback_budget_shared = ReadbackBudgetShared::default();
+        app.insert_resource(readback_budget_shared.clone());
 
         // get render app
         let render_app = app.sub_app_mut(RenderApp);
         
         render_app.add_systems(Render, prepare_particle_buffers.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, prepare_obstacles.in_set(RenderSet::Prepare).after(prepare_particle_buffers));
+        render_app.add_systems(Render, prepare_force_fields.in_set(RenderSet::Prepare).after(prepare_particle_buffers));
+        render_app.add_systems(Render, prepare_pressure_probes.in_set(RenderSet::Prepare).after(prepare_particle_buffers));
+        render_app.add_systems(Render, upload_emitted_particles.in_set(RenderSet::Prepare).after(prepare_particle_buffers));
+        render_app.add_systems(Render, prepare_surface_textures.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, prepare_retro_textures.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, update_render_pipeline_msaa.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, upload_surrogate_correction.in_set(RenderSet::Prepare).after(prepare_particle_buffers));
+        render_app.add_systems(Render, schedule_stats_reduction.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, begin_gpu_timing.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, reset_readback_budget.in_set(RenderSet::Prepare));
+        render_app.add_systems(Render, read_back_densities.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_hydrostatic_profile.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_scene_particles.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_field_export.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_training_data.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_surrogate_particles.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_body_impulses.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_stats.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_particle_probe.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_pressure_probes.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_obstacle_forces.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, update_pipeline_progress.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, snapshot_particles_for_recovery.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_max_speed.in_set(RenderSet::Cleanup));
+        render_app.add_systems(Render, read_back_gpu_timing.in_set(RenderSet::Cleanup));
+        render_app.insert_resource(density_sample);
+        render_app.init_resource::<DensityReadback>();
+        render_app.insert_resource(hydrostatic_shared);
+        render_app.init_resource::<HydrostaticReadback>();
+        render_app.insert_resource(scene_shared);
+        render_app.init_resource::<SceneReadback>();
+        render_app.insert_resource(field_export_shared);
+        render_app.init_resource::<FieldExportReadback>();
+        render_app.insert_resource(training_data_shared);
+        render_app.init_resource::<TrainingDataReadback>();
+        render_app.insert_resource(surrogate_shared);
+        render_app.init_resource::<SurrogateReadback>();
+        render_app.insert_resource(rigid_body_shared);
+        render_app.init_resource::<RigidBodyReadback>();
+        render_app.insert_resource(stats_shared);
+        render_app.init_resource::<StatsReadback>();
+        render_app.insert_resource(probe_shared);
+        render_app.init_resource::<ParticleProbeReadback>();
+        render_app.insert_resource(pressure_probe_shared);
+        render_app.init_resource::<PressureProbeReadback>();
+        render_app.insert_resource(obstacle_force_shared);
+        render_app.init_resource::<ObstacleForceReadback>();
+        render_app.init_resource::<ObstacleOrder>();
+        render_app.insert_resource(pipeline_progress);
+        render_app.insert_resource(device_recovery_shared);
+        render_app.init_resource::<DeviceSnapshotReadback>();
+        render_app.insert_resource(max_speed_sample);
+        render_app.init_resource::<MaxSpeedReadback>();
+        render_app.insert_resource(gpu_timing_shared);
+        render_app.insert_resource(readback_budget_shared);
+        render_app.init_resource::<ReadbackBudget>();
 
         // Create the render node
         let render_node = ParticleRenderNode::new(render_app.world_mut());
@@ -64,5 +199,11 @@ impl Plugin for ParticlePlugin
         // insert Custom Particle Pipelines into render world
         render_app.init_resource::<ParticleComputePipeline>();
         render_app.init_resource::<ParticleRenderPipeline>();
+        render_app.init_resource::<GpuTiming>();
+
+        let world = render_app.world();
+        watch_for_device_loss(world.resource::<RenderDevice>(), world.resource::<DeviceRecoveryShared>());
+        let supported_msaa = SupportedMsaa::from_adapter(world.resource::<RenderAdapter>());
+        app.insert_resource(supported_msaa);
     }
 }
@@ -7,232 +7,554 @@ use bevy::{
     },
 };
 
-use bytemuck::{Pod, Zeroable};
 use crate::ParticleSystem;
-use crate::particle_render::ParticleRenderPipeline;
+use crate::particle_render::{ParticleRenderPipeline, TRAIL_HISTORY, VELOCITY_FIELD_MAX_SAMPLES};
 use crate::ParticleConfig;
 use crate::particle::Particle;
-use crate::util::get_bind_group;
+use crate::util::{get_bind_group, get_render_bind_group};
+use crate::obstacle::OBSTACLE_BUFFER_SIZE;
+use crate::force_field::FORCE_FIELD_BUFFER_SIZE;
+use crate::rigid_body::BODY_IMPULSE_BUFFER_SIZE;
+use crate::stats::stats_buffer_size;
+use crate::pressure_probe::PRESSURE_PROBE_BUFFER_SIZE;
+use crate::obstacle_force::OBSTACLE_FORCE_BUFFER_SIZE;
+use crate::substeps::MAX_SPEED_BUFFER_SIZE;
+use crate::particle_compute::SCAN_BLOCK_SIZE;
+use crate::particle_systems::{system_config, ParticleSystemConfig};
+
+const PARTICLE_UPLOAD_CHUNK_SIZE: usize = 4 * 1024 * 1024;   // bytes of particle data uploaded per frame
 
 #[derive(Component)]
 pub struct GPUPipelineBuffers {
-    pub bind_group: BindGroup,  // shared between vertex and compute shaders
+    pub bind_group: BindGroup,  // compute shader
+    pub render_bind_group: BindGroup,   // the buffers the render shader reads, read only
     pub vertex_buffer: Buffer,
+    pub particle_buffer: Buffer,
     pub config_buffer: Buffer,
     pub spatial_lookup_buffer: Buffer,          // for debugging
     pub spatial_lookup_offsets_buffer: Buffer,  // for debugging
     pub particle_densities_buffer: Buffer,      // for debugging
     pub predictied_positions_buffer: Buffer,    // for debugging
+    pub obstacle_buffer: Buffer,
+    pub force_field_buffer: Buffer,
+    pub surrogate_correction_buffer: Buffer,   // rewritten whenever the surrogate model produces a new grid
+    pub body_impulse_buffer: Buffer,            // summed by the collision pass, cleared after each readback
+    pub stats_buffer: Buffer,                   // histogram and per workgroup partials of the stats reduction
+    pub pressure_probe_buffer: Buffer,          // probe points and the ring of sampled frames
+    pub obstacle_force_buffer: Buffer,          // fixed point impulses per obstacle, cleared after each readback
+    pub particle_temperatures_buffer: Buffer,   // carried over with the particles when the grids regrow
+    pub velocity_history_buffer: Buffer,        // carried over with the particles when the grids regrow
+    pub trail_positions_buffer: Buffer,         // carried over with the particles when the grids regrow
+    pub max_speed_buffer: Buffer,               // reduced on the main system while adaptive substepping is on
+    pub particle_count: u32,                    // count the buffers were sized for
+    pub scalar_grid_cells: u32,                 // cells the background grid buffers were sized for
+    pub spatial_grid_cells: u32,                // cells the spatial lookup offsets were sized for
+    pub generation: u32,                        // ParticleSystem generation the particle data came from
 } 
 
-#[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
-pub struct SortingParams    // Used for spatial lookup buffer sorting
+#[derive(Component)]
+pub struct ParticleUpload  // Initial particle data still waiting to be copied into the particle buffer
+{
+    particles: Vec<Particle>,
+    uploaded: usize,    // particles written so far
+}
+
+impl ParticleUpload
+{
+    fn new(particles: &[Particle]) -> Self
+    {
+        Self { particles: particles.to_vec(), uploaded: 0 }
+    }
+
+    // returns true once every particle has been written to the GPU
+    pub fn is_finished(&self) -> bool
+    {
+        self.uploaded >= self.particles.len()
+    }
+
+    // serialize the next chunk of particles and write it into the particle buffer, so neither
+    // the encoding nor the copy of a huge system lands in a single frame
+    fn upload_chunk(&mut self, render_queue: &RenderQueue, particle_buffer: &Buffer)
+    {
+        let stride = std::mem::size_of::<Particle>();
+        let end = (self.uploaded + (PARTICLE_UPLOAD_CHUNK_SIZE / stride).max(1)).min(self.particles.len());
+
+        let mut byte_buffer = Vec::<u8>::new();
+        let mut buffer = encase::StorageBuffer::new(&mut byte_buffer);
+        buffer.write(&self.particles[self.uploaded..end]).unwrap();
+        render_queue.write_buffer(particle_buffer, (self.uploaded * stride) as u64, &byte_buffer);
+        self.uploaded = end;
+    }
+}
+
+// allocate every buffer for the current particle count and bind them; the particle data
+// is left to a ParticleUpload streamed in over the following frames
+fn create_pipeline_buffers(
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+    render_pipeline: &ParticleRenderPipeline,
+    config: &ParticleConfig,
+) -> GPUPipelineBuffers
 {
-    n: u32,
-    group_width: u32,
-    group_height: u32,
-    step_index: u32
+    // config buffer uniform
+    let config_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("uniform_buffer"),
+        size: std::mem::size_of::<ParticleConfig>() as u64,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    render_queue.write_buffer(&config_buffer, 0, bytemuck::bytes_of(config));
+
+    let config_buffer_size = config_buffer.size();
+    let config_buffer_size = std::num::NonZeroU64::new(config_buffer_size).unwrap();
+
+    // per particle buffers keep at least one slot so an empty system still gets non zero bindings;
+    // the shaders and draws only ever touch the first particle_count of them
+    let particle_slots = (config.particle_count as usize).max(1);
+
+    // particle buffer, particle data is serialized and uploaded in chunks across frames so huge
+    // systems don't exceed per-submission limits or stall setup
+    let particle_buffer = render_device.create_buffer(&BufferDescriptor {   
+        label: Some("storage_buffer"), 
+        size: (std::mem::size_of::<Particle>() * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+
+    let particle_buffer_size = (std::mem::size_of::<Particle>() * particle_slots) as u64;
+    let particle_buffer_size = std::num::NonZeroU64::new(particle_buffer_size).unwrap();
+
+    // spatial lookup buffer
+    let spatial_lookup_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("grid_metadata_buffer"),
+        size: (std::mem::size_of::<u32>() * 2 * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let spatial_lookup_buffer_size = spatial_lookup_buffer.size();
+    let spatial_lookup_buffer_size = std::num::NonZeroU64::new(spatial_lookup_buffer_size).unwrap();
+
+    // counting sort scratch: cell key and rank within the key per particle, particles per key,
+    // and the per block totals of the key count prefix sum. There's a key per spatial grid cell.
+    let sort_scratch_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("sort_scratch_buffer"),
+        size: (std::mem::size_of::<u32>() * 2 * particle_slots) as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let sort_scratch_buffer_size = sort_scratch_buffer.size();
+    let sort_scratch_buffer_size = std::num::NonZeroU64::new(sort_scratch_buffer_size).unwrap();
+
+    let spatial_grid_cells = config.spatial_grid_cells().max(1) as usize;
+    let key_counts_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("key_counts_buffer"),
+        size: (std::mem::size_of::<u32>() * spatial_grid_cells) as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let key_counts_buffer_size = key_counts_buffer.size();
+    let key_counts_buffer_size = std::num::NonZeroU64::new(key_counts_buffer_size).unwrap();
+
+    let scan_blocks = spatial_grid_cells.div_ceil(SCAN_BLOCK_SIZE as usize);
+    let scan_block_totals_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("scan_block_totals_buffer"),
+        size: (std::mem::size_of::<u32>() * scan_blocks) as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let scan_block_totals_buffer_size = scan_block_totals_buffer.size();
+    let scan_block_totals_buffer_size = std::num::NonZeroU64::new(scan_block_totals_buffer_size).unwrap();
+
+    // spatial lookup offsets buffer
+    let spatial_lookup_offsets_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("spatial_lookup_offsets_buffer"),
+        size: (std::mem::size_of::<u32>() * spatial_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let spatial_lookup_offsets_buffer_size = spatial_lookup_offsets_buffer.size();
+    let spatial_lookup_offsets_buffer_size = std::num::NonZeroU64::new(spatial_lookup_offsets_buffer_size).unwrap();
+
+    // particle densities buffer
+    let particle_densities_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("particle_densities_buffer"),
+        size: (std::mem::size_of::<f32>() * 2 * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let particle_densities_buffer_size = particle_densities_buffer.size();
+    let particle_densities_buffer_size = std::num::NonZeroU64::new(particle_densities_buffer_size).unwrap();
+
+    // predicted positions buffer
+    let predictied_positions_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("predictied_positions_buffer"),
+        size: (std::mem::size_of::<f32>() * 2 * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let predictied_positions_buffer_size = predictied_positions_buffer.size();
+    let predictied_positions_buffer_size = std::num::NonZeroU64::new(predictied_positions_buffer_size).unwrap();
+
+    // background grid buffers: fixed point accumulation (velocity x/y, weight, dye) per cell,
+    // resolved and projected cell velocities, two ping-ponged halves of the advected scalar,
+    // and divergence plus two ping-ponged pressure fields for the FLIP projection
+    let scalar_grid_cells = (config.scalar_grid_width * config.scalar_grid_height).max(1) as usize;
+
+    let scalar_grid_accumulation_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("scalar_grid_accumulation_buffer"),
+        size: (std::mem::size_of::<i32>() * 4 * scalar_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let scalar_grid_accumulation_buffer_size = scalar_grid_accumulation_buffer.size();
+    let scalar_grid_accumulation_buffer_size = std::num::NonZeroU64::new(scalar_grid_accumulation_buffer_size).unwrap();
+
+    let scalar_grid_velocities_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("scalar_grid_velocities_buffer"),
+        size: (std::mem::size_of::<[f32; 2]>() * 2 * scalar_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let scalar_grid_velocities_buffer_size = scalar_grid_velocities_buffer.size();
+    let scalar_grid_velocities_buffer_size = std::num::NonZeroU64::new(scalar_grid_velocities_buffer_size).unwrap();
+
+    let scalar_field_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("scalar_field_buffer"),
+        size: (std::mem::size_of::<f32>() * 2 * scalar_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let scalar_field_buffer_size = scalar_field_buffer.size();
+    let scalar_field_buffer_size = std::num::NonZeroU64::new(scalar_field_buffer_size).unwrap();
+
+    let grid_pressure_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("grid_pressure_buffer"),
+        size: (std::mem::size_of::<f32>() * 3 * scalar_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let grid_pressure_buffer_size = grid_pressure_buffer.size();
+    let grid_pressure_buffer_size = std::num::NonZeroU64::new(grid_pressure_buffer_size).unwrap();
+
+    // obstacle colliders, a count followed by a fixed size array rewritten every frame
+    let obstacle_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("obstacle_buffer"),
+        size: OBSTACLE_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let obstacle_buffer_size = std::num::NonZeroU64::new(OBSTACLE_BUFFER_SIZE).unwrap();
+
+    // analytic force fields, a count followed by a fixed size array rewritten every frame
+    let force_field_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("force_field_buffer"),
+        size: FORCE_FIELD_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let force_field_buffer_size = std::num::NonZeroU64::new(FORCE_FIELD_BUFFER_SIZE).unwrap();
+
+    // learned velocity correction per scalar grid cell, zeroed until the surrogate model writes it
+    let surrogate_correction_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("surrogate_correction_buffer"),
+        size: (std::mem::size_of::<[f32; 2]>() * scalar_grid_cells) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let surrogate_correction_buffer_size = surrogate_correction_buffer.size();
+    let surrogate_correction_buffer_size = std::num::NonZeroU64::new(surrogate_correction_buffer_size).unwrap();
+
+    // fixed point impulses the particles have given each rigid body slot
+    let body_impulse_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("body_impulse_buffer"),
+        size: BODY_IMPULSE_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let body_impulse_buffer_size = std::num::NonZeroU64::new(BODY_IMPULSE_BUFFER_SIZE).unwrap();
+
+    // only written when the stats overlay asks for a sample
+    let stats_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("stats_buffer"),
+        size: stats_buffer_size(config.particle_count),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let stats_buffer_size = std::num::NonZeroU64::new(stats_buffer.size()).unwrap();
+
+    // running mean velocity, mean squared speed and the variance they give, per particle
+    let velocity_history_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("velocity_history_buffer"),
+        size: (std::mem::size_of::<[f32; 4]>() * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let velocity_history_buffer_size = std::num::NonZeroU64::new(velocity_history_buffer.size()).unwrap();
+
+    // pressure probe points written every frame, followed by the samples read back from it
+    let pressure_probe_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("pressure_probe_buffer"),
+        size: PRESSURE_PROBE_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let pressure_probe_buffer_size = std::num::NonZeroU64::new(PRESSURE_PROBE_BUFFER_SIZE).unwrap();
+
+    // fixed point impulses the particles have given each obstacle, indexed like the obstacle buffer
+    let obstacle_force_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("obstacle_force_buffer"),
+        size: OBSTACLE_FORCE_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let obstacle_force_buffer_size = std::num::NonZeroU64::new(OBSTACLE_FORCE_BUFFER_SIZE).unwrap();
+
+    // current and next temperature per particle, every particle starting at the ambient temperature
+    let temperatures = vec![[config.ambient_temperature; 2]; particle_slots];
+    let particle_temperatures_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("particle_temperatures_buffer"),
+        contents: bytemuck::cast_slice(&temperatures),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+    });
+    let particle_temperatures_buffer_size = std::num::NonZeroU64::new(particle_temperatures_buffer.size()).unwrap();
+
+    // largest particle speed of the frame as float bits, cleared before each reduction and read back for substepping
+    let max_speed_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("max_speed_buffer"),
+        size: MAX_SPEED_BUFFER_SIZE,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let max_speed_buffer_size = std::num::NonZeroU64::new(MAX_SPEED_BUFFER_SIZE).unwrap();
+
+    // the last TRAIL_HISTORY positions per particle, a ring indexed by frame count
+    let trail_positions_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("trail_positions_buffer"),
+        size: (std::mem::size_of::<[f32; 2]>() * TRAIL_HISTORY as usize * particle_slots) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let trail_positions_buffer_size = std::num::NonZeroU64::new(trail_positions_buffer.size()).unwrap();
+
+    // averaged particle velocity at each sample of the coarse grid drawn as arrows or streamlines
+    let velocity_field_buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("velocity_field_buffer"),
+        size: (std::mem::size_of::<[f32; 2]>() * VELOCITY_FIELD_MAX_SAMPLES as usize) as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let velocity_field_buffer_size = std::num::NonZeroU64::new(velocity_field_buffer.size()).unwrap();
+
+    let bind_group = get_bind_group(
+        "bind_group",
+        &render_device,
+        &render_pipeline.bind_group_layout,
+        &particle_buffer,
+        particle_buffer_size,
+        &config_buffer,
+        config_buffer_size,
+        &spatial_lookup_buffer,
+        spatial_lookup_buffer_size,
+        &spatial_lookup_offsets_buffer,
+        spatial_lookup_offsets_buffer_size,
+        &sort_scratch_buffer,
+        sort_scratch_buffer_size,
+        &particle_densities_buffer,
+        particle_densities_buffer_size,
+        &predictied_positions_buffer,
+        predictied_positions_buffer_size,
+        &scalar_grid_accumulation_buffer,
+        scalar_grid_accumulation_buffer_size,
+        &scalar_grid_velocities_buffer,
+        scalar_grid_velocities_buffer_size,
+        &scalar_field_buffer,
+        scalar_field_buffer_size,
+        &grid_pressure_buffer,
+        grid_pressure_buffer_size,
+        &obstacle_buffer,
+        obstacle_buffer_size,
+        &key_counts_buffer,
+        key_counts_buffer_size,
+        &scan_block_totals_buffer,
+        scan_block_totals_buffer_size,
+        &surrogate_correction_buffer,
+        surrogate_correction_buffer_size,
+        &body_impulse_buffer,
+        body_impulse_buffer_size,
+        &stats_buffer,
+        stats_buffer_size,
+        &velocity_history_buffer,
+        velocity_history_buffer_size,
+        &pressure_probe_buffer,
+        pressure_probe_buffer_size,
+        &obstacle_force_buffer,
+        obstacle_force_buffer_size,
+        &particle_temperatures_buffer,
+        particle_temperatures_buffer_size,
+        &max_speed_buffer,
+        max_speed_buffer_size,
+        &trail_positions_buffer,
+        trail_positions_buffer_size,
+        &velocity_field_buffer,
+        velocity_field_buffer_size,
+        &force_field_buffer,
+        force_field_buffer_size,
+    );
+    let render_bind_group = get_render_bind_group(
+        &render_device,
+        &render_pipeline.render_bind_group_layout,
+        &[
+            (&particle_buffer, particle_buffer_size),
+            (&config_buffer, config_buffer_size),
+            (&particle_densities_buffer, particle_densities_buffer_size),
+            (&scalar_field_buffer, scalar_field_buffer_size),
+            (&grid_pressure_buffer, grid_pressure_buffer_size),
+            (&velocity_history_buffer, velocity_history_buffer_size),
+            (&particle_temperatures_buffer, particle_temperatures_buffer_size),
+            (&trail_positions_buffer, trail_positions_buffer_size),
+            (&velocity_field_buffer, velocity_field_buffer_size),
+        ],
+    );
+
+    let quad_vertices: &[f32; 24] = &[
+        // x,    y,    u,    v
+        -0.5, -0.5, 0.0, 1.0, // bottom-left
+        0.5, -0.5, 1.0, 1.0, // bottom-right
+        -0.5,  0.5, 0.0, 0.0, // top-left
+        0.5, -0.5, 1.0, 1.0, // bottom-right
+        0.5,  0.5, 1.0, 0.0, // top-right
+        -0.5,  0.5, 0.0, 0.0, // top-left
+    ];
+
+    // Upload to GPU:
+    let vertex_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("quad_vertex_buffer"),
+        contents: bytemuck::cast_slice(quad_vertices),
+        usage: BufferUsages::VERTEX,
+    });
+    
+    GPUPipelineBuffers 
+    {
+        bind_group: bind_group,
+        render_bind_group: render_bind_group,
+        vertex_buffer: vertex_buffer,
+        particle_buffer: particle_buffer,
+        config_buffer: config_buffer,
+        spatial_lookup_buffer: spatial_lookup_buffer,
+        spatial_lookup_offsets_buffer: spatial_lookup_offsets_buffer,
+        particle_densities_buffer: particle_densities_buffer,
+        predictied_positions_buffer: predictied_positions_buffer,
+        obstacle_buffer: obstacle_buffer,
+        force_field_buffer: force_field_buffer,
+        surrogate_correction_buffer: surrogate_correction_buffer,
+        body_impulse_buffer: body_impulse_buffer,
+        stats_buffer: stats_buffer,
+        pressure_probe_buffer: pressure_probe_buffer,
+        obstacle_force_buffer: obstacle_force_buffer,
+        particle_temperatures_buffer: particle_temperatures_buffer,
+        velocity_history_buffer: velocity_history_buffer,
+        trail_positions_buffer: trail_positions_buffer,
+        max_speed_buffer: max_speed_buffer,
+        particle_count: config.particle_count,
+        scalar_grid_cells: scalar_grid_cells as u32,
+        spatial_grid_cells: spatial_grid_cells as u32,
+        generation: 0,
+    }
 }
 
 pub fn prepare_particle_buffers(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
-    particle_system_query: Query<(Entity, &ParticleSystem)>,
-    pipeline_buffers_query: Query<&GPUPipelineBuffers>,
+    particle_system_query: Query<(Entity, &ParticleSystem, Option<&ParticleSystemConfig>, Option<&GPUPipelineBuffers>, Has<ParticleUpload>)>,
+    mut upload_query: Query<(Entity, &GPUPipelineBuffers, Option<&ParticleSystemConfig>, &mut ParticleUpload)>,
     render_pipeline: Res<ParticleRenderPipeline>,
     mut config: ResMut<ParticleConfig>,
     camera_query: Query<&ExtractedView, With<Camera>>,
     mut commands: Commands,
-    mut ran: Local<bool>,
 )
 {
-    if !*ran 
+    // currently hacky for making gui work, update view proj every frame
+    if let Ok(view) = camera_query.single() {
+        let view_matrix = view.world_from_view.compute_matrix().inverse();
+        let view_proj = view.clip_from_view * view_matrix;
+        config.view_proj = view_proj.to_cols_array_2d();
+    }
+
+    // (re)allocate when a system first appears, its particle count changed or its particles
+    // were replaced, once the extracted particles match the configured count. Bounds or a
+    // smoothing radius that outgrow the background or spatial grid also reallocate, carrying the
+    // particles over on the GPU since the extracted ones are only the initial scatter.
+    for (entity, particle_system, local_config, pipeline_buffers, uploading) in particle_system_query.iter()
     {
-        *ran = true;
-
-        // config buffer uniform
-        let config_buffer = render_device.create_buffer(&BufferDescriptor {
-            label: Some("uniform_buffer"),
-            size: std::mem::size_of::<ParticleConfig>() as u64,
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-            mapped_at_creation: false,
+        let config = system_config(local_config, &config);
+        let grid_cells = (config.scalar_grid_width * config.scalar_grid_height).max(1);
+        let replaced = pipeline_buffers.is_none_or(|buffers| {
+            buffers.particle_count != config.particle_count || buffers.generation != particle_system.generation
+        });
+        let grid_outgrown = pipeline_buffers.is_some_and(|buffers| {
+            buffers.scalar_grid_cells < grid_cells || buffers.spatial_grid_cells < config.spatial_grid_cells()
         });
 
-        let config_buffer_size = config_buffer.size();
-        let config_buffer_size = std::num::NonZeroU64::new(config_buffer_size).unwrap();
-
-        if let Ok((entity, particle_system)) = particle_system_query.single() 
-        {   
-            // particle buffer
-            let particles = &particle_system.particles;
-
-            let mut byte_buffer = Vec::<u8>::new();
-            let mut buffer = encase::StorageBuffer::new(&mut byte_buffer);
-            buffer.write(&particles).unwrap();
-
-            let particle_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {   
-                label: Some("storage_buffer"), 
-                contents: buffer.into_inner(), 
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-            });
-
-            let particle_buffer_size = (std::mem::size_of::<Particle>() * config.particle_count as usize) as u64;
-            let particle_buffer_size = std::num::NonZeroU64::new(particle_buffer_size).unwrap();
-
-            // spatial lookup buffer
-            let spatial_lookup_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("grid_metadata_buffer"),
-                size: (std::mem::size_of::<u32>() * 2 * config.particle_count.next_power_of_two() as usize) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-            });
-            let spatial_lookup_buffer_size = spatial_lookup_buffer.size();
-            let spatial_lookup_buffer_size = std::num::NonZeroU64::new(spatial_lookup_buffer_size).unwrap();
-
-            // bitonic merge sort sorting params uniform buffer (used with dynamic offset)
-            let n = config.particle_count;
-            let next_pow_2 = n.next_power_of_two();
-
-            let num_stages = u32::ilog2(next_pow_2);
-            let mut total_iterations = 0usize;
-            for stage in 0..num_stages as usize {
-                total_iterations += stage + 1;
+        if replaced && particle_system.particles.len() == config.particle_count as usize
+        {
+            let mut pipeline_buffers = create_pipeline_buffers(
+                &render_device,
+                &render_queue,
+                &render_pipeline,
+                &config,
+            );
+            pipeline_buffers.generation = particle_system.generation;
+
+            let mut particle_upload = ParticleUpload::new(&particle_system.particles);
+            particle_upload.upload_chunk(&render_queue, &pipeline_buffers.particle_buffer);
+            commands.entity(entity).insert(pipeline_buffers);
+
+            if particle_upload.is_finished()
+            {
+                commands.entity(entity).remove::<ParticleUpload>();
             }
-            const UNIFORM_ALIGNMENT: usize = 256;
-            let aligned_size = ((std::mem::size_of::<SortingParams>() + UNIFORM_ALIGNMENT - 1) 
-                            / UNIFORM_ALIGNMENT) * UNIFORM_ALIGNMENT;
-
-            let sorting_params_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("Sorting Params Buffer"),
-                size: (total_iterations as u64 * aligned_size as u64),
-                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-
-            // Create aligned parameter data
-            let mut sorting_buffer_data = vec![0u8; total_iterations * UNIFORM_ALIGNMENT];
-            let mut iteration = 0;
-
-            for stage_index in 0..num_stages {
-                for step_index in 0..=stage_index {
-                    let group_width = 1 << (stage_index - step_index);
-                    let group_height = 2 * group_width - 1;
-                    let params = SortingParams { 
-                        n: next_pow_2, 
-                        group_width, 
-                        group_height, 
-                        step_index 
-                    };
-                    
-                    // Write at aligned offset
-                    let offset = iteration * UNIFORM_ALIGNMENT;
-                    sorting_buffer_data[offset..offset + std::mem::size_of::<SortingParams>()]
-                        .copy_from_slice(bytemuck::bytes_of(&params));
-                    
-                    iteration += 1;
-                }
+            else
+            {
+                commands.entity(entity).insert(particle_upload);
             }
-
-            // Write all parameters at once
-            render_queue.write_buffer(&sorting_params_buffer, 0, &sorting_buffer_data);
-
-            // spatial lookup offsets buffer
-            let spatial_lookup_offsets_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("spatial_lookup_offsets_buffer"),
-                size: (std::mem::size_of::<u32>() * config.particle_count as usize) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-            });
-            let spatial_lookup_offsets_buffer_size = spatial_lookup_offsets_buffer.size();
-            let spatial_lookup_offsets_buffer_size = std::num::NonZeroU64::new(spatial_lookup_offsets_buffer_size).unwrap();
-
-            // particle densities buffer
-            let particle_densities_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("particle_densities_buffer"),
-                size: (std::mem::size_of::<f32>() * 2 * config.particle_count as usize) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-            });
-            let particle_densities_buffer_size = particle_densities_buffer.size();
-            let particle_densities_buffer_size = std::num::NonZeroU64::new(particle_densities_buffer_size).unwrap();
-
-            // predicted positions buffer
-            let predictied_positions_buffer = render_device.create_buffer(&BufferDescriptor {
-                label: Some("predictied_positions_buffer"),
-                size: (std::mem::size_of::<f32>() * 2 * config.particle_count as usize) as u64,
-                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-                mapped_at_creation: false,
-            });
-            let predictied_positions_buffer_size = predictied_positions_buffer.size();
-            let predictied_positions_buffer_size = std::num::NonZeroU64::new(predictied_positions_buffer_size).unwrap();
-
-            let bind_group = get_bind_group(
-                "bind_group",
+        }
+        else if let Some(old_buffers) = pipeline_buffers.filter(|_| grid_outgrown && !uploading)
+        {
+            let mut pipeline_buffers = create_pipeline_buffers(
                 &render_device,
-                &render_pipeline.bind_group_layout,
-                &particle_buffer,
-                particle_buffer_size,
-                &config_buffer,
-                config_buffer_size,
-                &spatial_lookup_buffer,
-                spatial_lookup_buffer_size,
-                &spatial_lookup_offsets_buffer,
-                spatial_lookup_offsets_buffer_size,
-                &sorting_params_buffer,
-                &particle_densities_buffer,
-                particle_densities_buffer_size,
-                &predictied_positions_buffer,
-                predictied_positions_buffer_size,
+                &render_queue,
+                &render_pipeline,
+                &config,
             );
+            pipeline_buffers.generation = old_buffers.generation;
 
-            let quad_vertices: &[f32; 24] = &[
-                // x,    y,    u,    v
-                -0.5, -0.5, 0.0, 1.0, // bottom-left
-                0.5, -0.5, 1.0, 1.0, // bottom-right
-                -0.5,  0.5, 0.0, 0.0, // top-left
-                0.5, -0.5, 1.0, 1.0, // bottom-right
-                0.5,  0.5, 1.0, 0.0, // top-right
-                -0.5,  0.5, 0.0, 0.0, // top-left
-            ];
-
-            // Upload to GPU:
-            let vertex_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-                label: Some("quad_vertex_buffer"),
-                contents: bytemuck::cast_slice(quad_vertices),
-                usage: BufferUsages::VERTEX,
-            });
-            
-            commands.entity(entity).insert(GPUPipelineBuffers 
-            {
-                bind_group: bind_group,
-                vertex_buffer: vertex_buffer,
-                config_buffer: config_buffer,
-                spatial_lookup_buffer: spatial_lookup_buffer,
-                spatial_lookup_offsets_buffer: spatial_lookup_offsets_buffer,
-                particle_densities_buffer: particle_densities_buffer,
-                predictied_positions_buffer: predictied_positions_buffer,
-            });
+            // the per particle state the sim has built up comes along, submitted after the
+            // initial values create_pipeline_buffers wrote so it wins
+            let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor { label: Some("grid_regrow_encoder") });
+            encoder.copy_buffer_to_buffer(&old_buffers.particle_buffer, 0, &pipeline_buffers.particle_buffer, 0, old_buffers.particle_buffer.size());
+            encoder.copy_buffer_to_buffer(&old_buffers.particle_temperatures_buffer, 0, &pipeline_buffers.particle_temperatures_buffer, 0, old_buffers.particle_temperatures_buffer.size());
+            encoder.copy_buffer_to_buffer(&old_buffers.velocity_history_buffer, 0, &pipeline_buffers.velocity_history_buffer, 0, old_buffers.velocity_history_buffer.size());
+            encoder.copy_buffer_to_buffer(&old_buffers.trail_positions_buffer, 0, &pipeline_buffers.trail_positions_buffer, 0, old_buffers.trail_positions_buffer.size());
+            render_queue.submit(std::iter::once(encoder.finish()));
+            commands.entity(entity).insert(pipeline_buffers);
+        }
+        // Update the uniform buffer on the GPU with this system's config
+        else if let Some(pipeline_buffers) = pipeline_buffers
+        {
+            render_queue.write_buffer(&pipeline_buffers.config_buffer, 0, bytemuck::bytes_of(&config));
         }
     }
-    else 
+
+    // continue any in-progress particle upload, one chunk per frame (skipping uploads
+    // for buffers that are about to be replaced)
+    for (entity, pipeline_buffers, local_config, mut particle_upload) in upload_query.iter_mut()
     {
-        // currently hacky for making gui work, update view proj every frame
-        if let Ok(view) = camera_query.single() {
-            let view_matrix = view.world_from_view.compute_matrix().inverse();
-            let view_proj = view.clip_from_view * view_matrix;
-            config.view_proj = view_proj.to_cols_array_2d();
-        }
-        // Update time delta
-        config.frame_count += 1;
-        
-        // Update the uniform buffer on the GPU
-        if let Ok(render_particle_buffers) = pipeline_buffers_query.single() {
-            render_queue.write_buffer(
-                &render_particle_buffers.config_buffer,
-                0,
-                bytemuck::bytes_of(config.as_ref()),
-            );
+        if pipeline_buffers.particle_count != system_config(local_config, &config).particle_count { continue; }
+        particle_upload.upload_chunk(&render_queue, &pipeline_buffers.particle_buffer);
+        if particle_upload.is_finished()
+        {
+            commands.entity(entity).remove::<ParticleUpload>();
         }
     }
-}
\ No newline at end of file
+}
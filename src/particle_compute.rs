@@ -8,11 +8,20 @@ use bevy::{
 
 use crate::{particle_compute::render_graph::NodeRunError, ParticleConfig};
 use crate::ParticleSystem;
-use crate::particle_buffers::GPUPipelineBuffers;
-use crate::util::{get_bind_group_layout, get_compute_pipeline_descriptor};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::{system_config, ParticleSystemConfig};
+use crate::util::{get_bind_group_layout, get_compute_pipeline_descriptor, COMPUTE_STORAGE_BUFFERS};
+use crate::stats::{stats_workgroups, StatsShared};
+use crate::pressure_probe::PressureProbeShared;
+use crate::sort_backend::SortBackend;
+use crate::cpu_backend::SimBackend;
+use crate::parameter_gui::SolverKind;
+use crate::particle_render::VELOCITY_FIELD_MAX_SAMPLES;
+use crate::gpu_timing::{GpuPhase, GpuTiming};
 
 const WORKGROUP_SIZE: u32 = 64;
-const UNIFORM_ALIGNMENT: usize = 256;
+pub const SCAN_BLOCK_SIZE: u32 = 256;   // keys prefix summed per workgroup, must match compute_shader.wgsl
+const COMPENSATED_SUMMATION_DEF: &str = "COMPENSATED_SUMMATION";
 
 #[derive(RenderLabel, Hash, Debug, Eq, PartialEq, Clone)]
 pub struct ParticleComputeLabel;
@@ -20,11 +29,37 @@ pub struct ParticleComputeLabel;
 #[derive(Resource)]
 pub struct ParticleComputePipeline 
 {
+    compute_clear_key_counts_pipeline_id: CachedComputePipelineId,
     compute_grid_pipeline_id: CachedComputePipelineId,
+    compute_scan_key_counts_pipeline_id: CachedComputePipelineId,
+    compute_scan_block_sums_pipeline_id: CachedComputePipelineId,
+    compute_add_block_offsets_pipeline_id: CachedComputePipelineId,
     compute_sort_particles_pipeline_id: CachedComputePipelineId,
-    compute_spatial_lookup_offsets_pipeline_id: CachedComputePipelineId,
+    compute_order_cell_contents_pipeline_id: CachedComputePipelineId,
     compute_pre_sim_step_pipeline_id: CachedComputePipelineId,
     compute_sim_step_pipeline_id: CachedComputePipelineId,
+    compute_pre_sim_step_compensated_pipeline_id: CachedComputePipelineId,
+    compute_sim_step_compensated_pipeline_id: CachedComputePipelineId,
+    compute_pbf_predict_pipeline_id: CachedComputePipelineId,
+    compute_pbf_lambdas_pipeline_id: CachedComputePipelineId,
+    compute_pbf_corrections_pipeline_id: CachedComputePipelineId,
+    compute_pbf_apply_pipeline_id: CachedComputePipelineId,
+    compute_pbf_finalize_pipeline_id: CachedComputePipelineId,
+    compute_reduce_max_speed_pipeline_id: CachedComputePipelineId,
+    compute_record_trail_positions_pipeline_id: CachedComputePipelineId,
+    compute_sample_velocity_field_pipeline_id: CachedComputePipelineId,
+    compute_clear_scalar_grid_pipeline_id: CachedComputePipelineId,
+    compute_splat_scalar_grid_pipeline_id: CachedComputePipelineId,
+    compute_resolve_grid_velocities_pipeline_id: CachedComputePipelineId,
+    compute_advect_scalar_field_pipeline_id: CachedComputePipelineId,
+    compute_grid_divergence_pipeline_id: CachedComputePipelineId,
+    compute_jacobi_forward_pipeline_id: CachedComputePipelineId,
+    compute_jacobi_backward_pipeline_id: CachedComputePipelineId,
+    compute_project_grid_pipeline_id: CachedComputePipelineId,
+    compute_transfer_grid_pipeline_id: CachedComputePipelineId,
+    compute_clear_stats_histogram_pipeline_id: CachedComputePipelineId,
+    compute_reduce_stats_pipeline_id: CachedComputePipelineId,
+    compute_sample_pressure_probes_pipeline_id: CachedComputePipelineId,
 }
 
 impl FromWorld for ParticleComputePipeline 
@@ -32,9 +67,20 @@ impl FromWorld for ParticleComputePipeline
     // called when Pipeline is created
     fn from_world(world: &mut World) -> Self 
     {
+        let sort_backend = SortBackend::select(world);
+
         // get render device
         let render_device = world.resource::<RenderDevice>();
 
+        // the pipelines would fail validation and never compile, most browsers' WebGPU adapters
+        // expose fewer storage buffers per stage than the compute layout binds
+        let max_storage_buffers = render_device.limits().max_storage_buffers_per_shader_stage;
+        if max_storage_buffers < COMPUTE_STORAGE_BUFFERS
+        {
+            error!("[Setup] The device allows {} storage buffers per shader stage but the compute shader binds {}, the simulation can't run on it",
+                max_storage_buffers, COMPUTE_STORAGE_BUFFERS);
+        }
+
         // get shader handle
         let shader_handle = world.resource::<AssetServer>().load("compute_shader.wgsl");
         
@@ -44,19 +90,35 @@ impl FromWorld for ParticleComputePipeline
         // create the render pipeline and store it in the pipeline cache
         let pipeline_cache = world.resource_mut::<PipelineCache>();
         
-        // pipeline for grid creation and cell binning
+        // reset the per cell key particle counts
+        let compute_clear_key_counts_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "clear_key_counts")
+        );
+
+        // pipeline for grid creation and cell binning, counting particles per key
         let compute_grid_pipeline_id = pipeline_cache.queue_compute_pipeline(
             get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "bin_particles_in_grid")
         );
 
-        // need to sort the array of grid cell keys here
+        // prefix sum the key counts into the spatial lookup offsets
+        let compute_scan_key_counts_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            with_shader_defs(get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "scan_key_counts"), sort_backend.shader_defs())
+        );
+        let compute_scan_block_sums_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            with_shader_defs(get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "scan_block_sums"), sort_backend.shader_defs())
+        );
+        let compute_add_block_offsets_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "add_block_offsets")
+        );
+
+        // scatter the grid cell keys into sorted order
         let compute_sort_particles_pipeline_id = pipeline_cache.queue_compute_pipeline(
             get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "sort_particles")
         );
 
-        // figure out offsets into spatial lookup buffer
-        let compute_spatial_lookup_offsets_pipeline_id = pipeline_cache.queue_compute_pipeline(
-            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "calculate_spatial_lookup_offsets")
+        // order particles within each cell by index, seeded runs only
+        let compute_order_cell_contents_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "order_cell_contents")
         );
 
         // calculate predicted positions and densities
@@ -68,19 +130,189 @@ impl FromWorld for ParticleComputePipeline
         let compute_sim_step_pipeline_id = pipeline_cache.queue_compute_pipeline(
             get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "simulation_step")
         );
+
+        // the same two steps with Kahan summed neighbor loops, used while compensated summation is on
+        let compute_pre_sim_step_compensated_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            with_compensated_summation(get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pre_simulation_step"))
+        );
+        let compute_sim_step_compensated_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            with_compensated_summation(get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "simulation_step"))
+        );
+
+        // position based fluids: predict, then lambda, correction and apply passes per iteration
+        let compute_pbf_predict_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pbf_predict")
+        );
+        let compute_pbf_lambdas_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pbf_compute_lambdas")
+        );
+        let compute_pbf_corrections_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pbf_compute_corrections")
+        );
+        let compute_pbf_apply_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pbf_apply_corrections")
+        );
+        let compute_pbf_finalize_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "pbf_finalize")
+        );
+
+        // largest particle speed for the adaptive substep count
+        let compute_reduce_max_speed_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "reduce_max_speed")
+        );
+
+        // ring of recent positions for the trails
+        let compute_record_trail_positions_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "record_trail_positions")
+        );
+
+        // coarse grid of averaged particle velocities for the velocity field view
+        let compute_sample_velocity_field_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "sample_velocity_field")
+        );
+
+        // background grid: clear, splat particle velocities, resolve cell velocities
+        let compute_clear_scalar_grid_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "clear_scalar_grid")
+        );
+        let compute_splat_scalar_grid_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "splat_particles_to_scalar_grid")
+        );
+        let compute_resolve_grid_velocities_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "resolve_grid_velocities")
+        );
+
+        // smoke: advect the scalar field
+        let compute_advect_scalar_field_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "advect_scalar_field")
+        );
+
+        // FLIP/PIC: pressure projection on the grid and transfer back to particles
+        let compute_grid_divergence_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "compute_grid_divergence")
+        );
+        let compute_jacobi_forward_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "jacobi_pressure_forward")
+        );
+        let compute_jacobi_backward_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "jacobi_pressure_backward")
+        );
+        let compute_project_grid_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "project_grid_velocities")
+        );
+        let compute_transfer_grid_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "transfer_grid_to_particles")
+        );
+
+
+        // stats overlay: per workgroup reduction of the particle stats and the cell histogram
+        let compute_clear_stats_histogram_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "clear_stats_histogram")
+        );
+        let compute_reduce_stats_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            with_shader_defs(get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "reduce_stats"), sort_backend.shader_defs())
+        );
+
+        // pressure probes: density and pressure sampled at the user's probe points
+        let compute_sample_pressure_probes_pipeline_id = pipeline_cache.queue_compute_pipeline(
+            get_compute_pipeline_descriptor(&bind_group_layout, &shader_handle, "sample_pressure_probes")
+        );
         
         // return the ParticleComputePipeline object
         ParticleComputePipeline 
         {  
+            compute_clear_key_counts_pipeline_id: compute_clear_key_counts_pipeline_id,
             compute_grid_pipeline_id: compute_grid_pipeline_id,
+            compute_scan_key_counts_pipeline_id: compute_scan_key_counts_pipeline_id,
+            compute_scan_block_sums_pipeline_id: compute_scan_block_sums_pipeline_id,
+            compute_add_block_offsets_pipeline_id: compute_add_block_offsets_pipeline_id,
             compute_sort_particles_pipeline_id: compute_sort_particles_pipeline_id,
-            compute_spatial_lookup_offsets_pipeline_id: compute_spatial_lookup_offsets_pipeline_id,
+            compute_order_cell_contents_pipeline_id: compute_order_cell_contents_pipeline_id,
             compute_sim_step_pipeline_id: compute_sim_step_pipeline_id,
             compute_pre_sim_step_pipeline_id: compute_pre_sim_step_pipeline_id,
+            compute_pre_sim_step_compensated_pipeline_id: compute_pre_sim_step_compensated_pipeline_id,
+            compute_sim_step_compensated_pipeline_id: compute_sim_step_compensated_pipeline_id,
+            compute_pbf_predict_pipeline_id: compute_pbf_predict_pipeline_id,
+            compute_pbf_lambdas_pipeline_id: compute_pbf_lambdas_pipeline_id,
+            compute_pbf_corrections_pipeline_id: compute_pbf_corrections_pipeline_id,
+            compute_pbf_apply_pipeline_id: compute_pbf_apply_pipeline_id,
+            compute_pbf_finalize_pipeline_id: compute_pbf_finalize_pipeline_id,
+            compute_reduce_max_speed_pipeline_id: compute_reduce_max_speed_pipeline_id,
+            compute_record_trail_positions_pipeline_id: compute_record_trail_positions_pipeline_id,
+            compute_sample_velocity_field_pipeline_id: compute_sample_velocity_field_pipeline_id,
+            compute_clear_scalar_grid_pipeline_id: compute_clear_scalar_grid_pipeline_id,
+            compute_splat_scalar_grid_pipeline_id: compute_splat_scalar_grid_pipeline_id,
+            compute_resolve_grid_velocities_pipeline_id: compute_resolve_grid_velocities_pipeline_id,
+            compute_advect_scalar_field_pipeline_id: compute_advect_scalar_field_pipeline_id,
+            compute_grid_divergence_pipeline_id: compute_grid_divergence_pipeline_id,
+            compute_jacobi_forward_pipeline_id: compute_jacobi_forward_pipeline_id,
+            compute_jacobi_backward_pipeline_id: compute_jacobi_backward_pipeline_id,
+            compute_project_grid_pipeline_id: compute_project_grid_pipeline_id,
+            compute_transfer_grid_pipeline_id: compute_transfer_grid_pipeline_id,
+            compute_clear_stats_histogram_pipeline_id: compute_clear_stats_histogram_pipeline_id,
+            compute_reduce_stats_pipeline_id: compute_reduce_stats_pipeline_id,
+            compute_sample_pressure_probes_pipeline_id: compute_sample_pressure_probes_pipeline_id,
         }
     }
 }
 
+fn with_shader_defs(mut descriptor: ComputePipelineDescriptor, shader_defs: Vec<ShaderDefVal>) -> ComputePipelineDescriptor
+{
+    descriptor.shader_defs.extend(shader_defs);
+    descriptor
+}
+
+fn with_compensated_summation(mut descriptor: ComputePipelineDescriptor) -> ComputePipelineDescriptor
+{
+    descriptor.shader_defs.push(COMPENSATED_SUMMATION_DEF.into());
+    descriptor
+}
+
+impl ParticleComputePipeline
+{
+    // number of compute pipelines finished compiling, out of the total queued
+    pub fn pipeline_progress(&self, pipeline_cache: &PipelineCache) -> (u32, u32)
+    {
+        let pipeline_ids = [
+            self.compute_clear_key_counts_pipeline_id,
+            self.compute_grid_pipeline_id,
+            self.compute_scan_key_counts_pipeline_id,
+            self.compute_scan_block_sums_pipeline_id,
+            self.compute_add_block_offsets_pipeline_id,
+            self.compute_sort_particles_pipeline_id,
+            self.compute_order_cell_contents_pipeline_id,
+            self.compute_pre_sim_step_pipeline_id,
+            self.compute_sim_step_pipeline_id,
+            self.compute_pre_sim_step_compensated_pipeline_id,
+            self.compute_sim_step_compensated_pipeline_id,
+            self.compute_pbf_predict_pipeline_id,
+            self.compute_pbf_lambdas_pipeline_id,
+            self.compute_pbf_corrections_pipeline_id,
+            self.compute_pbf_apply_pipeline_id,
+            self.compute_pbf_finalize_pipeline_id,
+            self.compute_reduce_max_speed_pipeline_id,
+            self.compute_record_trail_positions_pipeline_id,
+            self.compute_sample_velocity_field_pipeline_id,
+            self.compute_clear_scalar_grid_pipeline_id,
+            self.compute_splat_scalar_grid_pipeline_id,
+            self.compute_resolve_grid_velocities_pipeline_id,
+            self.compute_advect_scalar_field_pipeline_id,
+            self.compute_grid_divergence_pipeline_id,
+            self.compute_jacobi_forward_pipeline_id,
+            self.compute_jacobi_backward_pipeline_id,
+            self.compute_project_grid_pipeline_id,
+            self.compute_transfer_grid_pipeline_id,
+            self.compute_clear_stats_histogram_pipeline_id,
+            self.compute_reduce_stats_pipeline_id,
+            self.compute_sample_pressure_probes_pipeline_id,
+        ];
+        let ready = pipeline_ids.iter()
+            .filter(|id| matches!(pipeline_cache.get_compute_pipeline_state(**id), CachedPipelineState::Ok(_)))
+            .count();
+        (ready as u32, pipeline_ids.len() as u32)
+    }
+}
+
 pub struct ParticleComputeNode 
 {
     particle_system: QueryState<Entity, With<ParticleSystem>>,
@@ -97,98 +329,312 @@ impl Node for ParticleComputeNode
     {
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<ParticleComputePipeline>();
-        let config = world.resource::<ParticleConfig>();
+        let global_config = world.resource::<ParticleConfig>();
+        let stats_shared = world.resource::<StatsShared>();
+        let pressure_probe_count = world.resource::<PressureProbeShared>().probe_count();
+        let cpu_backend = *world.resource::<SimBackend>() == SimBackend::Cpu;
+        let gpu_timing = world.resource::<GpuTiming>();
 
         for entity in self.particle_system.iter_manual(world) {
+            // don't simulate until the initial particle data is fully on the GPU
+            if world.get::<ParticleUpload>(entity).is_some() { continue; }
+
+            let config = &system_config(world.get::<ParticleSystemConfig>(entity), global_config);
+
+            // paused: leave the buffers untouched so the render node keeps drawing the frozen state
+            if config.paused != 0 { continue; }
+
+            // the CPU backend writes the main system's particles straight into its buffer
+            if cpu_backend && world.get::<ParticleSystemConfig>(entity).is_none() { continue; }
+
             if let Some(pipeline_buffers) = world.get::<GPUPipelineBuffers>(entity) {
+                // buffers are reallocated in prepare once the new particle data arrives, or once an
+                // upload that held back a grown spatial grid finishes
+                if pipeline_buffers.particle_count != config.particle_count { continue; }
+                if pipeline_buffers.spatial_grid_cells < config.spatial_grid_cells() { continue; }
 
-                // Pass 1: assign particles to cells in uniform grid
+                // the main system's substeps are timed for the stats overlay, up to the first one
+                // that skipped a phase
+                let timed = gpu_timing.is_active() && world.get::<ParticleSystemConfig>(entity).is_none();
+                let timestamp_writes = |phases: &[GpuPhase], index: usize, substep: u32| {
+                    if timed { gpu_timing.compute_pass_writes(phases, index, substep) } else { None }
+                };
+                let mut timed_substeps = 0;
+
+                // Passes 1-4 run once per substep, each advancing the particles by substep_delta_time
+                for substep in 0..config.substeps.max(1)
                 {
-                    let mut pass = render_context.command_encoder().begin_compute_pass(&ComputePassDescriptor::default());
+                    let mut substep_timed = true;
 
-                    if let Some(pipeline_id_grid) = pipeline_cache.get_compute_pipeline(pipeline.compute_grid_pipeline_id)
+                    // Passes 1-2: assign particles to cells in uniform grid and counting sort them by cell key;
+                    // the prefix sum of the per key counts doubles as the spatial lookup offsets. The
+                    // subgroup backend runs the same passes with its scan kernels specialized
                     {
-                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[0]);
-                        pass.set_pipeline(pipeline_id_grid);
-                        pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                        let particle_workgroups = (config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+                        let cell_workgroups = config.spatial_grid_cells().div_ceil(WORKGROUP_SIZE);
+                        let scan_blocks = config.spatial_grid_cells().div_ceil(SCAN_BLOCK_SIZE);
+                        let mut sort_passes = vec![
+                            (pipeline.compute_clear_key_counts_pipeline_id, cell_workgroups, GpuPhase::Grid),
+                            (pipeline.compute_grid_pipeline_id, particle_workgroups, GpuPhase::Grid),
+                            (pipeline.compute_scan_key_counts_pipeline_id, scan_blocks, GpuPhase::Offsets),
+                            (pipeline.compute_scan_block_sums_pipeline_id, 1, GpuPhase::Offsets),
+                            (pipeline.compute_add_block_offsets_pipeline_id, cell_workgroups, GpuPhase::Offsets),
+                            (pipeline.compute_sort_particles_pipeline_id, particle_workgroups, GpuPhase::Sort),
+                        ];
+                        if config.deterministic_order != 0
+                        {
+                            sort_passes.push((pipeline.compute_order_cell_contents_pipeline_id, cell_workgroups, GpuPhase::Sort));
+                        }
+                        let sort_phases: Vec<GpuPhase> = sort_passes.iter().map(|(_, _, phase)| *phase).collect();
+
+                        // each pass consumes the previous one's output, so only run once all have compiled
+                        let sort_pipelines: Option<Vec<_>> = sort_passes.iter()
+                            .map(|(pipeline_id, workgroups, _)| {
+                                pipeline_cache.get_compute_pipeline(*pipeline_id).map(|compute_pipeline| (compute_pipeline, *workgroups))
+                            })
+                            .collect();
+                        substep_timed &= sort_pipelines.is_some();
+
+                        for (index, (compute_pipeline, workgroups)) in sort_pipelines.into_iter().flatten().enumerate()
+                        {
+                            let mut pass = render_context.command_encoder()
+                                .begin_compute_pass(&ComputePassDescriptor {
+                                    label: None,
+                                    timestamp_writes: timestamp_writes(&sort_phases, index, substep),
+                                });
+
+                            pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                            pass.set_pipeline(compute_pipeline);
+                            pass.dispatch_workgroups(workgroups, 1, 1);
+                        }
                     }
-                }
-                
-                // Pass 2: sort particles by grid cell key
-                {
-                    if let Some(pipeline_id_sort) =
-                        pipeline_cache.get_compute_pipeline(pipeline.compute_sort_particles_pipeline_id)
+
+                    if SolverKind::from_u32(config.solver) == SolverKind::Pbf
+                    {
+                        // Passes 3-4 (PBF): predict positions, project them onto the density constraint
+                        // pbf_iterations times, then viscosity, collisions and bounds
+                        let mut pbf_passes = vec![pipeline.compute_pbf_predict_pipeline_id];
+                        for _ in 0..config.pbf_iterations.max(1)
+                        {
+                            pbf_passes.extend([
+                                pipeline.compute_pbf_lambdas_pipeline_id,
+                                pipeline.compute_pbf_corrections_pipeline_id,
+                                pipeline.compute_pbf_apply_pipeline_id,
+                            ]);
+                        }
+                        pbf_passes.push(pipeline.compute_pbf_finalize_pipeline_id);
+                        // the prediction is timed as the pre-sim, the constraint iterations and finalize as the sim
+                        let pbf_phases: Vec<GpuPhase> = (0..pbf_passes.len())
+                            .map(|index| if index == 0 { GpuPhase::PreSim } else { GpuPhase::Sim })
+                            .collect();
+
+                        // the corrections are parked in the velocities mid step, so only run once all have compiled
+                        let pbf_pipelines: Option<Vec<_>> = pbf_passes.iter()
+                            .map(|pipeline_id| pipeline_cache.get_compute_pipeline(*pipeline_id))
+                            .collect();
+                        substep_timed &= pbf_pipelines.is_some();
+
+                        for (index, compute_pipeline) in pbf_pipelines.into_iter().flatten().enumerate()
+                        {
+                            let mut pass = render_context.command_encoder()
+                                .begin_compute_pass(&ComputePassDescriptor {
+                                    label: None,
+                                    timestamp_writes: timestamp_writes(&pbf_phases, index, substep),
+                                });
+
+                            pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                            pass.set_pipeline(compute_pipeline);
+                            pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                        }
+                    }
+                    else
                     {
-                        let n = config.particle_count;
-                        let next_pow_2 = n.next_power_of_two();
+                        let (pre_sim_step_pipeline_id, sim_step_pipeline_id) = if config.compensated_summation != 0 {
+                            (pipeline.compute_pre_sim_step_compensated_pipeline_id, pipeline.compute_sim_step_compensated_pipeline_id)
+                        } else {
+                            (pipeline.compute_pre_sim_step_pipeline_id, pipeline.compute_sim_step_pipeline_id)
+                        };
+
+                        // Pass 3: update predicted positions and particle densities
+                        {
+                            let mut pass = render_context.command_encoder()
+                                .begin_compute_pass(&ComputePassDescriptor {
+                                    label: None,
+                                    timestamp_writes: timestamp_writes(&[GpuPhase::PreSim], 0, substep),
+                                });
+
+                            if let Some(pipeline_id_pre_sim_step) =
+                                pipeline_cache.get_compute_pipeline(pre_sim_step_pipeline_id)
+                            {
+                                pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                                pass.set_pipeline(pipeline_id_pre_sim_step);
+                                pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                            }
+                        } 
 
-                        let num_pairs = next_pow_2 / 2;
-                        let num_stages = u32::ilog2(next_pow_2);
-                        let mut iteration = 0;
-                        for stage_index in 0..num_stages
+                        // Pass 4: integrate particle dynamics
                         {
-                            for _ in 0..=stage_index    // step_index
+                            let mut pass = render_context.command_encoder()
+                                .begin_compute_pass(&ComputePassDescriptor {
+                                    label: None,
+                                    timestamp_writes: timestamp_writes(&[GpuPhase::Sim], 0, substep),
+                                });
+
+                            if let Some(pipeline_id_sim_step) =
+                                pipeline_cache.get_compute_pipeline(sim_step_pipeline_id)
                             {
-                                // Create pass in a scope so it's dropped after dispatch
-                                {
-                                    let mut pass = render_context.command_encoder()
-                                        .begin_compute_pass(&ComputePassDescriptor::default());
-                                    
-                                    pass.set_pipeline(pipeline_id_sort);
-
-                                    let dynamic_offset = (iteration * UNIFORM_ALIGNMENT) as u32;
-                                    pass.set_bind_group(0, &pipeline_buffers.bind_group, &[dynamic_offset]);
-                                    
-                                    let num_workgroups = (num_pairs + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;  // 64 threads per workgroup
-                                    pass.dispatch_workgroups(num_workgroups, 1, 1);
-                                } // Pass is dropped here, ensuring completion
-                                iteration += 1;
+                                pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                                pass.set_pipeline(pipeline_id_sim_step);
+                                pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
                             }
                         }
                     }
+
+                    if substep_timed && timed_substeps == substep
+                    {
+                        timed_substeps += 1;
+                    }
+                }
+                if timed
+                {
+                    gpu_timing.mark_compute_timed(timed_substeps);
                 }
 
-                // Pass 3: Calculate grid start idxs
+                // Trails record the frame's positions into their ring, once after the last substep
+                if config.trail_length > 1
                 {
                     let mut pass = render_context.command_encoder()
                         .begin_compute_pass(&ComputePassDescriptor::default());
 
-                    if let Some(pipeline_id_spatial_lookup_offsets) =
-                        pipeline_cache.get_compute_pipeline(pipeline.compute_spatial_lookup_offsets_pipeline_id)
+                    if let Some(compute_pipeline) =
+                        pipeline_cache.get_compute_pipeline(pipeline.compute_record_trail_positions_pipeline_id)
                     {
-                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[0]);
-                        pass.set_pipeline(pipeline_id_spatial_lookup_offsets);
+                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                        pass.set_pipeline(compute_pipeline);
                         pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
                     }
-                } 
+                }
 
-                // Pass 4: update predicted positions and particle densities
+                // Velocity field samples, sized by the shader from the screen bounds, so every possible sample gets an invocation
+                if config.velocity_field_mode != 0
                 {
                     let mut pass = render_context.command_encoder()
                         .begin_compute_pass(&ComputePassDescriptor::default());
 
-                    if let Some(pipeline_id_pre_sim_step) =
-                        pipeline_cache.get_compute_pipeline(pipeline.compute_pre_sim_step_pipeline_id)
+                    if let Some(compute_pipeline) =
+                        pipeline_cache.get_compute_pipeline(pipeline.compute_sample_velocity_field_pipeline_id)
+                    {
+                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                        pass.set_pipeline(compute_pipeline);
+                        pass.dispatch_workgroups((VELOCITY_FIELD_MAX_SAMPLES + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                    }
+                }
+
+                // Max speed reduction for the next frames' substep count, on the main system while adaptive substepping is on
+                if config.max_substeps > 1 && world.get::<ParticleSystemConfig>(entity).is_none()
+                {
+                    if let Some(compute_pipeline) =
+                        pipeline_cache.get_compute_pipeline(pipeline.compute_reduce_max_speed_pipeline_id)
                     {
-                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[0]);
-                        pass.set_pipeline(pipeline_id_pre_sim_step);
+                        render_context.command_encoder().clear_buffer(&pipeline_buffers.max_speed_buffer, 0, None);
+
+                        let mut pass = render_context.command_encoder()
+                            .begin_compute_pass(&ComputePassDescriptor::default());
+
+                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                        pass.set_pipeline(compute_pipeline);
                         pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
                     }
-                } 
+                }
 
-                // Pass 5: integrate particle dynamics
+                // Passes 5+: background grid for smoke advection, divergence view and the FLIP/PIC projection
+                let smoke_enabled = config.smoke_enabled != 0;
+                let flip_enabled = config.flip_enabled != 0;
+                let divergence_view = config.divergence_view != 0;
+                if smoke_enabled || flip_enabled || divergence_view
+                {
+                    let scalar_grid_cells = config.scalar_grid_width * config.scalar_grid_height;
+                    let mut scalar_grid_passes = vec![
+                        (pipeline.compute_clear_scalar_grid_pipeline_id, scalar_grid_cells),
+                        (pipeline.compute_splat_scalar_grid_pipeline_id, config.particle_count),
+                        (pipeline.compute_resolve_grid_velocities_pipeline_id, scalar_grid_cells),
+                    ];
+                    if flip_enabled || divergence_view
+                    {
+                        scalar_grid_passes.push((pipeline.compute_grid_divergence_pipeline_id, scalar_grid_cells));
+                    }
+                    if flip_enabled
+                    {
+                        // jacobi iterations ping-pong between two pressure fields, so run them in pairs
+                        for _ in 0..(config.pressure_iterations / 2).max(1)
+                        {
+                            scalar_grid_passes.push((pipeline.compute_jacobi_forward_pipeline_id, scalar_grid_cells));
+                            scalar_grid_passes.push((pipeline.compute_jacobi_backward_pipeline_id, scalar_grid_cells));
+                        }
+                        scalar_grid_passes.push((pipeline.compute_project_grid_pipeline_id, scalar_grid_cells));
+                        scalar_grid_passes.push((pipeline.compute_transfer_grid_pipeline_id, config.particle_count));
+                    }
+                    if smoke_enabled
+                    {
+                        scalar_grid_passes.push((pipeline.compute_advect_scalar_field_pipeline_id, scalar_grid_cells));
+                    }
+
+                    for (pipeline_id, invocations) in scalar_grid_passes
+                    {
+                        let mut pass = render_context.command_encoder()
+                            .begin_compute_pass(&ComputePassDescriptor::default());
+
+                        if let Some(compute_pipeline) = pipeline_cache.get_compute_pipeline(pipeline_id)
+                        {
+                            pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                            pass.set_pipeline(compute_pipeline);
+                            pass.dispatch_workgroups((invocations + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                        }
+                    }
+                }
+
+                // Pressure probes sample this frame's densities on the main system, one workgroup for all of them
+                if pressure_probe_count > 0 && world.get::<ParticleSystemConfig>(entity).is_none()
                 {
                     let mut pass = render_context.command_encoder()
                         .begin_compute_pass(&ComputePassDescriptor::default());
 
-                    if let Some(pipeline_id_sim_step) =
-                        pipeline_cache.get_compute_pipeline(pipeline.compute_sim_step_pipeline_id)
+                    if let Some(compute_pipeline) =
+                        pipeline_cache.get_compute_pipeline(pipeline.compute_sample_pressure_probes_pipeline_id)
                     {
-                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[0]);
-                        pass.set_pipeline(pipeline_id_sim_step);
-                        pass.dispatch_workgroups((config.particle_count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE, 1, 1);
+                        pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                        pass.set_pipeline(compute_pipeline);
+                        pass.dispatch_workgroups(1, 1, 1);
                     }
-                } 
+                }
+
+                // Stats reduction for the overlay, on the main system and only when a sample is due
+                if stats_shared.reduction_requested() && world.get::<ParticleSystemConfig>(entity).is_none()
+                {
+                    let stats_passes = [
+                        (pipeline.compute_clear_stats_histogram_pipeline_id, 1),
+                        (pipeline.compute_reduce_stats_pipeline_id, stats_workgroups(config.particle_count)),
+                    ];
+                    let stats_pipelines: Option<Vec<_>> = stats_passes.iter()
+                        .map(|(pipeline_id, workgroups)| {
+                            pipeline_cache.get_compute_pipeline(*pipeline_id).map(|compute_pipeline| (compute_pipeline, *workgroups))
+                        })
+                        .collect();
+
+                    if let Some(stats_pipelines) = stats_pipelines
+                    {
+                        for (compute_pipeline, workgroups) in stats_pipelines
+                        {
+                            let mut pass = render_context.command_encoder()
+                                .begin_compute_pass(&ComputePassDescriptor::default());
+
+                            pass.set_bind_group(0, &pipeline_buffers.bind_group, &[]);
+                            pass.set_pipeline(compute_pipeline);
+                            pass.dispatch_workgroups(workgroups, 1, 1);
+                        }
+                        stats_shared.mark_dispatched();
+                    }
+                }
             }
         }
         Ok(())
@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+
+const PROBE_INTERVAL: u32 = 10;     // frames between particle position readbacks
+
+// shared between main and render worlds so positions are only read back while something uses them
+#[derive(Resource, Clone, Default)]
+pub struct ParticleProbeShared
+{
+    enabled: Arc<AtomicBool>,
+    positions: Arc<Mutex<Option<Vec<Vec2>>>>,
+}
+
+// render world side of the position readback
+#[derive(Resource)]
+pub struct ParticleProbeReadback
+{
+    particles: GpuReadback,
+    frame_count: u32,
+}
+
+impl Default for ParticleProbeReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("probe_particle_readback_buffer"), frame_count: 0 }
+    }
+}
+
+// latest particle positions, indexed like the particle buffer (a few frames old). Systems
+// that need positions set `wanted` every frame before update_particle_probe runs.
+#[derive(Resource, Default)]
+pub struct ParticleProbe
+{
+    pub wanted: bool,
+    pub positions: Option<Vec<Vec2>>,
+    pub updated: bool,      // positions were refreshed this frame
+}
+
+pub fn read_back_particle_probe(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    shared: Res<ParticleProbeShared>,
+    mut probe_readback: ResMut<ParticleProbeReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(particles) = probe_readback.particles.try_read::<[f32; 8]>(&render_device)
+    {
+        let positions = particles.iter().map(|particle| Vec2::new(particle[0], particle[1])).collect();
+        *shared.positions.lock().unwrap() = Some(positions);
+    }
+
+    if !shared.enabled.load(Ordering::Relaxed) { return; }
+
+    probe_readback.frame_count += 1;
+    if probe_readback.frame_count < PROBE_INTERVAL || !probe_readback.particles.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        let size = (std::mem::size_of::<[f32; 8]>() * pipeline_buffers.particle_count as usize) as u64;
+        if !budget.try_acquire("particle_probe", size, 1) { return; }
+
+        probe_readback.frame_count = 0;
+        probe_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            size,
+        );
+    }
+}
+
+pub fn update_particle_probe(
+    shared: Res<ParticleProbeShared>,
+    mut probe: ResMut<ParticleProbe>,
+)
+{
+    shared.enabled.store(probe.wanted, Ordering::Relaxed);
+    probe.wanted = false;
+
+    probe.updated = false;
+    if let Some(positions) = shared.positions.lock().unwrap().take()
+    {
+        probe.positions = Some(positions);
+        probe.updated = true;
+    }
+}
@@ -3,25 +3,72 @@ use bevy::{
     render::{
         render_graph::{self, Node, RenderGraphContext, RenderLabel}, 
         render_resource::{*}, 
-        renderer::{RenderContext, RenderDevice},
-        view::ViewTarget,
+        renderer::{RenderAdapter, RenderContext, RenderDevice},
+        view::{ExtractedView, Msaa, ViewTarget},
     },
 };
 
 use crate::{particle_render::render_graph::NodeRunError, ParticleConfig};
 use crate::ParticleSystem;
-use crate::particle_buffers::GPUPipelineBuffers;
-use crate::util::{get_bind_group_layout, get_render_pipeline_descriptor};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::{system_config, ParticleSystemConfig};
+use crate::util::{
+    get_bind_group_layout, get_render_bind_group_layout, get_render_pipeline_descriptor, get_overlay_pipeline_descriptor, get_line_pipeline_descriptor,
+    get_surface_texture_bind_group_layout, get_surface_splat_pipeline_descriptor, get_surface_pass_pipeline_descriptor,
+};
+use crate::surface_render::{render_surface_thickness, SurfaceTextures, RENDER_MODE_SURFACE};
+use crate::heatmap_render::{render_density_heatmap, RENDER_MODE_HEATMAP};
+use crate::retro_render::{render_palette, RetroTextures};
+use crate::gpu_timing::GpuTiming;
+
 
+pub const TRAIL_HISTORY: u32 = 16;    // positions kept per particle for the trails, must match both shaders
+pub const VELOCITY_FIELD_MAX_SIDE: u32 = 64;    // velocity field samples per side at most, must match both shaders
+pub const VELOCITY_FIELD_MAX_SAMPLES: u32 = VELOCITY_FIELD_MAX_SIDE * VELOCITY_FIELD_MAX_SIDE;
+pub const STREAMLINE_STEPS: u32 = 12;           // segments traced from each sample, must match render_shader.wgsl
+const VELOCITY_FIELD_ARROWS: u32 = 1;           // VelocityFieldMode::Arrows
+const VELOCITY_FIELD_STREAMLINES: u32 = 2;      // VelocityFieldMode::Streamlines
 
 #[derive(RenderLabel, Hash, Debug, Eq, PartialEq, Clone)]
 pub struct ParticleRenderLabel;
 
+// MSAA settings the adapter can render the view with, offered in the Rendering section
+#[derive(Resource, Clone)]
+pub struct SupportedMsaa(pub Vec<Msaa>);
+
+impl SupportedMsaa
+{
+    pub fn from_adapter(render_adapter: &RenderAdapter) -> Self
+    {
+        let format_flags = render_adapter.get_texture_format_features(TextureFormat::Rgba8UnormSrgb).flags;
+        Self([Msaa::Off, Msaa::Sample2, Msaa::Sample4, Msaa::Sample8].into_iter()
+            .filter(|msaa| format_flags.sample_count_supported(msaa.samples()))
+            .collect())
+    }
+}
+
 #[derive(Resource)]
 pub struct ParticleRenderPipeline 
 {
     pub bind_group_layout: BindGroupLayout, // shared with compute shader
+    pub render_bind_group_layout: BindGroupLayout,  // read only view of it for the render shader
+    shader_handle: Handle<Shader>,
+    sample_count: u32,                      // the view pipelines below were queued for
     render_pipeline_id: CachedRenderPipelineId,
+    scalar_overlay_pipeline_id: CachedRenderPipelineId,
+    divergence_overlay_pipeline_id: CachedRenderPipelineId,
+    outline_pipeline_id: CachedRenderPipelineId,
+    palette_pipeline_id: CachedRenderPipelineId,
+    trail_pipeline_id: CachedRenderPipelineId,
+    velocity_field_pipeline_id: CachedRenderPipelineId,
+    pub surface_texture_layout: BindGroupLayout,
+    pub surface_sampler: Sampler,
+    pub surface_splat_pipeline_id: CachedRenderPipelineId,
+    pub surface_blur_horizontal_pipeline_id: CachedRenderPipelineId,
+    pub surface_blur_vertical_pipeline_id: CachedRenderPipelineId,
+    pub surface_composite_pipeline_id: CachedRenderPipelineId,
+    pub heatmap_splat_pipeline_id: CachedRenderPipelineId,
+    pub heatmap_composite_pipeline_id: CachedRenderPipelineId,
 }
 
 impl FromWorld for ParticleRenderPipeline 
@@ -35,22 +82,140 @@ impl FromWorld for ParticleRenderPipeline
         // get shader handle
         let shader_handle = world.resource::<AssetServer>().load("render_shader.wgsl");
         
-        // get bind group layout
+        // get bind group layouts
         let bind_group_layout = get_bind_group_layout(render_device);
+        let render_bind_group_layout = get_render_bind_group_layout(render_device);
+        let surface_texture_layout = get_surface_texture_bind_group_layout(render_device);
+        let surface_sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("surface_sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
 
         // create the render pipeline and store it in the pipeline cache
         let pipeline_cache = world.resource_mut::<PipelineCache>();
 
-        // queue the render pipeline
-        let render_pipeline_id = pipeline_cache.queue_render_pipeline(
-            get_render_pipeline_descriptor(&bind_group_layout, &shader_handle)
+        // queue the liquid surface passes: splat and separable blur, the composite is a view pipeline
+        let surface_splat_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_splat_pipeline_descriptor(&render_bind_group_layout, &shader_handle, "surface_splat_vertex", "surface_splat_fragment")
+        );
+        let surface_blur_horizontal_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(&render_bind_group_layout, &surface_texture_layout, &shader_handle, "surface_blur_horizontal", None)
+        );
+        let surface_blur_vertical_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(&render_bind_group_layout, &surface_texture_layout, &shader_handle, "surface_blur_vertical", None)
+        );
+
+        // queue the density heatmap splat, a kernel splat into the surface texture
+        let heatmap_splat_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_splat_pipeline_descriptor(&render_bind_group_layout, &shader_handle, "heatmap_splat_vertex", "heatmap_splat_fragment")
         );
 
-        ParticleRenderPipeline 
+        let mut pipeline = ParticleRenderPipeline 
         {  
             bind_group_layout,
-            render_pipeline_id
-        }
+            render_bind_group_layout,
+            shader_handle,
+            sample_count: 0,
+            render_pipeline_id: CachedRenderPipelineId::INVALID,
+            scalar_overlay_pipeline_id: CachedRenderPipelineId::INVALID,
+            divergence_overlay_pipeline_id: CachedRenderPipelineId::INVALID,
+            outline_pipeline_id: CachedRenderPipelineId::INVALID,
+            palette_pipeline_id: CachedRenderPipelineId::INVALID,
+            trail_pipeline_id: CachedRenderPipelineId::INVALID,
+            velocity_field_pipeline_id: CachedRenderPipelineId::INVALID,
+            surface_texture_layout,
+            surface_sampler,
+            surface_splat_pipeline_id,
+            surface_blur_horizontal_pipeline_id,
+            surface_blur_vertical_pipeline_id,
+            surface_composite_pipeline_id: CachedRenderPipelineId::INVALID,
+            heatmap_splat_pipeline_id,
+            heatmap_composite_pipeline_id: CachedRenderPipelineId::INVALID,
+        };
+        pipeline.queue_view_pipelines(&pipeline_cache, Msaa::default().samples());
+        pipeline
+    }
+}
+
+impl ParticleRenderPipeline
+{
+    // (re)queue the pipelines that draw into the view, whose sample count has to match the view's
+    // MSAA setting: particles, trails, the smoke and divergence overlays, the velocity field and the
+    // surface/heatmap composites, outline and retro palette pass. The retro textures follow the
+    // view's sample count too, so the same pipelines draw into them.
+    fn queue_view_pipelines(&mut self, pipeline_cache: &PipelineCache, sample_count: u32)
+    {
+        let (layout, surface_texture_layout, shader_handle) = (&self.render_bind_group_layout, &self.surface_texture_layout, &self.shader_handle);
+
+        self.render_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_render_pipeline_descriptor(layout, shader_handle, sample_count)
+        );
+        self.scalar_overlay_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_overlay_pipeline_descriptor(layout, shader_handle, "scalar_overlay_fragment", sample_count)
+        );
+        self.divergence_overlay_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_overlay_pipeline_descriptor(layout, shader_handle, "divergence_overlay_fragment", sample_count)
+        );
+        self.trail_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_line_pipeline_descriptor(layout, shader_handle, "trail_vertex", sample_count)
+        );
+        self.velocity_field_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_line_pipeline_descriptor(layout, shader_handle, "velocity_field_vertex", sample_count)
+        );
+        self.outline_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(layout, surface_texture_layout, shader_handle, "outline_fragment", Some(sample_count))
+        );
+        self.palette_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(layout, surface_texture_layout, shader_handle, "palette_fragment", Some(sample_count))
+        );
+        self.surface_composite_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(layout, surface_texture_layout, shader_handle, "surface_composite_fragment", Some(sample_count))
+        );
+        self.heatmap_composite_pipeline_id = pipeline_cache.queue_render_pipeline(
+            get_surface_pass_pipeline_descriptor(layout, surface_texture_layout, shader_handle, "heatmap_composite_fragment", Some(sample_count))
+        );
+        self.sample_count = sample_count;
+    }
+
+    // number of render pipelines finished compiling, out of the total queued
+    pub fn pipeline_progress(&self, pipeline_cache: &PipelineCache) -> (u32, u32)
+    {
+        let pipeline_ids = [
+            self.render_pipeline_id,
+            self.scalar_overlay_pipeline_id,
+            self.divergence_overlay_pipeline_id,
+            self.outline_pipeline_id,
+            self.palette_pipeline_id,
+            self.trail_pipeline_id,
+            self.velocity_field_pipeline_id,
+            self.surface_splat_pipeline_id,
+            self.surface_blur_horizontal_pipeline_id,
+            self.surface_blur_vertical_pipeline_id,
+            self.surface_composite_pipeline_id,
+            self.heatmap_splat_pipeline_id,
+            self.heatmap_composite_pipeline_id,
+        ];
+        let ready = pipeline_ids.iter()
+            .filter(|id| matches!(pipeline_cache.get_render_pipeline_state(**id), CachedPipelineState::Ok(_)))
+            .count();
+        (ready as u32, pipeline_ids.len() as u32)
+    }
+}
+
+// requeue the view pipelines when the camera's MSAA setting changes; the particles are skipped
+// for the few frames the new ones take to compile
+pub fn update_render_pipeline_msaa(
+    pipeline_cache: Res<PipelineCache>,
+    mut pipeline: ResMut<ParticleRenderPipeline>,
+    view_query: Query<&Msaa, With<ExtractedView>>,
+)
+{
+    let Some(msaa) = view_query.iter().next() else { return; };
+    if msaa.samples() != pipeline.sample_count
+    {
+        pipeline.queue_view_pipelines(&pipeline_cache, msaa.samples());
     }
 }
 
@@ -71,38 +236,155 @@ impl Node for ParticleRenderNode
     {
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<ParticleRenderPipeline>();
-        let config = world.resource::<ParticleConfig>();
+        let global_config = world.resource::<ParticleConfig>();
+        let gpu_timing = world.resource::<GpuTiming>();
 
         for target in self.view_query.iter_manual(world) 
         {
             for entity in self.particle_system.iter_manual(world)
             {
+                // don't draw until the initial particle data is fully on the GPU, the rest of the
+                // buffer is still zeroed
+                if world.get::<ParticleUpload>(entity).is_some() { continue; }
+
+                let config = &system_config(world.get::<ParticleSystemConfig>(entity), global_config);
+
                 // check if pipeline is ready yet
                 if let Some(render_pipeline_id) = pipeline_cache.get_render_pipeline(pipeline.render_pipeline_id)
                 {
                     // check if pipeline buffers are ready
                     if let Some(render_pipeline_buffers) = world.get::<GPUPipelineBuffers>(entity)
                     {
+                        // surface and heatmap modes fill the surface texture first, falling back to
+                        // particles until their pipelines and textures are ready. Particles only
+                        // splat the thickness when the outline needs their silhouette.
+                        let outline = config.outline_enabled != 0;
+                        let surface_bind_group = world.get_resource::<SurfaceTextures>()
+                            .and_then(|surface_textures| match config.render_mode {
+                                RENDER_MODE_SURFACE => render_surface_thickness(render_context, pipeline_cache, pipeline, render_pipeline_buffers, surface_textures),
+                                RENDER_MODE_HEATMAP => render_density_heatmap(render_context, pipeline_cache, pipeline, render_pipeline_buffers, surface_textures),
+                                _ if outline => render_surface_thickness(render_context, pipeline_cache, pipeline, render_pipeline_buffers, surface_textures),
+                                _ => None,
+                            });
+                        let composite_pipeline_id = match config.render_mode {
+                            RENDER_MODE_SURFACE => Some(pipeline.surface_composite_pipeline_id),
+                            RENDER_MODE_HEATMAP => Some(pipeline.heatmap_composite_pipeline_id),
+                            _ => None,
+                        };
+
+                        // retro mode draws the system offscreen for the palette pass to quantize onto
+                        // the view, drawing straight to it until the textures and pass are ready
+                        let retro = world.get_resource::<RetroTextures>()
+                            .filter(|_| config.retro_enabled != 0)
+                            .zip(pipeline_cache.get_render_pipeline(pipeline.palette_pipeline_id));
+                        let color_attachment = match retro {
+                            Some((retro_textures, _)) => retro_textures.color_attachment(),
+                            None => target.get_color_attachment(),
+                        };
+
+                        // the main system's pass is timed for the stats overlay
+                        let timestamp_writes = world.get::<ParticleSystemConfig>(entity).is_none()
+                            .then(|| gpu_timing.render_pass_writes())
+                            .flatten();
+                        if timestamp_writes.is_some()
+                        {
+                            gpu_timing.mark_render_timed();
+                        }
+
                         // create render pass and set attributes
                         let mut render_pass = RenderContext::begin_tracked_render_pass(
                         render_context, 
                         RenderPassDescriptor
                             {
                                 label: Some("render_pass_descriptor"),
-                                color_attachments: &[Some(target.get_color_attachment())],
+                                color_attachments: &[Some(color_attachment)],
                                 depth_stencil_attachment: None,
-                                timestamp_writes: None,
+                                timestamp_writes,
                                 occlusion_query_set: None
                             }
                         );
-                        render_pass.set_render_pipeline(render_pipeline_id);
-                        render_pass.set_bind_group(0, &render_pipeline_buffers.bind_group, &[0]);
-                        render_pass.set_vertex_buffer(0, render_pipeline_buffers.vertex_buffer.slice(..));
-                        render_pass.draw(0..6, 0..config.particle_count as u32);
+                        render_pass.set_bind_group(0, &render_pipeline_buffers.render_bind_group, &[]);
+
+                        // trails underneath, a quad per segment between consecutive recorded positions
+                        if config.trail_length > 1
+                        {
+                            if let Some(trail_pipeline) = pipeline_cache.get_render_pipeline(pipeline.trail_pipeline_id)
+                            {
+                                render_pass.set_render_pipeline(trail_pipeline);
+                                render_pass.draw(0..6, 0..render_pipeline_buffers.particle_count * (config.trail_length - 1));
+                            }
+                        }
+
+                        let surface_composite_pipeline = composite_pipeline_id.and_then(|id| pipeline_cache.get_render_pipeline(id));
+                        if let (Some(surface_bind_group), Some(surface_composite_pipeline)) = (surface_bind_group, surface_composite_pipeline)
+                        {
+                            render_pass.set_render_pipeline(surface_composite_pipeline);
+                            render_pass.set_bind_group(1, surface_bind_group, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        }
+                        else
+                        {
+                            render_pass.set_render_pipeline(render_pipeline_id);
+                            render_pass.set_vertex_buffer(0, render_pipeline_buffers.vertex_buffer.slice(..));
+                            render_pass.draw(0..6, 0..render_pipeline_buffers.particle_count);
+                        }
+
+                        // outline just outside the silhouette in the surface texture
+                        if let (true, Some(surface_bind_group), Some(outline_pipeline)) = (outline, surface_bind_group, pipeline_cache.get_render_pipeline(pipeline.outline_pipeline_id))
+                        {
+                            render_pass.set_render_pipeline(outline_pipeline);
+                            render_pass.set_bind_group(1, surface_bind_group, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        }
+
+                        // smoke overlay on top of the particles
+                        if config.smoke_enabled != 0
+                        {
+                            if let Some(scalar_overlay_pipeline) = pipeline_cache.get_render_pipeline(pipeline.scalar_overlay_pipeline_id)
+                            {
+                                render_pass.set_render_pipeline(scalar_overlay_pipeline);
+                                render_pass.draw(0..6, 0..1);
+                            }
+                        }
+
+                        // divergence overlay
+                        if config.divergence_view != 0
+                        {
+                            if let Some(divergence_overlay_pipeline) = pipeline_cache.get_render_pipeline(pipeline.divergence_overlay_pipeline_id)
+                            {
+                                render_pass.set_render_pipeline(divergence_overlay_pipeline);
+                                render_pass.draw(0..6, 0..1);
+                            }
+                        }
+
+                        // velocity field on top of everything; the shader sizes the grid from the screen
+                        // bounds, so the largest one is drawn and the samples past it collapse
+                        if let Some(velocity_field_pipeline) = pipeline_cache.get_render_pipeline(pipeline.velocity_field_pipeline_id)
+                        {
+                            let (vertices, instances) = match config.velocity_field_mode {
+                                VELOCITY_FIELD_ARROWS => (9, VELOCITY_FIELD_MAX_SAMPLES),
+                                VELOCITY_FIELD_STREAMLINES => (6, VELOCITY_FIELD_MAX_SAMPLES * STREAMLINE_STEPS),
+                                _ => (0, 0),
+                            };
+                            if instances > 0
+                            {
+                                render_pass.set_render_pipeline(velocity_field_pipeline);
+                                render_pass.draw(0..vertices, 0..instances);
+                            }
+                        }
+                        drop(render_pass);
+
+                        if let Some((retro_textures, palette_pipeline)) = retro
+                        {
+                            render_palette(render_context, palette_pipeline, render_pipeline_buffers, retro_textures, target);
+                        }
                     }
                 }
             }
         }
+
+        // every timed pass has been recorded by now
+        gpu_timing.resolve(render_context);
         Ok(())
     }
 
@@ -0,0 +1,172 @@
+use bevy::{
+    prelude::*,
+    render::extract_component::ExtractComponent,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{setup_particles_scatter, spatial_grid_size, ParticleConfig, ParticleSystem};
+use crate::parameter_gui::Colormap;
+use crate::initial_layout::InitialLayout;
+use crate::sim_rng::SimRng;
+
+// parameters for a system that doesn't follow the global ParticleConfig. Systems without one
+// use the global config as is; the readbacks, emitters and scenes only ever act on that one.
+#[derive(ExtractComponent, Component, Clone, Copy)]
+pub struct ParticleSystemConfig(pub ParticleConfig);
+
+// the config a system is simulated and drawn with: its own fluid parameters, with the screen,
+// clock, interaction and display fields of the global config since those are shared by all systems
+pub fn system_config(local: Option<&ParticleSystemConfig>, global: &ParticleConfig) -> ParticleConfig
+{
+    let Some(ParticleSystemConfig(local)) = local else { return *global; };
+    let [spatial_grid_width, spatial_grid_height] = spatial_grid_size(global.screen_bounds, local.smoothing_radius);
+    ParticleConfig
+    {
+        fixed_delta_time: global.fixed_delta_time,
+        substeps: global.substeps,
+        substep_delta_time: global.substep_delta_time,
+        frame_count: global.frame_count,
+        velocity_history_frames: global.velocity_history_frames,
+        paused: global.paused,
+
+        // the global bounds in cells of this system's smoothing radius
+        spatial_grid_width,
+        spatial_grid_height,
+
+        scalar_grid_width: global.scalar_grid_width,
+        scalar_grid_height: global.scalar_grid_height,
+        scalar_grid_cell_size: global.scalar_grid_cell_size,
+
+        render_mode: global.render_mode,
+        surface_threshold: global.surface_threshold,
+        surface_blur_radius: global.surface_blur_radius,
+        heatmap_range: global.heatmap_range,
+        trail_length: global.trail_length,
+        trail_opacity: global.trail_opacity,
+        trail_width: global.trail_width,
+        tonemapping: global.tonemapping,
+        exposure: global.exposure,
+        density_alpha_enabled: global.density_alpha_enabled,
+        density_alpha_min: global.density_alpha_min,
+        density_alpha_range: global.density_alpha_range,
+        density_alpha_gamma: global.density_alpha_gamma,
+        velocity_field_mode: global.velocity_field_mode,
+        velocity_field_spacing: global.velocity_field_spacing,
+        velocity_field_full_speed: global.velocity_field_full_speed,
+        outline_enabled: global.outline_enabled,
+        outline_width: global.outline_width,
+        outline_color: global.outline_color,
+        retro_enabled: global.retro_enabled,
+        palette_size: global.palette_size,
+        palette_dither: global.palette_dither,
+        retro_pixel_size: global.retro_pixel_size,
+        palette: global.palette,
+        compensated_summation: global.compensated_summation,
+        deterministic_order: global.deterministic_order,
+        solver: global.solver,
+        pbf_iterations: global.pbf_iterations,
+        pbf_relaxation: global.pbf_relaxation,
+        cfl_number: global.cfl_number,
+        max_substeps: global.max_substeps,
+
+        interaction_position: global.interaction_position,
+        interaction_strength: global.interaction_strength,
+        interaction_radius: global.interaction_radius,
+        impulse_position: global.impulse_position,
+        impulse_strength: global.impulse_strength,
+        impulse_radius: global.impulse_radius,
+        fan_origin: global.fan_origin,
+        fan_direction: global.fan_direction,
+        fan_strength: global.fan_strength,
+        fan_reach: global.fan_reach,
+        fan_cos_half_angle: global.fan_cos_half_angle,
+
+        // the surrogate model only ever sees the main system
+        surrogate_enabled: 0,
+
+        boundary_modes: global.boundary_modes,
+        screen_bounds: global.screen_bounds,
+        view_proj: global.view_proj,
+        ..*local
+    }
+}
+
+// add and tune systems alongside the primary one
+pub fn particle_systems_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut system_query: Query<(Entity, &mut ParticleSystemConfig), With<ParticleSystem>>,
+    config: Res<ParticleConfig>,
+    layout: Res<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Particle Systems")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 950.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label("Extra fluids simulated alongside the main one, each with its own parameters");
+
+            // start from the main system's parameters with a different colormap to tell them apart
+            if ui.button("Add System").clicked() {
+                let mut params = *config;
+                params.particle_count = (config.particle_count / 2).max(1000);
+                params.colormap = (config.colormap + 1 + system_query.iter().count() as u32) % Colormap::ALL.len() as u32;
+                let particles = setup_particles_scatter(&layout, &mut sim_rng, config.screen_bounds, params.particle_count);
+                commands.spawn((ParticleSystem { particles, ..default() }, ParticleSystemConfig(params)));
+            }
+
+            for (index, (entity, mut params)) in system_query.iter_mut().enumerate()
+            {
+                let params = &mut params.0;
+                egui::CollapsingHeader::new(format!("System {}", index + 2))
+                    .id_salt(entity)
+                    .show(ui, |ui| {
+                        // like the main count, only rescatter once the slider is released
+                        let count_id = egui::Id::new((entity, "particle_count"));
+                        let mut particle_count = ui.data(|data| data.get_temp(count_id)).unwrap_or(params.particle_count);
+                        let response = ui.add(egui::Slider::new(&mut particle_count, 1000..=500_000)
+                            .text("Particle Count")
+                            .logarithmic(true));
+                        if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                            params.particle_count = particle_count;
+                            ui.data_mut(|data| data.remove::<u32>(count_id));
+                        } else if response.dragged() {
+                            ui.data_mut(|data| data.insert_temp(count_id, particle_count));
+                        }
+
+                        // strength only, the direction stays where it was
+                        let gravity = Vec2::from(params.gravity);
+                        let mut strength = gravity.length();
+                        if ui.add(egui::Slider::new(&mut strength, 0.0..=1000.0).text("Gravity")).changed() {
+                            params.gravity = (gravity.normalize_or(Vec2::NEG_Y) * strength).to_array();
+                        }
+
+                        ui.add(egui::Slider::new(&mut params.target_density, 0.0..=0.1)
+                            .text("Target Density"));
+                        ui.add(egui::Slider::new(&mut params.pressure_multiplier, 1.0..=100000.0)
+                            .text("Pressure Multiplier")
+                            .logarithmic(true));
+                        ui.add(egui::Slider::new(&mut params.viscocity_strength, 0.0..=10.0)
+                            .text("Viscocity Strength"));
+
+                        let mut colormap = Colormap::from_u32(params.colormap);
+                        egui::ComboBox::from_id_salt((entity, "colormap"))
+                            .selected_text(colormap.name())
+                            .show_ui(ui, |ui| {
+                                for map in Colormap::ALL {
+                                    ui.selectable_value(&mut colormap, map, map.name());
+                                }
+                            });
+                        params.colormap = colormap as u32;
+
+                        if ui.button("Remove").clicked() {
+                            commands.entity(entity).despawn();
+                        }
+                    });
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::PipelineCache,
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
+
+use crate::particle_compute::ParticleComputePipeline;
+use crate::particle_render::ParticleRenderPipeline;
+
+// sent once when every particle pipeline has finished compiling
+#[derive(Event)]
+pub struct SimulationReady;
+
+// compiled pipeline count written by the render world, read by the main world
+#[derive(Resource, Clone, Default)]
+pub struct PipelineProgress
+{
+    ready: Arc<AtomicU32>,
+    total: Arc<AtomicU32>,
+}
+
+impl PipelineProgress
+{
+    pub fn ready(&self) -> u32
+    {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u32
+    {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ready(&self) -> bool
+    {
+        self.total() > 0 && self.ready() == self.total()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SimulationReadiness
+{
+    pub ready: bool,
+}
+
+// render world: count the particle pipelines the pipeline cache has finished compiling
+pub fn update_pipeline_progress(
+    pipeline_cache: Res<PipelineCache>,
+    compute_pipeline: Res<ParticleComputePipeline>,
+    render_pipeline: Res<ParticleRenderPipeline>,
+    progress: Res<PipelineProgress>,
+)
+{
+    let (compute_ready, compute_total) = compute_pipeline.pipeline_progress(&pipeline_cache);
+    let (render_ready, render_total) = render_pipeline.pipeline_progress(&pipeline_cache);
+
+    progress.ready.store(compute_ready + render_ready, Ordering::Relaxed);
+    progress.total.store(compute_total + render_total, Ordering::Relaxed);
+}
+
+// main world: flip readiness and notify host apps once all pipelines are compiled
+pub fn announce_simulation_ready(
+    progress: Res<PipelineProgress>,
+    mut readiness: ResMut<SimulationReadiness>,
+    mut ready_events: EventWriter<SimulationReady>,
+)
+{
+    if !readiness.ready && progress.is_ready()
+    {
+        readiness.ready = true;
+        ready_events.write(SimulationReady);
+        info!("[Setup] All {} particle pipelines compiled", progress.total());
+    }
+}
+
+// centered "compiling shaders" overlay shown until the simulation is ready
+pub fn pipeline_progress_overlay(
+    mut contexts: EguiContexts,
+    progress: Res<PipelineProgress>,
+    readiness: Res<SimulationReadiness>,
+) -> Result
+{
+    if readiness.ready { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    egui::Area::new(egui::Id::new("pipeline_progress_overlay"))
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.heading(format!("Compiling shaders... ({}/{})", progress.ready(), progress.total()));
+        });
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow},
+    tasks::IoTaskPool,
+    winit::{UpdateMode, WinitSettings},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::headless::HeadlessRun;
+use crate::parameter_gui::GUIConfig;
+
+const DEFAULT_FRAME_RATE: f32 = 30.0;       // frames and gui repaints per second
+const DEFAULT_SIM_RATE: f32 = 30.0;         // sim steps per second
+const DEFAULT_PARTICLE_CAP: u32 = 20000;
+const BATTERY_POLL_INTERVAL: f32 = 10.0;    // seconds
+
+// For leaving the demo running on a laptop. While engaged the app only updates at the frame
+// rate (or on input), the sim steps at most sim_rate times a second, vsync is forced on and the
+// particle count is capped. Engaged by hand, or automatically while running on battery.
+#[derive(Resource)]
+pub struct PowerSaving
+{
+    pub enabled: bool,
+    pub auto_on_battery: bool,
+    pub frame_rate: f32,
+    pub sim_rate: f32,
+    pub particle_cap: u32,
+    on_battery: Arc<Mutex<Option<bool>>>,   // None where the power source can't be read, read off the main thread
+    since_poll: Option<f32>,
+    engaged: bool,                          // what was last applied to the window and params
+    applied_frame_rate: f32,                // the frame rate the winit settings were last written for
+    sim_budget: f32,                        // sim steps owed, one is taken per stepped frame
+    present_mode: Option<PresentMode>,      // the window's own, restored on disengaging
+    winit_settings: Option<WinitSettings>,  // the app's own, restored on disengaging
+}
+
+impl Default for PowerSaving
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            enabled: false,
+            auto_on_battery: true,
+            frame_rate: DEFAULT_FRAME_RATE,
+            sim_rate: DEFAULT_SIM_RATE,
+            particle_cap: DEFAULT_PARTICLE_CAP,
+            on_battery: Arc::new(Mutex::new(None)),
+            since_poll: None,
+            engaged: false,
+            applied_frame_rate: DEFAULT_FRAME_RATE,
+            sim_budget: 0.0,
+            present_mode: None,
+            winit_settings: None,
+        }
+    }
+}
+
+impl PowerSaving
+{
+    pub fn engaged(&self) -> bool
+    {
+        self.enabled || (self.auto_on_battery && self.on_battery() == Some(true))
+    }
+
+    fn on_battery(&self) -> Option<bool>
+    {
+        *self.on_battery.lock().unwrap()
+    }
+
+    fn low_power_settings(&self) -> WinitSettings
+    {
+        let wait = Duration::from_secs_f32(1.0 / self.frame_rate.max(1.0));
+        WinitSettings { focused_mode: UpdateMode::reactive_low_power(wait), unfocused_mode: UpdateMode::reactive_low_power(wait) }
+    }
+
+    pub fn cap_particle_count(&self, particle_count: u32) -> u32
+    {
+        if self.engaged { particle_count.min(self.particle_cap) } else { particle_count }
+    }
+
+    // whether the sim should step this frame, always while disengaged
+    pub fn sim_step_due(&mut self, delta: f32) -> bool
+    {
+        if !self.engaged { return true; }
+        // a frame's worth of steps at most, so a hitch doesn't bank a burst of them
+        self.sim_budget = (self.sim_budget + delta * self.sim_rate).min(1.0);
+        if self.sim_budget < 1.0 { return false; }
+        self.sim_budget -= 1.0;
+        true
+    }
+}
+
+// Some(true) while discharging, from the kernel's power supply class
+#[cfg(target_os = "linux")]
+fn read_on_battery() -> Option<bool>
+{
+    let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    let mut on_battery = false;
+    for supply in supplies.flatten()
+    {
+        let read = |name: &str| std::fs::read_to_string(supply.path().join(name)).unwrap_or_default();
+        if read("type").trim() == "Battery" && read("status").trim() == "Discharging"
+        {
+            on_battery = true;
+        }
+    }
+    Some(on_battery)
+}
+
+// pmset names the source the machine is drawing from on its first line
+#[cfg(target_os = "macos")]
+fn read_on_battery() -> Option<bool>
+{
+    let output = std::process::Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_on_battery() -> Option<bool>
+{
+    None
+}
+
+// poll the power source and apply the window side of engaging or disengaging; the particle cap
+// goes through apply_gui_updates like any other param change
+pub fn update_power_saving(
+    time: Res<Time<Real>>,
+    headless: Option<Res<HeadlessRun>>,
+    mut winit_settings: Option<ResMut<WinitSettings>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut power_saving: ResMut<PowerSaving>,
+)
+{
+    // batch runs want every step as fast as possible
+    if headless.is_some() { return; }
+
+    // pmset is a subprocess, so the power source is read on the IO pool rather than blocking a frame
+    let since_poll = power_saving.since_poll.map_or(BATTERY_POLL_INTERVAL, |since| since + time.delta_secs());
+    if since_poll >= BATTERY_POLL_INTERVAL
+    {
+        let on_battery = power_saving.on_battery.clone();
+        IoTaskPool::get().spawn(async move {
+            *on_battery.lock().unwrap() = read_on_battery();
+        }).detach();
+        power_saving.since_poll = Some(0.0);
+    }
+    else
+    {
+        power_saving.since_poll = Some(since_poll);
+    }
+
+    let engaged = power_saving.engaged();
+    // the frame rate slider moved while engaged
+    if engaged && power_saving.engaged && power_saving.frame_rate != power_saving.applied_frame_rate
+    {
+        if let Some(winit_settings) = winit_settings.as_deref_mut()
+        {
+            *winit_settings = power_saving.low_power_settings();
+        }
+        power_saving.applied_frame_rate = power_saving.frame_rate;
+    }
+    if engaged == power_saving.engaged { return; }
+    power_saving.engaged = engaged;
+    power_saving.sim_budget = 0.0;
+    info!("[Power] Power saving {}", if engaged { "engaged" } else { "disengaged" });
+
+    // only written when engaging or disengaging, so the settings the app was started with come back
+    if let Some(mut winit_settings) = winit_settings
+    {
+        if engaged
+        {
+            power_saving.winit_settings = Some(winit_settings.clone());
+            *winit_settings = power_saving.low_power_settings();
+            power_saving.applied_frame_rate = power_saving.frame_rate;
+        }
+        else if let Some(settings) = power_saving.winit_settings.take()
+        {
+            *winit_settings = settings;
+        }
+    }
+
+    if let Ok(mut window) = window_query.single_mut()
+    {
+        if engaged
+        {
+            power_saving.present_mode = Some(window.present_mode);
+            window.present_mode = PresentMode::AutoVsync;
+        }
+        else if let Some(present_mode) = power_saving.present_mode.take()
+        {
+            window.present_mode = present_mode;
+        }
+    }
+    gui_config.applied_changes = true;
+}
+
+pub fn power_saving_gui(
+    mut contexts: EguiContexts,
+    mut power_saving: ResMut<PowerSaving>,
+    mut gui_config: ResMut<GUIConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Power Saving")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1400.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.checkbox(&mut power_saving.enabled, "Power saving");
+            ui.checkbox(&mut power_saving.auto_on_battery, "Engage automatically on battery");
+            ui.label(match power_saving.on_battery() {
+                Some(true) => "Running on battery",
+                Some(false) => "Running on mains power",
+                None => "Power source unknown",
+            });
+
+            ui.separator();
+            ui.add(egui::Slider::new(&mut power_saving.frame_rate, 5.0..=60.0)
+                .text("Frame Rate (fps)"));
+            ui.add(egui::Slider::new(&mut power_saving.sim_rate, 5.0..=120.0)
+                .text("Sim Rate (steps/s)"));
+            let response = ui.add(egui::Slider::new(&mut power_saving.particle_cap, 1000..=100_000)
+                .text("Particle Cap")
+                .logarithmic(true));
+            // the cap rescatters the fluid like the particle count slider, so only on release
+            if response.drag_stopped() || (response.changed() && !response.dragged())
+            {
+                gui_config.applied_changes |= power_saving.engaged;
+            }
+
+            let state = if power_saving.engaged { "Engaged" } else { "Not engaged" };
+            ui.label(format!("{}: vsync on, GUI repaints only on input or at the frame rate", state));
+        });
+    Ok(())
+}
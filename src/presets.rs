@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::parameter_gui::{GUIConfig, GUIDefaults, GravityPreset};
+use crate::param_migration::{check_params_version, migrate_params, PARAMS_VERSION, UNVERSIONED_PARAMS};
+
+const PRESETS_PATH: &str = "presets.ron";
+
+// a parameter value as written to the presets file
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum ParamValue
+{
+    Bool(bool),
+    Integer(u32),
+    Float(f32),
+}
+
+impl ParamValue
+{
+    // a value from the param text, typed the same way RON reads it
+    pub fn parse(text: &str) -> Option<Self>
+    {
+        if let Ok(value) = text.parse() { return Some(ParamValue::Bool(value)); }
+        if let Ok(value) = text.parse() { return Some(ParamValue::Integer(value)); }
+        text.parse().ok().map(ParamValue::Float)
+    }
+}
+
+// a named set of parameters. The particle count is left out so picking a preset changes how
+// the fluid behaves without rescattering it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Preset
+{
+    pub name: String,
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl Preset
+{
+    fn capture(name: &str, gui_config: &GUIConfig) -> Self
+    {
+        let params = gui_config.param_values().into_iter()
+            .filter(|(param, _)| *param != "particle_count")
+            .map(|(param, value)| (param.to_string(), value))
+            .collect();
+        Self { name: name.to_string(), params }
+    }
+
+    // unknown names are skipped so presets saved by other versions still load
+    fn apply(&self, gui_config: &mut GUIConfig)
+    {
+        for (param, value) in &self.params
+        {
+            if !gui_config.set_param(param, *value)
+            {
+                warn!("[Presets] {}: ignoring unknown parameter `{}`", self.name, param);
+            }
+        }
+        gui_config.applied_changes = true;
+    }
+}
+
+// the launch defaults with a few params changed, captured in full like user presets
+fn built_in_presets(defaults: &GUIConfig) -> Vec<Preset>
+{
+    let looks: [(&str, fn(&mut GUIConfig)); 4] = [
+        ("Water", |params| {
+            params.gravity = GravityPreset::Earth.gravity();
+        }),
+        ("Honey", |params| {
+            params.gravity = GravityPreset::Earth.gravity();
+            params.viscocity_strength *= 8.0;
+            params.damping_factor = 0.05;
+        }),
+        ("Gas", |params| {
+            params.gravity = GravityPreset::Moon.gravity() * 0.1;
+            params.target_density *= 0.25;
+            params.pressure_multiplier *= 2.0;
+            params.near_density_multiplier *= 0.5;
+            params.viscocity_strength = 0.0;
+        }),
+        ("Zero-G", |params| {
+            params.gravity = GravityPreset::ZeroG.gravity();
+            params.oscillate_gravity = false;
+        }),
+    ];
+
+    looks.iter()
+        .map(|(name, look)| {
+            let mut params = *defaults;
+            look(&mut params);
+            Preset::capture(name, &params)
+        })
+        .collect()
+}
+
+// files from before the version was written have none and load as UNVERSIONED_PARAMS
+#[derive(Serialize, Deserialize)]
+struct PresetsFile
+{
+    #[serde(default = "unversioned_params")]
+    version: u32,
+    presets: Vec<Preset>,
+}
+
+fn unversioned_params() -> u32
+{
+    UNVERSIONED_PARAMS
+}
+
+#[derive(Resource)]
+pub struct Presets
+{
+    user: Vec<Preset>,      // saved with the current params, persisted to PRESETS_PATH
+    selected: String,
+    new_name: String,
+    status: Option<String>,
+    newer_file: bool,       // PRESETS_PATH is from a newer version, saving would drop its presets
+}
+
+impl Default for Presets
+{
+    fn default() -> Self
+    {
+        Self { user: Vec::new(), selected: "Water".to_string(), new_name: String::new(), status: None, newer_file: false }
+    }
+}
+
+impl Presets
+{
+    fn save(&mut self)
+    {
+        if self.newer_file
+        {
+            self.status = Some(format!("Not saving, {} is from a newer version", PRESETS_PATH));
+            return;
+        }
+        let file = PresetsFile { version: PARAMS_VERSION, presets: self.user.clone() };
+        let written = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|text| std::fs::write(PRESETS_PATH, text).map_err(|error| error.to_string()));
+        self.status = Some(match written {
+            Ok(()) => format!("Saved {} presets to {}", self.user.len(), PRESETS_PATH),
+            Err(error) => format!("Failed to save {}: {}", PRESETS_PATH, error),
+        });
+    }
+}
+
+// a missing file just means nothing has been saved yet; presets saved by older versions are
+// migrated, and saving writes them back at the current version
+pub fn load_presets(mut presets: ResMut<Presets>)
+{
+    let text = match std::fs::read_to_string(PRESETS_PATH) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return,
+        Err(error) => {
+            presets.status = Some(format!("Failed to read {}: {}", PRESETS_PATH, error));
+            return;
+        }
+    };
+    let loaded = ron::from_str::<PresetsFile>(&text)
+        .map_err(|error| error.to_string())
+        .and_then(|file| {
+            presets.newer_file = file.version > PARAMS_VERSION;
+            check_params_version(file.version).map(|_| file)
+        });
+    presets.status = Some(match loaded {
+        Ok(mut file) => {
+            for preset in &mut file.presets
+            {
+                migrate_params(file.version, &mut preset.params);
+            }
+            presets.user = file.presets;
+            format!("Loaded {} presets from {}", presets.user.len(), PRESETS_PATH)
+        }
+        Err(error) => format!("Failed to load {}: {}", PRESETS_PATH, error),
+    });
+    info!("[Presets] {}", presets.status.as_ref().unwrap());
+}
+
+pub fn presets_gui(
+    mut contexts: EguiContexts,
+    mut presets: ResMut<Presets>,
+    mut gui_config: ResMut<GUIConfig>,
+    defaults: Res<GUIDefaults>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let built_in = built_in_presets(&defaults.0);
+
+    egui::Window::new("Presets")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 750.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            let presets = presets.as_mut();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("preset_select")
+                    .selected_text(presets.selected.as_str())
+                    .show_ui(ui, |ui| {
+                        for preset in built_in.iter().chain(&presets.user)
+                        {
+                            ui.selectable_value(&mut presets.selected, preset.name.clone(), preset.name.as_str());
+                        }
+                    });
+
+                let selected = built_in.iter().chain(&presets.user).find(|preset| preset.name == presets.selected);
+                if ui.add_enabled(selected.is_some(), egui::Button::new("Apply")).clicked()
+                {
+                    if let Some(preset) = selected
+                    {
+                        preset.apply(&mut gui_config);
+                    }
+                }
+
+                let user_index = presets.user.iter().position(|preset| preset.name == presets.selected);
+                if ui.add_enabled(user_index.is_some(), egui::Button::new("Delete")).clicked()
+                {
+                    if let Some(index) = user_index
+                    {
+                        presets.user.remove(index);
+                        presets.save();
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut presets.new_name);
+                let name = presets.new_name.trim().to_string();
+                let taken = built_in.iter().any(|preset| preset.name == name);
+                if ui.add_enabled(!name.is_empty() && !taken, egui::Button::new("Save Current As Preset")).clicked()
+                {
+                    // saving under an existing user preset's name replaces it
+                    let preset = Preset::capture(&name, &gui_config);
+                    match presets.user.iter_mut().find(|existing| existing.name == name) {
+                        Some(existing) => *existing = preset,
+                        None => presets.user.push(preset),
+                    }
+                    presets.selected = name;
+                    presets.new_name.clear();
+                    presets.save();
+                }
+            });
+            if let Some(status) = &presets.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,355 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+use crate::gui_scale::GuiScale;
+
+pub const MAX_PRESSURE_PROBES: usize = 16;      // must match MAX_PRESSURE_PROBES in compute_shader.wgsl
+const PRESSURE_PROBE_HISTORY: usize = 64;       // must match PRESSURE_PROBE_HISTORY in compute_shader.wgsl
+const PRESSURE_PROBE_HEADER_SIZE: u64 = 16;     // count, padded to the positions' alignment
+const PRESSURE_PROBE_POSITIONS_SIZE: u64 = (std::mem::size_of::<[f32; 4]>() * MAX_PRESSURE_PROBES) as u64;
+pub const PRESSURE_PROBE_BUFFER_SIZE: u64 = PRESSURE_PROBE_HEADER_SIZE + PRESSURE_PROBE_POSITIONS_SIZE
+    + (std::mem::size_of::<[f32; 4]>() * MAX_PRESSURE_PROBES * PRESSURE_PROBE_HISTORY) as u64;
+const PLOT_FRAMES: usize = 300;                 // recorded frames shown in the pressure plot
+const PRESSURE_PROBE_PATH: &str = "pressure_probes.csv";
+const PROBE_COLORS: [egui::Color32; 4] = [
+    egui::Color32::LIGHT_BLUE,
+    egui::Color32::LIGHT_GREEN,
+    egui::Color32::YELLOW,
+    egui::Color32::LIGHT_RED,
+];
+
+// density, near density and pressure at one probe
+pub type ProbeSample = [f32; 3];
+
+// shared between main and render worlds. The main world owns the probe points, the render
+// world hands back every sampled frame it hasn't passed on yet.
+#[derive(Resource, Clone, Default)]
+pub struct PressureProbeShared
+{
+    points: Arc<Mutex<Vec<Vec2>>>,
+    rows: Arc<Mutex<Vec<(u32, Vec<ProbeSample>)>>>,
+}
+
+impl PressureProbeShared
+{
+    pub fn probe_count(&self) -> u32
+    {
+        self.points.lock().unwrap().len() as u32
+    }
+}
+
+// render world side of the probe readback
+#[derive(Resource)]
+pub struct PressureProbeReadback
+{
+    readback: GpuReadback,
+    last_frame: u32,    // newest sim frame already handed to the main world
+}
+
+impl Default for PressureProbeReadback
+{
+    fn default() -> Self
+    {
+        Self { readback: GpuReadback::new("pressure_probe_readback_buffer"), last_frame: 0 }
+    }
+}
+
+// placeable pressure sensors, sampled on the GPU every sim frame
+#[derive(Resource, Default)]
+pub struct PressureProbes
+{
+    pub points: Vec<Vec2>,
+    pub placing: bool,                          // the next click on the fluid adds a probe
+    pub recording: bool,
+    pub history: Vec<(u32, Vec<ProbeSample>)>,  // sim frame and a sample per probe, while recording
+    pub latest: Vec<ProbeSample>,
+    status: Option<String>,
+}
+
+impl PressureProbes
+{
+    fn export_csv(&self) -> std::io::Result<usize>
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(PRESSURE_PROBE_PATH)?);
+        write!(file, "frame")?;
+        for probe in 0..self.points.len()
+        {
+            write!(file, ",probe_{probe}_density,probe_{probe}_near_density,probe_{probe}_pressure")?;
+        }
+        writeln!(file)?;
+
+        for (frame, samples) in &self.history
+        {
+            write!(file, "{frame}")?;
+            for [density, near_density, pressure] in samples
+            {
+                write!(file, ",{density},{near_density},{pressure}")?;
+            }
+            writeln!(file)?;
+        }
+        file.flush()?;
+        Ok(self.history.len())
+    }
+}
+
+// write the probe points into the main system's probe buffer
+pub fn prepare_pressure_probes(
+    render_queue: Res<RenderQueue>,
+    shared: Res<PressureProbeShared>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, Without<ParticleSystemConfig>>,
+)
+{
+    let points = shared.points.lock().unwrap();
+    let positions: Vec<[f32; 4]> = points.iter()
+        .take(MAX_PRESSURE_PROBES)
+        .map(|point| [point.x, point.y, 0.0, 0.0])
+        .collect();
+    let header = [positions.len() as u32, 0, 0, 0];
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        render_queue.write_buffer(&pipeline_buffers.pressure_probe_buffer, 0, bytemuck::bytes_of(&header));
+        if !positions.is_empty()
+        {
+            render_queue.write_buffer(&pipeline_buffers.pressure_probe_buffer, PRESSURE_PROBE_HEADER_SIZE, bytemuck::cast_slice(&positions));
+        }
+    }
+}
+
+// the ring holds the last PRESSURE_PROBE_HISTORY frames, far more than a readback takes, so
+// copying it whenever the previous copy has landed picks up every frame
+pub fn read_back_pressure_probes(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    shared: Res<PressureProbeShared>,
+    mut probe_readback: ResMut<PressureProbeReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(words) = probe_readback.readback.try_read::<u32>(&render_device)
+    {
+        let count = (words[0] as usize).min(MAX_PRESSURE_PROBES);
+        let samples_start = ((PRESSURE_PROBE_HEADER_SIZE + PRESSURE_PROBE_POSITIONS_SIZE) / 4) as usize;
+        let samples = &words[samples_start..];
+
+        let mut rows: Vec<(u32, Vec<ProbeSample>)> = (0..PRESSURE_PROBE_HISTORY)
+            .filter_map(|slot| {
+                let row = &samples[slot * MAX_PRESSURE_PROBES * 4..(slot + 1) * MAX_PRESSURE_PROBES * 4];
+                // the frame is stored in every probe's w, unwritten slots are still zero
+                let frame = row[3];
+                (count > 0 && frame > probe_readback.last_frame).then(|| {
+                    let values = row.chunks_exact(4)
+                        .take(count)
+                        .map(|sample| [0, 1, 2].map(|k| f32::from_bits(sample[k])))
+                        .collect();
+                    (frame, values)
+                })
+            })
+            .collect();
+        rows.sort_by_key(|(frame, _)| *frame);
+
+        if let Some((frame, _)) = rows.last()
+        {
+            probe_readback.last_frame = *frame;
+        }
+        shared.rows.lock().unwrap().extend(rows);
+    }
+
+    if shared.probe_count() == 0 || !probe_readback.readback.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        // the history ring keeps recording while the copy waits its turn
+        if !budget.try_acquire("pressure_probes", PRESSURE_PROBE_BUFFER_SIZE, 1) { return; }
+
+        probe_readback.readback.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.pressure_probe_buffer,
+            PRESSURE_PROBE_BUFFER_SIZE,
+        );
+    }
+}
+
+// place probes with a click, hand the points to the render world and collect the samples
+pub fn update_pressure_probes(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut contexts: EguiContexts,
+    shared: Res<PressureProbeShared>,
+    mut probes: ResMut<PressureProbes>,
+)
+{
+    let pointer_over_gui = contexts.ctx_mut()
+        .map(|ctx| ctx.is_pointer_over_area() || ctx.wants_pointer_input())
+        .unwrap_or(false);
+
+    if probes.placing && mouse_buttons.just_pressed(MouseButton::Left) && !pointer_over_gui
+    {
+        let cursor_world_position = windows.single().ok()
+            .and_then(|window| window.cursor_position())
+            .and_then(|cursor| {
+                let (camera, transform) = camera_query.single().ok()?;
+                camera.viewport_to_world_2d(transform, cursor).ok()
+            });
+        if let Some(position) = cursor_world_position.filter(|_| probes.points.len() < MAX_PRESSURE_PROBES)
+        {
+            probes.points.push(position);
+        }
+        probes.placing = false;
+    }
+
+    // samples taken before the points changed belong to other columns, so start over
+    let mut points = shared.points.lock().unwrap();
+    if *points != probes.points
+    {
+        *points = probes.points.clone();
+        probes.history.clear();
+        probes.latest.clear();
+        shared.rows.lock().unwrap().clear();
+        return;
+    }
+    drop(points);
+
+    let rows = std::mem::take(&mut *shared.rows.lock().unwrap());
+    let probe_count = probes.points.len();
+    for (frame, samples) in rows.into_iter().filter(|(_, samples)| samples.len() == probe_count)
+    {
+        probes.latest = samples.clone();
+        if probes.recording
+        {
+            probes.history.push((frame, samples));
+        }
+    }
+}
+
+// numbered crosshairs at each probe point behind the egui windows
+pub fn draw_pressure_probes(
+    mut contexts: EguiContexts,
+    probes: Res<PressureProbes>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if probes.points.is_empty() { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let painter = ctx.layer_painter(egui::LayerId::background());
+
+    for (index, point) in probes.points.iter().enumerate()
+    {
+        let Ok(viewport) = camera.world_to_viewport(camera_transform, point.extend(0.0)) else { continue; };
+        let center = egui::pos2(viewport.x, viewport.y) / gui_scale.applied;
+        let stroke = egui::Stroke::new(1.5, PROBE_COLORS[index % PROBE_COLORS.len()]);
+
+        painter.circle_stroke(center, 6.0, stroke);
+        painter.hline(center.x - 9.0..=center.x + 9.0, center.y, stroke);
+        painter.vline(center.x, center.y - 9.0..=center.y + 9.0, stroke);
+        painter.text(center + egui::vec2(8.0, -8.0), egui::Align2::LEFT_BOTTOM, index.to_string(),
+            egui::FontId::monospace(11.0), stroke.color);
+    }
+    Ok(())
+}
+
+pub fn pressure_probe_gui(
+    mut contexts: EguiContexts,
+    mut probes: ResMut<PressureProbes>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Pressure Probes")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1200.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            let probes = probes.as_mut();
+            ui.horizontal(|ui| {
+                let full = probes.points.len() >= MAX_PRESSURE_PROBES;
+                ui.add_enabled_ui(!full, |ui| ui.toggle_value(&mut probes.placing, "Place Probe"));
+                if probes.placing
+                {
+                    ui.label("Click the fluid to place it");
+                }
+            });
+
+            let mut removed = None;
+            egui::Grid::new("pressure_probe_grid").num_columns(4).show(ui, |ui| {
+                for (index, point) in probes.points.iter_mut().enumerate()
+                {
+                    ui.colored_label(PROBE_COLORS[index % PROBE_COLORS.len()], format!("#{index}"));
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut point.x).speed(1.0).prefix("x "));
+                        ui.add(egui::DragValue::new(&mut point.y).speed(1.0).prefix("y "));
+                    });
+                    match probes.latest.get(index) {
+                        Some([density, _, pressure]) => ui.label(format!("ρ {:.2}  p {:.2}", density, pressure)),
+                        None => ui.label("-"),
+                    };
+                    if ui.small_button("Remove").clicked()
+                    {
+                        removed = Some(index);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(index) = removed
+            {
+                probes.points.remove(index);
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut probes.recording, "Record");
+                if ui.button("Clear").clicked()
+                {
+                    probes.history.clear();
+                }
+                if ui.add_enabled(!probes.history.is_empty(), egui::Button::new("Export CSV")).clicked()
+                {
+                    probes.status = Some(match probes.export_csv() {
+                        Ok(rows) => format!("Wrote {} frames to {}", rows, PRESSURE_PROBE_PATH),
+                        Err(error) => format!("Failed to write {}: {}", PRESSURE_PROBE_PATH, error),
+                    });
+                }
+            });
+            ui.label(format!("Recorded frames: {}", probes.history.len()));
+
+            // pressure over the last PLOT_FRAMES recorded frames, scaled to the largest magnitude
+            let recent = &probes.history[probes.history.len().saturating_sub(PLOT_FRAMES)..];
+            let (response, painter) = ui.allocate_painter(egui::vec2(260.0, 80.0), egui::Sense::hover());
+            let rect = response.rect;
+            painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, egui::Color32::DARK_GRAY), egui::StrokeKind::Inside);
+            painter.hline(rect.x_range(), rect.center().y, egui::Stroke::new(1.0, egui::Color32::GRAY));
+
+            let scale = recent.iter()
+                .flat_map(|(_, samples)| samples.iter().map(|sample| sample[2].abs()))
+                .fold(0.0f32, f32::max)
+                .max(f32::EPSILON);
+            for probe in 0..probes.points.len()
+            {
+                let points: Vec<egui::Pos2> = recent.iter().enumerate().map(|(i, (_, samples))| {
+                    let x = rect.left() + rect.width() * i as f32 / (PLOT_FRAMES - 1) as f32;
+                    let y = rect.center().y - samples[probe][2] / scale * rect.height() * 0.5;
+                    egui::pos2(x, y)
+                }).collect();
+                painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, PROBE_COLORS[probe % PROBE_COLORS.len()])));
+            }
+
+            if let Some(status) = &probes.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+use crate::parameter_gui::GUIConfig;
+use crate::gui_scale::GuiScale;
+
+const GAUGE_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 220, 255);
+
+// set by the Sim Params window while the smoothing radius slider is hovered or dragged
+#[derive(Resource, Default)]
+pub struct SmoothingRadiusGauge
+{
+    pub active: bool,
+}
+
+// The smoothing radius at the cursor's zoom, drawn over the windows so it can be held up
+// against the fluid while the slider is in use, with a particle for scale and the number of
+// neighbors it spans at the target density (particles have unit mass).
+pub fn draw_smoothing_radius_gauge(
+    mut contexts: EguiContexts,
+    gauge: Res<SmoothingRadiusGauge>,
+    gui_config: Res<GUIConfig>,
+    sim_config: Res<ParticleConfig>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if !gauge.active { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    let Some(center) = ctx.pointer_latest_pos() else { return Ok(()); };
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+
+    // egui points per world unit, the same everywhere for the orthographic camera
+    let Ok(origin) = camera.world_to_viewport(camera_transform, Vec3::ZERO) else { return Ok(()); };
+    let Ok(unit) = camera.world_to_viewport(camera_transform, Vec3::X) else { return Ok(()); };
+    let points_per_unit = origin.distance(unit) / gui_scale.applied;
+
+    let neighbors = gui_config.target_density.neighbors_within(gui_config.smoothing_radius);
+    let radius = gui_config.smoothing_radius.0;
+
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Tooltip, egui::Id::new("smoothing_radius_gauge")));
+    painter.circle(center, radius * points_per_unit, GAUGE_COLOR.gamma_multiply(0.15), egui::Stroke::new(1.5, GAUGE_COLOR));
+    painter.circle_filled(center, (sim_config.particle_size * 0.5 * points_per_unit).max(1.0), GAUGE_COLOR);
+    painter.text(
+        center + egui::vec2(0.0, -radius * points_per_unit - 4.0),
+        egui::Align2::CENTER_BOTTOM,
+        format!("r = {:.1} ({:.1}x particle size), ~{:.0} neighbors", radius, radius / sim_config.particle_size.max(f32::EPSILON), neighbors),
+        egui::FontId::proportional(13.0),
+        GAUGE_COLOR,
+    );
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        view::{ExtractedView, ViewTarget},
+    },
+};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::particle_render::ParticleRenderPipeline;
+
+pub const MAX_PALETTE_COLORS: usize = 16;   // must match render_shader.wgsl
+
+// Retro mode draws each system into these instead of the view, then the palette pass quantizes
+// the resolved copy onto the view. Sized and multisampled like the view, so the view pipelines
+// draw into them unchanged.
+#[derive(Resource)]
+pub struct RetroTextures
+{
+    size: UVec2,
+    sample_count: u32,
+    multisampled: Option<TextureView>,  // only with MSAA on, resolved into `resolved`
+    resolved: TextureView,
+    resolved_bind_group: BindGroup,     // group 1 for the palette pass
+}
+
+impl RetroTextures
+{
+    // cleared to transparent, so the palette pass can tell the fluid from the background
+    pub fn color_attachment(&self) -> RenderPassColorAttachment<'_>
+    {
+        let (view, resolve_target) = match &self.multisampled {
+            Some(multisampled) => (&**multisampled, Some(&*self.resolved)),
+            None => (&*self.resolved, None),
+        };
+        RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: Operations { load: LoadOp::Clear(Default::default()), store: StoreOp::Store },
+        }
+    }
+}
+
+fn create_retro_texture(render_device: &RenderDevice, label: &'static str, size: UVec2, sample_count: u32) -> TextureView
+{
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8UnormSrgb,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    texture.create_view(&TextureViewDescriptor::default())
+}
+
+// (re)create the retro textures while retro mode is on and the view size or MSAA setting changes
+pub fn prepare_retro_textures(
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ParticleRenderPipeline>,
+    config: Res<ParticleConfig>,
+    view_query: Query<(&ExtractedView, &Msaa)>,
+    retro_textures: Option<Res<RetroTextures>>,
+    mut commands: Commands,
+)
+{
+    if config.retro_enabled == 0 { return; }
+    let Some((view, msaa)) = view_query.iter().next() else { return; };
+
+    let size = UVec2::new(view.viewport.z, view.viewport.w).max(UVec2::ONE);
+    let sample_count = msaa.samples();
+    if retro_textures.is_some_and(|textures| textures.size == size && textures.sample_count == sample_count) { return; }
+
+    let resolved = create_retro_texture(&render_device, "retro_resolved_texture", size, 1);
+    let resolved_bind_group = render_device.create_bind_group(
+        "retro_resolved_texture",
+        &pipeline.surface_texture_layout,
+        &[
+            BindGroupEntry { binding: 0, resource: BindingResource::TextureView(&resolved) },
+            BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&pipeline.surface_sampler) },
+        ],
+    );
+    commands.insert_resource(RetroTextures
+    {
+        size,
+        sample_count,
+        multisampled: (sample_count > 1).then(|| create_retro_texture(&render_device, "retro_multisampled_texture", size, sample_count)),
+        resolved,
+        resolved_bind_group,
+    });
+}
+
+// quantize the system's drawing onto the view, after its render pass into the retro textures
+pub fn render_palette(
+    render_context: &mut RenderContext,
+    palette_pipeline: &RenderPipeline,
+    pipeline_buffers: &GPUPipelineBuffers,
+    retro_textures: &RetroTextures,
+    target: &ViewTarget,
+)
+{
+    let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor
+    {
+        label: Some("retro_palette_pass"),
+        color_attachments: &[Some(target.get_color_attachment())],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None
+    });
+    render_pass.set_render_pipeline(palette_pipeline);
+    render_pass.set_bind_group(0, &pipeline_buffers.render_bind_group, &[]);
+    render_pass.set_bind_group(1, &retro_textures.resolved_bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
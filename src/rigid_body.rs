@@ -0,0 +1,232 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::ParticleConfig;
+use crate::obstacle::{Obstacle, ObstacleShape, MAX_OBSTACLES};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::GpuReadback;
+
+const BODY_FIXED_POINT_SCALE: f32 = 16.0;           // must match BODY_FIXED_POINT_SCALE in compute_shader.wgsl
+const BODY_ANGULAR_FIXED_POINT_SCALE: f32 = 1.0;    // must match BODY_ANGULAR_FIXED_POINT_SCALE in compute_shader.wgsl
+pub const BODY_IMPULSE_BUFFER_SIZE: u64 = (std::mem::size_of::<[i32; 3]>() * MAX_OBSTACLES) as u64;
+const DEFAULT_RELATIVE_DENSITY: f32 = 0.5;
+
+// An obstacle that moves. The collision pass pushes particles out with the body's surface
+// velocity and sums the opposite impulses into its slot of the body impulse buffer; those are
+// read back and integrated here along with gravity. Only the main system's impulses are read.
+#[derive(ExtractComponent, Component, Clone, Copy)]
+pub struct RigidBody
+{
+    pub slot: u32,              // index into the body impulse buffer, unique among bodies
+    pub mass: f32,
+    pub inertia: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32,  // radians per second, counter clockwise
+}
+
+impl RigidBody
+{
+    // mass of the shape's area at `relative_density` times the fluid's target density (particles
+    // have unit mass), so bodies below 1 float
+    pub fn new(shape: &ObstacleShape, relative_density: f32, target_density: f32, slot: u32) -> Self
+    {
+        let (area, inertia_per_mass) = match *shape {
+            ObstacleShape::Circle { radius } => (std::f32::consts::PI * radius * radius, radius * radius / 2.0),
+            ObstacleShape::Aabb { half_extents } | ObstacleShape::OrientedBox { half_extents, .. } =>
+                (4.0 * half_extents.x * half_extents.y, half_extents.length_squared() / 3.0),
+        };
+        let mass = (area * relative_density * target_density).max(0.001);
+        Self { slot, mass, inertia: mass * inertia_per_mass, velocity: Vec2::ZERO, angular_velocity: 0.0 }
+    }
+}
+
+#[derive(Resource)]
+pub struct RigidBodies
+{
+    pub relative_density: f32,
+}
+
+impl Default for RigidBodies
+{
+    fn default() -> Self
+    {
+        Self { relative_density: DEFAULT_RELATIVE_DENSITY }
+    }
+}
+
+// shared between main and render worlds: the main world asks for impulses while bodies exist,
+// the render world adds every readback to the impulses not yet applied
+#[derive(Resource, Clone, Default)]
+pub struct RigidBodyShared
+{
+    wanted: Arc<AtomicBool>,
+    impulses: Arc<Mutex<Vec<[f32; 3]>>>,
+}
+
+// render world side of the impulse readback
+#[derive(Resource)]
+pub struct RigidBodyReadback
+{
+    impulses: GpuReadback,
+}
+
+impl Default for RigidBodyReadback
+{
+    fn default() -> Self
+    {
+        Self { impulses: GpuReadback::new("body_impulse_readback_buffer") }
+    }
+}
+
+// copy the main system's impulses and clear them, so each readback holds the impulses of
+// every step since the one before and none are lost while a copy is in flight
+pub fn read_back_body_impulses(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    shared: Res<RigidBodyShared>,
+    mut body_readback: ResMut<RigidBodyReadback>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(impulses) = body_readback.impulses.try_read::<[i32; 3]>(&render_device)
+    {
+        let mut pending = shared.impulses.lock().unwrap();
+        pending.resize(impulses.len(), [0.0; 3]);
+        for (sum, impulse) in pending.iter_mut().zip(&impulses)
+        {
+            sum[0] += impulse[0] as f32 / BODY_FIXED_POINT_SCALE;
+            sum[1] += impulse[1] as f32 / BODY_FIXED_POINT_SCALE;
+            sum[2] += impulse[2] as f32 / BODY_ANGULAR_FIXED_POINT_SCALE;
+        }
+    }
+
+    if !body_readback.impulses.is_idle() || !shared.wanted.load(Ordering::Relaxed) { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        body_readback.impulses.request(&render_device, &render_queue, &pipeline_buffers.body_impulse_buffer, BODY_IMPULSE_BUFFER_SIZE);
+        // the copy is already submitted, so the clear lands after it and before the next step
+        render_queue.write_buffer(&pipeline_buffers.body_impulse_buffer, 0, &[0u8; BODY_IMPULSE_BUFFER_SIZE as usize]);
+    }
+}
+
+// keep the body's bounding box on screen, bouncing off the edges like the particles do
+fn keep_in_bounds(body: &mut RigidBody, obstacle: &mut Obstacle, screen_bounds: [f32; 4], damping_factor: f32)
+{
+    let extent = match obstacle.shape {
+        ObstacleShape::Circle { radius } => Vec2::splat(radius),
+        ObstacleShape::Aabb { half_extents } => half_extents,
+        ObstacleShape::OrientedBox { half_extents, rotation } => {
+            let (sin, cos) = rotation.sin_cos();
+            Vec2::new(
+                cos.abs() * half_extents.x + sin.abs() * half_extents.y,
+                sin.abs() * half_extents.x + cos.abs() * half_extents.y,
+            )
+        }
+    };
+    let [x_min, x_max, y_min, y_max] = screen_bounds;
+    let min = Vec2::new(x_min, y_min) + extent;
+    let max = Vec2::new(x_max, y_max) - extent;
+
+    for axis in 0..2
+    {
+        if obstacle.position[axis] < min[axis]
+        {
+            obstacle.position[axis] = min[axis];
+            body.velocity[axis] = body.velocity[axis].abs() * damping_factor;
+        }
+        else if obstacle.position[axis] > max[axis]
+        {
+            obstacle.position[axis] = max[axis];
+            body.velocity[axis] = -body.velocity[axis].abs() * damping_factor;
+        }
+    }
+}
+
+// apply the particles' impulses, then step the bodies with the sim's timestep and gravity
+pub fn update_rigid_bodies(
+    shared: Res<RigidBodyShared>,
+    sim_config: Res<ParticleConfig>,
+    mut body_query: Query<(&mut RigidBody, &mut Obstacle)>,
+)
+{
+    shared.wanted.store(!body_query.is_empty(), Ordering::Relaxed);
+    let impulses = std::mem::take(&mut *shared.impulses.lock().unwrap());
+
+    for (mut body, mut obstacle) in body_query.iter_mut()
+    {
+        if let Some(&[x, y, angular]) = impulses.get(body.slot as usize)
+        {
+            body.velocity += Vec2::new(x, y) / body.mass;
+            body.angular_velocity += angular / body.inertia;
+        }
+        if sim_config.paused != 0 { continue; }
+
+        let dt = sim_config.fixed_delta_time;
+        body.velocity += Vec2::from(sim_config.gravity) * dt;
+        obstacle.position += body.velocity * dt;
+        match &mut obstacle.shape {
+            ObstacleShape::OrientedBox { rotation, .. } => *rotation += body.angular_velocity * dt,
+            ObstacleShape::Aabb { .. } => body.angular_velocity = 0.0,  // axis aligned boxes can't turn
+            ObstacleShape::Circle { .. } => {}
+        }
+        keep_in_bounds(&mut body, &mut obstacle, sim_config.screen_bounds, sim_config.damping_factor);
+    }
+}
+
+// spawn bodies near the top of the screen so they drop into the fluid
+pub fn rigid_body_gui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut rigid_bodies: ResMut<RigidBodies>,
+    body_query: Query<(Entity, &RigidBody)>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    let [x_min, x_max, y_min, y_max] = config.screen_bounds;
+    let position = Vec2::new((x_min + x_max) / 2.0, y_max - (y_max - y_min) * 0.2);
+    let size = ((x_max - x_min).min(y_max - y_min) * 0.05).max(10.0);
+    let free_slot = (0..MAX_OBSTACLES as u32).find(|slot| body_query.iter().all(|(_, body)| body.slot != *slot));
+
+    egui::Window::new("Rigid Bodies")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 650.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label("Obstacles with mass, pushed around by the fluid");
+            ui.add(egui::Slider::new(&mut rigid_bodies.relative_density, 0.05..=3.0)
+                .text("Density (x fluid)")
+                .logarithmic(true));
+
+            ui.add_enabled_ui(free_slot.is_some(), |ui| {
+                ui.horizontal(|ui| {
+                    let mut shape = None;
+                    if ui.button("Floating Circle").clicked() {
+                        shape = Some(ObstacleShape::Circle { radius: size });
+                    }
+                    if ui.button("Floating Box").clicked() {
+                        shape = Some(ObstacleShape::OrientedBox { half_extents: Vec2::new(size * 1.5, size), rotation: 0.0 });
+                    }
+                    if let (Some(shape), Some(slot)) = (shape, free_slot) {
+                        let body = RigidBody::new(&shape, rigid_bodies.relative_density, config.target_density, slot);
+                        commands.spawn((Obstacle { position, shape }, body));
+                    }
+                });
+            });
+            if ui.button("Clear").clicked() {
+                for (entity, _) in body_query.iter() {
+                    commands.entity(entity).despawn();
+                }
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::{ParticleConfig, ParticleSystem};
+use crate::particle::Particle;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget};
+use crate::parameter_gui::GUIConfig;
+
+const SCENE_MAGIC: &[u8; 4] = b"PSCN";
+const SCENE_VERSION: u32 = 2;
+const OLDEST_SCENE_VERSION: u32 = 1;
+const DEFAULT_SCENE_PATH: &str = "scene.pscn";
+
+// Scene file layout, little endian:
+//   magic, version, particle count, param text length (u32 each)
+//   param text, the GUIConfig `name = value` lines that ParticleConfig is rebuilt from
+//   particles, position, velocity and color as 8 f32 each
+// Version 2 param text starts with its own version line, version 1 text has none and is
+// migrated like any unversioned params; the binary layout is the same.
+type ParticleRecord = [f32; 8];
+
+pub fn encode_scene(params: &str, particles: &[ParticleRecord]) -> Vec<u8>
+{
+    let mut bytes = Vec::with_capacity(16 + params.len() + std::mem::size_of_val(particles));
+    bytes.extend_from_slice(SCENE_MAGIC);
+    bytes.extend_from_slice(&SCENE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(particles.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(params.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(params.as_bytes());
+    for value in particles.iter().flatten()
+    {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+fn decode_scene(bytes: &[u8]) -> std::result::Result<(String, Vec<ParticleRecord>), String>
+{
+    let read_u32 = |offset: usize| -> std::result::Result<u32, String> {
+        bytes.get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .ok_or_else(|| "file is truncated".to_string())
+    };
+
+    if bytes.get(0..4) != Some(SCENE_MAGIC.as_slice()) { return Err("not a scene file".to_string()); }
+    let version = read_u32(4)?;
+    if !(OLDEST_SCENE_VERSION..=SCENE_VERSION).contains(&version) { return Err(format!("unsupported scene version {version}")); }
+    let particle_count = read_u32(8)? as usize;
+    let params_len = read_u32(12)? as usize;
+    if particle_count == 0 { return Err("scene has no particles".to_string()); }
+
+    let params_end = 16 + params_len;
+    let params = bytes.get(16..params_end).ok_or("file is truncated")?;
+    let params = String::from_utf8(params.to_vec()).map_err(|_| "parameter text is not utf-8".to_string())?;
+
+    let particle_bytes = bytes.get(params_end..).unwrap_or(&[]);
+    if particle_bytes.len() != particle_count * std::mem::size_of::<ParticleRecord>()
+    {
+        return Err(format!("expected {} particles of data", particle_count));
+    }
+    let mut values = particle_bytes.chunks_exact(4).map(|word| f32::from_le_bytes(word.try_into().unwrap()));
+    let particles = (0..particle_count)
+        .map(|_| std::array::from_fn(|_| values.next().unwrap()))
+        .collect();
+
+    Ok((params, particles))
+}
+
+// shared between main and render worlds: the main world asks for a save, the render
+// world hands back the particle buffer contents
+#[derive(Resource, Clone, Default)]
+pub struct SceneShared
+{
+    save_requested: Arc<AtomicBool>,
+    particles: Arc<Mutex<Option<Vec<ParticleRecord>>>>,
+}
+
+// render world side of the save readback
+#[derive(Resource)]
+pub struct SceneReadback
+{
+    particles: GpuReadback,
+}
+
+impl Default for SceneReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("scene_particle_readback_buffer") }
+    }
+}
+
+#[derive(Resource)]
+pub struct SceneIo
+{
+    pub path: String,
+    pub status: Option<String>,
+    save_path: Option<String>,      // where the in-flight save will be written
+    load_path: Option<String>,      // applied once the particle system exists
+}
+
+impl Default for SceneIo
+{
+    fn default() -> Self
+    {
+        // a scene passed on the command line is loaded at startup
+        let startup_scene = std::env::args().nth(1).filter(|arg| !arg.starts_with("--"));
+        Self
+        {
+            path: startup_scene.clone().unwrap_or_else(|| DEFAULT_SCENE_PATH.to_string()),
+            status: None,
+            save_path: None,
+            load_path: startup_scene,
+        }
+    }
+}
+
+impl SceneIo
+{
+    pub fn request_save(&mut self, shared: &SceneShared)
+    {
+        let path = self.path.clone();
+        self.request_save_to(path, shared);
+    }
+
+    // save somewhere other than the path in the Scene window, e.g. the autosave's recovery file
+    pub fn request_save_to(&mut self, path: String, shared: &SceneShared)
+    {
+        if self.save_path.is_some() { return; }
+        self.save_path = Some(path);
+        shared.save_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn request_load(&mut self)
+    {
+        self.load_path = Some(self.path.clone());
+    }
+
+    pub fn request_load_from(&mut self, path: String)
+    {
+        self.load_path = Some(path);
+    }
+
+    // a save was requested and hasn't been written yet
+    pub fn save_pending(&self) -> bool
+    {
+        self.save_path.is_some()
+    }
+}
+
+pub fn read_back_scene_particles(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    shared: Res<SceneShared>,
+    mut scene_readback: ResMut<SceneReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(particles) = scene_readback.particles.try_read::<ParticleRecord>(&render_device)
+    {
+        *shared.particles.lock().unwrap() = Some(particles);
+    }
+
+    if !scene_readback.particles.is_idle() || !shared.save_requested.load(Ordering::Relaxed) { return; }
+
+    // wait for a fully uploaded particle buffer
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        // the save stays requested until the budget lets the copy through
+        let size = (std::mem::size_of::<ParticleRecord>() * pipeline_buffers.particle_count as usize) as u64;
+        if !budget.try_acquire("scene", size, 1) { return; }
+
+        shared.save_requested.store(false, Ordering::Relaxed);
+        scene_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            size,
+        );
+    }
+}
+
+// F5 saves and F9 loads; finishes saves once the readback arrives and applies pending loads
+pub fn update_scene_io(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    shared: Res<SceneShared>,
+    mut scene_io: ResMut<SceneIo>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut sim_config: ResMut<ParticleConfig>,
+    mut particle_system_query: Query<&mut ParticleSystem, Without<ParticleSystemConfig>>,
+)
+{
+    if keyboard_input.just_pressed(KeyCode::F5)
+    {
+        scene_io.request_save(&shared);
+    }
+    if keyboard_input.just_pressed(KeyCode::F9)
+    {
+        scene_io.request_load();
+    }
+
+    if let Some(particles) = shared.particles.lock().unwrap().take()
+    {
+        let path = scene_io.save_path.take().unwrap_or_else(|| scene_io.path.clone());
+        let bytes = encode_scene(&gui_config.to_text(), &particles);
+        scene_io.status = Some(match std::fs::write(&path, bytes) {
+            Ok(()) => format!("Saved {} particles to {}", particles.len(), path),
+            Err(error) => format!("Failed to save {}: {}", path, error),
+        });
+        info!("[Scene] {}", scene_io.status.as_ref().unwrap());
+    }
+
+    let Ok(mut particle_system) = particle_system_query.single_mut() else { return; };
+    let Some(path) = scene_io.load_path.take() else { return; };
+
+    let loaded = std::fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|bytes| decode_scene(&bytes))
+        .and_then(|(params, particles)| {
+            let mut updated = *gui_config;
+            updated.apply_text(&params)?;
+            Ok((updated, particles))
+        });
+
+    scene_io.status = Some(match loaded {
+        Ok((updated, particles)) => {
+            // set the count directly as well so the particles aren't rescattered before
+            // the gui params are applied next frame
+            let particle_count = particles.len() as u32;
+            *gui_config = GUIConfig { particle_count, ..updated };
+            sim_config.particle_count = particle_count;
+
+            particle_system.particles = particles.iter()
+                .map(|record| Particle {
+                    position: [record[0], record[1]],
+                    velocity: [record[2], record[3]],
+                    color: [record[4], record[5], record[6], record[7]],
+                })
+                .collect();
+            particle_system.generation = particle_system.generation.wrapping_add(1);
+            format!("Loaded {} particles from {}", particle_count, path)
+        }
+        Err(error) => format!("Failed to load {}: {}", path, error),
+    });
+    info!("[Scene] {}", scene_io.status.as_ref().unwrap());
+}
+
+pub fn scene_gui(
+    mut contexts: EguiContexts,
+    shared: Res<SceneShared>,
+    mut scene_io: ResMut<SceneIo>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Scene")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 800.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut scene_io.path);
+            });
+            ui.horizontal(|ui| {
+                if ui.add_enabled(scene_io.save_path.is_none(), egui::Button::new("Save (F5)")).clicked() {
+                    scene_io.request_save(&shared);
+                }
+                if ui.button("Load (F9)").clicked() {
+                    scene_io.request_load();
+                }
+            });
+            if let Some(status) = &scene_io.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
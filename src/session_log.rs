@@ -0,0 +1,249 @@
+use bevy::{
+    prelude::*,
+    render::renderer::RenderAdapterInfo,
+};
+use bevy_egui::{egui, EguiContexts};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use crate::ParticleConfig;
+use crate::parameter_gui::GUIConfig;
+use crate::presets::ParamValue;
+use crate::stats::StatsOverlay;
+
+const DEFAULT_LOG_DIRECTORY: &str = "session_logs";
+const MAX_FRAME_SAMPLES: usize = 1 << 20;   // about 4 hours at 60 fps, later frames aren't sampled
+const DIVERGENCE_SPEED: f32 = 1.0e5;        // a max speed past this means the sim has blown up
+const FPS_PERCENTILES: [f32; 4] = [1.0, 5.0, 50.0, 95.0];
+
+// A plain text record of one session for attaching to performance reports: the params it
+// started with, every param change, frame rate percentiles and the times the sim diverged.
+// Nothing leaves the machine, the log is only written to `directory` on exit (or when asked)
+// while enabled. `--session-log` enables it at launch.
+#[derive(Resource)]
+pub struct SessionLog
+{
+    pub enabled: bool,
+    pub directory: String,
+    started: Option<f32>,               // real seconds since launch when logging started
+    started_at: u64,                    // unix seconds, names the file
+    start_params: String,
+    last_params: Option<GUIConfig>,
+    events: Vec<String>,                // param changes and divergences, each prefixed with its time
+    frame_times: Vec<f32>,              // seconds
+    diverged: bool,                     // inside a divergence, so it's logged once
+    last_stats_frame: Option<u32>,
+    status: Option<String>,
+}
+
+impl Default for SessionLog
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            enabled: std::env::args().any(|arg| arg == "--session-log"),
+            directory: DEFAULT_LOG_DIRECTORY.to_string(),
+            started: None,
+            started_at: 0,
+            start_params: String::new(),
+            last_params: None,
+            events: Vec::new(),
+            frame_times: Vec::new(),
+            diverged: false,
+            last_stats_frame: None,
+            status: None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_seconds() -> u64
+{
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+// the system clock panics in the browser, where the log can't be written anyway
+#[cfg(target_arch = "wasm32")]
+fn unix_seconds() -> u64
+{
+    0
+}
+
+fn format_value(value: ParamValue) -> String
+{
+    match value {
+        ParamValue::Bool(value) => value.to_string(),
+        ParamValue::Integer(value) => value.to_string(),
+        ParamValue::Float(value) => format!("{:?}", value),
+    }
+}
+
+impl SessionLog
+{
+    fn start(&mut self, elapsed: f32, gui_config: &GUIConfig)
+    {
+        self.started = Some(elapsed);
+        self.started_at = unix_seconds();
+        self.start_params = gui_config.to_text();
+        self.last_params = Some(GUIConfig { applied_changes: false, ..*gui_config });
+        self.events.clear();
+        self.frame_times.clear();
+        self.diverged = false;
+    }
+
+    fn render(&self, elapsed: f32, config: &ParticleConfig, adapter: Option<&RenderAdapterInfo>) -> String
+    {
+        let mut text = String::new();
+        let _ = writeln!(text, "session started at unix time {}, logged for {:.1} s", self.started_at, elapsed - self.started.unwrap_or(elapsed));
+        if let Some(adapter) = adapter
+        {
+            let _ = writeln!(text, "adapter: {} ({:?}, {:?}), driver {} {}", adapter.name, adapter.backend, adapter.device_type, adapter.driver, adapter.driver_info);
+        }
+        let _ = writeln!(text, "particles at exit: {}, sim frames: {}", config.particle_count, config.frame_count);
+
+        let _ = writeln!(text, "\n[frame rate]");
+        if self.frame_times.is_empty()
+        {
+            let _ = writeln!(text, "no frames sampled");
+        }
+        else
+        {
+            // the slow frames are the low fps percentiles
+            let mut frame_times = self.frame_times.clone();
+            frame_times.sort_by(|a, b| b.total_cmp(a));
+            let _ = writeln!(text, "frames: {}", frame_times.len());
+            for percentile in FPS_PERCENTILES
+            {
+                let index = ((percentile / 100.0 * frame_times.len() as f32) as usize).min(frame_times.len() - 1);
+                let _ = writeln!(text, "p{}: {:.1} fps", percentile, 1.0 / frame_times[index].max(f32::EPSILON));
+            }
+        }
+
+        let _ = writeln!(text, "\n[events]");
+        for event in &self.events
+        {
+            let _ = writeln!(text, "{}", event);
+        }
+
+        let _ = writeln!(text, "\n[start params]");
+        text.push_str(&self.start_params);
+        text
+    }
+
+    fn write(&mut self, elapsed: f32, config: &ParticleConfig, adapter: Option<&RenderAdapterInfo>)
+    {
+        let path = PathBuf::from(&self.directory).join(format!("session_{}.txt", self.started_at));
+        let written = std::fs::create_dir_all(&self.directory)
+            .and_then(|_| std::fs::write(&path, self.render(elapsed, config, adapter)));
+        self.status = Some(match written {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(error) => format!("Failed to write {}: {}", path.display(), error),
+        });
+        info!("[SessionLog] {}", self.status.as_ref().unwrap());
+    }
+}
+
+// sample the frame time and note param changes and divergences while enabled
+pub fn update_session_log(
+    time: Res<Time<Real>>,
+    gui_config: Res<GUIConfig>,
+    stats: Res<StatsOverlay>,
+    mut log: ResMut<SessionLog>,
+)
+{
+    let elapsed = time.elapsed_secs();
+    if !log.enabled
+    {
+        log.started = None;
+        return;
+    }
+    let Some(started) = log.started else {
+        log.start(elapsed, &gui_config);
+        return;
+    };
+    let stamp = format!("{:>9.2} s", elapsed - started);
+
+    if log.frame_times.len() < MAX_FRAME_SAMPLES && time.delta_secs() > 0.0
+    {
+        log.frame_times.push(time.delta_secs());
+    }
+
+    let current = GUIConfig { applied_changes: false, ..*gui_config };
+    let changed = log.last_params.map(|last| last.diff(&current)).unwrap_or_default();
+    if !changed.is_empty()
+    {
+        let values = current.param_values();
+        for name in changed
+        {
+            if let Some((_, value)) = values.iter().find(|(param, _)| *param == name)
+            {
+                log.events.push(format!("{}  {} = {}", stamp, name, format_value(*value)));
+            }
+        }
+        log.last_params = Some(current);
+    }
+
+    // stats are sampled while logging, see stats_overlay
+    if let Some(snapshot) = stats.latest.filter(|snapshot| Some(snapshot.sim_frame) != log.last_stats_frame)
+    {
+        log.last_stats_frame = Some(snapshot.sim_frame);
+        let diverged = !snapshot.kinetic_energy.is_finite() || !snapshot.max_speed.is_finite() || snapshot.max_speed > DIVERGENCE_SPEED;
+        if diverged && !log.diverged
+        {
+            log.events.push(format!("{}  diverged at sim frame {}, max speed {:.3e}, kinetic energy {:.3e}",
+                stamp, snapshot.sim_frame, snapshot.max_speed, snapshot.kinetic_energy));
+        }
+        log.diverged = diverged;
+    }
+}
+
+// runs last so it sees exits requested during the frame
+pub fn write_session_log_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    time: Res<Time<Real>>,
+    config: Res<ParticleConfig>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    mut log: ResMut<SessionLog>,
+)
+{
+    if exit_events.read().next().is_some() && log.enabled && log.started.is_some()
+    {
+        log.write(time.elapsed_secs(), &config, adapter.as_deref());
+    }
+}
+
+pub fn session_log_gui(
+    mut contexts: EguiContexts,
+    time: Res<Time<Real>>,
+    config: Res<ParticleConfig>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    mut log: ResMut<SessionLog>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Session Log")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1450.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.checkbox(&mut log.enabled, "Log this session");
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                ui.text_edit_singleline(&mut log.directory);
+            });
+            ui.label("Params, param changes, frame rates and divergences, written locally on exit");
+            if log.started.is_some()
+            {
+                ui.label(format!("{} frames, {} events so far", log.frame_times.len(), log.events.len()));
+                if ui.button("Write Now").clicked()
+                {
+                    log.write(time.elapsed_secs(), &config, adapter.as_deref());
+                }
+            }
+            if let Some(status) = &log.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowOccluded},
+};
+use bevy_egui::EguiContexts;
+
+use crate::ParticleConfig;
+use crate::parameter_gui::GUIConfig;
+use crate::power_saving::PowerSaving;
+use crate::sim_rng::SimRng;
+
+const DELTA_SMOOTHING: f32 = 0.1;   // weight of the newest frame delta in the moving average
+const SPIKE_FACTOR: f32 = 4.0;      // frame deltas are capped at this multiple of the smoothed delta
+
+#[derive(Resource)]
+pub struct SimClock
+{
+    pub paused: bool,
+    pub step_requested: bool,   // advance one timestep while paused
+    pub time_scale: f32,        // multiplies the timestep for slow motion
+    pub suspend_in_background: bool,   // hold the sim while the window is unfocused or minimized
+    pub suspended: bool,        // held for being in the background, the last frame stays on screen
+    step_accumulator: f32,      // real seconds not yet simulated, seeded runs only
+}
+
+impl Default for SimClock
+{
+    fn default() -> Self
+    {
+        Self { paused: false, step_requested: false, time_scale: 1.0, suspend_in_background: true, suspended: false, step_accumulator: 0.0 }
+    }
+}
+
+impl SimClock
+{
+    // whether a whole timestep of real time has built up; at most one step is owed, so slow
+    // frames slow the sim down rather than skipping ahead
+    fn fixed_step_due(&mut self, delta: f32, timestep: f32) -> bool
+    {
+        self.step_accumulator = (self.step_accumulator + delta).min(timestep);
+        if self.step_accumulator < timestep { return false; }
+        self.step_accumulator -= timestep;
+        true
+    }
+}
+
+// spacebar toggles pause; a requested step lets exactly one frame of sim passes through.
+// Power saving holds the sim on frames between its steps, and so does the window being in the
+// background, which like pausing leaves the frame count alone. Seeded runs with a window step on
+// a fixed clock, one timestep of sim per timestep of real time; headless ones step every frame
+pub fn update_sim_clock(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut occluded_events: EventReader<WindowOccluded>,
+    mut occluded: Local<bool>,
+    mut contexts: EguiContexts,
+    mut clock: ResMut<SimClock>,
+    mut power_saving: ResMut<PowerSaving>,
+    sim_rng: Res<SimRng>,
+    mut sim_config: ResMut<ParticleConfig>,
+)
+{
+    let typing = contexts.ctx_mut().map(|ctx| ctx.wants_keyboard_input()).unwrap_or(false);
+    if keyboard_input.just_pressed(KeyCode::Space) && !typing
+    {
+        clock.paused = !clock.paused;
+    }
+
+    // minimized windows report being occluded, or on some platforms a zero size
+    for event in occluded_events.read()
+    {
+        *occluded = event.occluded;
+    }
+    let in_background = windows.single().is_ok_and(|window| {
+        !window.focused || *occluded || window.physical_width() == 0 || window.physical_height() == 0
+    });
+    clock.suspended = clock.suspend_in_background && in_background;
+
+    let stepping = std::mem::take(&mut clock.step_requested) && clock.paused;
+    let fixed_clock = sim_rng.is_seeded() && windows.single().is_ok();
+    let timestep = sim_config.fixed_delta_time;
+    let held = clock.suspended || (!clock.paused && !power_saving.sim_step_due(time.delta_secs()))
+        || (!clock.paused && fixed_clock && !clock.fixed_step_due(time.delta_secs(), timestep));
+    sim_config.paused = ((clock.paused && !stepping) || held) as u32;
+}
+
+// count frames in the main world; the render world copy is replaced whenever the
+// config is re-extracted, so counting there would reset on every parameter change.
+// paused frames aren't counted so the ping-ponged grid halves stay put
+pub fn advance_frame_count(mut sim_config: ResMut<ParticleConfig>)
+{
+    if sim_config.paused != 0 { return; }
+    sim_config.frame_count = sim_config.frame_count.wrapping_add(1);
+}
+
+// drive the sim timestep from the frame time when enabled, rejecting hitches
+// (window drags, shader compiles) and clamping to the max step. Coming back from the
+// background starts the average over, so the gap isn't taken as one long frame. Seeded runs
+// keep the fixed timestep so they replay exactly
+pub fn update_delta_time(
+    time: Res<Time<Real>>,
+    gui_config: Res<GUIConfig>,
+    clock: Res<SimClock>,
+    sim_rng: Res<SimRng>,
+    mut sim_config: ResMut<ParticleConfig>,
+    mut smoothed_delta: Local<Option<f32>>,
+)
+{
+    if !gui_config.variable_delta_time || clock.suspended || sim_rng.is_seeded()
+    {
+        *smoothed_delta = None;
+        return;
+    }
+
+    let raw_delta = time.delta_secs();
+    if raw_delta <= 0.0 { return; }
+
+    let smoothed = match *smoothed_delta {
+        Some(smoothed) => {
+            let accepted = raw_delta.min(SPIKE_FACTOR * smoothed);
+            smoothed + DELTA_SMOOTHING * (accepted - smoothed)
+        }
+        None => raw_delta.min(gui_config.max_delta_time.0),
+    };
+    *smoothed_delta = Some(smoothed);
+
+    sim_config.fixed_delta_time = smoothed.min(gui_config.max_delta_time.0) * clock.time_scale;
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+// The one source of randomness on the CPU side: scattering, emitters, the brush and training
+// scenes all draw from it. `--seed <n>` seeds it, and seeded runs also step the sim on a fixed
+// clock (see update_sim_clock) and order each grid cell's particles so the GPU sums neighbors
+// the same way every time, so the same seed and params replay the same trajectories on the
+// same GPU and driver. Mouse input is still whatever the user does.
+#[derive(Resource)]
+pub struct SimRng
+{
+    pub seed: Option<u64>,
+    rng: StdRng,
+}
+
+impl SimRng
+{
+    pub fn from_args() -> Self
+    {
+        let args: Vec<String> = std::env::args().collect();
+        let seed = args.iter().position(|arg| arg == "--seed")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|value| value.parse().map_err(|_| warn!("[Seed] --seed expects a number, running unseeded")).ok());
+        if let Some(seed) = seed
+        {
+            info!("[Seed] Seeded run with seed {}", seed);
+        }
+        Self { seed, rng: seed.map_or_else(StdRng::from_os_rng, StdRng::seed_from_u64) }
+    }
+
+    pub fn is_seeded(&self) -> bool
+    {
+        self.seed.is_some()
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng
+    {
+        &mut self.rng
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{ShaderDefVal, WgpuFeatures},
+        renderer::{RenderAdapterInfo, RenderDevice},
+    },
+};
+
+// How particles are sorted into the spatial grid each frame. Backends are listed most preferred
+// first, and the first one the device supports is used unless `--sort-backend <name>` asks for
+// another; `auto` or no flag picks. The counting sort only needs storage atomics and workgroup
+// memory, so it runs everywhere (WebGPU included) and is always the last resort.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortBackend
+{
+    Subgroup,   // the counting sort with subgroup scans, which also speed up the stats reduction
+    Counting,   // atomic per cell counts, a blocked workgroup prefix sum, then a scatter
+}
+
+impl SortBackend
+{
+    pub const ALL: [SortBackend; 2] = [SortBackend::Subgroup, SortBackend::Counting];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            SortBackend::Subgroup => "subgroup",
+            SortBackend::Counting => "counting",
+        }
+    }
+
+    // specializes the scan and stats reduction kernels, see compute_shader.wgsl
+    pub fn shader_defs(&self) -> Vec<ShaderDefVal>
+    {
+        match self {
+            SortBackend::Subgroup => vec!["SUBGROUP_OPERATIONS".into()],
+            SortBackend::Counting => vec![],
+        }
+    }
+
+    fn required_features(&self) -> WgpuFeatures
+    {
+        match self {
+            SortBackend::Subgroup => WgpuFeatures::SUBGROUP,
+            SortBackend::Counting => WgpuFeatures::empty(),
+        }
+    }
+
+    fn requested() -> Option<String>
+    {
+        let args: Vec<String> = std::env::args().collect();
+        args.iter().position(|arg| arg == "--sort-backend")
+            .and_then(|index| args.get(index + 1).cloned())
+            .filter(|name| name != "auto")
+    }
+
+    // pick for the render device, logging the choice and why
+    pub fn select(world: &World) -> Self
+    {
+        let features = world.resource::<RenderDevice>().features();
+        let adapter = world.get_resource::<RenderAdapterInfo>().map_or("unknown adapter".to_string(), |adapter| {
+            format!("{} ({:?})", adapter.name, adapter.backend)
+        });
+        let supported = |backend: &SortBackend| features.contains(backend.required_features());
+
+        if let Some(name) = Self::requested()
+        {
+            match Self::ALL.iter().find(|backend| backend.name() == name) {
+                Some(backend) if supported(backend) => {
+                    info!("[Sort] Using the {} sort backend on {}, as requested", backend.name(), adapter);
+                    return *backend;
+                }
+                Some(backend) => warn!("[Sort] {} doesn't support the {} sort backend, picking another", adapter, backend.name()),
+                None => {
+                    let known: Vec<&str> = Self::ALL.iter().map(|backend| backend.name()).collect();
+                    warn!("[Sort] Unknown sort backend `{}`, expected auto or one of {}", name, known.join(", "));
+                }
+            }
+        }
+
+        let backend = Self::ALL.into_iter().find(supported).unwrap_or(SortBackend::Counting);
+        info!("[Sort] Using the {} sort backend on {}", backend.name(), adapter);
+        backend
+    }
+}
@@ -0,0 +1,302 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU32, Ordering}};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::{GpuReadback, ReadbackBudget, ReadbackBudgetShared, DEFAULT_READBACK_KILOBYTES, DEFAULT_READBACK_TRANSFERS};
+use crate::hud::HudSettings;
+use crate::session_log::SessionLog;
+use crate::gpu_timing::{GpuPhase, GpuTimingShared, GpuTimings};
+
+pub const STATS_HISTOGRAM_BINS: usize = 16;     // must match STATS_HISTOGRAM_BINS in compute_shader.wgsl
+const STATS_WORKGROUP_SIZE: u32 = 256;          // must match STATS_WORKGROUP_SIZE in compute_shader.wgsl
+const STATS_HEADER_SIZE: u64 = (std::mem::size_of::<u32>() * STATS_HISTOGRAM_BINS) as u64;
+const DEFAULT_STATS_INTERVAL: u32 = 10;         // frames between samples
+
+pub fn stats_workgroups(particle_count: u32) -> u32
+{
+    particle_count.div_ceil(STATS_WORKGROUP_SIZE).max(1)
+}
+
+// histogram followed by two vec4 partials per reduction workgroup
+pub fn stats_buffer_size(particle_count: u32) -> u64
+{
+    STATS_HEADER_SIZE + (std::mem::size_of::<[[f32; 4]; 2]>() as u32 * stats_workgroups(particle_count)) as u64
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct StatsSnapshot
+{
+    pub sim_frame: u32,
+    pub particle_count: u32,
+    pub average_density: f32,
+    pub max_speed: f32,
+    pub kinetic_energy: f32,                        // total, particles have unit mass
+    pub turbulence_intensity: f32,                  // average RMS velocity fluctuation over the history window
+    pub histogram: [u32; STATS_HISTOGRAM_BINS],     // occupied cells by particle count, the last bin open ended
+}
+
+impl StatsSnapshot
+{
+    // add up the per workgroup partials after the histogram
+    fn decode(words: &[u32], sim_frame: u32) -> Self
+    {
+        let mut snapshot = Self { sim_frame, ..default() };
+        snapshot.histogram.copy_from_slice(&words[..STATS_HISTOGRAM_BINS]);
+
+        let mut density_sum = 0.0;
+        let mut turbulence_sum = 0.0;
+        for partial in words[STATS_HISTOGRAM_BINS..].chunks_exact(8)
+        {
+            let [density, energy, speed, count, turbulence] = [0, 1, 2, 3, 4].map(|k| f32::from_bits(partial[k]));
+            density_sum += density;
+            turbulence_sum += turbulence;
+            snapshot.kinetic_energy += energy;
+            snapshot.max_speed = snapshot.max_speed.max(speed);
+            snapshot.particle_count += count as u32;
+        }
+        snapshot.average_density = density_sum / snapshot.particle_count.max(1) as f32;
+        snapshot.turbulence_intensity = turbulence_sum / snapshot.particle_count.max(1) as f32;
+        snapshot
+    }
+}
+
+// shared between main and render worlds. Prepare requests a reduction every `interval` frames
+// while the overlay is open, the compute node marks it dispatched, and cleanup copies the result.
+#[derive(Resource, Clone, Default)]
+pub struct StatsShared
+{
+    wanted: Arc<AtomicBool>,
+    interval: Arc<AtomicU32>,
+    requested: Arc<AtomicBool>,
+    dispatched: Arc<AtomicBool>,
+    snapshot: Arc<Mutex<Option<StatsSnapshot>>>,
+}
+
+impl StatsShared
+{
+    pub fn reduction_requested(&self) -> bool
+    {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    pub fn mark_dispatched(&self)
+    {
+        self.dispatched.store(true, Ordering::Relaxed);
+    }
+}
+
+// render world side of the stats readback
+#[derive(Resource)]
+pub struct StatsReadback
+{
+    readback: GpuReadback,
+    frame_count: u32,
+    requested_frame: u32,
+}
+
+impl Default for StatsReadback
+{
+    fn default() -> Self
+    {
+        Self { readback: GpuReadback::new("stats_readback_buffer"), frame_count: 0, requested_frame: 0 }
+    }
+}
+
+#[derive(Resource)]
+pub struct StatsOverlay
+{
+    pub interval: u32,
+    pub latest: Option<StatsSnapshot>,
+    pub gpu_timings: Option<GpuTimings>,
+    pub readback_kilobytes: u32,    // per frame budget shared by the inspection readbacks
+    pub readback_transfers: u32,
+}
+
+impl Default for StatsOverlay
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            interval: DEFAULT_STATS_INTERVAL,
+            latest: None,
+            gpu_timings: None,
+            readback_kilobytes: DEFAULT_READBACK_KILOBYTES,
+            readback_transfers: DEFAULT_READBACK_TRANSFERS,
+        }
+    }
+}
+
+// ask for a reduction once the interval has passed and the previous copy has landed
+pub fn schedule_stats_reduction(
+    shared: Res<StatsShared>,
+    mut stats_readback: ResMut<StatsReadback>,
+)
+{
+    if !shared.wanted.load(Ordering::Relaxed) || !stats_readback.readback.is_idle() { return; }
+
+    stats_readback.frame_count += 1;
+    if stats_readback.frame_count >= shared.interval.load(Ordering::Relaxed).max(1)
+    {
+        stats_readback.frame_count = 0;
+        shared.requested.store(true, Ordering::Relaxed);
+    }
+}
+
+// copy the stats buffer on frames the reduction actually ran, which it doesn't while paused
+pub fn read_back_stats(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<StatsShared>,
+    mut stats_readback: ResMut<StatsReadback>,
+    mut budget: ResMut<ReadbackBudget>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(words) = stats_readback.readback.try_read::<u32>(&render_device)
+    {
+        *shared.snapshot.lock().unwrap() = Some(StatsSnapshot::decode(&words, stats_readback.requested_frame));
+    }
+
+    if !shared.dispatched.load(Ordering::Relaxed) { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        // turned down, the reduction stays requested and runs again next frame
+        let size = stats_buffer_size(pipeline_buffers.particle_count);
+        if !budget.try_acquire("stats", size, 1) { return; }
+
+        shared.dispatched.store(false, Ordering::Relaxed);
+        shared.requested.store(false, Ordering::Relaxed);
+        stats_readback.requested_frame = config.frame_count;
+        stats_readback.readback.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.stats_buffer,
+            size,
+        );
+    }
+}
+
+pub fn stats_overlay(
+    mut contexts: EguiContexts,
+    shared: Res<StatsShared>,
+    mut settings: ResMut<HudSettings>,
+    mut overlay: ResMut<StatsOverlay>,
+    session_log: Res<SessionLog>,
+    gpu_timing: Res<GpuTimingShared>,
+    readback_budget: Res<ReadbackBudgetShared>,
+) -> Result
+{
+    // the session log watches the samples for divergence
+    shared.wanted.store(settings.stats_visible || session_log.enabled, Ordering::Relaxed);
+    shared.interval.store(overlay.interval, Ordering::Relaxed);
+    if let Some(snapshot) = shared.snapshot.lock().unwrap().take()
+    {
+        overlay.latest = Some(snapshot);
+    }
+    gpu_timing.wanted.store(settings.stats_visible, Ordering::Relaxed);
+    if let Some(timings) = gpu_timing.timings.lock().unwrap().take()
+    {
+        overlay.gpu_timings = Some(timings);
+    }
+    let timestamps_supported = gpu_timing.supported.load(Ordering::Relaxed);
+    readback_budget.max_kilobytes.store(overlay.readback_kilobytes, Ordering::Relaxed);
+    readback_budget.max_transfers.store(overlay.readback_transfers, Ordering::Relaxed);
+    let waiting_readbacks = readback_budget.waiting.load(Ordering::Relaxed);
+    if !settings.stats_visible { return Ok(()); }
+
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Statistics")
+        .open(&mut settings.stats_visible)
+        .collapsible(true)
+        .default_pos([ctx.screen_rect().width() - 310.0, ctx.screen_rect().height() - 420.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.add(egui::Slider::new(&mut overlay.interval, 1..=120)
+                .text("Sample Every (frames)"));
+
+            // per pass GPU time of the main system, compute phases summed over the frame's substeps
+            egui::CollapsingHeader::new("GPU Time").default_open(true).show(ui, |ui| {
+                if !timestamps_supported
+                {
+                    ui.label("Timestamp queries unsupported");
+                    return;
+                }
+                let Some(timings) = overlay.gpu_timings else {
+                    ui.label("Waiting for the first timed frame");
+                    return;
+                };
+                egui::Grid::new("gpu_time_grid").num_columns(2).show(ui, |ui| {
+                    for (phase, milliseconds) in GpuPhase::ALL.iter().zip(timings.milliseconds)
+                    {
+                        ui.label(phase.name());
+                        ui.label(format!("{:.3} ms", milliseconds));
+                        ui.end_row();
+                    }
+                    ui.label("Total");
+                    ui.label(format!("{:.3} ms", timings.milliseconds.iter().sum::<f32>()));
+                    ui.end_row();
+                });
+                ui.label(format!("Sim frame {}, {} substeps", timings.sim_frame, timings.substeps));
+            });
+
+            // stats, probes, exports and timings take turns copying back within this budget
+            egui::CollapsingHeader::new("Readback Budget").show(ui, |ui| {
+                ui.add(egui::Slider::new(&mut overlay.readback_kilobytes, 64..=65536)
+                    .text("KB per Frame")
+                    .logarithmic(true));
+                ui.add(egui::Slider::new(&mut overlay.readback_transfers, 1..=8)
+                    .text("Copies per Frame"));
+                ui.label(format!("{} requests waiting", waiting_readbacks));
+            });
+            ui.separator();
+
+            let Some(snapshot) = overlay.latest else {
+                ui.label("Waiting for the first sample");
+                return;
+            };
+
+            egui::Grid::new("stats_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Sim Frame");
+                ui.label(format!("{}", snapshot.sim_frame));
+                ui.end_row();
+                ui.label("Particles");
+                ui.label(format!("{}", snapshot.particle_count));
+                ui.end_row();
+                ui.label("Average Density");
+                ui.label(format!("{:.5}", snapshot.average_density));
+                ui.end_row();
+                ui.label("Max Speed");
+                ui.label(format!("{:.1}", snapshot.max_speed));
+                ui.end_row();
+                ui.label("Kinetic Energy");
+                ui.label(format!("{:.3e}", snapshot.kinetic_energy));
+                ui.end_row();
+                ui.label("Turbulence Intensity");
+                ui.label(format!("{:.2}", snapshot.turbulence_intensity));
+                ui.end_row();
+            });
+
+            ui.separator();
+            ui.label("Occupied cells by particle count");
+            let max_cells = snapshot.histogram.iter().copied().max().unwrap_or(0).max(1);
+            for (bin, cells) in snapshot.histogram.iter().enumerate()
+            {
+                let label = if bin + 1 == STATS_HISTOGRAM_BINS { format!("{}+", bin + 1) } else { format!("{}", bin + 1) };
+                ui.horizontal(|ui| {
+                    ui.add_sized([28.0, 14.0], egui::Label::new(egui::RichText::new(label).monospace()));
+                    ui.add(egui::ProgressBar::new(*cells as f32 / max_cells as f32)
+                        .desired_height(12.0)
+                        .text(format!("{}", cells)));
+                });
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use std::sync::{Arc, Mutex};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::GpuReadback;
+use crate::sim_rng::SimRng;
+
+pub const MAX_SPEED_BUFFER_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+// Adaptive substepping. While it's on, the compute node reduces the main system's largest particle
+// speed after every frame's particle passes, and the next frames repeat those passes (sort, density,
+// forces, integration) as many times as keep the fastest particle within cfl_number smoothing radii
+// per substep. The grid passes, probes and stats still run once a frame. The speed arrives a few
+// frames late through the readback, so a sudden kick gets a frame or two at the old count.
+// Extra particle systems follow the main system's count; the CPU backend always takes one step.
+
+// latest max speed read back from the GPU, shared between main and render worlds
+#[derive(Resource, Clone, Default)]
+pub struct MaxSpeedSample
+{
+    max_speed: Arc<Mutex<Option<f32>>>,
+}
+
+// render world side of the max speed readback
+#[derive(Resource)]
+pub struct MaxSpeedReadback
+{
+    readback: GpuReadback,
+}
+
+impl Default for MaxSpeedReadback
+{
+    fn default() -> Self
+    {
+        Self { readback: GpuReadback::new("max_speed_readback_buffer") }
+    }
+}
+
+// copy the reduced speed back on frames the reduction ran, which it doesn't while paused
+pub fn read_back_max_speed(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    sample: Res<MaxSpeedSample>,
+    mut max_speed_readback: ResMut<MaxSpeedReadback>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(words) = max_speed_readback.readback.try_read::<u32>(&render_device)
+    {
+        if let Some(bits) = words.first()
+        {
+            *sample.max_speed.lock().unwrap() = Some(f32::from_bits(*bits));
+        }
+    }
+
+    if config.max_substeps <= 1 || config.paused != 0 || !max_speed_readback.readback.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        max_speed_readback.readback.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.max_speed_buffer,
+            MAX_SPEED_BUFFER_SIZE,
+        );
+    }
+}
+
+// split the frame's time step by the latest max speed, after the time step itself is settled.
+// Seeded runs keep a single step, since the readback's latency would make them not replay
+pub fn update_substeps(
+    sample: Res<MaxSpeedSample>,
+    sim_rng: Res<SimRng>,
+    mut sim_config: ResMut<ParticleConfig>,
+    mut max_speed: Local<f32>,
+)
+{
+    if let Some(latest) = sample.max_speed.lock().unwrap().take()
+    {
+        *max_speed = latest;
+    }
+
+    let substeps = if sim_config.max_substeps <= 1 || sim_rng.is_seeded() {
+        1
+    } else {
+        let max_distance = (sim_config.cfl_number * sim_config.smoothing_radius).max(f32::EPSILON);
+        let needed = (*max_speed * sim_config.fixed_delta_time / max_distance).ceil();
+        // a diverged (NaN or infinite) speed takes as many substeps as allowed
+        if needed.is_finite() { (needed as u32).clamp(1, sim_config.max_substeps) } else { sim_config.max_substeps }
+    };
+    let substep_delta_time = sim_config.fixed_delta_time / substeps as f32;
+
+    if sim_config.substeps != substeps || sim_config.substep_delta_time != substep_delta_time
+    {
+        sim_config.substeps = substeps;
+        sim_config.substep_delta_time = substep_delta_time;
+    }
+}
@@ -0,0 +1,145 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        view::ExtractedView,
+    },
+};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::GPUPipelineBuffers;
+use crate::particle_render::ParticleRenderPipeline;
+use crate::util::SURFACE_TEXTURE_FORMAT;
+use crate::heatmap_render::RENDER_MODE_HEATMAP;
+
+pub const RENDER_MODE_SURFACE: u32 = 1;
+
+pub struct SurfaceTexture
+{
+    pub view: TextureView,
+    pub bind_group: BindGroup,  // group 1 for passes that read this texture
+}
+
+// thickness splatted by the particles and the intermediate of the separable blur, sized to the view;
+// the blur goes thickness -> blurred -> thickness, so the composite reads `thickness`. The
+// heatmap splats its density into `thickness` as well.
+#[derive(Resource)]
+pub struct SurfaceTextures
+{
+    size: UVec2,
+    pub thickness: SurfaceTexture,
+    blurred: SurfaceTexture,
+}
+
+fn create_surface_texture(
+    render_device: &RenderDevice,
+    pipeline: &ParticleRenderPipeline,
+    label: &'static str,
+    size: UVec2,
+) -> SurfaceTexture
+{
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: SURFACE_TEXTURE_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let bind_group = render_device.create_bind_group(
+        label,
+        &pipeline.surface_texture_layout,
+        &[
+            BindGroupEntry { binding: 0, resource: BindingResource::TextureView(&view) },
+            BindGroupEntry { binding: 1, resource: BindingResource::Sampler(&pipeline.surface_sampler) },
+        ],
+    );
+    SurfaceTexture { view, bind_group }
+}
+
+// (re)create the surface textures while the surface, heatmap or outline is drawn and the view size changes
+pub fn prepare_surface_textures(
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ParticleRenderPipeline>,
+    config: Res<ParticleConfig>,
+    view_query: Query<&ExtractedView>,
+    surface_textures: Option<Res<SurfaceTextures>>,
+    mut commands: Commands,
+)
+{
+    if config.render_mode != RENDER_MODE_SURFACE && config.render_mode != RENDER_MODE_HEATMAP && config.outline_enabled == 0 { return; }
+    let Some(view) = view_query.iter().next() else { return; };
+
+    let size = UVec2::new(view.viewport.z, view.viewport.w).max(UVec2::ONE);
+    if surface_textures.is_some_and(|textures| textures.size == size) { return; }
+
+    commands.insert_resource(SurfaceTextures
+    {
+        size,
+        thickness: create_surface_texture(&render_device, &pipeline, "surface_thickness_texture", size),
+        blurred: create_surface_texture(&render_device, &pipeline, "surface_blurred_texture", size),
+    });
+}
+
+pub fn surface_pass<'a>(
+    render_context: &'a mut RenderContext,
+    label: &'static str,
+    target: &'a TextureView,
+) -> TrackedRenderPass<'a>
+{
+    render_context.begin_tracked_render_pass(RenderPassDescriptor
+    {
+        label: Some(label),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: Operations { load: LoadOp::Clear(Default::default()), store: StoreOp::Store },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None
+    })
+}
+
+// splat and blur the particles' thickness; returns the bind group the composite should read
+pub fn render_surface_thickness<'a>(
+    render_context: &mut RenderContext,
+    pipeline_cache: &PipelineCache,
+    pipeline: &ParticleRenderPipeline,
+    pipeline_buffers: &GPUPipelineBuffers,
+    surface_textures: &'a SurfaceTextures,
+) -> Option<&'a BindGroup>
+{
+    let splat_pipeline = pipeline_cache.get_render_pipeline(pipeline.surface_splat_pipeline_id)?;
+    let blur_horizontal_pipeline = pipeline_cache.get_render_pipeline(pipeline.surface_blur_horizontal_pipeline_id)?;
+    let blur_vertical_pipeline = pipeline_cache.get_render_pipeline(pipeline.surface_blur_vertical_pipeline_id)?;
+    pipeline_cache.get_render_pipeline(pipeline.surface_composite_pipeline_id)?;
+
+    {
+        let mut render_pass = surface_pass(render_context, "surface_splat_pass", &surface_textures.thickness.view);
+        render_pass.set_render_pipeline(splat_pipeline);
+        render_pass.set_bind_group(0, &pipeline_buffers.render_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, pipeline_buffers.vertex_buffer.slice(..));
+        render_pass.draw(0..6, 0..pipeline_buffers.particle_count);
+    }
+    {
+        let mut render_pass = surface_pass(render_context, "surface_blur_horizontal_pass", &surface_textures.blurred.view);
+        render_pass.set_render_pipeline(blur_horizontal_pipeline);
+        render_pass.set_bind_group(0, &pipeline_buffers.render_bind_group, &[]);
+        render_pass.set_bind_group(1, &surface_textures.thickness.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    {
+        let mut render_pass = surface_pass(render_context, "surface_blur_vertical_pass", &surface_textures.thickness.view);
+        render_pass.set_render_pipeline(blur_vertical_pipeline);
+        render_pass.set_bind_group(0, &pipeline_buffers.render_bind_group, &[]);
+        render_pass.set_bind_group(1, &surface_textures.blurred.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    Some(&surface_textures.thickness.bind_group)
+}
@@ -0,0 +1,269 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::ParticleConfig;
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::GpuReadback;
+use crate::field_export::FieldGrid;
+
+const SURROGATE_INTERVAL: u32 = 5;      // frames between particle readbacks fed to the model
+const DEFAULT_MODEL_PATH: &str = "surrogate.onnx";
+
+// Experimental learned correction step, only available with `--features surrogate`.
+// The model runs on the scalar grid (config.scalar_grid_width x height cells):
+//   input   [1, 3, height, width] f32, particle density, velocity x and velocity y per cell
+//   output  [1, 2, height, width] f32, acceleration to add to the particles in each cell
+// rows run from the bottom of the screen, like the field export. The compute shader samples the
+// latest output every step until the next one arrives, scaled by `surrogate_strength`.
+#[cfg(feature = "surrogate")]
+mod model
+{
+    use tract_onnx::prelude::*;
+
+    pub struct SurrogateModel
+    {
+        runnable: TypedRunnableModel<TypedModel>,
+        width: usize,
+        height: usize,
+    }
+
+    impl SurrogateModel
+    {
+        pub fn load(path: &str, width: usize, height: usize) -> Result<Self, String>
+        {
+            let runnable = tract_onnx::onnx()
+                .model_for_path(path)
+                .and_then(|model| model.with_input_fact(0, f32::fact([1, 3, height, width]).into()))
+                .and_then(|model| model.into_optimized())
+                .and_then(|model| model.into_runnable())
+                .map_err(|error| error.to_string())?;
+            Ok(Self { runnable, width, height })
+        }
+
+        pub fn grid_size(&self) -> (usize, usize)
+        {
+            (self.width, self.height)
+        }
+
+        pub fn run(&self, input: Vec<f32>) -> Result<Vec<[f32; 2]>, String>
+        {
+            let cells = self.width * self.height;
+            let input = tract_ndarray::Array4::from_shape_vec((1, 3, self.height, self.width), input)
+                .map_err(|error| error.to_string())?;
+            let outputs = self.runnable.run(tvec!(Tensor::from(input).into()))
+                .map_err(|error| error.to_string())?;
+            let output = outputs[0].to_array_view::<f32>().map_err(|error| error.to_string())?;
+            if output.len() != 2 * cells
+            {
+                return Err(format!("expected a [1, 2, {}, {}] output", self.height, self.width));
+            }
+
+            let values: Vec<f32> = output.iter().copied().collect();
+            Ok((0..cells).map(|cell| [values[cell], values[cells + cell]]).collect())
+        }
+    }
+}
+
+// without the feature no model can be loaded, so there is never a value to run
+#[cfg(not(feature = "surrogate"))]
+mod model
+{
+    pub enum SurrogateModel {}
+
+    impl SurrogateModel
+    {
+        pub fn load(_path: &str, _width: usize, _height: usize) -> Result<Self, String>
+        {
+            Err("built without the `surrogate` feature".to_string())
+        }
+
+        pub fn grid_size(&self) -> (usize, usize)
+        {
+            match *self {}
+        }
+
+        pub fn run(&self, _input: Vec<f32>) -> Result<Vec<[f32; 2]>, String>
+        {
+            match *self {}
+        }
+    }
+}
+
+use model::SurrogateModel;
+
+// shared between main and render worlds: the main world asks for particles and hands back
+// the model's correction grid, the render world copies one way and uploads the other
+#[derive(Resource, Clone, Default)]
+pub struct SurrogateShared
+{
+    wanted: Arc<AtomicBool>,
+    particles: Arc<Mutex<Option<Vec<[f32; 8]>>>>,
+    correction: Arc<Mutex<Option<Vec<[f32; 2]>>>>,
+}
+
+// render world side of the particle readback
+#[derive(Resource)]
+pub struct SurrogateReadback
+{
+    particles: GpuReadback,
+    frame_count: u32,
+}
+
+impl Default for SurrogateReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("surrogate_particle_readback_buffer"), frame_count: 0 }
+    }
+}
+
+#[derive(Resource)]
+pub struct Surrogate
+{
+    pub path: String,
+    pub enabled: bool,
+    pub strength: f32,
+    pub status: Option<String>,
+    model: Option<SurrogateModel>,
+}
+
+impl Default for Surrogate
+{
+    fn default() -> Self
+    {
+        Self { path: DEFAULT_MODEL_PATH.to_string(), enabled: false, strength: 1.0, status: None, model: None }
+    }
+}
+
+pub fn read_back_surrogate_particles(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    shared: Res<SurrogateShared>,
+    mut surrogate_readback: ResMut<SurrogateReadback>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(particles) = surrogate_readback.particles.try_read::<[f32; 8]>(&render_device)
+    {
+        *shared.particles.lock().unwrap() = Some(particles);
+    }
+
+    if !shared.wanted.load(Ordering::Relaxed) { return; }
+
+    surrogate_readback.frame_count += 1;
+    if surrogate_readback.frame_count % SURROGATE_INTERVAL != 0 || !surrogate_readback.particles.is_idle() { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        surrogate_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            (std::mem::size_of::<[f32; 8]>() * pipeline_buffers.particle_count as usize) as u64,
+        );
+    }
+}
+
+// write the newest correction grid into the main system's buffer
+pub fn upload_surrogate_correction(
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<SurrogateShared>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, Without<ParticleSystemConfig>>,
+)
+{
+    let Some(correction) = shared.correction.lock().unwrap().take() else { return; };
+    if correction.len() != (config.scalar_grid_width * config.scalar_grid_height) as usize { return; }
+
+    for pipeline_buffers in pipeline_buffers_query.iter()
+    {
+        render_queue.write_buffer(&pipeline_buffers.surrogate_correction_buffer, 0, bytemuck::cast_slice(&correction));
+    }
+}
+
+// rasterize each readback onto the scalar grid and run the model on it
+pub fn update_surrogate(
+    shared: Res<SurrogateShared>,
+    mut surrogate: ResMut<Surrogate>,
+    mut sim_config: ResMut<ParticleConfig>,
+)
+{
+    let active = surrogate.enabled && surrogate.model.is_some();
+    shared.wanted.store(active, Ordering::Relaxed);
+
+    let surrogate_enabled = active as u32;
+    if sim_config.surrogate_enabled != surrogate_enabled || sim_config.surrogate_strength != surrogate.strength
+    {
+        sim_config.surrogate_enabled = surrogate_enabled;
+        sim_config.surrogate_strength = surrogate.strength;
+    }
+
+    let Some(particles) = shared.particles.lock().unwrap().take() else { return; };
+    if !active { return; }
+    let Some(model) = surrogate.model.as_ref() else { return; };
+
+    let grid = FieldGrid::rasterize(&particles, sim_config.screen_bounds, sim_config.scalar_grid_cell_size);
+    if model.grid_size() != (grid.width as usize, grid.height as usize) { return; }
+
+    let input: Vec<f32> = grid.density.iter().copied()
+        .chain(grid.velocity.iter().map(|velocity| velocity[0]))
+        .chain(grid.velocity.iter().map(|velocity| velocity[1]))
+        .collect();
+
+    match model.run(input)
+    {
+        Ok(correction) => *shared.correction.lock().unwrap() = Some(correction),
+        Err(error) => {
+            // a model that fails once will keep failing, so stop feeding it
+            surrogate.enabled = false;
+            surrogate.status = Some(format!("Model failed: {}", error));
+            warn!("[Surrogate] {}", surrogate.status.as_ref().unwrap());
+        }
+    }
+}
+
+pub fn surrogate_gui(
+    mut contexts: EguiContexts,
+    mut surrogate: ResMut<Surrogate>,
+    config: Res<ParticleConfig>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Surrogate Step (Experimental)")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1100.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label(format!("ONNX model from a [1, 3, {h}, {w}] density/velocity grid to a [1, 2, {h}, {w}] acceleration",
+                h = config.scalar_grid_height, w = config.scalar_grid_width));
+            ui.horizontal(|ui| {
+                ui.label("Model");
+                ui.text_edit_singleline(&mut surrogate.path);
+                if ui.button("Load").clicked() {
+                    let loaded = SurrogateModel::load(&surrogate.path, config.scalar_grid_width as usize, config.scalar_grid_height as usize);
+                    surrogate.status = Some(match loaded {
+                        Ok(model) => {
+                            surrogate.model = Some(model);
+                            format!("Loaded {}", surrogate.path)
+                        }
+                        Err(error) => format!("Failed to load {}: {}", surrogate.path, error),
+                    });
+                }
+            });
+
+            ui.add_enabled_ui(surrogate.model.is_some(), |ui| {
+                ui.checkbox(&mut surrogate.enabled, "Apply Correction");
+                ui.add(egui::Slider::new(&mut surrogate.strength, 0.0..=2.0)
+                    .text("Strength"));
+            });
+            if let Some(status) = &surrogate.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ParticleConfig;
+use crate::gui_scale::GuiScale;
+
+const HEATER_COLOR: egui::Color32 = egui::Color32::from_rgba_premultiplied(120, 40, 10, 90);
+
+// Heat diffuses between neighbors and buoyancy acts in the compute shader; the params are in
+// GUIConfig under Temperature. This just shows where the heated floor regions are.
+pub fn draw_heaters(
+    mut contexts: EguiContexts,
+    config: Res<ParticleConfig>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    gui_scale: Res<GuiScale>,
+) -> Result
+{
+    if config.temperature_enabled == 0 || config.screen_bounds == [0.0; 4] { return Ok(()); }
+    let [x_min, x_max, y_min, _] = config.screen_bounds;
+    let ctx = contexts.ctx_mut()?;
+    let Ok((camera, camera_transform)) = camera_query.single() else { return Ok(()); };
+    let to_screen = |world: Vec2| {
+        camera.world_to_viewport(camera_transform, world.extend(0.0)).ok()
+            .map(|viewport| egui::pos2(viewport.x, viewport.y) / gui_scale.applied)
+    };
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for heater in 0..config.heater_count as usize
+    {
+        let span = config.heater_spans[heater / 2];
+        let (start, end) = (span[heater % 2 * 2], span[heater % 2 * 2 + 1]);
+        // viewport y runs down, so the top left corner is the heater's (start, y_min + height)
+        let top_left = to_screen(Vec2::new(x_min + start * (x_max - x_min), y_min + config.heater_height));
+        let bottom_right = to_screen(Vec2::new(x_min + end * (x_max - x_min), y_min));
+        if let (Some(top_left), Some(bottom_right)) = (top_left, bottom_right)
+        {
+            painter.rect_filled(egui::Rect::from_min_max(top_left, bottom_right), 0.0, HEATER_COLOR);
+        }
+    }
+    Ok(())
+}
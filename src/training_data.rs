@@ -0,0 +1,344 @@
+use bevy::{
+    prelude::*,
+    render::renderer::{RenderDevice, RenderQueue},
+};
+use bevy_egui::{egui, EguiContexts};
+use rand::Rng;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+
+use crate::{setup_particles_scatter, ParticleConfig, ParticleSystem};
+use crate::particle_buffers::{GPUPipelineBuffers, ParticleUpload};
+use crate::particle_systems::ParticleSystemConfig;
+use crate::gpu_readback::GpuReadback;
+use crate::parameter_gui::{GUIConfig, GUIDefaults, GravityPreset};
+use crate::sim_clock::SimClock;
+use crate::initial_layout::InitialLayout;
+use crate::sim_rng::SimRng;
+
+const TRAINING_MAGIC: &[u8; 4] = b"PTRN";
+const TRAINING_VERSION: u32 = 1;
+const DEFAULT_TRAINING_DIRECTORY: &str = "training_data";
+const DEFAULT_SCENE_COUNT: u32 = 100;
+const DEFAULT_FRAMES_PER_SCENE: u32 = 200;
+
+// Training file layout (one per scene), little endian:
+//   magic, version, particle count, state count, param text length (u32 each)
+//   param text, the GUIConfig `name = value` lines the scene was simulated with
+//   states, `state count` snapshots of every particle's position and velocity as 4 f32 each,
+//   exactly one timestep apart, so states i and i + 1 form each (state, next-state) pair
+type StateRecord = [f32; 4];
+
+// shared between main and render worlds: the main world asks for snapshots, the render world
+// hands them back tagged with the particle generation and sim frame they were copied at
+#[derive(Resource, Clone, Default)]
+pub struct TrainingDataShared
+{
+    wanted: Arc<AtomicBool>,
+    snapshot: Arc<Mutex<Option<(u32, u32, Vec<[f32; 8]>)>>>,
+}
+
+// render world side of the snapshot readback
+#[derive(Resource)]
+pub struct TrainingDataReadback
+{
+    particles: GpuReadback,
+    requested: (u32, u32),
+}
+
+impl Default for TrainingDataReadback
+{
+    fn default() -> Self
+    {
+        Self { particles: GpuReadback::new("training_data_readback_buffer"), requested: (0, 0) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TrainingState
+{
+    Idle,
+    StartScene,
+    // the sim is paused, waiting for the snapshot of this generation and frame
+    WaitingForState { generation: u32, frame: u32 },
+}
+
+#[derive(Resource)]
+pub struct TrainingData
+{
+    pub directory: String,
+    pub scene_count: u32,
+    pub frames_per_scene: u32,
+    pub exit_when_done: bool,       // batch runs started from the command line close the app at the end
+    pub status: Option<String>,
+    state: TrainingState,
+    scene_index: u32,
+    states_written: u32,
+    file: Option<std::io::BufWriter<std::fs::File>>,
+    restore: Option<(GUIConfig, bool)>,     // params and pause state from before the run
+}
+
+// `--training-data <dir>` starts a batch run at launch, `--training-scenes <n>` and
+// `--training-frames <k>` override the scene count and frames per scene
+fn arg_value(name: &str) -> Option<String>
+{
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == name).and_then(|index| args.get(index + 1).cloned())
+}
+
+impl Default for TrainingData
+{
+    fn default() -> Self
+    {
+        let directory = arg_value("--training-data");
+        Self
+        {
+            exit_when_done: directory.is_some(),
+            state: if directory.is_some() { TrainingState::StartScene } else { TrainingState::Idle },
+            directory: directory.unwrap_or_else(|| DEFAULT_TRAINING_DIRECTORY.to_string()),
+            scene_count: arg_value("--training-scenes").and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_SCENE_COUNT),
+            frames_per_scene: arg_value("--training-frames").and_then(|value| value.parse().ok()).unwrap_or(DEFAULT_FRAMES_PER_SCENE),
+            status: None,
+            scene_index: 0,
+            states_written: 0,
+            file: None,
+            restore: None,
+        }
+    }
+}
+
+impl TrainingData
+{
+    pub fn is_running(&self) -> bool
+    {
+        self.state != TrainingState::Idle
+    }
+
+    fn start(&mut self)
+    {
+        self.state = TrainingState::StartScene;
+        self.scene_index = 0;
+        self.status = None;
+    }
+}
+
+// fluid parameters drawn around the launch defaults, so every scene stays simulatable
+fn randomize_params(defaults: &GUIConfig, rng: &mut impl Rng) -> GUIConfig
+{
+    GUIConfig
+    {
+        gravity: rng.random_range(0.0..=2.0) * GravityPreset::Earth.gravity(),
+        gravity_angle: rng.random_range(-30.0..=30.0),
+        oscillate_gravity: false,
+        variable_delta_time: false,     // every pair is exactly one fixed timestep apart
+        target_density: defaults.target_density * rng.random_range(0.5..=1.5),
+        pressure_multiplier: defaults.pressure_multiplier * rng.random_range(0.5..=2.0),
+        viscocity_strength: defaults.viscocity_strength * rng.random_range(0.0..=2.0),
+        applied_changes: true,
+        ..*defaults
+    }
+}
+
+fn open_scene_file(path: &PathBuf, params: &str, particle_count: u32, state_count: u32) -> std::io::Result<std::io::BufWriter<std::fs::File>>
+{
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(TRAINING_MAGIC)?;
+    for word in [TRAINING_VERSION, particle_count, state_count, params.len() as u32]
+    {
+        file.write_all(&word.to_le_bytes())?;
+    }
+    file.write_all(params.as_bytes())?;
+    Ok(file)
+}
+
+fn write_state(file: &mut impl Write, particles: &[[f32; 8]]) -> std::io::Result<()>
+{
+    for particle in particles
+    {
+        let state: StateRecord = [particle[0], particle[1], particle[2], particle[3]];
+        for value in state
+        {
+            file.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// keep copying the main system's particles while a snapshot is wanted, once the
+// particle data is fully uploaded
+pub fn read_back_training_data(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    config: Res<ParticleConfig>,
+    shared: Res<TrainingDataShared>,
+    mut training_readback: ResMut<TrainingDataReadback>,
+    pipeline_buffers_query: Query<&GPUPipelineBuffers, (Without<ParticleUpload>, Without<ParticleSystemConfig>)>,
+)
+{
+    if let Some(particles) = training_readback.particles.try_read::<[f32; 8]>(&render_device)
+    {
+        let (generation, frame) = training_readback.requested;
+        *shared.snapshot.lock().unwrap() = Some((generation, frame, particles));
+    }
+
+    if !training_readback.particles.is_idle() || !shared.wanted.load(Ordering::Relaxed) { return; }
+
+    if let Ok(pipeline_buffers) = pipeline_buffers_query.single()
+    {
+        training_readback.requested = (pipeline_buffers.generation, config.frame_count);
+        training_readback.particles.request(
+            &render_device,
+            &render_queue,
+            &pipeline_buffers.particle_buffer,
+            (std::mem::size_of::<[f32; 8]>() * pipeline_buffers.particle_count as usize) as u64,
+        );
+    }
+}
+
+// one scene at a time: randomize the params and particles, then alternate between writing the
+// current state and stepping the paused sim by exactly one frame
+pub fn update_training_data(
+    shared: Res<TrainingDataShared>,
+    defaults: Option<Res<GUIDefaults>>,
+    mut training: ResMut<TrainingData>,
+    mut gui_config: ResMut<GUIConfig>,
+    mut sim_clock: ResMut<SimClock>,
+    sim_config: Res<ParticleConfig>,
+    layout: Res<InitialLayout>,
+    mut sim_rng: ResMut<SimRng>,
+    mut particle_system_query: Query<&mut ParticleSystem, Without<ParticleSystemConfig>>,
+    mut exit: EventWriter<AppExit>,
+)
+{
+    shared.wanted.store(matches!(training.state, TrainingState::WaitingForState { .. }), Ordering::Relaxed);
+
+    match training.state
+    {
+        TrainingState::Idle => {}
+        TrainingState::StartScene => {
+            let Some(defaults) = defaults else { return; };
+            let Ok(mut particle_system) = particle_system_query.single_mut() else { return; };
+
+            if training.scene_index == 0
+            {
+                training.restore = Some((*gui_config, sim_clock.paused));
+            }
+            if training.scene_index >= training.scene_count
+            {
+                // done, hand the sim back as it was
+                if let Some((params, paused)) = training.restore.take()
+                {
+                    *gui_config = GUIConfig { applied_changes: true, ..params };
+                    sim_clock.paused = paused;
+                }
+                training.state = TrainingState::Idle;
+                training.status = Some(format!("Wrote {} scenes to {}", training.scene_count, training.directory));
+                info!("[TrainingData] {}", training.status.as_ref().unwrap());
+                if training.exit_when_done
+                {
+                    exit.write(AppExit::Success);
+                }
+                return;
+            }
+
+            let params = randomize_params(&defaults.0, sim_rng.rng());
+            let path = PathBuf::from(&training.directory).join(format!("scene_{:04}.ptrn", training.scene_index));
+            let file = std::fs::create_dir_all(&training.directory)
+                .and_then(|_| open_scene_file(&path, &params.to_text(), params.particle_count, training.frames_per_scene + 1));
+            match file
+            {
+                Ok(file) => training.file = Some(file),
+                Err(error) => {
+                    training.state = TrainingState::Idle;
+                    training.status = Some(format!("Failed to write {}: {}", path.display(), error));
+                    warn!("[TrainingData] {}", training.status.as_ref().unwrap());
+                    return;
+                }
+            }
+
+            // the count is set directly so resize_particle_system doesn't rescatter again
+            *gui_config = params;
+            particle_system.particles = setup_particles_scatter(&layout, &mut sim_rng, sim_config.screen_bounds, params.particle_count);
+            particle_system.generation = particle_system.generation.wrapping_add(1);
+            sim_clock.paused = true;
+
+            training.states_written = 0;
+            training.state = TrainingState::WaitingForState { generation: particle_system.generation, frame: sim_config.frame_count };
+        }
+        TrainingState::WaitingForState { generation, frame } => {
+            let Some((snapshot_generation, snapshot_frame, particles)) = shared.snapshot.lock().unwrap().take() else { return; };
+            // older copies may still land after a step or a new scene
+            if snapshot_generation != generation || snapshot_frame != frame { return; }
+
+            let written = training.file.as_mut()
+                .map_or(Ok(()), |file| write_state(file, &particles));
+            if let Err(error) = written
+            {
+                training.state = TrainingState::Idle;
+                training.file = None;
+                training.status = Some(format!("Failed to write scene {}: {}", training.scene_index, error));
+                warn!("[TrainingData] {}", training.status.as_ref().unwrap());
+                return;
+            }
+            training.states_written += 1;
+
+            if training.states_written > training.frames_per_scene
+            {
+                if let Some(mut file) = training.file.take()
+                {
+                    let _ = file.flush();
+                }
+                training.scene_index += 1;
+                training.state = TrainingState::StartScene;
+            }
+            else
+            {
+                sim_clock.step_requested = true;
+                training.state = TrainingState::WaitingForState { generation, frame: frame.wrapping_add(1) };
+            }
+        }
+    }
+}
+
+pub fn training_data_gui(
+    mut contexts: EguiContexts,
+    mut training: ResMut<TrainingData>,
+) -> Result
+{
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Training Data")
+        .collapsible(true)
+        .default_open(false)
+        .default_pos([10.0, 1050.0])
+        .show(ctx, |ui: &mut egui::Ui| {
+            ui.label("Randomized scenes stepped one frame at a time, saved as consecutive particle states");
+            let running = training.is_running();
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Directory");
+                    ui.text_edit_singleline(&mut training.directory);
+                });
+                ui.add(egui::Slider::new(&mut training.scene_count, 1..=10000)
+                    .text("Scenes")
+                    .logarithmic(true));
+                ui.add(egui::Slider::new(&mut training.frames_per_scene, 1..=10000)
+                    .text("Frames Per Scene")
+                    .logarithmic(true));
+            });
+
+            if running {
+                ui.label(format!("Scene {} of {}, frame {} of {}",
+                    (training.scene_index + 1).min(training.scene_count),
+                    training.scene_count,
+                    training.states_written.saturating_sub(1),
+                    training.frames_per_scene));
+            } else if ui.button("Generate").clicked() {
+                training.start();
+            }
+            if let Some(status) = &training.status {
+                ui.label(status);
+            }
+        });
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::ops::{Mul, MulAssign};
+
+// Units of the scriptable params. The GPU's Config is plain f32s, so GUIConfig holds these
+// instead and they're unwrapped where the config is uploaded; a length can't end up in a
+// duration by accident. World units are pixels at the default zoom.
+
+// a duration of sim time
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Seconds(pub f32);
+
+// a distance in world units
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct WorldLength(pub f32);
+
+// particles per square world unit, which is also mass per area since particles have unit mass
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Density(pub f32);
+
+// scaling keeps the unit
+macro_rules! impl_scale {
+    ($unit:ident) => {
+        impl Mul<f32> for $unit
+        {
+            type Output = $unit;
+            fn mul(self, factor: f32) -> $unit { $unit(self.0 * factor) }
+        }
+
+        impl MulAssign<f32> for $unit
+        {
+            fn mul_assign(&mut self, factor: f32) { self.0 *= factor; }
+        }
+    };
+}
+
+impl_scale!(Seconds);
+impl_scale!(WorldLength);
+impl_scale!(Density);
+
+impl Density
+{
+    // particles inside a circle of the radius at this density
+    pub fn neighbors_within(self, radius: WorldLength) -> f32
+    {
+        std::f32::consts::PI * radius.0 * radius.0 * self.0
+    }
+
+    // distance between neighbors on a square lattice at this density
+    pub fn rest_spacing(self) -> WorldLength
+    {
+        WorldLength(1.0 / self.0.max(f32::EPSILON).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn scaling_keeps_the_unit_and_undoes()
+    {
+        assert_eq!(Seconds(0.01) * 0.5, Seconds(0.005));
+        assert_eq!(WorldLength(4.0) * 0.25 * 4.0, WorldLength(4.0));
+
+        let mut density = Density(0.04);
+        density *= 2.0;
+        assert_eq!(density, Density(0.08));
+        density *= 0.5;
+        assert_eq!(density, Density(0.04));
+    }
+
+    #[test]
+    fn rest_spacing_inverts_the_density()
+    {
+        for density in [0.01, 0.04, 0.25, 1.0]
+        {
+            let spacing = Density(density).rest_spacing();
+            assert!((1.0 / (spacing.0 * spacing.0) - density).abs() < 1e-5 * density.max(1.0));
+        }
+        assert!(Density(0.0).rest_spacing().0.is_finite());
+    }
+
+    #[test]
+    fn neighbors_within_the_rest_spacing()
+    {
+        // a lattice point's circle of one spacing holds pi particles on average
+        let density = Density(0.04);
+        let neighbors = density.neighbors_within(density.rest_spacing());
+        assert!((neighbors - std::f32::consts::PI).abs() < 1e-4);
+    }
+}
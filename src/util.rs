@@ -3,14 +3,14 @@ use bevy::{
     render::{
         render_resource::*, 
         renderer::RenderDevice,
-        view::Msaa,
     },
 };
 use std::borrow::Cow;
-use std::num::NonZeroU64;
-use crate::particle_buffers::SortingParams;
 
-// returns the bind group layout for group 0 (used by render shader and main compute shader)
+// storage buffers the compute layout below puts in the compute stage, must match its entries
+pub const COMPUTE_STORAGE_BUFFERS: u32 = 24;
+
+// returns the bind group layout for group 0 of the compute shader
 pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
 {
     // create the bind group layout
@@ -19,7 +19,7 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         &[BindGroupLayoutEntry
         {
             binding: 0,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Storage { read_only: false },
                 has_dynamic_offset: false,
@@ -30,7 +30,7 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         BindGroupLayoutEntry
         {
             binding: 1,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Uniform,
                 has_dynamic_offset: false,
@@ -41,18 +41,18 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         BindGroupLayoutEntry
         {
             binding: 2,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
-                ty: BufferBindingType::Uniform,
-                has_dynamic_offset: true,
-                min_binding_size: NonZeroU64::new(std::mem::size_of::<SortingParams>() as u64),
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
             },
             count: None
         },
         BindGroupLayoutEntry
         {
             binding: 3,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Storage { read_only: false },
                 has_dynamic_offset: false,
@@ -63,7 +63,7 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         BindGroupLayoutEntry
         {
             binding: 4,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Storage { read_only: false },
                 has_dynamic_offset: false,
@@ -74,7 +74,7 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         BindGroupLayoutEntry
         {
             binding: 5,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Storage { read_only: false },
                 has_dynamic_offset: false,
@@ -85,7 +85,194 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
         BindGroupLayoutEntry
         {
             binding: 6,
-            visibility: ShaderStages::VERTEX | ShaderStages::COMPUTE,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 7,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 8,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 9,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 10,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 11,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 12,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 13,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 14,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 15,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 16,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 17,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 18,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 19,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 20,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 21,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 22,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 23,
+            visibility: ShaderStages::COMPUTE,
             ty: BindingType::Buffer {
                 ty: BufferBindingType::Storage { read_only: false },
                 has_dynamic_offset: false,
@@ -93,10 +280,158 @@ pub fn get_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
             },
             count: None
         },
+        BindGroupLayoutEntry
+        {
+            binding: 24,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        ]
+    )
+}
+
+// the compute layout's bindings the render shader reads, bound read only as its own group 0.
+// WebGPU doesn't allow writable storage buffers in the vertex stage and only guarantees 8
+// storage buffers per stage, so the render pipelines get this smaller view of the same buffers
+pub const RENDER_BINDINGS: [u32; 9] = [0, 1, 5, 9, 10, 17, 20, 22, 23];
+
+// returns the bind group layout for group 0 of the render shader
+pub fn get_render_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
+{
+    render_device.create_bind_group_layout(
+        "render_bind_group_layout0",
+        &[
+        BindGroupLayoutEntry
+        {
+            binding: 0,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 1,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 5,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 9,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 10,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 17,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 20,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 22,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 23,
+            visibility: ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None
+        },
         ]
     )
 }
 
+// returns bind group for group 0 of the render shader, from the compute bind group's buffers
+pub fn get_render_bind_group(
+    render_device: &RenderDevice,
+    bind_group_layout: &BindGroupLayout,
+    buffers: &[(&Buffer, std::num::NonZeroU64); RENDER_BINDINGS.len()],
+) -> BindGroup
+{
+    let entries: Vec<BindGroupEntry> = RENDER_BINDINGS.iter()
+        .zip(buffers)
+        .map(|(binding, (buffer, size))| BindGroupEntry
+        {
+            binding: *binding,
+            resource: BindingResource::Buffer(BufferBinding
+                {
+                    buffer,
+                    offset: 0,
+                    size: Some(*size)
+                })
+        })
+        .collect();
+    render_device.create_bind_group("render_bind_group", bind_group_layout, &entries)
+}
+
 // returns bind group for group 0 
 pub fn get_bind_group(
     label: &str,
@@ -110,11 +445,48 @@ pub fn get_bind_group(
     spatial_lookup_buffer_size: std::num::NonZeroU64,
     spatial_lookup_offsets_buffer: &Buffer,
     spatial_lookup_offsets_buffer_size: std::num::NonZeroU64,
-    sorting_params_buffer: &Buffer,
+    sort_scratch_buffer: &Buffer,
+    sort_scratch_buffer_size: std::num::NonZeroU64,
     particle_densities_buffer : &Buffer,
     particle_densities_buffer_size: std::num::NonZeroU64,
     predicted_positions_buffer : &Buffer,
     predicted_positions_buffer_size: std::num::NonZeroU64,
+    scalar_grid_accumulation_buffer: &Buffer,
+    scalar_grid_accumulation_buffer_size: std::num::NonZeroU64,
+    scalar_grid_velocities_buffer: &Buffer,
+    scalar_grid_velocities_buffer_size: std::num::NonZeroU64,
+    scalar_field_buffer: &Buffer,
+    scalar_field_buffer_size: std::num::NonZeroU64,
+    grid_pressure_buffer: &Buffer,
+    grid_pressure_buffer_size: std::num::NonZeroU64,
+    obstacle_buffer: &Buffer,
+    obstacle_buffer_size: std::num::NonZeroU64,
+    key_counts_buffer: &Buffer,
+    key_counts_buffer_size: std::num::NonZeroU64,
+    scan_block_totals_buffer: &Buffer,
+    scan_block_totals_buffer_size: std::num::NonZeroU64,
+    surrogate_correction_buffer: &Buffer,
+    surrogate_correction_buffer_size: std::num::NonZeroU64,
+    body_impulse_buffer: &Buffer,
+    body_impulse_buffer_size: std::num::NonZeroU64,
+    stats_buffer: &Buffer,
+    stats_buffer_size: std::num::NonZeroU64,
+    velocity_history_buffer: &Buffer,
+    velocity_history_buffer_size: std::num::NonZeroU64,
+    pressure_probe_buffer: &Buffer,
+    pressure_probe_buffer_size: std::num::NonZeroU64,
+    obstacle_force_buffer: &Buffer,
+    obstacle_force_buffer_size: std::num::NonZeroU64,
+    particle_temperatures_buffer: &Buffer,
+    particle_temperatures_buffer_size: std::num::NonZeroU64,
+    max_speed_buffer: &Buffer,
+    max_speed_buffer_size: std::num::NonZeroU64,
+    trail_positions_buffer: &Buffer,
+    trail_positions_buffer_size: std::num::NonZeroU64,
+    velocity_field_buffer: &Buffer,
+    velocity_field_buffer_size: std::num::NonZeroU64,
+    force_field_buffer: &Buffer,
+    force_field_buffer_size: std::num::NonZeroU64,
 ) -> BindGroup
 {
     render_device.create_bind_group(
@@ -146,9 +518,9 @@ pub fn get_bind_group(
             binding: 2,
             resource: BindingResource::Buffer(BufferBinding 
                 {   
-                    buffer: &sorting_params_buffer, 
+                    buffer: &sort_scratch_buffer, 
                     offset: 0, 
-                    size: NonZeroU64::new(std::mem::size_of::<SortingParams>() as u64)
+                    size: Some(sort_scratch_buffer_size)
                 })
         },
         BindGroupEntry
@@ -190,14 +562,216 @@ pub fn get_bind_group(
                     offset: 0, 
                     size: Some(predicted_positions_buffer_size)
                 })
+        },
+        BindGroupEntry
+        {
+            binding: 7,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &scalar_grid_accumulation_buffer, 
+                    offset: 0, 
+                    size: Some(scalar_grid_accumulation_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 8,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &scalar_grid_velocities_buffer, 
+                    offset: 0, 
+                    size: Some(scalar_grid_velocities_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 9,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &scalar_field_buffer, 
+                    offset: 0, 
+                    size: Some(scalar_field_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 10,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &grid_pressure_buffer, 
+                    offset: 0, 
+                    size: Some(grid_pressure_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 11,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &obstacle_buffer, 
+                    offset: 0, 
+                    size: Some(obstacle_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 12,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &key_counts_buffer, 
+                    offset: 0, 
+                    size: Some(key_counts_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 13,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &scan_block_totals_buffer, 
+                    offset: 0, 
+                    size: Some(scan_block_totals_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 14,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &surrogate_correction_buffer, 
+                    offset: 0, 
+                    size: Some(surrogate_correction_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 15,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &body_impulse_buffer, 
+                    offset: 0, 
+                    size: Some(body_impulse_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 16,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &stats_buffer, 
+                    offset: 0, 
+                    size: Some(stats_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 17,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &velocity_history_buffer, 
+                    offset: 0, 
+                    size: Some(velocity_history_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 18,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &pressure_probe_buffer, 
+                    offset: 0, 
+                    size: Some(pressure_probe_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 19,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &obstacle_force_buffer, 
+                    offset: 0, 
+                    size: Some(obstacle_force_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 20,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &particle_temperatures_buffer, 
+                    offset: 0, 
+                    size: Some(particle_temperatures_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 21,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &max_speed_buffer, 
+                    offset: 0, 
+                    size: Some(max_speed_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 22,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &trail_positions_buffer, 
+                    offset: 0, 
+                    size: Some(trail_positions_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 23,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &velocity_field_buffer, 
+                    offset: 0, 
+                    size: Some(velocity_field_buffer_size)
+                })
+        },
+        BindGroupEntry
+        {
+            binding: 24,
+            resource: BindingResource::Buffer(BufferBinding 
+                {   
+                    buffer: &force_field_buffer, 
+                    offset: 0, 
+                    size: Some(force_field_buffer_size)
+                })
         }
     ])
 }
 
+// per-vertex quad corner and uv, shared by every pipeline that draws a quad per particle
+fn particle_quad_vertex_layout() -> VertexBufferLayout
+{
+    VertexBufferLayout {
+        array_stride: 16, // 8 bytes per vec2, two vec2s = 16
+        step_mode: VertexStepMode::Vertex,
+        attributes: vec![
+            VertexAttribute {
+                shader_location: 0,
+                offset: 0,
+                format: VertexFormat::Float32x2, // position
+            },
+            VertexAttribute {
+                shader_location: 1,
+                offset: 8,
+                format: VertexFormat::Float32x2, // uv
+            },
+        ],
+    }
+}
+
 // returns pipeline descriptor for render pipeline
 pub fn get_render_pipeline_descriptor(
     bind_group_layout: &BindGroupLayout,
-    shader_handle: &Handle<Shader>) -> RenderPipelineDescriptor
+    shader_handle: &Handle<Shader>,
+    sample_count: u32) -> RenderPipelineDescriptor
 {
     RenderPipelineDescriptor 
     {   label: Some("render_pipeline_descriptor".into()), 
@@ -208,24 +782,7 @@ pub fn get_render_pipeline_descriptor(
             shader: shader_handle.clone(),
             shader_defs: vec![],
             entry_point: "vertex_main".into(),
-            buffers: vec![
-                VertexBufferLayout {
-                    array_stride: 16, // 8 bytes per vec2, two vec2s = 16
-                    step_mode: VertexStepMode::Vertex,
-                    attributes: vec![
-                        VertexAttribute {
-                            shader_location: 0,
-                            offset: 0,
-                            format: VertexFormat::Float32x2, // position
-                        },
-                        VertexAttribute {
-                            shader_location: 1,
-                            offset: 8,
-                            format: VertexFormat::Float32x2, // uv
-                        },
-                    ],
-                }
-            ]
+            buffers: vec![particle_quad_vertex_layout()]
         }, 
         primitive: PrimitiveState 
         {
@@ -240,7 +797,7 @@ pub fn get_render_pipeline_descriptor(
         depth_stencil: None, 
         multisample: MultisampleState
         {
-            count: Msaa::Sample4 as u32,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false
         },
@@ -260,6 +817,251 @@ pub fn get_render_pipeline_descriptor(
     }
 }
 
+// returns pipeline descriptor for a grid overlay, a single quad spanning the screen bounds
+pub fn get_overlay_pipeline_descriptor(
+    bind_group_layout: &BindGroupLayout,
+    shader_handle: &Handle<Shader>,
+    fragment_entry_point: &str,
+    sample_count: u32,
+) -> RenderPipelineDescriptor
+{
+    RenderPipelineDescriptor 
+    {   label: Some("overlay_pipeline_descriptor".into()), 
+        layout: vec![bind_group_layout.clone()], 
+        push_constant_ranges: vec![], 
+        vertex: VertexState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: "scalar_overlay_vertex".into(),
+            buffers: vec![]     // corners are generated from the vertex index
+        }, 
+        primitive: PrimitiveState 
+        {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None, 
+        multisample: MultisampleState
+        {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false
+        },
+        fragment: Some(FragmentState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: Cow::from(fragment_entry_point.to_owned()),
+            targets: vec![Some(ColorTargetState 
+                {
+                format: TextureFormat::Rgba8UnormSrgb,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+                })]
+        }), 
+        zero_initialize_workgroup_memory: false 
+    }
+}
+
+// returns pipeline descriptor for quads along line segments, generated from the vertex and instance
+// index: the particle trails and the velocity field's arrows and streamlines
+pub fn get_line_pipeline_descriptor(
+    bind_group_layout: &BindGroupLayout,
+    shader_handle: &Handle<Shader>,
+    vertex_entry_point: &str,
+    sample_count: u32,
+) -> RenderPipelineDescriptor
+{
+    RenderPipelineDescriptor 
+    {   label: Some("line_pipeline_descriptor".into()), 
+        layout: vec![bind_group_layout.clone()], 
+        push_constant_ranges: vec![], 
+        vertex: VertexState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: Cow::from(vertex_entry_point.to_owned()),
+            buffers: vec![]     // corners are generated from the vertex index
+        }, 
+        primitive: PrimitiveState 
+        {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,    // segments face either way depending on the direction of travel
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None, 
+        multisample: MultisampleState
+        {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false
+        },
+        fragment: Some(FragmentState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: "line_fragment".into(),
+            targets: vec![Some(ColorTargetState 
+                {
+                format: TextureFormat::Rgba8UnormSrgb,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+                })]
+        }), 
+        zero_initialize_workgroup_memory: false 
+    }
+}
+
+pub const SURFACE_TEXTURE_FORMAT: TextureFormat = TextureFormat::R16Float;
+
+// returns the bind group layout for group 1 of the liquid surface passes, the texture being read
+pub fn get_surface_texture_bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout
+{
+    render_device.create_bind_group_layout(
+        "surface_texture_bind_group_layout",
+        &[
+        BindGroupLayoutEntry
+        {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None
+        },
+        BindGroupLayoutEntry
+        {
+            binding: 1,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None
+        },
+        ]
+    )
+}
+
+// returns pipeline descriptor that additively splats particles into a surface texture, the
+// liquid surface's thickness or the heatmap's density
+pub fn get_surface_splat_pipeline_descriptor(
+    bind_group_layout: &BindGroupLayout,
+    shader_handle: &Handle<Shader>,
+    vertex_entry_point: &str,
+    fragment_entry_point: &str,
+) -> RenderPipelineDescriptor
+{
+    RenderPipelineDescriptor 
+    {   label: Some("surface_splat_pipeline_descriptor".into()), 
+        layout: vec![bind_group_layout.clone()], 
+        push_constant_ranges: vec![], 
+        vertex: VertexState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: Cow::from(vertex_entry_point.to_owned()),
+            buffers: vec![particle_quad_vertex_layout()]
+        }, 
+        primitive: PrimitiveState 
+        {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None, 
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: Cow::from(fragment_entry_point.to_owned()),
+            targets: vec![Some(ColorTargetState 
+                {
+                format: SURFACE_TEXTURE_FORMAT,
+                blend: Some(BlendState {
+                    color: BlendComponent { src_factor: BlendFactor::One, dst_factor: BlendFactor::One, operation: BlendOperation::Add },
+                    alpha: BlendComponent::OVER,
+                }),
+                write_mask: ColorWrites::ALL,
+                })]
+        }), 
+        zero_initialize_workgroup_memory: false 
+    }
+}
+
+// returns pipeline descriptor for a fullscreen surface pass reading the group 1 texture; the
+// blur passes write another surface texture, the composite writes the view with its sample count
+pub fn get_surface_pass_pipeline_descriptor(
+    bind_group_layout: &BindGroupLayout,
+    surface_texture_layout: &BindGroupLayout,
+    shader_handle: &Handle<Shader>,
+    fragment_entry_point: &str,
+    composite_sample_count: Option<u32>,
+) -> RenderPipelineDescriptor
+{
+    let (format, blend, sample_count) = match composite_sample_count {
+        Some(sample_count) => (TextureFormat::Rgba8UnormSrgb, Some(BlendState::ALPHA_BLENDING), sample_count),
+        None => (SURFACE_TEXTURE_FORMAT, None, 1),
+    };
+
+    RenderPipelineDescriptor 
+    {   label: Some("surface_pass_pipeline_descriptor".into()), 
+        layout: vec![bind_group_layout.clone(), surface_texture_layout.clone()], 
+        push_constant_ranges: vec![], 
+        vertex: VertexState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: "surface_fullscreen_vertex".into(),
+            buffers: vec![]     // the triangle is generated from the vertex index
+        }, 
+        primitive: PrimitiveState 
+        {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None, 
+        multisample: MultisampleState
+        {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false
+        },
+        fragment: Some(FragmentState
+        {
+            shader: shader_handle.clone(),
+            shader_defs: vec![],
+            entry_point: Cow::from(fragment_entry_point.to_owned()),
+            targets: vec![Some(ColorTargetState 
+                {
+                format,
+                blend,
+                write_mask: ColorWrites::ALL,
+                })]
+        }), 
+        zero_initialize_workgroup_memory: false 
+    }
+}
+
 // returns pipeline descriptor for compute pipeline
 pub fn get_compute_pipeline_descriptor(
     bind_group_layout: &BindGroupLayout,